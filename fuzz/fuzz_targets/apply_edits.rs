@@ -0,0 +1,29 @@
+//! Suggested edits, whose positions needn't fit the file they're for.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use lintrunner::lint_message::{apply_edits, TextEdit};
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    text: String,
+    /// Small positions, so most of them land in or near the text.
+    edits: Vec<(u8, u8, u8, u8, String)>,
+}
+
+fuzz_target!(|input: Input| {
+    let edits = input
+        .edits
+        .into_iter()
+        .map(|(line, char, end_line, end_char, replacement)| TextEdit {
+            line: line.into(),
+            char: char.into(),
+            end_line: end_line.into(),
+            end_char: end_char.into(),
+            replacement,
+        })
+        .collect::<Vec<_>>();
+    let _ = apply_edits(&input.text, &edits);
+});
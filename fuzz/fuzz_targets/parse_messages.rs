@@ -0,0 +1,9 @@
+//! Linter output, which lintrunner must reject rather than crash on.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|output: &str| {
+    let _ = lintrunner::linter::parse_messages(output);
+});
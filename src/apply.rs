@@ -0,0 +1,181 @@
+//! Applying the patches suggested by linters to files on disk.
+//!
+//! Patches are written by a small pool of threads, so applying thousands of
+//! fixes doesn't take one write at a time, without opening thousands of files
+//! at once either. Each file is locked while it's checked and written, as
+//! linters finishing at the same time may patch the same file. Before writing,
+//! the file is checked against the linter's `original` text: if it changed
+//! since the linter read it, the patch is skipped as stale instead of
+//! clobbering the change.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::debug;
+
+use crate::{
+    io_limit,
+    lint_message::{LintMessage, LintSeverity},
+    path::AbsPath,
+};
+
+/// The full replacement text for one file.
+pub struct Patch<'a> {
+    pub path: AbsPath,
+    /// What the linter saw in the file, if it said.
+    pub original: Option<&'a str>,
+    pub replacement: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchOutcome {
+    Applied,
+    /// The file no longer matches the patch's `original`, so it was left
+    /// alone.
+    Stale,
+}
+
+/// One lock per file being patched, shared by everything applying patches in
+/// a run.
+#[derive(Default)]
+pub struct FileLocks {
+    locks: Mutex<HashMap<AbsPath, Arc<Mutex<()>>>>,
+}
+
+impl FileLocks {
+    fn get(&self, path: &AbsPath) -> Arc<Mutex<()>> {
+        Arc::clone(self.locks.lock().unwrap().entry(path.clone()).or_default())
+    }
+}
+
+fn apply_patch(patch: &Patch, locks: &FileLocks) -> Result<PatchOutcome> {
+    let lock = locks.get(&patch.path);
+    let _guard = lock.lock().unwrap();
+
+    if let Some(original) = patch.original {
+        let current = std::fs::read(&patch.path)
+            .with_context(|| format!("Failed to read file: '{}'", patch.path.display()))?;
+        // Linters see files that aren't UTF-8 decoded lossily, so compare
+        // them the same way.
+        if String::from_utf8_lossy(&current) != original {
+            debug!("Skipping stale patch for: {}", patch.path.display());
+            return Ok(PatchOutcome::Stale);
+        }
+    }
+    std::fs::write(&patch.path, patch.replacement).with_context(|| {
+        format!(
+            "Failed to write apply patch to file: '{}'",
+            patch.path.display()
+        )
+    })?;
+    Ok(PatchOutcome::Applied)
+}
+
+/// Reported in place of a fix that wasn't applied because the file changed
+/// after linter `code` read it.
+pub fn stale_patch_message(code: &str, path: &str) -> LintMessage {
+    LintMessage {
+        path: Some(path.to_string()),
+        description: Some(
+            "The file changed after the linter read it, so its fix was not applied. \
+             Re-run lintrunner to get a fresh one."
+                .to_string(),
+        ),
+        ..LintMessage::new(code, LintSeverity::Warning, "stale replacement")
+    }
+}
+
+/// The error when two linters propose changes to the same file, since their
+/// replacements can't both be applied.
+pub fn conflicting_patches_error(path: impl std::fmt::Display) -> anyhow::Error {
+    anyhow!(
+        "Two different linters proposed changes for the same file: {}.\n\
+         This is not yet supported, file an issue if you want it.",
+        path
+    )
+}
+
+/// Apply `patches` using up to `io_threads` threads. Returns the outcome of
+/// each patch, in order.
+pub fn apply_patches(
+    patches: &[Patch],
+    locks: &FileLocks,
+    io_threads: usize,
+) -> Result<Vec<PatchOutcome>> {
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(vec![None; patches.len()]);
+    let workers = io_threads.clamp(1, patches.len().max(1));
+    thread::scope(|scope| {
+        let handles = (0..workers)
+            .map(|_| {
+                scope.spawn(|| -> Result<()> {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::SeqCst);
+                        let patch = match patches.get(i) {
+                            Some(patch) => patch,
+                            None => return Ok(()),
+                        };
+                        let outcome = io_limit::limited(|| apply_patch(patch, locks))?;
+                        outcomes.lock().unwrap()[i] = Some(outcome);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().unwrap())
+    })?;
+    Ok(outcomes
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn applies_patches_and_skips_stale_ones() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut paths = Vec::new();
+        for i in 0..20 {
+            let path = dir.path().join(format!("{}.txt", i));
+            std::fs::write(&path, "old\n")?;
+            paths.push(AbsPath::try_from(path.as_path())?);
+        }
+        // Someone edited this one after the linter ran.
+        std::fs::write(&paths[3], "edited\n")?;
+
+        let patches = paths
+            .iter()
+            .map(|path| Patch {
+                path: path.clone(),
+                original: Some("old\n"),
+                replacement: "new\n",
+            })
+            .collect::<Vec<_>>();
+        let outcomes = apply_patches(&patches, &FileLocks::default(), 4)?;
+
+        for (i, (path, outcome)) in paths.iter().zip(outcomes).enumerate() {
+            if i == 3 {
+                assert_eq!(outcome, PatchOutcome::Stale);
+                assert_eq!(std::fs::read_to_string(path)?, "edited\n");
+            } else {
+                assert_eq!(outcome, PatchOutcome::Applied);
+                assert_eq!(std::fs::read_to_string(path)?, "new\n");
+            }
+        }
+        Ok(())
+    }
+}
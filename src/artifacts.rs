@@ -0,0 +1,146 @@
+//! Keeping the artifacts of failed linters.
+//!
+//! A linter can list files it leaves behind that help debug it, like logs or
+//! crash dumps, as glob patterns relative to the config file:
+//!
+//! ```toml
+//! artifacts = ['.mypy_cache/**/*.log']
+//! ```
+//!
+//! When the linter fails, the matching files are copied into the run's data
+//! dir, where they survive the next run overwriting them. The failure message
+//! says where they went, and `lintrunner rage` lists them (`rage --all`
+//! bundles them).
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::debug;
+
+use crate::{
+    lint_message::LintMessage,
+    linter::{Linter, LINTER_FAILED},
+};
+
+/// Stop copying a linter's artifacts once this many bytes were copied.
+const MAX_ARTIFACT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Copy the files matching `linter`'s artifact patterns into `dest`, keeping
+/// their paths relative to the config dir. Returns those relative paths.
+fn collect(linter: &Linter, dest: &Path) -> Result<Vec<PathBuf>> {
+    let config_dir = linter.get_config_dir();
+    let mut copied = Vec::new();
+    let mut total_bytes = 0;
+    for pattern in &linter.artifacts {
+        let pattern = config_dir.join(pattern);
+        let pattern = pattern.to_string_lossy();
+        let paths = glob::glob(&pattern)
+            .with_context(|| format!("Invalid artifact pattern '{}'", pattern))?;
+        for path in paths.filter_map(|path| path.ok()) {
+            let relative = match path.strip_prefix(config_dir) {
+                Ok(relative) if path.is_file() => relative.to_path_buf(),
+                _ => continue,
+            };
+            if copied.contains(&relative) {
+                continue;
+            }
+            let size = path.metadata()?.len();
+            if total_bytes + size > MAX_ARTIFACT_BYTES {
+                debug!(
+                    "Not keeping artifact {} of linter {}: over {} bytes in total",
+                    path.display(),
+                    linter.code,
+                    MAX_ARTIFACT_BYTES
+                );
+                continue;
+            }
+            let target = dest.join(&relative);
+            std::fs::create_dir_all(target.parent().unwrap_or(dest))?;
+            std::fs::copy(&path, &target)
+                .with_context(|| format!("Failed to copy artifact '{}'", path.display()))?;
+            total_bytes += size;
+            copied.push(relative);
+        }
+    }
+    Ok(copied)
+}
+
+/// If `linter` failed, keep its artifacts in `artifacts_dir` and say so in
+/// the failure message.
+pub fn keep_artifacts(linter: &Linter, lints: &mut [LintMessage], artifacts_dir: &Path) {
+    if linter.artifacts.is_empty() {
+        return;
+    }
+    let failure = match lints
+        .iter_mut()
+        .find(|lint| lint.path.is_none() && lint.name == LINTER_FAILED)
+    {
+        Some(failure) => failure,
+        None => return,
+    };
+    let dest = artifacts_dir.join(&linter.code);
+    let note = match collect(linter, &dest) {
+        Ok(copied) if copied.is_empty() => {
+            "No files matched the linter's artifact patterns.".to_string()
+        }
+        Ok(copied) => format!(
+            "Artifacts of the failed run were saved to {}:\n{}",
+            dest.display(),
+            copied
+                .iter()
+                .map(|path| format!("  {}", path.display()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+        Err(err) => format!("Failed to save the linter's artifacts: {:#}", err),
+    };
+    let description = failure.description.get_or_insert_with(String::new);
+    description.push_str("\n\n");
+    description.push_str(&note);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::path::AbsPath;
+
+    #[test]
+    fn keeps_artifacts_of_failed_linters() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let config_path = root.join(".lintrunner.toml");
+        std::fs::write(&config_path, "")?;
+        std::fs::create_dir_all(root.join("cache/deep"))?;
+        std::fs::write(root.join("cache/deep/crash.log"), "boom")?;
+        std::fs::write(root.join("cache/data.json"), "{}")?;
+        let artifacts_dir = root.join("artifacts");
+
+        let mut linter = Linter::with_config_error(
+            "MYPY",
+            String::new(),
+            &AbsPath::try_from(config_path.as_path())?,
+        );
+        linter.artifacts = vec!["cache/**/*.log".to_string()];
+
+        let failure = crate::linter::linter_failure_message("MYPY", &anyhow::anyhow!("crashed"));
+        let mut lints = vec![failure];
+        keep_artifacts(&linter, &mut lints, &artifacts_dir);
+        let description = lints[0].description.as_deref().unwrap_or_default();
+        assert!(
+            description.ends_with(&format!(
+                "saved to {}:\n  cache/deep/crash.log",
+                artifacts_dir.join("MYPY").display()
+            )),
+            "{}",
+            description
+        );
+        assert_eq!(
+            std::fs::read_to_string(artifacts_dir.join("MYPY/cache/deep/crash.log"))?,
+            "boom"
+        );
+        assert!(!artifacts_dir.join("MYPY/cache/data.json").exists());
+        Ok(())
+    }
+}
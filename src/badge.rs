@@ -0,0 +1,138 @@
+//! Writing an SVG status badge after a run, for `--badge`.
+//!
+//! The badge looks like the ones from shields.io: "lint | passing" in green,
+//! or the number of errors and warnings in red (or yellow, if there are only
+//! warnings).
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+
+use crate::lint_message::{LintMessage, LintSeverity};
+
+const LABEL: &str = "lint";
+const GREEN: &str = "#4c1";
+const YELLOW: &str = "#dfb317";
+const RED: &str = "#e05d44";
+const GREY: &str = "#555";
+
+/// Rough width of `text` in the badge font (11px Verdana), in pixels.
+fn text_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'l' | 'j' | '.' | ',' | ':' | ' ' | '|' | '!' => 4,
+            'm' | 'w' | 'M' | 'W' => 10,
+            _ => 7,
+        })
+        .sum()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+pub(crate) fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+/// The text and color of the badge's right side.
+fn status(lint_messages: &HashMap<Option<String>, Vec<LintMessage>>) -> (String, &'static str) {
+    let count = |severity: fn(&LintSeverity) -> bool| {
+        lint_messages
+            .values()
+            .flatten()
+            .filter(|message| severity(&message.severity))
+            .count()
+    };
+    let errors = count(|s| matches!(s, LintSeverity::Error));
+    let warnings = count(|s| matches!(s, LintSeverity::Warning));
+    match (errors, warnings) {
+        (0, 0) => ("passing".to_string(), GREEN),
+        (0, warnings) => (plural(warnings, "warning"), YELLOW),
+        (errors, 0) => (plural(errors, "error"), RED),
+        (errors, warnings) => (
+            format!(
+                "{}, {}",
+                plural(errors, "error"),
+                plural(warnings, "warning")
+            ),
+            RED,
+        ),
+    }
+}
+
+/// Render the badge for a run that produced `lint_messages`.
+pub fn render_badge(lint_messages: &HashMap<Option<String>, Vec<LintMessage>>) -> String {
+    let (message, color) = status(lint_messages);
+    let label_width = text_width(LABEL) + 10;
+    let message_width = text_width(&message) + 10;
+    let width = label_width + message_width;
+    let label_x = label_width / 2;
+    let message_x = label_width + message_width / 2;
+    let message = escape(&message);
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{LABEL}: {message}">
+  <title>{LABEL}: {message}</title>
+  <linearGradient id="s" x2="0" y2="100%">
+    <stop offset="0" stop-color="#bbb" stop-opacity=".1"/>
+    <stop offset="1" stop-opacity=".1"/>
+  </linearGradient>
+  <clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="20" fill="{GREY}"/>
+    <rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>
+    <rect width="{width}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x}" y="14">{LABEL}</text>
+    <text x="{message_x}" y="14">{message}</text>
+  </g>
+</svg>
+"##
+    )
+}
+
+/// Write the badge for a run that produced `lint_messages` to `path`.
+pub fn write_badge(
+    path: &str,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<()> {
+    std::fs::write(path, render_badge(lint_messages))
+        .with_context(|| format!("Failed to write badge to '{}'", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(severity: LintSeverity) -> LintMessage {
+        LintMessage {
+            path: Some("foo.py".to_string()),
+            ..LintMessage::new("X", severity, "x")
+        }
+    }
+
+    #[test]
+    fn badge_status() {
+        let mut lints = HashMap::new();
+        assert_eq!(status(&lints), ("passing".to_string(), GREEN));
+
+        lints.insert(
+            Some("foo.py".to_string()),
+            vec![
+                message(LintSeverity::Warning),
+                message(LintSeverity::Advice),
+            ],
+        );
+        assert_eq!(status(&lints), ("1 warning".to_string(), YELLOW));
+
+        lints.insert(
+            None,
+            vec![message(LintSeverity::Error), message(LintSeverity::Error)],
+        );
+        assert_eq!(status(&lints), ("2 errors, 1 warning".to_string(), RED));
+        assert!(render_badge(&lints).contains(">2 errors, 1 warning</text>"));
+    }
+}
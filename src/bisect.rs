@@ -0,0 +1,405 @@
+//! Finding the commit that introduced a finding, for `lintrunner bisect-lint`.
+//!
+//! The first-parent history from `--from` to `--to` is linted every `--step`
+//! commits, each revision in a temporary git worktree with the current config
+//! files copied in, so that a linter added just now can be run on the code as
+//! it used to be. Once a sampled revision has the finding, the commits since
+//! the last sample without it are bisected for the first one that has it.
+//!
+//! Linter commands run in the worktree, so any adapter scripts they refer to
+//! are the ones at that revision. The config files have to be trusted before
+//! the search starts, since their copies can't be.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use log::{debug, warn};
+use tempfile::TempDir;
+
+use crate::{
+    exit_code::UsageResult,
+    lint_message::LintMessage,
+    linter::LINTER_FAILED,
+    log_utils::ensure_output,
+    trust::{check_trusted, TRUST_ALL_ENV},
+};
+
+/// Which messages count as the finding being looked for.
+pub struct FindingFilter {
+    /// The code of the linter that reports it.
+    pub code: String,
+    /// If set, only messages with this name.
+    pub name: Option<String>,
+    /// If set, only messages about this path, relative to the repo root.
+    pub path: Option<String>,
+}
+
+impl FindingFilter {
+    fn matches(&self, message: &LintMessage) -> bool {
+        message.code == self.code
+            && message.name != LINTER_FAILED
+            && self.name.as_ref().is_none_or(|name| &message.name == name)
+            && self.path.as_ref().is_none_or(|path| {
+                message
+                    .path
+                    .as_deref()
+                    .is_some_and(|message_path| Path::new(message_path) == Path::new(path))
+            })
+    }
+}
+
+pub struct BisectOpts {
+    pub from: String,
+    pub to: String,
+    /// Lint every this many commits before bisecting.
+    pub step: usize,
+    pub finding: FindingFilter,
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .context("Failed to run git")?;
+    ensure_output(&format!("git {}", args.join(" ")), &output)?;
+    Ok(String::from_utf8(output.stdout)?.trim().to_string())
+}
+
+/// A git worktree checked out at a revision, removed when dropped.
+struct Worktree {
+    repo_root: PathBuf,
+    path: PathBuf,
+    _dir: TempDir,
+}
+
+impl Worktree {
+    fn new(repo_root: &Path, revision: &str) -> Result<Worktree> {
+        let dir = tempfile::Builder::new()
+            .prefix("lintrunner-bisect")
+            .tempdir()?;
+        let path = dir.path().canonicalize()?.join("worktree");
+        let path_str = path.to_str().context("Temporary directory is not UTF-8")?;
+        git(
+            repo_root,
+            &["worktree", "add", "--detach", "--quiet", path_str, revision],
+        )?;
+        Ok(Worktree {
+            repo_root: repo_root.to_path_buf(),
+            path,
+            _dir: dir,
+        })
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.to_str() {
+            if let Err(err) = git(&self.repo_root, &["worktree", "remove", "--force", path]) {
+                warn!("Failed to remove worktree '{}': {}", path, err);
+            }
+        }
+    }
+}
+
+/// `from` and the first-parent history after it up to `to`, oldest first.
+fn revisions(repo_root: &Path, from: &str, to: &str) -> Result<Vec<String>> {
+    let from = git(
+        repo_root,
+        &["rev-parse", "--verify", &format!("{from}^{{commit}}")],
+    )?;
+    let to = git(
+        repo_root,
+        &["rev-parse", "--verify", &format!("{to}^{{commit}}")],
+    )?;
+    let is_ancestor = Command::new("git")
+        .args(["merge-base", "--is-ancestor", &from, &to])
+        .current_dir(repo_root)
+        .status()?
+        .success();
+    ensure!(is_ancestor, "--from must be an ancestor of --to");
+    let after = git(
+        repo_root,
+        &[
+            "rev-list",
+            "--first-parent",
+            "--reverse",
+            &format!("{from}..{to}"),
+        ],
+    )?;
+    Ok(std::iter::once(from)
+        .chain(after.lines().map(|line| line.to_string()))
+        .collect())
+}
+
+/// The indices of `len` revisions to lint first: every `step`th one, and the
+/// last.
+fn sample(len: usize, step: usize) -> Vec<usize> {
+    let mut indices = (0..len).step_by(step).collect::<Vec<_>>();
+    if len > 0 && indices.last() != Some(&(len - 1)) {
+        indices.push(len - 1);
+    }
+    indices
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    /// The revision at this index is the first with the finding.
+    FirstFound(usize),
+    /// The finding first appeared after the revision at `clean` (or at the
+    /// start, if unset) and at or before the one at `found`, but a revision
+    /// in between couldn't be linted.
+    Between {
+        clean: Option<usize>,
+        found: usize,
+    },
+    NotFound,
+}
+
+/// Find the first of `len` revisions where `has_finding` is true, assuming
+/// that once the finding appears it stays. `has_finding` returns `None` for
+/// revisions that couldn't be linted.
+fn search(
+    len: usize,
+    step: usize,
+    mut has_finding: impl FnMut(usize) -> Result<Option<bool>>,
+) -> Result<Outcome> {
+    let mut last_clean = None;
+    for index in sample(len, step) {
+        match has_finding(index)? {
+            None => continue,
+            Some(false) => last_clean = Some(index),
+            Some(true) => {
+                let mut clean = match last_clean {
+                    Some(clean) => clean,
+                    None if index == 0 => return Ok(Outcome::FirstFound(0)),
+                    None => {
+                        return Ok(Outcome::Between {
+                            clean: None,
+                            found: index,
+                        })
+                    }
+                };
+                let mut found = index;
+                while found - clean > 1 {
+                    let middle = clean + (found - clean) / 2;
+                    match has_finding(middle)? {
+                        Some(true) => found = middle,
+                        Some(false) => clean = middle,
+                        None => {
+                            return Ok(Outcome::Between {
+                                clean: Some(clean),
+                                found,
+                            })
+                        }
+                    }
+                }
+                return Ok(Outcome::FirstFound(found));
+            }
+        }
+    }
+    Ok(Outcome::NotFound)
+}
+
+/// Lint `revision` with linter `code` of the config files, and return its
+/// messages, with paths relative to the repo root.
+fn lint_revision(
+    repo_root: &Path,
+    revision: &str,
+    config_paths: &[PathBuf],
+    config_files: &[PathBuf],
+    code: &str,
+) -> Result<Vec<LintMessage>> {
+    let worktree = Worktree::new(repo_root, revision)?;
+    let copy_of = |file: &Path| -> Result<PathBuf> {
+        let relative = file.strip_prefix(repo_root).with_context(|| {
+            format!(
+                "Config file '{}' is outside of the repo, so it can't be used at other revisions",
+                file.display()
+            )
+        })?;
+        Ok(worktree.path.join(relative))
+    };
+    for file in config_files {
+        let copy = copy_of(file)?;
+        // Unwrap is fine here because the copy is under the worktree.
+        std::fs::create_dir_all(copy.parent().unwrap())?;
+        std::fs::copy(file, &copy)
+            .with_context(|| format!("Failed to copy '{}' into the worktree", file.display()))?;
+    }
+    let configs = config_paths
+        .iter()
+        .map(|path| Ok(copy_of(path)?.display().to_string()))
+        .collect::<Result<Vec<_>>>()?
+        .join(",");
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg(format!("--configs={configs}"))
+        .args(["--all-files", "--output=json", "--non-interactive"])
+        .arg(format!("--take={code}"))
+        // The configs were checked before the search started. Their copies
+        // are untracked and at a new path, so they'd never pass the check.
+        .env(TRUST_ALL_ENV, "1")
+        .current_dir(&worktree.path);
+    debug!("Linting {}: {:?}", revision, command);
+    let output = command.output().context("Failed to run lintrunner")?;
+    // Exits with 1 when there are findings.
+    if output.status.code() != Some(1) {
+        ensure_output("lintrunner", &output)?;
+    }
+    let mut messages = Vec::new();
+    for line in std::str::from_utf8(&output.stdout)?.lines() {
+        let mut message: LintMessage = serde_json::from_str(line)
+            .with_context(|| format!("Failed to parse lintrunner output: '{}'", line))?;
+        if let Some(path) = &message.path {
+            if let Ok(relative) = Path::new(path).strip_prefix(&worktree.path) {
+                message.path = Some(relative.display().to_string());
+            }
+        }
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+fn describe(repo_root: &Path, revision: &str) -> Result<String> {
+    git(repo_root, &["log", "-1", "--format=%h %s", revision])
+}
+
+/// Find the first commit between `opts.from` and `opts.to` where linting
+/// reports `opts.finding`. `config_paths` are the configs passed to
+/// lintrunner, and `config_files` all the files they were read from.
+pub fn do_bisect_lint(
+    opts: &BisectOpts,
+    config_paths: &[String],
+    config_files: &[PathBuf],
+) -> Result<i32> {
+    ensure!(opts.step > 0, "--step must be at least 1");
+    check_trusted(config_files).usage_error()?;
+    let cwd = std::env::current_dir()?;
+    let repo_root = match git(&cwd, &["rev-parse", "--show-toplevel"]) {
+        Ok(root) => PathBuf::from(root).canonicalize()?,
+        Err(_) => bail!("bisect-lint only works in git repos"),
+    };
+    let config_paths = config_paths
+        .iter()
+        .map(|path| Path::new(path).canonicalize())
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let revisions = revisions(&repo_root, &opts.from, &opts.to)?;
+    eprintln!(
+        "Looking for {} in {} revisions",
+        opts.finding.code,
+        revisions.len()
+    );
+
+    let outcome = search(revisions.len(), opts.step, |index| {
+        let revision = &revisions[index];
+        let description = describe(&repo_root, revision)?;
+        let messages = match lint_revision(
+            &repo_root,
+            revision,
+            &config_paths,
+            config_files,
+            &opts.finding.code,
+        ) {
+            Ok(messages) => messages,
+            Err(err) => {
+                warn!("Skipping {}, which failed to lint: {:#}", description, err);
+                return Ok(None);
+            }
+        };
+        if messages
+            .iter()
+            .any(|message| message.code == opts.finding.code && message.name == LINTER_FAILED)
+        {
+            warn!(
+                "Skipping {}, where {} failed",
+                description, opts.finding.code
+            );
+            return Ok(None);
+        }
+        let found = messages
+            .iter()
+            .filter(|message| opts.finding.matches(message))
+            .count();
+        eprintln!("  {}: {} matching", description, found);
+        Ok(Some(found > 0))
+    })?;
+
+    match outcome {
+        Outcome::FirstFound(0) => {
+            println!(
+                "Already found at --from: {}",
+                describe(&repo_root, &revisions[0])?
+            );
+        }
+        Outcome::FirstFound(index) => {
+            println!(
+                "First found at {}",
+                describe(&repo_root, &revisions[index])?
+            );
+        }
+        Outcome::Between { clean, found } => {
+            let after = match clean {
+                Some(clean) => describe(&repo_root, &revisions[clean])?,
+                None => "--from".to_string(),
+            };
+            println!(
+                "First found after {} and at or before {}, but revisions in between couldn't be linted",
+                after,
+                describe(&repo_root, &revisions[found])?
+            );
+        }
+        Outcome::NotFound => {
+            println!("Not found at any linted revision");
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_every_step_and_the_last() {
+        assert_eq!(sample(10, 3), vec![0, 3, 6, 9]);
+        assert_eq!(sample(11, 3), vec![0, 3, 6, 9, 10]);
+        assert_eq!(sample(1, 10), vec![0]);
+        assert_eq!(sample(3, 1), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn bisects_between_samples() {
+        // The finding appears at revision 13 of 40.
+        let mut linted = Vec::new();
+        let outcome = search(40, 10, |index| {
+            linted.push(index);
+            Ok(Some(index >= 13))
+        })
+        .unwrap();
+        assert_eq!(outcome, Outcome::FirstFound(13));
+        assert_eq!(linted, vec![0, 10, 20, 15, 12, 13]);
+
+        assert_eq!(
+            search(40, 10, |_| Ok(Some(false))).unwrap(),
+            Outcome::NotFound
+        );
+        assert_eq!(
+            search(40, 10, |_| Ok(Some(true))).unwrap(),
+            Outcome::FirstFound(0)
+        );
+        // Revision 15 can't be linted.
+        assert_eq!(
+            search(40, 10, |index| Ok((index != 15).then_some(index >= 13))).unwrap(),
+            Outcome::Between {
+                clean: Some(10),
+                found: 20
+            }
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Ratcheting limits on how many findings a linter may report.
+//!
+//! A budget caps the number of messages (errors, warnings and advice) a
+//! linter reports in a run; going over it fails the run. As a cleanup
+//! progresses, `lintrunner budget tighten` lowers each budget to the count
+//! of the last run, so that fixed findings can't come back.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use console::Term;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    persistent_data::PersistentDataStore,
+    trend::SeverityCounts,
+};
+
+/// The most messages linter `code` may report.
+///
+/// # Examples
+/// ```toml
+/// budgets = [{ code = 'MYPY', max = 125 }]
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Budget {
+    pub code: String,
+    pub max: usize,
+}
+
+/// A budget lowered by `lintrunner budget tighten`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TightenedBudget {
+    pub code: String,
+    pub old_max: usize,
+    pub new_max: usize,
+}
+
+/// A linter that reported more messages than its budget allows.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BudgetOverrun {
+    pub code: String,
+    pub max: usize,
+    pub count: usize,
+}
+
+/// Check the counts of a run against `budgets`. Linters that didn't run
+/// aren't checked.
+pub fn check_budgets(
+    budgets: &[Budget],
+    counts: &BTreeMap<String, SeverityCounts>,
+) -> Vec<BudgetOverrun> {
+    budgets
+        .iter()
+        .filter_map(|budget| {
+            let count = counts.get(&budget.code)?.total();
+            (count > budget.max).then(|| BudgetOverrun {
+                code: budget.code.clone(),
+                max: budget.max,
+                count,
+            })
+        })
+        .collect()
+}
+
+/// Whether `message` is allowed by a budget, so it shouldn't fail the run by
+/// itself. Errors that aren't about any file, like a linter failing, are
+/// never covered.
+pub fn covered_by_budget(budgets: &[Budget], message: &LintMessage) -> bool {
+    let linter_error = message.path.is_none() && matches!(message.severity, LintSeverity::Error);
+    !linter_error && budgets.iter().any(|budget| budget.code == message.code)
+}
+
+/// Lower the `max` of every budget in the TOML config `contents` to the
+/// count in `counts`, if that's smaller. Budgets may be written as an array
+/// of inline tables or as `[[budgets]]` tables. Returns the new contents and
+/// the budgets that changed.
+pub fn tighten_budgets(
+    contents: &str,
+    counts: &BTreeMap<String, SeverityCounts>,
+) -> Result<(String, Vec<TightenedBudget>)> {
+    let mut doc = contents.parse::<toml_edit::DocumentMut>()?;
+    let mut tightened = Vec::new();
+    let mut tighten = |table: &mut dyn toml_edit::TableLike| {
+        let code = match table.get("code").and_then(|code| code.as_str()) {
+            Some(code) => code.to_string(),
+            None => return,
+        };
+        let max = table
+            .get("max")
+            .and_then(|max| max.as_integer())
+            .and_then(|max| usize::try_from(max).ok());
+        let count = counts.get(&code).map(SeverityCounts::total);
+        if let (Some(max), Some(count)) = (max, count) {
+            if count < max {
+                // Keep the formatting (and any comment) around the value.
+                if let Some(value) = table.get_mut("max").and_then(|max| max.as_value_mut()) {
+                    let decor = value.decor().clone();
+                    *value = toml_edit::Value::from(count as i64);
+                    *value.decor_mut() = decor;
+                }
+                tightened.push(TightenedBudget {
+                    code,
+                    old_max: max,
+                    new_max: count,
+                });
+            }
+        }
+    };
+    match doc.get_mut("budgets") {
+        Some(toml_edit::Item::Value(toml_edit::Value::Array(array))) => {
+            for budget in array.iter_mut() {
+                if let Some(table) = budget.as_inline_table_mut() {
+                    tighten(table);
+                }
+            }
+        }
+        Some(toml_edit::Item::ArrayOfTables(tables)) => {
+            for table in tables.iter_mut() {
+                tighten(table);
+            }
+        }
+        _ => {}
+    }
+    Ok((doc.to_string(), tightened))
+}
+
+pub fn do_budget_tighten(
+    persistent_data_store: &PersistentDataStore,
+    config_paths: &[String],
+) -> Result<i32> {
+    let stdout = Term::stdout();
+    let counts = match persistent_data_store.trend_points()?.pop() {
+        Some(point) => point.counts,
+        None => {
+            stdout.write_line("No lint runs recorded yet; run `lintrunner --all-files` first.")?;
+            return Ok(1);
+        }
+    };
+
+    let mut any_tightened = false;
+    for path in config_paths {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config '{}'", path))?;
+        let (new_contents, tightened) = tighten_budgets(&contents, &counts)
+            .with_context(|| format!("Failed to parse config '{}'", path))?;
+        if tightened.is_empty() {
+            continue;
+        }
+        std::fs::write(path, new_contents)
+            .with_context(|| format!("Failed to write config '{}'", path))?;
+        for budget in tightened {
+            stdout.write_line(&format!(
+                "{}: {} -> {}",
+                budget.code, budget.old_max, budget.new_max
+            ))?;
+        }
+        any_tightened = true;
+    }
+    if !any_tightened {
+        stdout.write_line("All budgets are already tight.")?;
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(counts: &[(&str, usize)]) -> BTreeMap<String, SeverityCounts> {
+        counts
+            .iter()
+            .map(|(code, warnings)| {
+                (
+                    code.to_string(),
+                    SeverityCounts {
+                        warnings: *warnings,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn checks_budgets() {
+        let budgets = vec![
+            Budget {
+                code: "MYPY".to_string(),
+                max: 10,
+            },
+            Budget {
+                code: "FLAKE8".to_string(),
+                max: 0,
+            },
+        ];
+        assert_eq!(
+            check_budgets(&budgets, &counts(&[("MYPY", 11), ("CLANGFORMAT", 5)])),
+            vec![BudgetOverrun {
+                code: "MYPY".to_string(),
+                max: 10,
+                count: 11
+            }]
+        );
+        assert!(check_budgets(&budgets, &counts(&[("MYPY", 10), ("FLAKE8", 0)])).is_empty());
+    }
+
+    #[test]
+    fn tightens_budgets_in_place() -> Result<()> {
+        let config = "\
+budgets = [
+    { code = 'MYPY', max = 125 }, # burning down
+    { code = 'FLAKE8', max = 3 },
+]
+
+[[linter]]
+code = 'MYPY'
+";
+        let (new, tightened) = tighten_budgets(config, &counts(&[("MYPY", 118), ("FLAKE8", 7)]))?;
+        assert_eq!(
+            tightened,
+            vec![TightenedBudget {
+                code: "MYPY".to_string(),
+                old_max: 125,
+                new_max: 118
+            }]
+        );
+        assert_eq!(new, config.replace("max = 125", "max = 118"));
+
+        let config = "[[budgets]]\ncode = 'MYPY'\nmax = 5\n";
+        let (new, _) = tighten_budgets(config, &counts(&[("MYPY", 2)]))?;
+        assert_eq!(new, "[[budgets]]\ncode = 'MYPY'\nmax = 2\n");
+        Ok(())
+    }
+}
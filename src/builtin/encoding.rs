@@ -0,0 +1,231 @@
+//! The `encoding` builtin: checks that files use the expected text encoding.
+//!
+//! Files are expected to be UTF-8 without a byte order mark unless configured
+//! otherwise. BOMs are stripped by a safe fix; files that aren't valid UTF-8
+//! get a suggested fix that re-encodes them from Latin-1, the usual culprit.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use glob::Pattern;
+use serde::Deserialize;
+
+use super::{message, BuiltinLinter};
+use crate::{
+    lint_config::patterns_from_strs,
+    lint_message::{FixKind, LintMessage, LintSeverity},
+    linter::matches_relative_path,
+    path::AbsPath,
+};
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// At most this many bad byte offsets are listed per file.
+const MAX_REPORTED_OFFSETS: usize = 5;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "ascii")]
+    Ascii,
+    /// Any byte sequence is valid Latin-1, so this effectively disables the
+    /// check for matching files.
+    #[serde(rename = "latin-1")]
+    Latin1,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EncodingOverride {
+    /// Patterns, relative to the config, of files this applies to.
+    patterns: Vec<String>,
+    encoding: Encoding,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EncodingOptions {
+    /// The expected encoding. Defaults to UTF-8.
+    encoding: Option<Encoding>,
+    /// Whether UTF-8 files may start with a byte order mark. Defaults to
+    /// false.
+    allow_bom: Option<bool>,
+    /// Different encodings for some files. The first matching override wins.
+    overrides: Option<Vec<EncodingOverride>>,
+}
+
+pub struct EncodingCheck {
+    encoding: Encoding,
+    allow_bom: bool,
+    overrides: Vec<(Vec<Pattern>, Encoding)>,
+    config_dir: PathBuf,
+}
+
+/// The 1-based line and column of byte `offset` in `contents`.
+fn line_and_column(contents: &[u8], offset: usize) -> (usize, usize) {
+    let before = &contents[..offset];
+    let line_start = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |pos| pos + 1);
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let column = String::from_utf8_lossy(&before[line_start..])
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+/// Byte offsets of invalid sequences in `contents` for `encoding`.
+fn invalid_offsets(contents: &[u8], encoding: Encoding) -> Vec<usize> {
+    match encoding {
+        Encoding::Latin1 => Vec::new(),
+        Encoding::Ascii => contents
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| !b.is_ascii())
+            .map(|(offset, _)| offset)
+            .collect(),
+        Encoding::Utf8 => {
+            let mut offsets = Vec::new();
+            let mut start = 0;
+            while let Err(err) = std::str::from_utf8(&contents[start..]) {
+                let offset = start + err.valid_up_to();
+                offsets.push(offset);
+                start = offset + err.error_len().unwrap_or(contents.len() - offset);
+            }
+            offsets
+        }
+    }
+}
+
+fn decode_latin1(contents: &[u8]) -> String {
+    contents.iter().map(|&b| b as char).collect()
+}
+
+impl EncodingCheck {
+    pub fn new(options: EncodingOptions, config_dir: &Path) -> Result<EncodingCheck> {
+        let overrides = options
+            .overrides
+            .unwrap_or_default()
+            .into_iter()
+            .map(|o| Ok((patterns_from_strs(&o.patterns)?, o.encoding)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(EncodingCheck {
+            encoding: options.encoding.unwrap_or(Encoding::Utf8),
+            allow_bom: options.allow_bom.unwrap_or(false),
+            overrides,
+            config_dir: config_dir.to_path_buf(),
+        })
+    }
+
+    fn encoding_for(&self, path: &AbsPath) -> Encoding {
+        self.overrides
+            .iter()
+            .find(|(patterns, _)| {
+                patterns
+                    .iter()
+                    .any(|pattern| matches_relative_path(&self.config_dir, path, pattern))
+            })
+            .map_or(self.encoding, |(_, encoding)| *encoding)
+    }
+}
+
+impl BuiltinLinter for EncodingCheck {
+    fn lint_file(&self, code: &str, path: &AbsPath) -> Result<Vec<LintMessage>> {
+        let contents = std::fs::read(path)?;
+        let encoding = self.encoding_for(path);
+        let mut messages = Vec::new();
+
+        let has_bom = contents.starts_with(UTF8_BOM);
+        if encoding == Encoding::Utf8 && has_bom && !self.allow_bom {
+            let mut bom_message = message(
+                code,
+                path,
+                Some(1),
+                Some(1),
+                LintSeverity::Error,
+                "utf8-bom",
+                "This file starts with a UTF-8 byte order mark, which some tools \
+                 don't handle. Remove it."
+                    .to_string(),
+            );
+            // Only offer the fix if it's the only problem, since a file gets at
+            // most one replacement.
+            if let Ok(text) = std::str::from_utf8(&contents[UTF8_BOM.len()..]) {
+                bom_message.original = Some(String::from_utf8_lossy(&contents).into_owned());
+                bom_message.replacement = Some(text.to_string());
+            }
+            messages.push(bom_message);
+        }
+
+        let offsets = invalid_offsets(&contents, encoding);
+        if let Some(&first) = offsets.first() {
+            let (line, column) = line_and_column(&contents, first);
+            let listed = offsets
+                .iter()
+                .take(MAX_REPORTED_OFFSETS)
+                .map(|&offset| {
+                    let (line, _) = line_and_column(&contents, offset);
+                    format!("{} (line {}, 0x{:02X})", offset, line, contents[offset])
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let more = if offsets.len() > MAX_REPORTED_OFFSETS {
+                format!(", and {} more", offsets.len() - MAX_REPORTED_OFFSETS)
+            } else {
+                String::new()
+            };
+            let (name, expected) = match encoding {
+                Encoding::Ascii => ("non-ascii", "ASCII"),
+                _ => ("invalid-utf8", "UTF-8"),
+            };
+            let mut invalid_message = message(
+                code,
+                path,
+                Some(line),
+                Some(column),
+                LintSeverity::Error,
+                name,
+                format!(
+                    "This file is not valid {}. Invalid bytes at offsets {}{}.",
+                    expected, listed, more
+                ),
+            );
+            if encoding == Encoding::Utf8 && messages.is_empty() {
+                invalid_message.description = invalid_message.description.map(|description| {
+                    format!(
+                        "{}\nThe suggested fix re-encodes the file as UTF-8, assuming it \
+                         is Latin-1. Check the result before accepting it.",
+                        description
+                    )
+                });
+                invalid_message.original = Some(String::from_utf8_lossy(&contents).into_owned());
+                invalid_message.replacement = Some(decode_latin1(&contents));
+                invalid_message.fix_kind = Some(FixKind::Suggested);
+            }
+            messages.push(invalid_message);
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_invalid_sequences() {
+        let contents = b"ok\ncaf\xE9 \xFF\xFEx\n";
+        assert_eq!(invalid_offsets(contents, Encoding::Utf8), vec![6, 8, 9]);
+        assert_eq!(line_and_column(contents, 6), (2, 4));
+        assert_eq!(
+            invalid_offsets("naïve".as_bytes(), Encoding::Ascii),
+            vec![2, 3]
+        );
+        assert!(invalid_offsets("naïve".as_bytes(), Encoding::Utf8).is_empty());
+        assert!(invalid_offsets(contents, Encoding::Latin1).is_empty());
+        assert_eq!(decode_latin1(b"caf\xE9"), "café");
+    }
+}
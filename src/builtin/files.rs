@@ -0,0 +1,135 @@
+//! Cheap checks on files as a whole: the `large_files`, `conflict_markers`
+//! and `broken_symlinks` builtins.
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use super::{message, BuiltinLinter};
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    path::AbsPath,
+};
+
+const DEFAULT_MAX_KB: u64 = 500;
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LargeFilesOptions {
+    /// Files larger than this many KB are reported. Defaults to 500.
+    max_kb: Option<u64>,
+}
+
+/// Reports files over a size limit, e.g. binaries that should be in LFS.
+pub struct LargeFiles {
+    max_kb: u64,
+}
+
+impl LargeFiles {
+    pub fn new(options: LargeFilesOptions) -> LargeFiles {
+        LargeFiles {
+            max_kb: options.max_kb.unwrap_or(DEFAULT_MAX_KB),
+        }
+    }
+}
+
+impl BuiltinLinter for LargeFiles {
+    fn lint_file(&self, code: &str, path: &AbsPath) -> Result<Vec<LintMessage>> {
+        let kb = std::fs::metadata(path)?.len() / 1024;
+        if kb <= self.max_kb {
+            return Ok(Vec::new());
+        }
+        Ok(vec![message(
+            code,
+            path,
+            None,
+            None,
+            LintSeverity::Error,
+            "large-file",
+            format!(
+                "This file is {} KB, which is over the limit of {} KB.",
+                kb, self.max_kb
+            ),
+        )])
+    }
+}
+
+/// Reports merge conflict markers left in files.
+pub struct ConflictMarkers;
+
+fn is_conflict_marker(line: &str) -> bool {
+    line.starts_with("<<<<<<< ")
+        || line.starts_with(">>>>>>> ")
+        || line.starts_with("||||||| ")
+        || line.trim_end() == "======="
+}
+
+impl BuiltinLinter for ConflictMarkers {
+    fn lint_file(&self, code: &str, path: &AbsPath) -> Result<Vec<LintMessage>> {
+        let contents = std::fs::read(path)?;
+        if contents.contains(&0) {
+            return Ok(Vec::new());
+        }
+        let contents = String::from_utf8_lossy(&contents);
+        Ok(contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| is_conflict_marker(line))
+            .map(|(i, _)| {
+                message(
+                    code,
+                    path,
+                    Some(i + 1),
+                    Some(1),
+                    LintSeverity::Error,
+                    "merge-conflict-marker",
+                    "Merge conflict marker. Resolve the conflict before committing.".to_string(),
+                )
+            })
+            .collect())
+    }
+}
+
+/// Reports symlinks whose target doesn't exist.
+pub struct BrokenSymlinks;
+
+impl BuiltinLinter for BrokenSymlinks {
+    fn lint_file(&self, code: &str, path: &AbsPath) -> Result<Vec<LintMessage>> {
+        // Paths of valid symlinks are resolved before linters see them, so
+        // any symlink here is dangling.
+        if !path.symlink_metadata()?.file_type().is_symlink() || path.exists() {
+            return Ok(Vec::new());
+        }
+        let target = std::fs::read_link(path)?;
+        Ok(vec![message(
+            code,
+            path,
+            None,
+            None,
+            LintSeverity::Error,
+            "broken-symlink",
+            format!(
+                "This symlink points to '{}', which does not exist.",
+                target.display()
+            ),
+        )])
+    }
+
+    fn accepts_dangling_symlinks(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflict_markers() {
+        assert!(is_conflict_marker("<<<<<<< HEAD"));
+        assert!(is_conflict_marker("======="));
+        assert!(is_conflict_marker(">>>>>>> feature"));
+        // reStructuredText headings and the like.
+        assert!(!is_conflict_marker("=========="));
+        assert!(!is_conflict_marker("<<<<<<<<"));
+    }
+}
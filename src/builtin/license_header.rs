@@ -0,0 +1,202 @@
+//! The `license_header` builtin: checks that files start with a license or
+//! copyright header, and offers a fix that inserts it.
+//!
+//! Headers are templates in which `{year}` stands for a year. When checking,
+//! any year (or range of years, like `2019-2023`) is accepted; when inserting,
+//! the current year is used.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use chrono::Datelike;
+use glob::Pattern;
+use regex::Regex;
+use serde::Deserialize;
+
+use super::{message, BuiltinLinter};
+use crate::{
+    lint_config::patterns_from_strs,
+    lint_message::{LintMessage, LintSeverity},
+    linter::matches_relative_path,
+    path::AbsPath,
+};
+
+const YEAR_PLACEHOLDER: &str = "{year}";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HeaderConfig {
+    /// Patterns, relative to the config, of files this header applies to.
+    patterns: Vec<String>,
+    /// The header itself.
+    template: Option<String>,
+    /// A file, relative to the config, containing the header.
+    template_file: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LicenseHeaderOptions {
+    /// Headers for different kinds of files. The first matching one is used,
+    /// and files that match none are not checked.
+    headers: Vec<HeaderConfig>,
+}
+
+struct Header {
+    patterns: Vec<Pattern>,
+    /// The template, ending with a newline.
+    template: String,
+    /// Matches the template, with any year, at the start of a file.
+    regex: Regex,
+}
+
+pub struct LicenseHeader {
+    headers: Vec<Header>,
+    config_dir: PathBuf,
+}
+
+fn header_regex(template: &str) -> Result<Regex> {
+    let pattern = regex::escape(template.trim_end())
+        .replace(&regex::escape(YEAR_PLACEHOLDER), r"\d{4}(?:-\d{4})?");
+    Ok(Regex::new(&format!("^{}", pattern))?)
+}
+
+/// Split off a leading `#!` line, which must stay first.
+fn split_shebang(contents: &str) -> (&str, &str) {
+    if contents.starts_with("#!") {
+        let end = contents.find('\n').map_or(contents.len(), |pos| pos + 1);
+        contents.split_at(end)
+    } else {
+        ("", contents)
+    }
+}
+
+impl Header {
+    fn new(config: HeaderConfig, config_dir: &Path) -> Result<Header> {
+        let mut template = match (config.template, config.template_file) {
+            (Some(template), None) => template,
+            (None, Some(path)) => {
+                let path = config_dir.join(path);
+                std::fs::read_to_string(&path).with_context(|| {
+                    format!("Failed to read header template '{}'", path.display())
+                })?
+            }
+            _ => bail!("Each header needs exactly one of 'template' or 'template_file'"),
+        };
+        if !template.ends_with('\n') {
+            template.push('\n');
+        }
+        Ok(Header {
+            patterns: patterns_from_strs(&config.patterns)?,
+            regex: header_regex(&template)?,
+            template,
+        })
+    }
+
+    /// `contents` with this header inserted, after any shebang line.
+    fn insert_into(&self, contents: &str, year: i32) -> String {
+        let (shebang, rest) = split_shebang(contents);
+        let header = self.template.replace(YEAR_PLACEHOLDER, &year.to_string());
+        let separator = if shebang.is_empty() || shebang.ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        format!("{}{}{}{}", shebang, separator, header, rest)
+    }
+}
+
+impl LicenseHeader {
+    pub fn new(options: LicenseHeaderOptions, config_dir: &Path) -> Result<LicenseHeader> {
+        let headers = options
+            .headers
+            .into_iter()
+            .map(|config| Header::new(config, config_dir))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(LicenseHeader {
+            headers,
+            config_dir: config_dir.to_path_buf(),
+        })
+    }
+}
+
+impl BuiltinLinter for LicenseHeader {
+    fn lint_file(&self, code: &str, path: &AbsPath) -> Result<Vec<LintMessage>> {
+        let header = self.headers.iter().find(|header| {
+            header
+                .patterns
+                .iter()
+                .any(|pattern| matches_relative_path(&self.config_dir, path, pattern))
+        });
+        let header = match header {
+            Some(header) => header,
+            None => return Ok(Vec::new()),
+        };
+        let bytes = std::fs::read(path)?;
+        let contents = String::from_utf8_lossy(&bytes);
+        let (_, rest) = split_shebang(&contents);
+        if header.regex.is_match(rest) {
+            return Ok(Vec::new());
+        }
+        let mut lint = message(
+            code,
+            path,
+            Some(1),
+            Some(1),
+            LintSeverity::Error,
+            "missing-license-header",
+            "This file is missing the required license header.".to_string(),
+        );
+        // Rewriting a file that isn't valid UTF-8 would mangle it.
+        if let Ok(contents) = std::str::from_utf8(&bytes) {
+            lint.original = Some(contents.to_string());
+            lint.replacement = Some(header.insert_into(contents, chrono::Local::now().year()));
+        }
+        Ok(vec![lint])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(template: &str) -> Header {
+        Header::new(
+            HeaderConfig {
+                patterns: vec!["**".to_string()],
+                template: Some(template.to_string()),
+                template_file: None,
+            },
+            Path::new("."),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn matches_any_year() {
+        let header = header("# Copyright (c) {year} Acme Inc.");
+        assert!(header
+            .regex
+            .is_match("# Copyright (c) 2019 Acme Inc.\nimport os\n"));
+        assert!(header
+            .regex
+            .is_match("# Copyright (c) 2019-2023 Acme Inc.\n"));
+        assert!(!header.regex.is_match("# Copyright (c) 2019 Someone Else\n"));
+        assert!(!header
+            .regex
+            .is_match("import os\n# Copyright (c) 2019 Acme Inc.\n"));
+    }
+
+    #[test]
+    fn inserts_after_shebang() {
+        let header = header("# Copyright (c) {year} Acme Inc.\n\n");
+        assert_eq!(
+            header.insert_into("#!/usr/bin/env python\nimport os\n", 2024),
+            "#!/usr/bin/env python\n# Copyright (c) 2024 Acme Inc.\n\nimport os\n"
+        );
+        assert_eq!(
+            header.insert_into("import os\n", 2024),
+            "# Copyright (c) 2024 Acme Inc.\n\nimport os\n"
+        );
+    }
+}
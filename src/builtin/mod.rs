@@ -0,0 +1,112 @@
+//! Linters implemented in lintrunner itself, for checks that are common
+//! enough (or sensitive enough) that they shouldn't need an external tool.
+//!
+//! Builtins are configured like any other linter, but with `kind = 'builtin'`
+//! and the name of the builtin instead of a `command`. Options specific to
+//! the builtin go in an `options` table:
+//!
+//! ```toml
+//! [[linter]]
+//! code = 'SECRETS'
+//! kind = 'builtin'
+//! builtin = 'secrets'
+//! include_patterns = ['**']
+//! options = { allowlist = '.secrets-allowlist' }
+//! ```
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use figment::value::Value;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    path::AbsPath,
+};
+
+mod encoding;
+mod files;
+mod license_header;
+mod secrets;
+
+/// The names of all builtins, for error messages.
+const BUILTIN_NAMES: &[&str] = &[
+    "secrets",
+    "large_files",
+    "conflict_markers",
+    "broken_symlinks",
+    "encoding",
+    "license_header",
+];
+
+pub trait BuiltinLinter: Send + Sync {
+    /// Lint a single file, reporting messages under `code`.
+    fn lint_file(&self, code: &str, path: &AbsPath) -> Result<Vec<LintMessage>>;
+
+    /// Whether this builtin should be given symlinks whose target doesn't
+    /// exist. Other linters never see them.
+    fn accepts_dangling_symlinks(&self) -> bool {
+        false
+    }
+}
+
+fn parse_options<T: DeserializeOwned + Default>(options: Option<&Value>) -> Result<T> {
+    match options {
+        Some(options) => Ok(options.deserialize()?),
+        None => Ok(T::default()),
+    }
+}
+
+/// Create the builtin called `name`. Paths in `options` are relative to
+/// `config_dir`.
+pub fn builtin_from_config(
+    name: &str,
+    options: Option<&Value>,
+    config_dir: &Path,
+) -> Result<Box<dyn BuiltinLinter>> {
+    let builtin: Box<dyn BuiltinLinter> = match name {
+        "secrets" => Box::new(secrets::Secrets::new(
+            parse_options(options).context("Invalid options for builtin 'secrets'")?,
+            config_dir,
+        )?),
+        "large_files" => Box::new(files::LargeFiles::new(
+            parse_options(options).context("Invalid options for builtin 'large_files'")?,
+        )),
+        "conflict_markers" => Box::new(files::ConflictMarkers),
+        "broken_symlinks" => Box::new(files::BrokenSymlinks),
+        "encoding" => Box::new(encoding::EncodingCheck::new(
+            parse_options(options).context("Invalid options for builtin 'encoding'")?,
+            config_dir,
+        )?),
+        "license_header" => Box::new(license_header::LicenseHeader::new(
+            parse_options(options).context("Invalid options for builtin 'license_header'")?,
+            config_dir,
+        )?),
+        other => bail!(
+            "Unknown builtin '{}'. Available builtins: {}",
+            other,
+            BUILTIN_NAMES.join(", ")
+        ),
+    };
+    Ok(builtin)
+}
+
+/// A lint message about `path`, for builtins.
+fn message(
+    code: &str,
+    path: &AbsPath,
+    line: Option<usize>,
+    char: Option<usize>,
+    severity: LintSeverity,
+    name: &str,
+    description: String,
+) -> LintMessage {
+    LintMessage {
+        path: Some(path.display().to_string()),
+        line,
+        char,
+        description: Some(description),
+        ..LintMessage::new(code, severity, name)
+    }
+}
@@ -0,0 +1,119 @@
+//! Optional features of the linter protocol that a linter can declare
+//! support for, so that lintrunner can pick the best way to invoke it.
+//!
+//! Capabilities are either declared in the linter's config:
+//!
+//! ```toml
+//! capabilities = { json_pathsfile = true, fix = true }
+//! ```
+//!
+//! or, with `probe_capabilities = true`, reported by the linter itself.
+//! lintrunner runs the linter's command with any `{{PATHSFILE}}` argument
+//! dropped and `--lintrunner-capabilities` appended, and expects a JSON object
+//! of the same form on stdout, e.g. `{"json_pathsfile": true}`. Capabilities
+//! the linter doesn't mention are assumed to be unsupported.
+
+use std::{
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::network::{self, NetworkPolicy};
+
+/// Appended to a linter's command to ask for its capabilities.
+pub const PROBE_ARG: &str = "--lintrunner-capabilities";
+
+/// A probe that takes longer than this fails, and the linter is assumed to
+/// support nothing.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Capabilities {
+    /// Accepts the JSON `{{PATHSFILE}}` format (see [`crate::pathsfile`]). It
+    /// is used even if the linter's `pathsfile_format` isn't set.
+    pub json_pathsfile: bool,
+    /// Can limit its checks to the changed line ranges in the JSON paths
+    /// file. When only lints on changed lines will be reported anyway (with
+    /// `--diff-file`), `LINTRUNNER_LINE_RANGES=1` is set.
+    pub line_ranges: bool,
+    /// Can read its paths file from stdin. If the command has no
+    /// `{{PATHSFILE}}` argument, the paths file is written to its stdin.
+    pub stdin: bool,
+    /// Only computes fixes when asked to. When patches are going to be
+    /// applied, `LINTRUNNER_FIX=1` is set.
+    pub fix: bool,
+}
+
+/// Ask a linter for its capabilities by running `command` (with
+/// [`PROBE_ARG`] appended) in `cwd`, for at most [`PROBE_TIMEOUT`]. `command`
+/// should already be sandboxed as `network` requires; the environment is
+/// restricted here.
+pub fn probe(
+    command: &[String],
+    cwd: &Path,
+    env: &[(&str, &str)],
+    network: NetworkPolicy,
+) -> Result<Capabilities> {
+    let (program, arguments) = command.split_first().context("Linter command is empty")?;
+    let mut probe = Command::new(program);
+    probe
+        .args(arguments)
+        .arg(PROBE_ARG)
+        .current_dir(cwd)
+        .envs(env.iter().copied());
+    if !network.allows_run() {
+        network::strip_env(&mut probe);
+    }
+    let mut child = probe
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", program))?;
+    // Read stdout on another thread, so the probe can't block on a full pipe
+    // while we wait for it to exit. Unwrap ok because stdout is piped.
+    let mut stdout = child.stdout.take().unwrap();
+    let reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        stdout.read_to_end(&mut buf).map(|_| buf)
+    });
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > PROBE_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!(
+                "'{} {}' didn't finish within {} seconds",
+                program,
+                PROBE_ARG,
+                PROBE_TIMEOUT.as_secs()
+            );
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    ensure!(
+        status.success(),
+        "'{} {}' failed with {}",
+        program,
+        PROBE_ARG,
+        status
+    );
+    // Unwrap ok because reading doesn't panic.
+    let stdout = reader.join().unwrap()?;
+    serde_json::from_slice(&stdout).with_context(|| {
+        format!(
+            "'{} {}' didn't print a JSON object of capabilities",
+            program, PROBE_ARG
+        )
+    })
+}
@@ -0,0 +1,107 @@
+//! Mapping files to the Cargo crates they belong to, for linters that use
+//! `{{CRATE_DIR}}`.
+//!
+//! Tools like clippy and rustfmt work on a crate at a time and need to run
+//! from inside it. A linter whose command contains `{{CRATE_DIR}}` is run once
+//! per crate that has matching files, from the crate's directory, with only
+//! that crate's files in `{{PATHSFILE}}`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use crate::path::AbsPath;
+
+pub const CRATE_DIR_PLACEHOLDER: &str = "{{CRATE_DIR}}";
+
+/// Whether `dir` has a `Cargo.toml` describing a package (as opposed to a
+/// virtual workspace manifest, or no manifest at all).
+fn is_crate_dir(dir: &Path) -> bool {
+    let manifest = match std::fs::read_to_string(dir.join("Cargo.toml")) {
+        Ok(manifest) => manifest,
+        Err(_) => return false,
+    };
+    match toml::from_str::<toml::Value>(&manifest) {
+        Ok(manifest) => manifest.get("package").is_some(),
+        Err(err) => {
+            debug!(
+                "Ignoring invalid manifest '{}': {}",
+                dir.join("Cargo.toml").display(),
+                err
+            );
+            false
+        }
+    }
+}
+
+/// Group `files` by the crate they belong to: the closest directory above
+/// them, up to `root`, with a package manifest. Also returns the files that
+/// aren't in any crate.
+pub fn group_by_crate(
+    files: &[AbsPath],
+    root: &Path,
+) -> (BTreeMap<PathBuf, Vec<AbsPath>>, Vec<AbsPath>) {
+    let mut is_crate_cache: HashMap<PathBuf, bool> = HashMap::new();
+    let mut crates: BTreeMap<PathBuf, Vec<AbsPath>> = BTreeMap::new();
+    let mut outside = Vec::new();
+    for file in files {
+        let crate_dir = file
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(root))
+            .find(|dir| {
+                *is_crate_cache
+                    .entry(dir.to_path_buf())
+                    .or_insert_with(|| is_crate_dir(dir))
+            });
+        match crate_dir {
+            Some(crate_dir) => crates
+                .entry(crate_dir.to_path_buf())
+                .or_default()
+                .push(file.clone()),
+            None => outside.push(file.clone()),
+        }
+    }
+    (crates, outside)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn groups_files_by_workspace_member() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let write = |path: &str, contents: &str| -> Result<AbsPath> {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, contents)?;
+            AbsPath::try_from(path.as_path())
+        };
+        write("Cargo.toml", "[workspace]\nmembers = ['a', 'b']\n")?;
+        write("a/Cargo.toml", "[package]\nname = 'a'\n")?;
+        write("b/Cargo.toml", "[package]\nname = 'b'\n")?;
+        let a_lib = write("a/src/lib.rs", "")?;
+        let a_mod = write("a/src/foo/mod.rs", "")?;
+        let b_main = write("b/src/main.rs", "")?;
+        let script = write("scripts/build.rs", "")?;
+
+        let (crates, outside) = group_by_crate(
+            &[a_lib.clone(), a_mod.clone(), b_main.clone(), script.clone()],
+            &root,
+        );
+        assert_eq!(crates.len(), 2);
+        assert!(crates[&root.join("a")] == vec![a_lib, a_mod]);
+        assert!(crates[&root.join("b")] == vec![b_main]);
+        assert!(outside == vec![script]);
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! Converting columns between the units tools count them in.
+//!
+//! Lint messages are kept with columns in Unicode code points. Linters that
+//! report byte offsets or UTF-16 code units set `column_unit` in their config,
+//! and their messages are converted when they are parsed. Output is converted
+//! again as needed: to `--column-unit` for the default, oneline and JSON
+//! outputs, and always to UTF-16 for the VS Code output, as LSP requires.
+
+use std::collections::HashMap;
+
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::lint_message::LintMessage;
+
+/// What a column number counts.
+#[derive(
+    Serialize,
+    Deserialize,
+    ArgEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnUnit {
+    /// Bytes of UTF-8.
+    Byte,
+    /// Unicode code points.
+    #[default]
+    Char,
+    /// UTF-16 code units, as used by LSP and JavaScript.
+    Utf16,
+}
+
+impl ColumnUnit {
+    fn width(self, c: char) -> usize {
+        match self {
+            ColumnUnit::Byte => c.len_utf8(),
+            ColumnUnit::Char => 1,
+            ColumnUnit::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+/// Convert the 1-based `column` on `line` from `from` units to `to` units.
+/// A column in the middle of a character maps to the start of it, and
+/// columns past the end of the line count one unit per column.
+pub fn convert_column(line: &str, column: usize, from: ColumnUnit, to: ColumnUnit) -> usize {
+    if from == to || column == 0 {
+        return column;
+    }
+    let target = column - 1;
+    let mut from_offset = 0;
+    let mut to_offset = 0;
+    for c in line.chars() {
+        let from_width = from.width(c);
+        if from_offset + from_width > target {
+            return to_offset + 1;
+        }
+        from_offset += from_width;
+        to_offset += to.width(c);
+    }
+    to_offset + (target - from_offset) + 1
+}
+
+/// Lines of files, read as they are needed.
+#[derive(Default)]
+pub struct LineCache {
+    files: HashMap<String, Option<Vec<String>>>,
+}
+
+impl LineCache {
+    /// The 1-based `line` of the file at `path`, if it can be read.
+    pub fn line(&mut self, path: &str, line: usize) -> Option<&str> {
+        let lines = self.files.entry(path.to_string()).or_insert_with(|| {
+            std::fs::read_to_string(path)
+                .ok()
+                .map(|contents| contents.lines().map(str::to_string).collect())
+        });
+        lines
+            .as_ref()?
+            .get(line.checked_sub(1)?)
+            .map(String::as_str)
+    }
+
+    fn convert(
+        &mut self,
+        path: &str,
+        line: Option<usize>,
+        column: &mut Option<usize>,
+        from: ColumnUnit,
+        to: ColumnUnit,
+    ) {
+        if let (Some(line), Some(col)) = (line, column.as_mut()) {
+            // Columns past the end of the file are left alone.
+            let text = self.line(path, line).unwrap_or_default();
+            *col = convert_column(text, *col, from, to);
+        }
+    }
+}
+
+/// Convert all columns in `message` from `from` units to `to` units.
+pub fn convert_message(
+    message: &mut LintMessage,
+    from: ColumnUnit,
+    to: ColumnUnit,
+    cache: &mut LineCache,
+) {
+    if from == to {
+        return;
+    }
+    if let Some(path) = &message.path {
+        cache.convert(path, message.line, &mut message.char, from, to);
+        cache.convert(path, message.end_line, &mut message.end_char, from, to);
+        for suggestion in &mut message.suggestions {
+            for edit in &mut suggestion.edits {
+                let mut char = Some(edit.char);
+                let mut end_char = Some(edit.end_char);
+                cache.convert(path, Some(edit.line), &mut char, from, to);
+                cache.convert(path, Some(edit.end_line), &mut end_char, from, to);
+                edit.char = char.unwrap_or(edit.char);
+                edit.end_char = end_char.unwrap_or(edit.end_char);
+            }
+        }
+    }
+    for related in &mut message.related {
+        cache.convert(&related.path, related.line, &mut related.char, from, to);
+        cache.convert(
+            &related.path,
+            related.end_line,
+            &mut related.end_char,
+            from,
+            to,
+        );
+    }
+}
+
+/// Convert all columns in `messages` from `from` units to `to` units.
+pub fn convert_messages(messages: &mut [LintMessage], from: ColumnUnit, to: ColumnUnit) {
+    if from == to {
+        return;
+    }
+    let mut cache = LineCache::default();
+    for message in messages {
+        convert_message(message, from, to, &mut cache);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_columns() {
+        use ColumnUnit::*;
+        // 'é' is 2 bytes and 1 UTF-16 unit, '😀' is 4 bytes and 2 units.
+        let line = "é😀x";
+        assert_eq!(convert_column(line, 4, Char, Byte), 8);
+        assert_eq!(convert_column(line, 7, Byte, Char), 3);
+        assert_eq!(convert_column(line, 3, Char, Utf16), 4);
+        assert_eq!(convert_column(line, 4, Utf16, Byte), 7);
+        // The middle of a character maps to its start.
+        assert_eq!(convert_column(line, 4, Byte, Char), 2);
+        // Past the end of the line.
+        assert_eq!(convert_column(line, 6, Char, Byte), 10);
+        assert_eq!(convert_column(line, 1, Utf16, Byte), 1);
+    }
+}
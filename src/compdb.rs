@@ -0,0 +1,130 @@
+//! Keeping a compilation database (`compile_commands.json`) up to date for
+//! linters that need one, like clang-tidy.
+//!
+//! The database is generated by the command in the config's `[compdb]`
+//! section, before running any linter that sets `needs_compdb = true`. It is
+//! only regenerated when the files it is generated from change, as tracked by
+//! a hash of their contents kept in the persistent data directory.
+
+use std::{path::Path, process::Command};
+
+use anyhow::{bail, ensure, Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{linter::Linter, log_utils::ensure_output, persistent_data::PersistentDataStore};
+
+const DEFAULT_OUTPUT: &str = "compile_commands.json";
+
+/// How to generate the compilation database.
+///
+/// # Examples
+/// ```toml
+/// [compdb]
+/// command = ['cmake', '-B', 'build', '-DCMAKE_EXPORT_COMPILE_COMMANDS=ON']
+/// inputs = ['CMakeLists.txt', '**/CMakeLists.txt', 'cmake/**/*.cmake']
+/// output = 'build/compile_commands.json'
+/// ```
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CompdbConfig {
+    /// The command that generates the database. It is run from the directory
+    /// of the config file.
+    pub command: Vec<String>,
+
+    /// Glob patterns, relative to the config file, of the build files the
+    /// database is generated from. The database is regenerated when any of
+    /// them change.
+    pub inputs: Vec<String>,
+
+    /// Where the command writes the database, relative to the config file.
+    /// Defaults to `compile_commands.json`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// Hash the command and the paths and contents of all inputs.
+fn hash_inputs(config: &CompdbConfig, config_dir: &Path) -> Result<String> {
+    let mut paths = Vec::new();
+    for pattern in &config.inputs {
+        let full_pattern = config_dir.join(pattern);
+        let full_pattern = full_pattern
+            .to_str()
+            .context("compdb input pattern is not valid UTF-8")?;
+        for path in glob::glob(full_pattern)
+            .with_context(|| format!("Invalid compdb input pattern '{}'", pattern))?
+        {
+            let path = path?;
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    paths.dedup();
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(config.command.join("\0").as_bytes());
+    for path in paths {
+        hasher.update(b"\0");
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        hasher.update(&std::fs::read(&path)?);
+    }
+    Ok(hasher.finalize().to_string())
+}
+
+/// If any of `linters` needs the compilation database, make sure it exists
+/// and is up to date, generating it if not.
+pub fn ensure_compdb(
+    linters: &[Linter],
+    config: Option<&CompdbConfig>,
+    config_dir: &Path,
+    persistent_data_store: &PersistentDataStore,
+) -> Result<()> {
+    let needed_by = linters
+        .iter()
+        .filter(|linter| linter.needs_compdb)
+        .map(|linter| linter.code.as_str())
+        .collect::<Vec<_>>();
+    if needed_by.is_empty() {
+        return Ok(());
+    }
+    let config = match config {
+        Some(config) => config,
+        None => bail!(
+            "Linter(s) {} set `needs_compdb`, but the config has no [compdb] section \
+             saying how to generate the compilation database.",
+            needed_by.join(", ")
+        ),
+    };
+    let output = config_dir.join(config.output.as_deref().unwrap_or(DEFAULT_OUTPUT));
+    let hash = hash_inputs(config, config_dir)?;
+    if output.exists() && persistent_data_store.compdb_hash()?.as_deref() == Some(&hash) {
+        debug!("Compilation database is up to date");
+        return Ok(());
+    }
+
+    let (program, arguments) = config
+        .command
+        .split_first()
+        .context("`compdb.command` is empty.")?;
+    debug!(
+        "Generating compilation database for {}: {:?}",
+        needed_by.join(", "),
+        config.command
+    );
+    let command_output = Command::new(program)
+        .args(arguments)
+        .current_dir(config_dir)
+        .output()
+        .with_context(|| format!("Failed to run compdb command '{}'", program))?;
+    ensure_output("compdb command", &command_output)
+        .context("Failed to generate the compilation database")?;
+    ensure!(
+        output.exists(),
+        "The compdb command succeeded, but didn't write '{}'.",
+        output.display()
+    );
+    persistent_data_store.update_compdb_hash(&hash)?;
+    Ok(())
+}
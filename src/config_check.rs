@@ -0,0 +1,829 @@
+//! Checks for mistakes in a lintrunner config, used by `lintrunner config
+//! check`: errors that will break runs, like configs that don't parse or
+//! commands that don't exist, and warnings about likely mistakes, like
+//! patterns that match nothing or formatters that overlap.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use clap::ArgEnum;
+use console::{style, Term};
+use glob::{MatchOptions, Pattern};
+use log::debug;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    get_version_control,
+    lint_config::{
+        get_linters_from_configs, ConfigFormat, LintConfig, LintRunnerConfig, LinterKind,
+        TagSelection,
+    },
+    linter::{includes, matches_relative_path, matches_relative_regex, IncludePattern},
+    path::AbsPath,
+    shebang,
+    theme::ThemeExt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueSeverity {
+    /// Runs will fail, or the linter will never run.
+    Error,
+    /// Probably a mistake.
+    Warning,
+}
+
+/// A problem found in the config, attributed to a config file or a linter if
+/// possible.
+#[derive(Debug, Serialize)]
+pub struct ConfigIssue {
+    pub severity: IssueSeverity,
+    /// The config file the problem is in, if it is about a whole file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// The linter the problem is about, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(code: &str, message: String) -> ConfigIssue {
+        ConfigIssue {
+            severity: IssueSeverity::Error,
+            file: None,
+            code: Some(code.to_string()),
+            message,
+        }
+    }
+
+    fn warning(code: &str, message: String) -> ConfigIssue {
+        ConfigIssue {
+            severity: IssueSeverity::Warning,
+            ..ConfigIssue::error(code, message)
+        }
+    }
+
+    /// An issue about the config as a whole.
+    fn general(severity: IssueSeverity, message: String) -> ConfigIssue {
+        ConfigIssue {
+            severity,
+            file: None,
+            code: None,
+            message,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+pub enum CheckFormat {
+    Text,
+    Json,
+}
+
+// Heuristic for whether a command argument is meant to be a path to a file in
+// the repo, e.g. `tools/linter/flake8_linter.py`.
+fn looks_like_repo_path(arg: &str) -> bool {
+    !arg.starts_with('-')
+        && !arg.contains("{{")
+        && !arg.contains('=')
+        && !Path::new(arg).is_absolute()
+        && arg.contains('/')
+}
+
+// Returns true if every path matched by `narrow` is also matched by `wide`.
+// This is approximate: we check whether `wide` matches the literal text of
+// `narrow`, which is right for the common cases like `foo/**` vs `foo/bar/*.py`.
+fn pattern_covers(wide: &Pattern, narrow: &str) -> bool {
+    wide.matches_with(
+        narrow,
+        MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        },
+    )
+}
+
+/// Whether `program` can be run: as a path relative to `config_dir` if it
+/// has a directory part, otherwise from `$PATH`.
+fn executable_exists(program: &str, config_dir: &Path) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return config_dir.join(path).is_file();
+    }
+    let extensions: &[&str] = if cfg!(windows) {
+        &["", "exe", "cmd", "bat"]
+    } else {
+        &[""]
+    };
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| {
+            extensions.iter().any(|extension| match extension {
+                &"" => dir.join(path).is_file(),
+                extension => dir.join(format!("{}.{}", program, extension)).is_file(),
+            })
+        })
+    })
+}
+
+/// Parse the `kind` patterns of `linter` with `parse`, reporting those that
+/// don't parse.
+fn parse_patterns<T, E: Display>(
+    linter: &LintConfig,
+    kind: &str,
+    patterns: &[String],
+    parse: impl Fn(&str) -> Result<T, E>,
+    issues: &mut Vec<ConfigIssue>,
+) -> Vec<(String, T)> {
+    patterns
+        .iter()
+        .filter_map(|pattern| match parse(pattern) {
+            Ok(parsed) => Some((pattern.clone(), parsed)),
+            Err(err) => {
+                issues.push(ConfigIssue::error(
+                    &linter.code,
+                    format!("{} '{}' is invalid: {}", kind, pattern, err),
+                ));
+                None
+            }
+        })
+        .collect()
+}
+
+/// Check the include and exclude patterns of `linter`: that they parse, and
+/// that each of them matters for some file of `files`.
+fn check_patterns(
+    linter: &LintConfig,
+    config_dir: &Path,
+    files: &[AbsPath],
+    issues: &mut Vec<ConfigIssue>,
+) {
+    let (include_strs, include_patterns): (Vec<_>, Vec<_>) = parse_patterns(
+        linter,
+        "include pattern",
+        &linter.include_patterns,
+        IncludePattern::new,
+        issues,
+    )
+    .into_iter()
+    .unzip();
+    let exclude_patterns = parse_patterns(
+        linter,
+        "exclude pattern",
+        linter.exclude_patterns.as_deref().unwrap_or_default(),
+        Pattern::new,
+        issues,
+    );
+    let include_regexes = parse_patterns(
+        linter,
+        "include regex",
+        linter.include_regexes.as_deref().unwrap_or_default(),
+        Regex::new,
+        issues,
+    );
+    let exclude_regexes = parse_patterns(
+        linter,
+        "exclude regex",
+        linter.exclude_regexes.as_deref().unwrap_or_default(),
+        Regex::new,
+        issues,
+    );
+    // Without the repo's files, there is nothing to check them against.
+    if files.is_empty() {
+        return;
+    }
+
+    for (index, (pattern, parsed)) in include_strs.iter().zip(&include_patterns).enumerate() {
+        let mut matches = files
+            .iter()
+            .filter(|file| matches_relative_path(config_dir, file, &parsed.pattern));
+        if parsed.negated {
+            let earlier = &include_patterns[..index];
+            if !matches.any(|file| includes(config_dir, file, earlier)) {
+                issues.push(ConfigIssue::warning(
+                    &linter.code,
+                    format!(
+                        "include pattern '{}' un-includes none of the files included before it.",
+                        pattern
+                    ),
+                ));
+            }
+        } else if matches.next().is_none() {
+            issues.push(ConfigIssue::warning(
+                &linter.code,
+                format!("include pattern '{}' matches no files.", pattern),
+            ));
+        }
+    }
+    for (regex, parsed) in &include_regexes {
+        if !files
+            .iter()
+            .any(|file| matches_relative_regex(config_dir, file, parsed))
+        {
+            issues.push(ConfigIssue::warning(
+                &linter.code,
+                format!("include regex '{}' matches no files.", regex),
+            ));
+        }
+    }
+    let included = files
+        .iter()
+        .filter(|file| {
+            includes(config_dir, file, &include_patterns)
+                || include_regexes
+                    .iter()
+                    .any(|(_, parsed)| matches_relative_regex(config_dir, file, parsed))
+                || shebang::matches(file, linter.match_shebang.as_deref().unwrap_or_default())
+        })
+        .collect::<Vec<_>>();
+    for (pattern, parsed) in &exclude_patterns {
+        if !included
+            .iter()
+            .any(|file| matches_relative_path(config_dir, file, parsed))
+        {
+            issues.push(ConfigIssue::warning(
+                &linter.code,
+                format!(
+                    "exclude pattern '{}' excludes none of the included files.",
+                    pattern
+                ),
+            ));
+        }
+    }
+    for (regex, parsed) in &exclude_regexes {
+        if !included
+            .iter()
+            .any(|file| matches_relative_regex(config_dir, file, parsed))
+        {
+            issues.push(ConfigIssue::warning(
+                &linter.code,
+                format!(
+                    "exclude regex '{}' excludes none of the included files.",
+                    regex
+                ),
+            ));
+        }
+    }
+    if !included.is_empty()
+        && included.iter().all(|file| {
+            exclude_patterns
+                .iter()
+                .any(|(_, parsed)| matches_relative_path(config_dir, file, parsed))
+                || exclude_regexes
+                    .iter()
+                    .any(|(_, parsed)| matches_relative_regex(config_dir, file, parsed))
+        })
+    {
+        issues.push(ConfigIssue::error(
+            &linter.code,
+            "its exclude patterns exclude every file it includes, so it never runs.".to_string(),
+        ));
+    }
+}
+
+/// Check the config files at `config_paths`, along with the files they
+/// include. `files` is the set of files in the repo, which patterns are
+/// checked against, and used to detect formatters that would fight over the
+/// same file. Errors come first.
+pub fn check_config(config_paths: &[String], files: &[AbsPath]) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+    for path in config_paths {
+        let error = match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                let format = ConfigFormat::of(Path::new(path));
+                match format.parse(&contents) {
+                    Ok(_) => continue,
+                    Err(err) => format!("is not valid {}: {:#}", format.name(), err),
+                }
+            }
+            Err(err) => format!("could not be read: {}", err),
+        };
+        issues.push(ConfigIssue {
+            file: Some(PathBuf::from(path)),
+            ..ConfigIssue::general(IssueSeverity::Error, error)
+        });
+    }
+    if !issues.is_empty() {
+        return issues;
+    }
+    let config = match LintRunnerConfig::new(&config_paths.to_vec()) {
+        Ok(config) => config,
+        Err(err) => {
+            issues.push(ConfigIssue::general(
+                IssueSeverity::Error,
+                format!("{:#}", err),
+            ));
+            return issues;
+        }
+    };
+    // Unwrap is fine, since loading the config found the files.
+    let primary_config_path = AbsPath::try_from(config_paths[0].as_str()).unwrap();
+    // Unwrap is fine here because we know this path is absolute and won't be `/`
+    let config_dir = primary_config_path.parent().unwrap();
+
+    let mut definitions = BTreeMap::<&str, usize>::new();
+    for code in config
+        .linters
+        .iter()
+        .map(|linter| linter.code.as_str())
+        .chain(
+            config
+                .invalid_linters
+                .iter()
+                .map(|linter| linter.code.as_str()),
+        )
+    {
+        *definitions.entry(code).or_default() += 1;
+    }
+    for (code, count) in definitions {
+        if count > 1 {
+            issues.push(ConfigIssue::error(
+                code,
+                format!("is defined {} times.", count),
+            ));
+        }
+    }
+
+    for invalid_linter in &config.invalid_linters {
+        issues.push(ConfigIssue::error(
+            &invalid_linter.code,
+            format!("invalid configuration: {}", invalid_linter.error),
+        ));
+    }
+
+    for linter in &config.linters {
+        check_patterns(linter, config_dir, files, &mut issues);
+
+        if let Some(init_command) = &linter.init_command {
+            if init_command.iter().all(|arg| !arg.contains("{{DRYRUN}}")) {
+                issues.push(ConfigIssue::error(
+                    &linter.code,
+                    "init_command does not take a {{DRYRUN}} argument, so `lintrunner init` \
+                     will fail for this linter."
+                        .to_string(),
+                ));
+            }
+        }
+
+        // Behind a wrapper, the command may only exist where the wrapper
+        // runs it, so only the wrapper has to exist here.
+        let program = match &config.command_wrapper {
+            Some(wrapper) => wrapper.first(),
+            None if linter.kind == LinterKind::Command => linter.command.first(),
+            None => None,
+        };
+        if let Some(program) = program {
+            // Placeholders, like {{DOWNLOAD}}, are filled in when running.
+            if !program.contains("{{") && !executable_exists(program, config_dir) {
+                issues.push(ConfigIssue::error(
+                    &linter.code,
+                    format!("command executable '{}' was not found.", program),
+                ));
+            }
+        }
+
+        // The executable was checked above.
+        let arguments = match &config.command_wrapper {
+            Some(_) => &linter.command[..],
+            None => linter.command.get(1..).unwrap_or_default(),
+        };
+        for arg in arguments.iter().chain(linter.init_command.iter().flatten()) {
+            let arg = arg.trim_start_matches('@');
+            if looks_like_repo_path(arg) && !config_dir.join(arg).exists() {
+                issues.push(ConfigIssue::warning(
+                    &linter.code,
+                    format!("command references '{arg}', which does not exist."),
+                ));
+            }
+        }
+
+        if let Some(exclude_patterns) = &linter.exclude_patterns {
+            for wide in exclude_patterns {
+                let wide_pattern = match Pattern::new(wide) {
+                    Ok(pattern) => pattern,
+                    // Invalid patterns are reported with the other patterns.
+                    Err(_) => continue,
+                };
+                for narrow in exclude_patterns {
+                    if wide != narrow && pattern_covers(&wide_pattern, narrow) {
+                        issues.push(ConfigIssue::warning(
+                            &linter.code,
+                            format!(
+                                "exclude pattern '{narrow}' is redundant, \
+                                 since it is covered by '{wide}'."
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // Linters running the exact same command are probably duplicates.
+    let mut codes_by_command: HashMap<&Vec<String>, Vec<&str>> = HashMap::new();
+    for linter in config
+        .linters
+        .iter()
+        .filter(|l| l.kind == LinterKind::Command)
+    {
+        codes_by_command
+            .entry(&linter.command)
+            .or_default()
+            .push(&linter.code);
+    }
+    let mut duplicates = codes_by_command
+        .into_values()
+        .filter(|codes| codes.len() > 1)
+        .collect::<Vec<_>>();
+    duplicates.sort();
+    for codes in duplicates {
+        issues.push(ConfigIssue::general(
+            IssueSeverity::Warning,
+            format!("linters {} run the same command.", codes.join(", ")),
+        ));
+    }
+
+    // Building the linters finds some of the errors above again, like
+    // invalid patterns, which are only reported once.
+    let has_error = |issues: &[ConfigIssue], code: Option<&str>| {
+        issues.iter().any(|issue| {
+            issue.severity == IssueSeverity::Error
+                && (code.is_none() || issue.code.as_deref() == code)
+        })
+    };
+    match get_linters_from_configs(
+        &config.linters,
+        &[],
+        None,
+        None,
+        TagSelection::default(),
+        &primary_config_path,
+        false,
+    ) {
+        Ok((linters, _)) => {
+            for linter in &linters {
+                if let Some(config_error) = &linter.config_error {
+                    if has_error(&issues, Some(&linter.code)) {
+                        continue;
+                    }
+                    issues.push(ConfigIssue::error(
+                        &linter.code,
+                        format!("invalid configuration: {}", config_error),
+                    ));
+                }
+            }
+            // Formatters that match the same file will both try to patch it
+            // when run with `--apply-patches`, which fails.
+            let formatters = config
+                .linters
+                .iter()
+                .zip(linters.iter())
+                .filter(|(lint_config, _)| lint_config.is_formatter)
+                .map(|(_, linter)| (linter, linter.get_matches(files)))
+                .collect::<Vec<_>>();
+            for (i, (first, first_matches)) in formatters.iter().enumerate() {
+                for (second, second_matches) in &formatters[i + 1..] {
+                    if let Some(path) = first_matches
+                        .iter()
+                        .find(|path| second_matches.contains(path))
+                    {
+                        issues.push(ConfigIssue::general(
+                            IssueSeverity::Warning,
+                            format!(
+                                "formatters {} and {} both match '{}' (and possibly other files); \
+                                 applying their patches together will fail.",
+                                first.code,
+                                second.code,
+                                path.display()
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        Err(err) => {
+            if !has_error(&issues, None) {
+                issues.push(ConfigIssue::general(
+                    IssueSeverity::Error,
+                    format!("{:#}", err),
+                ));
+            }
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.severity);
+    issues
+}
+
+/// Print `issues`, returning the exit code for `lintrunner config check`:
+/// 1 if any of them is an error.
+pub fn render_config_issues(
+    stdout: &mut impl Write,
+    issues: &[ConfigIssue],
+    format: CheckFormat,
+) -> Result<i32> {
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == IssueSeverity::Error)
+        .count();
+    let warnings = issues.len() - errors;
+    match format {
+        CheckFormat::Json => writeln!(
+            stdout,
+            "{}",
+            serde_json::json!({
+                "errors": errors,
+                "warnings": warnings,
+                "issues": issues,
+            })
+        )?,
+        CheckFormat::Text => {
+            for issue in issues {
+                let label = match issue.severity {
+                    IssueSeverity::Error => style("error").red().theme_bold(),
+                    IssueSeverity::Warning => style("warning").yellow().theme_bold(),
+                };
+                let subject = match (&issue.file, &issue.code) {
+                    (Some(file), _) => format!(" {}:", file.display()),
+                    (None, Some(code)) => format!(" ({})", code),
+                    (None, None) => String::new(),
+                };
+                writeln!(stdout, "{}{} {}", label, subject, issue.message)?;
+            }
+            if issues.is_empty() {
+                writeln!(stdout, "{} No config issues.", style("ok").green())?;
+            } else {
+                writeln!(stdout, "{} errors, {} warnings", errors, warnings)?;
+            }
+        }
+    }
+    Ok(if errors > 0 { 1 } else { 0 })
+}
+
+pub fn do_config_check(config_paths: &[String], format: CheckFormat) -> Result<i32> {
+    // Checking patterns and overlapping formatters needs the files in the
+    // repo; if we can't get them, just skip those checks.
+    let files = match get_version_control().and_then(|repo| repo.get_all_files(None)) {
+        Ok(files) => files,
+        Err(err) => {
+            debug!("Couldn't list repo files, skipping pattern checks: {}", err);
+            Vec::new()
+        }
+    };
+    let issues = check_config(config_paths, &files);
+    render_config_issues(&mut Term::stdout(), &issues, format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use tempfile::TempDir;
+
+    /// Check `config` with `files` in the repo, returning each issue's
+    /// severity, and its message after the linter code, if any.
+    fn check(config: &str, files: &[&str]) -> Result<Vec<(IssueSeverity, String)>> {
+        let dir = TempDir::new()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        std::fs::write(&config_path, config)?;
+        std::fs::create_dir(dir.path().join("tools"))?;
+        std::fs::write(dir.path().join("tools/exists.py"), "")?;
+        let mut abs_files = Vec::new();
+        for file in files {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, "")?;
+            abs_files.push(AbsPath::try_from(path)?);
+        }
+
+        let config_paths = [config_path.to_string_lossy().to_string()];
+        let issues = check_config(&config_paths, &abs_files);
+        Ok(issues
+            .into_iter()
+            .map(|issue| {
+                let message = match issue.code {
+                    Some(code) => format!("{} {}", code, issue.message),
+                    None => issue.message,
+                };
+                (issue.severity, message)
+            })
+            .collect())
+    }
+
+    #[test]
+    fn clean_config_has_no_issues() -> Result<()> {
+        let issues = check(
+            "
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**/*.py']
+            exclude_patterns = ['third_party/**']
+            command = ['python3', 'tools/exists.py', '--', '@{{PATHSFILE}}']
+            init_command = ['python3', 'tools/exists.py', '--dry-run={{DRYRUN}}']
+            ",
+            &["foo.py", "third_party/bar.py"],
+        )?;
+        assert!(issues.is_empty(), "{:?}", issues);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_dryrun_and_files() -> Result<()> {
+        let issues = check(
+            "
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**/*.py']
+            command = ['python3', 'tools/missing.py']
+            init_command = ['pip', 'install', 'flake8']
+            ",
+            &[],
+        )?;
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].0, IssueSeverity::Error);
+        assert!(issues[0].1.contains("{{DRYRUN}}"));
+        assert_eq!(issues[1].0, IssueSeverity::Warning);
+        assert!(issues[1].1.contains("tools/missing.py"));
+        Ok(())
+    }
+
+    #[test]
+    fn redundant_excludes_and_duplicate_commands() -> Result<()> {
+        let issues = check(
+            "
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**']
+            exclude_patterns = ['third_party/**', 'third_party/foo/*.py']
+            command = ['tools/exists.py']
+
+            [[linter]]
+            code = 'B'
+            include_patterns = ['**']
+            command = ['tools/exists.py']
+            ",
+            &[],
+        )?;
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].1.contains("'third_party/foo/*.py' is redundant"));
+        assert!(issues[1].1.contains("A, B"));
+        Ok(())
+    }
+
+    #[test]
+    fn overlapping_formatters() -> Result<()> {
+        let issues = check(
+            "
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**/*.py']
+            command = ['tools/exists.py', 'black']
+            is_formatter = true
+
+            [[linter]]
+            code = 'B'
+            include_patterns = ['*.py']
+            command = ['tools/exists.py', 'yapf']
+            is_formatter = true
+
+            [[linter]]
+            code = 'C'
+            include_patterns = ['**/*.py']
+            command = ['tools/exists.py', 'flake8']
+            ",
+            &["foo.py"],
+        )?;
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].1.contains("formatters A and B both match"));
+        Ok(())
+    }
+
+    #[test]
+    fn valid_config_has_no_issues() -> Result<()> {
+        let issues = check(
+            "
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**/*.py']
+            exclude_patterns = ['b.py']
+            command = ['tools/exists.py', '@{{PATHSFILE}}']
+            init_command = ['{{DOWNLOAD}}', '--dry-run={{DRYRUN}}']
+            ",
+            &["a.py", "b.py"],
+        )?;
+        assert!(issues.is_empty(), "{:?}", issues);
+        Ok(())
+    }
+
+    #[test]
+    fn unparseable_config_is_an_error() -> Result<()> {
+        let issues = check("[[linter]\ncode = 'A'", &[])?;
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].0, IssueSeverity::Error);
+        assert!(issues[0].1.contains("is not valid TOML"), "{:?}", issues);
+        Ok(())
+    }
+
+    #[test]
+    fn errors_and_warnings() -> Result<()> {
+        let issues = check(
+            "
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**/*.py', 'docs/**', '[']
+            exclude_patterns = ['*.py']
+            command = ['lintrunner-no-such-tool']
+            init_command = ['pip', 'install', 'flake8']
+
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**']
+            command = ['tools/exists.py']
+            ",
+            &["a.py"],
+        )?;
+        let errors = issues
+            .iter()
+            .filter(|(severity, _)| *severity == IssueSeverity::Error)
+            .map(|(_, message)| message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            [
+                "A is defined 2 times.",
+                "A include pattern '[' is invalid: Pattern syntax error near position 0: invalid range pattern",
+                "A its exclude patterns exclude every file it includes, so it never runs.",
+                "A init_command does not take a {{DRYRUN}} argument, so `lintrunner init` will fail for this linter.",
+                "A command executable 'lintrunner-no-such-tool' was not found.",
+            ]
+        );
+        let warnings = issues
+            .iter()
+            .filter(|(severity, _)| *severity == IssueSeverity::Warning)
+            .map(|(_, message)| message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(warnings, ["A include pattern 'docs/**' matches no files."]);
+        Ok(())
+    }
+
+    #[test]
+    fn negated_include_patterns_are_checked() -> Result<()> {
+        let issues = check(
+            "
+            [[linter]]
+            code = 'A'
+            include_patterns = ['*', '!gen_*', '!*.md', '![']
+            exclude_patterns = ['b.py']
+            command = ['tools/exists.py']
+            ",
+            &["a.py", "b.py", "c.md"],
+        )?;
+        let messages = issues
+            .iter()
+            .map(|(_, message)| message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "A include pattern '![' is invalid: Pattern syntax error near position 0: invalid range pattern",
+                "A include pattern '!gen_*' un-includes none of the files included before it.",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn regexes_are_checked() -> Result<()> {
+        let issues = check(
+            "
+            [[linter]]
+            code = 'A'
+            include_regexes = ['^a[0-9]+\\.py$', 'gen', '(']
+            exclude_regexes = ['2', 'md$']
+            command = ['tools/exists.py']
+            ",
+            &["a1.py", "a2.py", "c.md"],
+        )?;
+        let messages = issues
+            .iter()
+            .map(|(_, message)| message.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert!(messages[0].starts_with("A include regex '(' is invalid: "));
+        assert_eq!(
+            messages[1..],
+            [
+                "A include regex 'gen' matches no files.",
+                "A exclude regex 'md$' excludes none of the included files.",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+//! `lintrunner config new`: scaffold a `.lintrunner.toml` for a repo that
+//! doesn't have one.
+//!
+//! The files under the config's directory are scanned for the languages they
+//! are in, and a stanza is offered for each linter that fits, with the ones for
+//! languages found already selected. External linters go through the adapters
+//! of [lintrunner-adapters](https://github.com/justinchuby/lintrunner-adapters),
+//! which their init commands install with pip, so the written config is ready
+//! for `lintrunner init`.
+
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, MultiSelect};
+use log::debug;
+
+use crate::{
+    interactive,
+    lint_config::LintRunnerConfig,
+    theme::{self, ThemeExt},
+};
+
+/// How many files to look at when detecting languages.
+const MAX_SCANNED_FILES: usize = 100_000;
+
+/// A linter that can be added to a new config.
+pub struct Template {
+    pub code: &'static str,
+    /// The language it lints, or `None` if it applies to any repo.
+    pub language: Option<&'static str>,
+    /// Extensions of files in that language.
+    extensions: &'static [&'static str],
+    description: &'static str,
+    stanza: &'static str,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        code: "RUFF",
+        language: Some("Python"),
+        extensions: &["py", "pyi"],
+        description: "lints Python with ruff",
+        stanza: r#"[[linter]]
+code = 'RUFF'
+include_patterns = ['**/*.py', '**/*.pyi']
+command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'ruff_linter',
+    '--',
+    '@{{PATHSFILE}}',
+]
+init_command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'pip_init',
+    '--dry-run={{DRYRUN}}',
+    'ruff',
+]
+"#,
+    },
+    Template {
+        code: "MYPY",
+        language: Some("Python"),
+        extensions: &["py", "pyi"],
+        description: "type checks Python with mypy",
+        stanza: r#"[[linter]]
+code = 'MYPY'
+include_patterns = ['**/*.py', '**/*.pyi']
+command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'mypy_linter',
+    '--',
+    '@{{PATHSFILE}}',
+]
+init_command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'pip_init',
+    '--dry-run={{DRYRUN}}',
+    'mypy',
+]
+"#,
+    },
+    Template {
+        code: "CLANGFORMAT",
+        language: Some("C/C++"),
+        extensions: &["c", "cc", "cpp", "cxx", "h", "hh", "hpp"],
+        description: "formats C and C++ with clang-format",
+        stanza: r#"[[linter]]
+code = 'CLANGFORMAT'
+include_patterns = ['**/*.c', '**/*.cc', '**/*.cpp', '**/*.cxx', '**/*.h', '**/*.hh', '**/*.hpp']
+command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'clangformat_linter',
+    '--binary=clang-format',
+    '--',
+    '@{{PATHSFILE}}',
+]
+init_command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'pip_init',
+    '--dry-run={{DRYRUN}}',
+    'clang-format',
+]
+is_formatter = true
+"#,
+    },
+    Template {
+        code: "RUSTFMT",
+        language: Some("Rust"),
+        extensions: &["rs"],
+        description: "formats Rust with rustfmt",
+        stanza: r#"[[linter]]
+code = 'RUSTFMT'
+include_patterns = ['**/*.rs']
+command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'rustfmt_linter',
+    '--binary=rustfmt',
+    '--',
+    '@{{PATHSFILE}}',
+]
+is_formatter = true
+"#,
+    },
+    Template {
+        code: "SHELLCHECK",
+        language: Some("Shell"),
+        extensions: &["sh", "bash"],
+        description: "lints shell scripts with shellcheck",
+        stanza: r#"[[linter]]
+code = 'SHELLCHECK'
+include_patterns = ['**/*.sh', '**/*.bash']
+match_shebang = ['sh', 'bash']
+command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'shellcheck_linter',
+    '--',
+    '@{{PATHSFILE}}',
+]
+init_command = [
+    'python3',
+    '-m',
+    'lintrunner_adapters',
+    'run',
+    'pip_init',
+    '--dry-run={{DRYRUN}}',
+    'shellcheck-py',
+]
+"#,
+    },
+    Template {
+        code: "SECRETS",
+        language: None,
+        extensions: &[],
+        description: "finds committed credentials (builtin)",
+        stanza: r#"[[linter]]
+code = 'SECRETS'
+kind = 'builtin'
+builtin = 'secrets'
+include_patterns = ['**']
+"#,
+    },
+    Template {
+        code: "CONFLICT_MARKERS",
+        language: None,
+        extensions: &[],
+        description: "finds leftover merge conflict markers (builtin)",
+        stanza: r#"[[linter]]
+code = 'CONFLICT_MARKERS'
+kind = 'builtin'
+builtin = 'conflict_markers'
+include_patterns = ['**']
+"#,
+    },
+];
+
+const HEADER: &str = "\
+# Generated by `lintrunner config new`. Run `lintrunner init` to install the
+# linters, then `lintrunner` to lint your changes.
+#
+# Linters that run `lintrunner_adapters` need it installed first:
+#   pip install lintrunner-adapters
+";
+
+/// The languages of the files under `root`, skipping hidden and ignored
+/// files.
+pub fn detect_languages(root: &Path) -> BTreeSet<&'static str> {
+    let mut extensions = BTreeSet::new();
+    let files = ignore::WalkBuilder::new(root)
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .take(MAX_SCANNED_FILES);
+    for file in files {
+        if let Some(extension) = file.path().extension().and_then(|ext| ext.to_str()) {
+            extensions.insert(extension.to_ascii_lowercase());
+        }
+    }
+    TEMPLATES
+        .iter()
+        .filter(|template| {
+            template
+                .extensions
+                .iter()
+                .any(|extension| extensions.contains(*extension))
+        })
+        .filter_map(|template| template.language)
+        .collect()
+}
+
+/// Whether `template` fits a repo with `languages` in it.
+fn fits(template: &Template, languages: &BTreeSet<&str>) -> bool {
+    template
+        .language
+        .is_none_or(|language| languages.contains(language))
+}
+
+/// The config made of `templates`.
+pub fn render(templates: &[&Template]) -> String {
+    let mut config = HEADER.to_string();
+    for template in templates {
+        config.push('\n');
+        config.push_str(template.stanza);
+    }
+    config
+}
+
+fn choose(languages: &BTreeSet<&str>) -> Result<Vec<&'static Template>> {
+    let items = TEMPLATES
+        .iter()
+        .map(|template| format!("{}: {}", template.code, template.description))
+        .collect::<Vec<_>>();
+    let defaults = TEMPLATES
+        .iter()
+        .map(|template| fits(template, languages))
+        .collect::<Vec<_>>();
+    let chosen = MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt("Linters to add (space to toggle, enter to confirm)")
+        .items(&items)
+        .defaults(&defaults)
+        .interact_opt()?;
+    match chosen {
+        Some(chosen) => Ok(chosen.into_iter().map(|index| &TEMPLATES[index]).collect()),
+        None => bail!("Not writing a config."),
+    }
+}
+
+/// Write a new config to `path`, asking which linters to put in it unless
+/// `yes` is set, in which case the ones for the languages found are used.
+pub fn do_config_new(path: &Path, yes: bool, force: bool) -> Result<i32> {
+    if path.exists() && !force {
+        bail!(
+            "'{}' already exists; pass --force to overwrite it",
+            path.display()
+        );
+    }
+    if !yes && !interactive::can_prompt() {
+        bail!("Can't ask which linters to add; pass --yes to add the ones for the languages found");
+    }
+    let root = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let languages = detect_languages(&root);
+    debug!("Languages found: {:?}", languages);
+
+    let templates = if yes {
+        TEMPLATES
+            .iter()
+            .filter(|template| fits(template, &languages))
+            .collect()
+    } else {
+        if languages.is_empty() {
+            writeln!(theme::stderr(), "No languages with linter templates found.")?;
+        } else {
+            writeln!(
+                theme::stderr(),
+                "Found {}.",
+                languages.iter().copied().collect::<Vec<_>>().join(", ")
+            )?;
+        }
+        choose(&languages)?
+    };
+
+    let config = render(&templates);
+    std::fs::write(path, &config)
+        .with_context(|| format!("Couldn't write '{}'", path.display()))?;
+    // Make sure what was written is a config lintrunner accepts.
+    LintRunnerConfig::new(&vec![path.to_string_lossy().into_owned()])?;
+
+    let codes = templates
+        .iter()
+        .map(|template| template.code)
+        .collect::<Vec<_>>();
+    writeln!(
+        theme::stderr(),
+        "Wrote {} with {} linters: {}\nRun {} next.",
+        path.display(),
+        templates.len(),
+        codes.join(", "),
+        style("lintrunner init").theme_bold()
+    )?;
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_template_is_a_valid_config() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(".lintrunner.toml");
+        std::fs::write(&path, render(&TEMPLATES.iter().collect::<Vec<_>>()))?;
+        let config = LintRunnerConfig::new(&vec![path.to_string_lossy().into_owned()])?;
+        assert_eq!(config.linters.len(), TEMPLATES.len());
+        Ok(())
+    }
+
+    #[test]
+    fn languages_come_from_extensions() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("src"))?;
+        std::fs::write(dir.path().join("src/main.rs"), "")?;
+        std::fs::write(dir.path().join("setup.py"), "")?;
+        std::fs::write(dir.path().join("README.md"), "")?;
+        let languages = detect_languages(dir.path());
+        assert_eq!(languages, BTreeSet::from(["Python", "Rust"]));
+
+        let fitting = TEMPLATES
+            .iter()
+            .filter(|template| fits(template, &languages))
+            .map(|template| template.code)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fitting,
+            ["RUFF", "MYPY", "RUSTFMT", "SECRETS", "CONFLICT_MARKERS"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,547 @@
+//! A JSON Schema for `.lintrunner.toml`, printed by `lintrunner config
+//! schema`, so that editors can complete and check config files and CI can
+//! validate them.
+//!
+//! The schema describes the serde definitions of [`LintRunnerConfig`] and the
+//! types it contains. It is written out by hand, and the tests make sure it
+//! has exactly the fields and variants that serde accepts, so a new config
+//! option fails the tests until it is added here.
+//!
+//! [`LintRunnerConfig`]: crate::lint_config::LintRunnerConfig
+
+use serde_json::{json, Value};
+
+const SEVERITIES: &[&str] = &["error", "warning", "advice", "disabled"];
+const OUTPUTS: &[&str] = &[
+    "default",
+    "json",
+    "oneline",
+    "vscode",
+    "summary-markdown",
+    "arc",
+];
+const PATHSFILE_FORMATS: &[&str] = &["lines", "json"];
+const COLUMN_UNITS: &[&str] = &["byte", "char", "utf16"];
+const LINTER_KINDS: &[&str] = &["command", "builtin"];
+const NETWORK_POLICIES: &[&str] = &["allowed", "init-only", "never"];
+const PLATFORMS: &[&str] = &["linux", "macos", "windows"];
+
+fn strings(description: &str) -> Value {
+    json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "description": description,
+    })
+}
+
+fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+fn boolean(description: &str) -> Value {
+    json!({ "type": "boolean", "description": description })
+}
+
+fn count(description: &str) -> Value {
+    json!({ "type": "integer", "minimum": 0, "description": description })
+}
+
+fn one_of(values: &[&str], description: &str) -> Value {
+    json!({ "type": "string", "enum": values, "description": description })
+}
+
+fn reference(definition: &str, description: &str) -> Value {
+    json!({
+        "allOf": [{ "$ref": format!("#/definitions/{definition}") }],
+        "description": description,
+    })
+}
+
+/// The properties of a linter that choose the paths it lints. They're
+/// separate from the rest because `json!` can't take all of them at once.
+fn linter_path_properties() -> Value {
+    json!({
+        "include_patterns": strings(
+            "Glob patterns, relative to the config file, of the paths to lint. A pattern \
+             starting with '!' un-includes the paths it matches; the last matching pattern \
+             decides.",
+        ),
+        "include_regexes": strings(
+            "Regular expressions matched anywhere in paths relative to the config file. \
+             Paths they match are linted too.",
+        ),
+        "match_shebang": strings(
+            "Interpreters, like 'python3'. Files whose shebang runs one of them are linted too.",
+        ),
+        "exclude_patterns": strings(
+            "Glob patterns of paths never to lint, even if they match an include pattern.",
+        ),
+        "exclude_regexes": strings(
+            "Regular expressions matched anywhere in paths relative to the config file. \
+             Paths they match are never linted.",
+        ),
+        "ignore_file": string(
+            "The tool's own ignore file, in gitignore syntax. Paths it ignores aren't \
+             passed to the linter.",
+        ),
+        "respect_gitignore": boolean(
+            "Skip paths that git ignores. Overrides the top-level respect_gitignore.",
+        ),
+        "max_file_size_bytes": count("Skip files larger than this many bytes."),
+        "skip_binary_files": boolean(
+            "Skip files with a NUL byte in their first 8000 bytes, as git does.",
+        ),
+    })
+}
+
+fn linter() -> Value {
+    let mut linter = json!({
+        "type": "object",
+        "description": "A linter, along with everything needed to invoke it.",
+        "required": ["code"],
+        "properties": {
+            "code": string(
+                "The name of the linter, conventionally capitals and numbers, e.g. 'FLAKE8'.",
+            ),
+            "tags": strings("Groups the linter belongs to, for --tag and --skip-tag."),
+            "priority": {
+                "type": "integer",
+                "description": "Linters with a higher priority get worker slots first and \
+                                have their messages shown first. Defaults to 0.",
+            },
+            "enabled": boolean(
+                "Set to false to drop the linter, e.g. from .lintrunner.private.toml. \
+                 Defaults to true.",
+            ),
+            "command": strings(
+                "The command to run. {{PATHSFILE}} is replaced by a file listing the paths \
+                 to lint. Not used by builtin linters.",
+            ),
+            "pathsfile_format": one_of(PATHSFILE_FORMATS, "The format of {{PATHSFILE}}."),
+            "capabilities": reference(
+                "capabilities",
+                "Optional protocol features the linter supports.",
+            ),
+            "probe_capabilities": boolean(
+                "Ask the linter for its capabilities with --lintrunner-capabilities.",
+            ),
+            "needs_compdb": boolean("Generate the compilation database before running."),
+            "use_pty": boolean("Give the linter a pseudo-terminal as stdout. Unix only."),
+            "max_output_bytes": count("The most bytes the linter may write to stdout."),
+            "timeout_seconds": count("How many seconds each invocation may run for."),
+            "column_unit": one_of(COLUMN_UNITS, "What the linter's columns count."),
+            "kind": one_of(
+                LINTER_KINDS,
+                "Whether the linter runs `command` or is built into lintrunner.",
+            ),
+            "builtin": string("For builtin linters, which builtin to run."),
+            "options": { "description": "Options for a builtin linter." },
+            "init_command": strings(
+                "The command run by `lintrunner init` to set up the linter. Must contain \
+                 {{DRYRUN}}.",
+            ),
+            "is_formatter": boolean("Run the linter with `lintrunner format`."),
+            "env_passthrough": strings(
+                "Environment variables passed through from the user's environment.",
+            ),
+            "env": {
+                "type": "object",
+                "additionalProperties": { "type": "string" },
+                "description": "Extra environment variables for the linter's commands. \
+                                {{CONFIG_DIR}} is replaced by the config file's directory.",
+            },
+            "download": reference(
+                "download",
+                "A prebuilt binary downloaded by `lintrunner init`, for {{DOWNLOAD}}.",
+            ),
+            "health_check": strings("A command that exits 0 if the linter's toolchain works."),
+            "cache_inputs": strings(
+                "Files, relative to the config file, whose contents affect the linter's \
+                 results.",
+            ),
+            "expand_on_config_change": boolean(
+                "Lint all files when one of `cache_inputs` changed.",
+            ),
+            "pass_directories": boolean("Pass directories to the linter instead of files."),
+            "directory_roots": strings("With pass_directories, the directories to pass."),
+            "artifacts": strings(
+                "Glob patterns of files to keep for debugging when the linter fails.",
+            ),
+            "network": one_of(NETWORK_POLICIES, "When the linter may use the network."),
+            "platforms": {
+                "type": "array",
+                "items": { "type": "string", "enum": PLATFORMS },
+                "description": "The operating systems the linter runs on.",
+            },
+            "protected": boolean("Pin the linter's configuration in .lintrunner.lock."),
+        },
+    });
+    if let Value::Object(path_properties) = linter_path_properties() {
+        // Unwrap is fine because "properties" is an object.
+        linter["properties"]
+            .as_object_mut()
+            .unwrap()
+            .extend(path_properties);
+    }
+    linter["anyOf"] = json!([
+        { "required": ["include_patterns"] },
+        { "required": ["include_regexes"] },
+        { "required": ["match_shebang"] },
+    ]);
+    linter
+}
+
+fn capabilities() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "json_pathsfile": boolean("Accepts the JSON {{PATHSFILE}} format."),
+            "line_ranges": boolean("Can limit its checks to changed line ranges."),
+            "stdin": boolean("Can read its paths file from stdin."),
+            "fix": boolean("Only computes fixes when asked to."),
+        },
+    })
+}
+
+fn download() -> Value {
+    json!({
+        "type": "object",
+        "required": ["repo", "asset_pattern", "version"],
+        "properties": {
+            "repo": string("The GitHub repository, as owner/name."),
+            "asset_pattern": string("A regex matching the full name of exactly one asset."),
+            "version": string("The release tag."),
+            "sha256": string("The expected sha256 of the asset, in hex."),
+            "signature": reference("signature", "How to verify the asset's signature."),
+        },
+    })
+}
+
+fn signature() -> Value {
+    json!({
+        "oneOf": [
+            {
+                "type": "object",
+                "required": ["minisign"],
+                "additionalProperties": false,
+                "properties": {
+                    "minisign": {
+                        "type": "object",
+                        "required": ["public_key"],
+                        "properties": {
+                            "public_key": string("The base64 public key."),
+                        },
+                    },
+                },
+            },
+            {
+                "type": "object",
+                "required": ["sigstore"],
+                "additionalProperties": false,
+                "properties": {
+                    "sigstore": {
+                        "type": "object",
+                        "required": ["identity", "issuer"],
+                        "properties": {
+                            "identity": string("The expected certificate identity."),
+                            "issuer": string("The expected OIDC issuer."),
+                        },
+                    },
+                },
+            },
+        ],
+    })
+}
+
+fn colors() -> Value {
+    let color = "A color name or an ANSI 256-color number.";
+    json!({
+        "type": "object",
+        "properties": {
+            "error": string(color),
+            "warning": string(color),
+            "advice": string(color),
+            "disabled": string(color),
+            "dim": boolean("Set to false to never render dim text."),
+            "bold": boolean("Set to false to never render bold text."),
+            "underline": boolean("Set to false to never underline text."),
+        },
+    })
+}
+
+fn compdb() -> Value {
+    json!({
+        "type": "object",
+        "required": ["command", "inputs"],
+        "properties": {
+            "command": strings("The command that generates the database."),
+            "inputs": strings("Glob patterns of the build files the database depends on."),
+            "output": string("Where the command writes the database."),
+        },
+    })
+}
+
+fn budget() -> Value {
+    json!({
+        "type": "object",
+        "required": ["code", "max"],
+        "properties": {
+            "code": string("The linter's code."),
+            "max": count("The most messages the linter may report."),
+        },
+    })
+}
+
+fn remote_exec() -> Value {
+    json!({
+        "type": "object",
+        "required": ["hosts"],
+        "additionalProperties": false,
+        "properties": {
+            "hosts": strings("The hosts to run linters on."),
+            "ssh_command": strings("How to run a command on a host."),
+        },
+    })
+}
+
+fn profile() -> Value {
+    json!({
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "take": strings("Linters to run, like --take."),
+            "skip": strings("Linters not to run, like --skip."),
+            "tag": strings("Tags of linters to run, like --tag."),
+            "skip_tag": strings("Tags of linters not to run, like --skip-tag."),
+            "output": one_of(OUTPUTS, "How to show results, like --output."),
+            "jobs": count("How many linter invocations may run at once, like --jobs."),
+            "min_severity": one_of(
+                SEVERITIES,
+                "Drop messages less severe than this, like --min-severity.",
+            ),
+            "linter": {
+                "type": "object",
+                "additionalProperties": { "type": "object" },
+                "description": "Fields of linters to override, by linter code.",
+            },
+        },
+    })
+}
+
+/// The JSON Schema of lintrunner config files.
+pub fn config_schema() -> Value {
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "lintrunner config",
+        "type": "object",
+        "properties": {
+            "linter": {
+                "type": "array",
+                "items": { "$ref": "#/definitions/linter" },
+                "description": "The linters to run.",
+            },
+            "merge_base_with": string(
+                "The default for --merge-base-with, e.g. the default branch.",
+            ),
+            "only_lint_under_config_dir": boolean(
+                "Only lint files under the directory of the config file.",
+            ),
+            "skip_vendored": boolean("Never lint files in vendored directories."),
+            "content_provider": strings(
+                "The command that prints the contents of {{PATH}} at {{REV}}, for --staged \
+                 and --contents-at.",
+            ),
+            "colors": reference("colors", "The colors and styling of terminal output."),
+            "compdb": reference(
+                "compdb",
+                "How to generate the compilation database for linters that need one.",
+            ),
+            "budgets": {
+                "type": "array",
+                "items": { "$ref": "#/definitions/budget" },
+                "description": "The most messages each listed linter may report.",
+            },
+            "command_wrapper": strings("A command to prefix every linter invocation with."),
+            "remote_exec": reference("remote_exec", "Hosts to run linters on with --remote-exec."),
+            "health_cache_minutes": count(
+                "How many minutes to reuse the results of health checks for.",
+            ),
+            "profile": {
+                "type": "object",
+                "additionalProperties": { "$ref": "#/definitions/profile" },
+                "description": "Named sets of options, selected with --profile.",
+            },
+            "respect_gitignore": boolean(
+                "Skip paths that git ignores, even if they are tracked or passed explicitly.",
+            ),
+            "nested_configs": boolean(
+                "Read linters from config files with the same name in subdirectories, for \
+                 the files under them.",
+            ),
+            "include": strings("Glob patterns of config fragments to read linters from."),
+        },
+        "definitions": {
+            "linter": linter(),
+            "capabilities": capabilities(),
+            "download": download(),
+            "signature": signature(),
+            "colors": colors(),
+            "compdb": compdb(),
+            "budget": budget(),
+            "remote_exec": remote_exec(),
+            "profile": profile(),
+        },
+    })
+}
+
+/// Print the config schema.
+pub fn do_config_schema() -> anyhow::Result<i32> {
+    println!("{}", serde_json::to_string_pretty(&config_schema())?);
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use serde::{
+        de::{value::Error, DeserializeOwned, Error as _, Visitor},
+        forward_to_deserialize_any, Deserializer,
+    };
+
+    use super::*;
+    use crate::{
+        budget::Budget,
+        capabilities::Capabilities,
+        columns::ColumnUnit,
+        compdb::CompdbConfig,
+        download::{DownloadConfig, SignatureConfig},
+        lint_config::{LintConfig, LintRunnerConfig, LinterKind, Platform},
+        lint_message::LintSeverity,
+        network::NetworkPolicy,
+        pathsfile::PathsFileFormat,
+        profile::Profile,
+        remote::RemoteExecConfig,
+        theme::ColorsConfig,
+        RenderOpt,
+    };
+
+    /// Records the names of the fields or variants a type asks serde for,
+    /// then gives up.
+    struct Names<'a>(&'a Cell<&'static [&'static str]>);
+
+    impl<'de> Deserializer<'de> for Names<'_> {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+            Err(Error::custom("not a struct or enum"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Error> {
+            self.0.set(fields);
+            Err(Error::custom("recorded"))
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _: &'static str,
+            variants: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Error> {
+            self.0.set(variants);
+            Err(Error::custom("recorded"))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map identifier ignored_any
+        }
+    }
+
+    fn serde_names<T: DeserializeOwned>() -> Vec<&'static str> {
+        let names = Cell::new(&[][..]);
+        assert!(T::deserialize(Names(&names)).is_err());
+        let mut names = names.get().to_vec();
+        names.sort_unstable();
+        names
+    }
+
+    fn schema_names(schema: &Value) -> Vec<&str> {
+        let mut names = schema["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    fn sorted(values: &[&'static str]) -> Vec<&'static str> {
+        let mut values = values.to_vec();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn schema_has_every_config_field() {
+        let schema = config_schema();
+        let definition = |name: &str| &schema["definitions"][name];
+        assert_eq!(schema_names(&schema), serde_names::<LintRunnerConfig>());
+        assert_eq!(
+            schema_names(definition("linter")),
+            serde_names::<LintConfig>()
+        );
+        assert_eq!(
+            schema_names(definition("capabilities")),
+            serde_names::<Capabilities>()
+        );
+        assert_eq!(
+            schema_names(definition("download")),
+            serde_names::<DownloadConfig>()
+        );
+        assert_eq!(
+            schema_names(definition("colors")),
+            serde_names::<ColorsConfig>()
+        );
+        assert_eq!(
+            schema_names(definition("compdb")),
+            serde_names::<CompdbConfig>()
+        );
+        assert_eq!(schema_names(definition("budget")), serde_names::<Budget>());
+        assert_eq!(
+            schema_names(definition("remote_exec")),
+            serde_names::<RemoteExecConfig>()
+        );
+        assert_eq!(
+            schema_names(definition("profile")),
+            serde_names::<Profile>()
+        );
+    }
+
+    #[test]
+    fn schema_has_every_variant() {
+        assert_eq!(sorted(SEVERITIES), serde_names::<LintSeverity>());
+        assert_eq!(sorted(OUTPUTS), serde_names::<RenderOpt>());
+        assert_eq!(sorted(PATHSFILE_FORMATS), serde_names::<PathsFileFormat>());
+        assert_eq!(sorted(COLUMN_UNITS), serde_names::<ColumnUnit>());
+        assert_eq!(sorted(LINTER_KINDS), serde_names::<LinterKind>());
+        assert_eq!(sorted(NETWORK_POLICIES), serde_names::<NetworkPolicy>());
+        assert_eq!(sorted(PLATFORMS), serde_names::<Platform>());
+        let signature = signature();
+        let mut signatures = signature["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .flat_map(schema_names)
+            .collect::<Vec<_>>();
+        signatures.sort_unstable();
+        assert_eq!(signatures, serde_names::<SignatureConfig>());
+    }
+}
@@ -0,0 +1,182 @@
+//! Linting file contents supplied by a command, rather than read from the
+//! working tree. This is what powers `--staged` and `--contents-at`.
+//!
+//! The contents of every file to lint are written to a temporary directory
+//! that mirrors the layout of the config directory, and linters are pointed
+//! at those copies. Paths in the resulting lint messages are mapped back to
+//! the real files.
+
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{ensure, Context, Result};
+use log::debug;
+use tempfile::TempDir;
+
+use crate::{
+    lint_message::LintMessage,
+    path::{path_relative_from, AbsPath},
+};
+
+/// Used when the config doesn't specify a `content_provider`.
+pub const DEFAULT_CONTENT_PROVIDER: &[&str] = &["git", "show", "{{REV}}:./{{PATH}}"];
+
+pub struct ContentProvider {
+    /// The command to run, with `{{REV}}` and `{{PATH}}` placeholders.
+    pub command: Vec<String>,
+    /// The revision to get contents at. An empty string means the index
+    /// (staged contents) for git.
+    pub rev: String,
+}
+
+impl ContentProvider {
+    fn contents(&self, relative_path: &str, config_dir: &Path) -> Result<Vec<u8>> {
+        let argv: Vec<String> = self
+            .command
+            .iter()
+            .map(|arg| {
+                arg.replace("{{REV}}", &self.rev)
+                    .replace("{{PATH}}", relative_path)
+            })
+            .collect();
+        debug!("Running content provider: {:?}", argv);
+        let output = Command::new(&argv[0])
+            .args(&argv[1..])
+            .current_dir(config_dir)
+            .output()
+            .with_context(|| format!("Failed to run content provider: {:?}", argv))?;
+        ensure!(
+            output.status.success(),
+            "Content provider failed for '{}': {}",
+            relative_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        Ok(output.stdout)
+    }
+
+    /// Write the provided contents of `files` to a temporary directory.
+    ///
+    /// Files outside `config_dir`, or that the provider has no contents for
+    /// (e.g. they don't exist at the requested revision), are skipped.
+    pub fn materialize(
+        &self,
+        files: &[AbsPath],
+        config_dir: &AbsPath,
+    ) -> Result<MaterializedFiles> {
+        let dir = TempDir::new()?;
+        let root = AbsPath::try_from(dir.path())?;
+        for file in files {
+            if !file.starts_with(config_dir) {
+                debug!(
+                    "Skipping '{}' for content provider: not under the config directory",
+                    file.display()
+                );
+                continue;
+            }
+            // Unwrap ok because both paths are absolute.
+            let relative_path = path_relative_from(file, config_dir).unwrap();
+            let relative_str = relative_path
+                .to_str()
+                .context("Could not convert path to string.")?
+                // Providers like git expect forward slashes.
+                .replace('\\', "/");
+            let contents = match self.contents(&relative_str, config_dir) {
+                Ok(contents) => contents,
+                Err(err) => {
+                    debug!("Skipping '{}': {:#}", file.display(), err);
+                    continue;
+                }
+            };
+            let target = root.join(&relative_path);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&target, contents)?;
+        }
+        Ok(MaterializedFiles {
+            _dir: dir,
+            root,
+            config_dir: config_dir.clone(),
+        })
+    }
+}
+
+/// Provided file contents, written to a temporary directory.
+pub struct MaterializedFiles {
+    // Held to keep the directory alive.
+    _dir: TempDir,
+    root: AbsPath,
+    config_dir: AbsPath,
+}
+
+impl MaterializedFiles {
+    /// Map worktree paths to their materialized copies, dropping any that
+    /// weren't materialized.
+    pub fn to_materialized(&self, files: &[AbsPath]) -> Vec<AbsPath> {
+        files
+            .iter()
+            .filter_map(|file| {
+                let relative_path = path_relative_from(file, &self.config_dir)?;
+                AbsPath::try_from(self.root.join(relative_path)).ok()
+            })
+            .collect()
+    }
+
+    fn to_worktree(&self, path: &str) -> Option<PathBuf> {
+        let relative_path = Path::new(path).strip_prefix(&*self.root).ok()?;
+        Some(self.config_dir.join(relative_path))
+    }
+
+    /// Rewrite paths in `lints` that point at materialized copies to point at
+    /// the corresponding worktree files.
+    pub fn restore_lint_paths(&self, lints: &mut [LintMessage]) {
+        for lint in lints {
+            if let Some(path) = lint.path.as_deref().and_then(|p| self.to_worktree(p)) {
+                lint.path = Some(path.display().to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(target_os = "windows", ignore)] // requires printf
+    fn materialize_and_restore() -> Result<()> {
+        let config_dir = TempDir::new()?;
+        let config_dir = AbsPath::try_from(config_dir.path())?;
+        std::fs::create_dir(config_dir.join("sub"))?;
+        std::fs::write(config_dir.join("sub/a.py"), "worktree")?;
+        let file = AbsPath::try_from(config_dir.join("sub/a.py"))?;
+
+        let provider = ContentProvider {
+            command: vec![
+                "printf".to_string(),
+                "%s@%s".to_string(),
+                "{{PATH}}".to_string(),
+                "{{REV}}".to_string(),
+            ],
+            rev: "HEAD".to_string(),
+        };
+        let materialized = provider.materialize(std::slice::from_ref(&file), &config_dir)?;
+        let copies = materialized.to_materialized(&[file]);
+        assert_eq!(copies.len(), 1);
+        assert_eq!(std::fs::read_to_string(&copies[0])?, "sub/a.py@HEAD");
+
+        let mut lints = vec![LintMessage {
+            path: Some(copies[0].display().to_string()),
+            ..LintMessage::new("TEST", crate::lint_message::LintSeverity::Error, "test")
+        }];
+        materialized.restore_lint_paths(&mut lints);
+        assert_eq!(
+            lints[0].path.as_deref(),
+            Some(config_dir.join("sub/a.py").to_str().unwrap())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+//! Using a unified diff to decide what to lint, for `--diff-file`.
+//!
+//! This lets patches that live outside the local VCS state (e.g. ones sent by
+//! email) be linted: the files touched by the diff are linted, and only lint
+//! messages on lines the diff adds or changes are reported.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    convert::TryFrom,
+};
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+
+use crate::{
+    lint_message::LintMessage,
+    path::AbsPath,
+    pathsfile::{ChangeInfo, ChangeKind, ChangeMetadata},
+};
+
+/// The files and lines changed by a diff.
+pub struct DiffScope {
+    changed_lines: HashMap<AbsPath, BTreeSet<usize>>,
+    /// Files the diff creates.
+    new_files: HashSet<AbsPath>,
+}
+
+/// A file touched by a diff.
+pub struct DiffedFile {
+    /// The path as written on the `+++` side, minus any `b/` prefix.
+    pub path: String,
+    /// The 1-based line numbers the diff adds.
+    pub lines: BTreeSet<usize>,
+    /// Whether the diff creates the file.
+    pub is_new: bool,
+}
+
+// Parse `@@ -1,2 +3,4 @@` into (old_count, new_start, new_count).
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = line.split_whitespace().skip(1);
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (_, old_count) = parse_range(parts.next()?.strip_prefix('-')?)?;
+    let (new_start, new_count) = parse_range(parts.next()?.strip_prefix('+')?)?;
+    Some((old_count, new_start, new_count))
+}
+
+/// Parse a unified diff into the files it touches. Deleted files are skipped.
+pub fn parse_unified_diff(diff: &str) -> Result<Vec<DiffedFile>> {
+    let mut files: Vec<DiffedFile> = Vec::new();
+    let mut in_deleted_file = false;
+    let mut old_is_null = false;
+    // Lines left in the current hunk, on the old and new sides.
+    let mut old_remaining: usize = 0;
+    let mut new_remaining: usize = 0;
+    let mut new_line = 0;
+
+    for line in diff.lines() {
+        if old_remaining > 0 || new_remaining > 0 {
+            // How many lines this one takes up on the old and new sides. Some
+            // tools strip the trailing space from empty context lines.
+            let (old_lines, new_lines) = match line.chars().next() {
+                Some('+') => {
+                    if let Some(file) = files.last_mut() {
+                        if !in_deleted_file {
+                            file.lines.insert(new_line);
+                        }
+                    }
+                    (0, 1)
+                }
+                Some('-') => (1, 0),
+                Some('\\') => (0, 0),
+                _ => (1, 1),
+            };
+            match (
+                old_remaining.checked_sub(old_lines),
+                new_remaining.checked_sub(new_lines),
+            ) {
+                (Some(old), Some(new)) => {
+                    old_remaining = old;
+                    new_remaining = new;
+                }
+                _ => bail!(
+                    "Malformed hunk in diff: '{}' is past the lines its header counts",
+                    line
+                ),
+            }
+            new_line += new_lines;
+            continue;
+        }
+
+        if let Some(path) = line.strip_prefix("--- ") {
+            old_is_null = path.starts_with("/dev/null");
+        } else if let Some(path) = line.strip_prefix("+++ ") {
+            // Drop any trailing timestamp.
+            let path = path.split('\t').next().unwrap_or(path).trim_end();
+            in_deleted_file = path == "/dev/null";
+            if !in_deleted_file {
+                let path = path.strip_prefix("b/").unwrap_or(path);
+                files.push(DiffedFile {
+                    path: path.to_string(),
+                    lines: BTreeSet::new(),
+                    is_new: old_is_null,
+                });
+            }
+        } else if line.starts_with("@@ ") {
+            let (old_count, new_start, new_count) = parse_hunk_header(line)
+                .with_context(|| format!("Invalid hunk header in diff: '{}'", line))?;
+            old_remaining = old_count;
+            new_remaining = new_count;
+            new_line = new_start;
+        }
+    }
+    Ok(files)
+}
+
+impl DiffScope {
+    /// Read a diff from `diff_path`. The diff is expected to already be
+    /// applied to the working tree, with paths relative to the current
+    /// directory.
+    pub fn from_file(diff_path: &str) -> Result<DiffScope> {
+        let diff = std::fs::read_to_string(diff_path)
+            .with_context(|| format!("Failed to read --diff-file '{}'", diff_path))?;
+        let mut changed_lines = HashMap::new();
+        let mut new_files = HashSet::new();
+        for file in parse_unified_diff(&diff)? {
+            match AbsPath::try_from(&file.path) {
+                Ok(path) => {
+                    if file.is_new {
+                        new_files.insert(path.clone());
+                    }
+                    changed_lines.insert(path, file.lines);
+                }
+                Err(_) => debug!("Skipping '{}' from diff: file not found", file.path),
+            }
+        }
+        Ok(DiffScope {
+            changed_lines,
+            new_files,
+        })
+    }
+
+    pub fn files(&self) -> Vec<AbsPath> {
+        self.changed_lines.keys().cloned().collect()
+    }
+
+    /// How the diff changes each file, for linters that take a JSON paths
+    /// file.
+    pub fn change_metadata(&self) -> ChangeMetadata {
+        self.changed_lines
+            .iter()
+            .map(|(path, lines)| {
+                let kind = if self.new_files.contains(path) {
+                    ChangeKind::Added
+                } else {
+                    ChangeKind::Modified
+                };
+                (
+                    path.clone(),
+                    ChangeInfo {
+                        kind,
+                        old_path: None,
+                        changed_lines: lines.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Drop lint messages on lines the diff didn't change. Messages that
+    /// aren't about a specific line are kept.
+    pub fn filter_lints(&self, lints: Vec<LintMessage>) -> Vec<LintMessage> {
+        lints
+            .into_iter()
+            .filter(|lint| {
+                let (path, line) = match (&lint.path, lint.line) {
+                    (Some(path), Some(line)) => (path, line),
+                    _ => return true,
+                };
+                let lines = AbsPath::try_from(path)
+                    .ok()
+                    .and_then(|path| self.changed_lines.get(&path));
+                match lines {
+                    Some(lines) => lines.contains(&line),
+                    None => true,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_added_lines() -> Result<()> {
+        let diff = "\
+diff --git a/foo.py b/foo.py
+--- a/foo.py
++++ b/foo.py
+@@ -1,3 +1,4 @@
+ a
+-b
++B
++c
+ d
+@@ -10 +11,2 @@
+-x
++y
++++z
+--- a/gone.py
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+--- /dev/null
++++ new.py\t2024-01-01 00:00:00
+@@ -0,0 +1 @@
++hello
+\\ No newline at end of file
+";
+        let files = parse_unified_diff(diff)?;
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "foo.py");
+        assert_eq!(
+            files[0].lines.iter().copied().collect::<Vec<_>>(),
+            vec![2, 3, 11, 12]
+        );
+        assert!(!files[0].is_new);
+        assert_eq!(files[1].path, "new.py");
+        assert_eq!(files[1].lines.iter().copied().collect::<Vec<_>>(), vec![1]);
+        assert!(files[1].is_new);
+        Ok(())
+    }
+
+    #[test]
+    fn hunks_longer_than_their_header_are_malformed() {
+        let diff = "\
+--- a/foo.py
++++ b/foo.py
+@@ -1,2 +1 @@
+ a
++b
+-c
+";
+        let err = match parse_unified_diff(diff) {
+            Ok(_) => panic!("parsed a malformed hunk"),
+            Err(err) => err,
+        };
+        assert!(err.to_string().contains("Malformed hunk"), "{}", err);
+    }
+}
@@ -0,0 +1,413 @@
+//! Fetching prebuilt linter binaries from GitHub releases during
+//! `lintrunner init`, for linters that declare a `download`.
+//!
+//! Binaries are cached in the lintrunner data directory, shared between
+//! checkouts, and linters refer to them in their command with `{{DOWNLOAD}}`.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use log::{debug, info};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{publish::curl_quote, toolchain};
+
+pub(crate) const DOWNLOADS_DIR_NAME: &str = "downloads";
+const CHECKSUM_NAME: &str = "sha256";
+
+/// Can be set to use a GitHub Enterprise instance, or a mirror.
+const GITHUB_API_URL_ENV: &str = "LINTRUNNER_GITHUB_API_URL";
+const DEFAULT_GITHUB_API_URL: &str = "https://api.github.com";
+
+/// A prebuilt binary published as a GitHub release asset.
+///
+/// # Examples
+/// ```toml
+/// download = { repo = 'mvdan/sh', asset_pattern = 'shfmt_.*_linux_amd64', version = 'v3.8.0' }
+/// ```
+/// - Pinning the binary's checksum and checking its minisign signature:
+/// ```toml
+/// [linter.download]
+/// repo = 'example/tool'
+/// asset_pattern = 'tool_.*_linux_amd64'
+/// version = 'v1.2.3'
+/// sha256 = 'e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855'
+/// signature = { minisign = { public_key = 'RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3' } }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DownloadConfig {
+    /// The GitHub repository, as `owner/name`.
+    pub repo: String,
+    /// A regex that must match the full name of exactly one asset of the
+    /// release. The asset must be the executable itself, not an archive.
+    pub asset_pattern: String,
+    /// The release tag.
+    pub version: String,
+    /// The expected sha256 of the asset, in hex. If set, `init` fails when
+    /// the download doesn't match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// How to verify the asset's signature, if at all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<SignatureConfig>,
+}
+
+/// A signature that must be valid for a downloaded asset. The signature is
+/// read from a release asset named after the binary's asset.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureConfig {
+    /// A minisign signature, in `<asset>.minisig`.
+    Minisign {
+        /// The base64 public key, as printed by `minisign -G`.
+        public_key: String,
+    },
+    /// A sigstore bundle, in `<asset>.sigstore.json`. Verified with
+    /// `cosign verify-blob`, which must be installed.
+    Sigstore {
+        /// The expected certificate identity, e.g. the release workflow.
+        identity: String,
+        /// The expected OIDC issuer, e.g.
+        /// `https://token.actions.githubusercontent.com`.
+        issuer: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct Release {
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+    /// e.g. `sha256:abc123...`. Only present for assets uploaded recently.
+    #[serde(default)]
+    digest: Option<String>,
+}
+
+/// The base URL of the GitHub API.
+pub(crate) fn github_api_url() -> String {
+    std::env::var(GITHUB_API_URL_ENV)
+        .unwrap_or_else(|_| DEFAULT_GITHUB_API_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn downloads_dir() -> Result<PathBuf> {
+    Ok(toolchain::data_dir()?.join(DOWNLOADS_DIR_NAME))
+}
+
+/// Fetch `url` with curl. A `GITHUB_TOKEN` is passed in curl's config on
+/// stdin, so that it doesn't show up in the process list.
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    debug!("Fetching {}", url);
+    let mut config = String::new();
+    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+        config = format!(
+            "header = {}\n",
+            curl_quote(&format!("Authorization: Bearer {}", token))
+        );
+    }
+    let mut child = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--config", "-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl; is it installed?")?;
+    child
+        .stdin
+        .take()
+        .context("Failed to open curl's stdin")?
+        .write_all(config.as_bytes())?;
+    let output = child.wait_with_output()?;
+    ensure!(
+        output.status.success(),
+        "Failed to fetch {}: {}",
+        url,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(output.stdout)
+}
+
+pub(crate) fn sha256_hex(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn find_asset<'a>(release: &'a Release, asset_pattern: &str) -> Result<&'a Asset> {
+    let pattern = Regex::new(&format!("^(?:{})$", asset_pattern))
+        .with_context(|| format!("Invalid asset_pattern '{}'", asset_pattern))?;
+    let names = release
+        .assets
+        .iter()
+        .map(|asset| &asset.name)
+        .collect::<Vec<_>>();
+    let matching = release
+        .assets
+        .iter()
+        .filter(|asset| pattern.is_match(&asset.name))
+        .collect::<Vec<_>>();
+    match matching.len() {
+        1 => Ok(matching[0]),
+        0 => bail!(
+            "No release asset matches '{}'. Available assets: {:?}",
+            asset_pattern,
+            names
+        ),
+        _ => bail!(
+            "More than one release asset matches '{}': {:?}",
+            asset_pattern,
+            matching.iter().map(|asset| &asset.name).collect::<Vec<_>>()
+        ),
+    }
+}
+
+fn find_signature_asset<'a>(release: &'a Release, name: &str) -> Result<&'a Asset> {
+    release
+        .assets
+        .iter()
+        .find(|asset| asset.name == name)
+        .with_context(|| format!("The release has no signature asset named '{}'", name))
+}
+
+fn verify_minisign(contents: &[u8], signature: &str, public_key: &str) -> Result<()> {
+    let public_key = minisign_verify::PublicKey::from_base64(public_key)
+        .map_err(|err| anyhow!("Invalid minisign public key: {}", err))?;
+    let signature = minisign_verify::Signature::decode(signature)
+        .map_err(|err| anyhow!("Invalid minisign signature: {}", err))?;
+    public_key
+        .verify(contents, &signature, true)
+        .map_err(|err| anyhow!("minisign signature verification failed: {}", err))
+}
+
+fn verify_sigstore(binary_path: &Path, bundle: &[u8], identity: &str, issuer: &str) -> Result<()> {
+    let bundle_file = tempfile::NamedTempFile::new()?;
+    std::fs::write(bundle_file.path(), bundle)?;
+    let output = Command::new("cosign")
+        .arg("verify-blob")
+        .arg("--bundle")
+        .arg(bundle_file.path())
+        .args(["--certificate-identity", identity])
+        .args(["--certificate-oidc-issuer", issuer])
+        .arg(binary_path)
+        .output()
+        .context("Failed to run cosign to verify the sigstore signature; is it installed?")?;
+    ensure!(
+        output.status.success(),
+        "sigstore signature verification failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(())
+}
+
+fn cached_checksum_matches(path: &Path, checksum_path: &Path, pinned: Option<&str>) -> bool {
+    match (std::fs::read(path), std::fs::read_to_string(checksum_path)) {
+        (Ok(contents), Ok(checksum)) => {
+            let actual = sha256_hex(&contents);
+            let matches_pin = match pinned {
+                Some(pinned) => pinned == actual,
+                None => true,
+            };
+            actual == checksum.trim() && matches_pin
+        }
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+impl DownloadConfig {
+    pub(crate) fn cache_dir(&self) -> Result<PathBuf> {
+        let (owner, name) = self.repo.split_once('/').with_context(|| {
+            format!("Invalid download repo '{}': expected owner/name", self.repo)
+        })?;
+        // Different asset patterns pick different binaries from the same
+        // release (e.g. for different platforms), so keep them apart.
+        let pattern_hash = blake3::hash(self.asset_pattern.as_bytes()).to_string();
+        Ok(downloads_dir()?
+            .join(owner)
+            .join(name)
+            .join(&self.version)
+            .join(&pattern_hash[..16]))
+    }
+
+    /// Where the binary is cached. This is what `{{DOWNLOAD}}` expands to.
+    pub fn cached_path(&self) -> Result<PathBuf> {
+        // Unwrap ok because we checked for a '/' in cache_dir.
+        let name = self.repo.split_once('/').unwrap().1;
+        Ok(self
+            .cache_dir()?
+            .join(format!("{}{}", name, std::env::consts::EXE_SUFFIX)))
+    }
+
+    /// Download the binary into the cache, unless an intact copy is already
+    /// there. In dry-run mode, only report what would be downloaded. In
+    /// offline mode, fail if the binary isn't already cached.
+    pub fn ensure_downloaded(&self, dry_run: bool, offline: bool) -> Result<PathBuf> {
+        let path = self.cached_path()?;
+        let checksum_path = self.cache_dir()?.join(CHECKSUM_NAME);
+        let pinned = self.sha256.as_ref().map(|sha256| sha256.to_lowercase());
+        if cached_checksum_matches(&path, &checksum_path, pinned.as_deref()) {
+            debug!("Using cached download at {}", path.display());
+            return Ok(path);
+        }
+        ensure!(
+            !offline,
+            "{} release {} is not cached, and downloading it is disabled by --offline",
+            self.repo,
+            self.version
+        );
+        if dry_run {
+            println!(
+                "Would download '{}' from {} release {} to {}",
+                self.asset_pattern,
+                self.repo,
+                self.version,
+                path.display()
+            );
+            return Ok(path);
+        }
+
+        let release_url = format!(
+            "{}/repos/{}/releases/tags/{}",
+            github_api_url(),
+            self.repo,
+            self.version
+        );
+        let release: Release = serde_json::from_slice(&fetch(&release_url)?)
+            .with_context(|| format!("Unexpected response from {}", release_url))?;
+        let asset = find_asset(&release, &self.asset_pattern)?;
+
+        info!("Downloading {} from {}", asset.name, self.repo);
+        let contents = fetch(&asset.browser_download_url)?;
+        let checksum = sha256_hex(&contents);
+        if let Some(digest) = asset
+            .digest
+            .as_deref()
+            .and_then(|d| d.strip_prefix("sha256:"))
+        {
+            ensure!(
+                digest == checksum,
+                "Checksum mismatch for {}: GitHub reports sha256 {}, but the download has {}",
+                asset.name,
+                digest,
+                checksum
+            );
+        }
+        if let Some(pinned) = &pinned {
+            ensure!(
+                *pinned == checksum,
+                "Checksum mismatch for {}: the config pins sha256 {}, but the download has {}",
+                asset.name,
+                pinned,
+                checksum
+            );
+        }
+
+        // Write to a temporary file first, so an interrupted download never
+        // looks like a cached binary.
+        let cache_dir = self.cache_dir()?;
+        std::fs::create_dir_all(&cache_dir)?;
+        let tmp_file = tempfile::NamedTempFile::new_in(&cache_dir)?;
+        std::fs::write(tmp_file.path(), &contents)?;
+        make_executable(tmp_file.path())?;
+        match &self.signature {
+            Some(SignatureConfig::Minisign { public_key }) => {
+                let signature_asset =
+                    find_signature_asset(&release, &format!("{}.minisig", asset.name))?;
+                let signature = fetch(&signature_asset.browser_download_url)?;
+                verify_minisign(&contents, &String::from_utf8_lossy(&signature), public_key)
+                    .with_context(|| format!("Refusing to install {}", asset.name))?;
+            }
+            Some(SignatureConfig::Sigstore { identity, issuer }) => {
+                let bundle_asset =
+                    find_signature_asset(&release, &format!("{}.sigstore.json", asset.name))?;
+                let bundle = fetch(&bundle_asset.browser_download_url)?;
+                verify_sigstore(tmp_file.path(), &bundle, identity, issuer)
+                    .with_context(|| format!("Refusing to install {}", asset.name))?;
+            }
+            None => {}
+        }
+        tmp_file
+            .persist(&path)
+            .with_context(|| format!("Failed to save download to {}", path.display()))?;
+        std::fs::write(&checksum_path, &checksum)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(names: &[&str]) -> Release {
+        Release {
+            assets: names
+                .iter()
+                .map(|name| Asset {
+                    name: name.to_string(),
+                    browser_download_url: format!("https://example.com/{}", name),
+                    digest: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn find_asset_by_pattern() -> Result<()> {
+        let names = [
+            "shfmt_v3.8.0_linux_amd64",
+            "shfmt_v3.8.0_linux_amd64.sha256",
+            "shfmt_v3.8.0_darwin_arm64",
+        ];
+        let release = release(&names);
+        let asset = find_asset(&release, "shfmt_.*_linux_amd64")?;
+        assert_eq!(asset.name, "shfmt_v3.8.0_linux_amd64");
+
+        assert!(find_asset(&release, "shfmt_.*_windows_amd64").is_err());
+        assert!(find_asset(&release, "shfmt_.*").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sha256() {
+        assert_eq!(
+            sha256_hex(b"hello"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn minisign() {
+        // Test vector from the minisign-verify crate.
+        let public_key = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+        let signature = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+        assert!(verify_minisign(b"test", signature, public_key).is_ok());
+        assert!(verify_minisign(b"Test", signature, public_key).is_err());
+    }
+}
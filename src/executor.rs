@@ -0,0 +1,478 @@
+//! Running the linters of a [`Plan`], and post-processing what they report.
+//!
+//! Each linter runs on its own thread, running the invocations the plan gave
+//! it. Its messages are then checked against files that changed while it ran,
+//! filtered by `--diff-file`, suppressions and `--min-severity`, and its
+//! fixes applied if asked, before being handed back.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use anyhow::{ensure, Result};
+use log::debug;
+
+use crate::{
+    apply::FileLocks,
+    apply_patches, artifacts,
+    content_provider::MaterializedFiles,
+    diff_file::DiffScope,
+    group_lints_by_file,
+    lint_message::{LintMessage, LintSeverity},
+    linter::{Linter, RunMode, SkipReason},
+    manifest,
+    path::AbsPath,
+    pathsfile::ChangeMetadata,
+    plan::{Plan, PlannedLinter},
+    progress::Progress,
+    record::Recorder,
+    remove_patchable_lints,
+    render::{render_lint_messages_vscode, render_linter_done_vscode},
+    scheduler::Scheduler,
+    snapshot::{self, FileSnapshot},
+    stream::LintStream,
+    suppress, RenderOpt,
+};
+
+/// Runs the linters of a plan locally.
+pub struct Executor {
+    pub run_mode: RunMode,
+    /// With `run_mode.applying_fixes`, also apply suggested fixes.
+    pub apply_suggested: bool,
+    /// Cancel the remaining linters once one reports an error.
+    pub fail_fast: bool,
+    /// Lint files that changed while being linted again, instead of flagging
+    /// their messages as possibly stale.
+    pub rerun_modified: bool,
+    pub min_severity: Option<LintSeverity>,
+    /// With `RenderOpt::Vscode`, each linter's messages are written to stdout
+    /// as soon as it finishes.
+    pub render_opt: RenderOpt,
+    pub scheduler: Scheduler,
+    /// Set when the remaining linters should stop.
+    pub cancelled: Arc<AtomicBool>,
+    /// Set when `cancelled` was set because the time budget ran out.
+    pub budget_exhausted: Arc<AtomicBool>,
+    pub recorder: Recorder,
+    pub progress: Progress,
+    /// With a content provider, the provided contents to lint instead of the
+    /// working tree.
+    pub materialized: Option<MaterializedFiles>,
+    /// With --diff-file, only messages on the lines it changes are kept.
+    pub diff_scope: Option<DiffScope>,
+    pub change_metadata: ChangeMetadata,
+    /// The files as they were before linting, to tell which changed.
+    pub snapshot: Option<FileSnapshot>,
+    pub stream_to: Option<LintStream>,
+    /// Where to keep the artifacts of failed linters.
+    pub artifacts_dir: PathBuf,
+    /// Hash each file as it is dispatched to a linter, for --write-manifest.
+    pub hash_dispatched: bool,
+}
+
+/// What running a plan produced.
+pub struct Executed {
+    /// The messages of all linters, by path.
+    pub lints: HashMap<Option<String>, Vec<LintMessage>>,
+    /// Which files each linter that ran to completion processed.
+    pub processed_files: BTreeMap<String, Vec<AbsPath>>,
+    /// With `hash_dispatched`, the same files, each with the hash of the
+    /// contents the linter was given.
+    pub processed_hashes: BTreeMap<String, Vec<(AbsPath, String)>>,
+    /// The linters that were cancelled before they finished.
+    pub cancelled: BTreeMap<String, SkipReason>,
+    /// How many fixes weren't applied because their file changed.
+    pub num_stale_patches: usize,
+}
+
+impl Executor {
+    /// Run `linters` according to `plan`, which must have been made for them.
+    pub fn execute(&self, linters: &[Linter], plan: &Plan) -> Result<Executed> {
+        ensure!(
+            linters.len() == plan.linters.len(),
+            "The plan is for {} linters, not {}",
+            plan.linters.len(),
+            linters.len()
+        );
+        let all_lints = Mutex::new(HashMap::new());
+        let processed_files = Mutex::new(BTreeMap::new());
+        let processed_hashes = Mutex::new(BTreeMap::new());
+        let cancelled_linters = Mutex::new(BTreeMap::new());
+        // Linters finishing at the same time may fix the same file.
+        let file_locks = FileLocks::default();
+        let num_stale_patches = AtomicUsize::new(0);
+
+        thread::scope(|scope| -> Result<()> {
+            let handles = linters
+                .iter()
+                .zip(&plan.linters)
+                .map(|(linter, planned)| {
+                    let all_lints = &all_lints;
+                    let processed_files = &processed_files;
+                    let processed_hashes = &processed_hashes;
+                    let cancelled_linters = &cancelled_linters;
+                    let file_locks = &file_locks;
+                    let num_stale_patches = &num_stale_patches;
+                    scope.spawn(move || -> Result<()> {
+                        let linter_progress = self.progress.start_linter(&linter.code);
+                        // Hashed now, before this linter's fixes (or another's)
+                        // change the files.
+                        let hashes = if self.hash_dispatched {
+                            self.hash_dispatched_files(&planned.files)?
+                        } else {
+                            Vec::new()
+                        };
+                        let lints = match self.run_linter(linter, planned)? {
+                            Some(lints) => lints,
+                            None => {
+                                let reason = if self.budget_exhausted.load(Ordering::SeqCst) {
+                                    SkipReason::TimeBudget
+                                } else {
+                                    SkipReason::Cancelled
+                                };
+                                cancelled_linters
+                                    .lock()
+                                    .unwrap()
+                                    .insert(linter.code.clone(), reason);
+                                linter_progress.cancel();
+                                return Ok(());
+                            }
+                        };
+                        // Linters of nested configs can share a code.
+                        processed_files
+                            .lock()
+                            .unwrap()
+                            .entry(linter.code.clone())
+                            .or_insert_with(Vec::new)
+                            .extend(planned.files.iter().cloned());
+                        if self.hash_dispatched {
+                            processed_hashes
+                                .lock()
+                                .unwrap()
+                                .entry(linter.code.clone())
+                                .or_insert_with(Vec::new)
+                                .extend(hashes);
+                        }
+
+                        // If we're applying patches later, don't consider
+                        // lints that would be fixed by that.
+                        let lints = if self.run_mode.applying_fixes {
+                            let stale_paths = apply_patches(
+                                &lints,
+                                self.apply_suggested,
+                                file_locks,
+                                self.snapshot.as_ref(),
+                            )?;
+                            num_stale_patches.fetch_add(stale_paths.len(), Ordering::SeqCst);
+                            remove_patchable_lints(lints, self.apply_suggested, &stale_paths)
+                        } else {
+                            lints
+                        };
+
+                        let mut all_lints = all_lints.lock().unwrap();
+                        let num_lints = lints.len();
+
+                        // The VS Code stream is incremental: write out results
+                        // as soon as each linter finishes. Holding the
+                        // `all_lints` lock keeps the output of different
+                        // linters from interleaving.
+                        if self.render_opt == RenderOpt::Vscode {
+                            let mut stdout = std::io::stdout().lock();
+                            render_lint_messages_vscode(&mut stdout, &lints)?;
+                            render_linter_done_vscode(&mut stdout, &linter.code, lints.len())?;
+                        }
+                        if let Some(stream_to) = &self.stream_to {
+                            stream_to.send(&lints);
+                        }
+
+                        group_lints_by_file(&mut all_lints, lints);
+
+                        linter_progress.finish(num_lints);
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            self.progress.join()?;
+            for handle in handles {
+                handle.join().unwrap()?;
+            }
+            Ok(())
+        })?;
+        self.progress.finish();
+
+        Ok(Executed {
+            lints: all_lints.into_inner().unwrap(),
+            processed_files: processed_files.into_inner().unwrap(),
+            processed_hashes: processed_hashes.into_inner().unwrap(),
+            cancelled: cancelled_linters.into_inner().unwrap(),
+            num_stale_patches: num_stale_patches.into_inner(),
+        })
+    }
+
+    /// Hash the contents a linter dispatched on `files` is given: the
+    /// provided contents with a content provider, the working tree otherwise.
+    fn hash_dispatched_files(&self, files: &[AbsPath]) -> Result<Vec<(AbsPath, String)>> {
+        let mut hashes = Vec::with_capacity(files.len());
+        for file in files {
+            let hash = match &self.materialized {
+                Some(materialized) => {
+                    match materialized
+                        .to_materialized(std::slice::from_ref(file))
+                        .pop()
+                    {
+                        Some(contents) => manifest::hash_file(&contents)?,
+                        // Not materialized, so the linter isn't given it.
+                        None => continue,
+                    }
+                }
+                None => manifest::hash_file(file)?,
+            };
+            hashes.push((file.clone(), hash));
+        }
+        Ok(hashes)
+    }
+
+    /// Run `linter` as `planned`, and filter its messages. Returns `None` if
+    /// it was cancelled.
+    fn run_linter(
+        &self,
+        linter: &Linter,
+        planned: &PlannedLinter,
+    ) -> Result<Option<Vec<LintMessage>>> {
+        let matches = &planned.files;
+        // With a content provider, point the linter at the provided contents
+        // instead of the working tree.
+        let (run_planned, run_metadata) = match &self.materialized {
+            Some(materialized) => {
+                let mut run_planned = planned.clone();
+                run_planned.files = materialized.to_materialized(matches);
+                for batch in &mut run_planned.batches {
+                    batch.paths = materialized.to_materialized(&batch.paths);
+                }
+                // Key the metadata by the paths the linter actually sees.
+                let run_metadata = matches
+                    .iter()
+                    .zip(&run_planned.files)
+                    .filter_map(|(path, run_path)| {
+                        self.change_metadata
+                            .get(path)
+                            .map(|info| (run_path.clone(), info.clone()))
+                    })
+                    .collect::<ChangeMetadata>();
+                (Cow::Owned(run_planned), Cow::Owned(run_metadata))
+            }
+            None => (Cow::Borrowed(planned), Cow::Borrowed(&self.change_metadata)),
+        };
+
+        let mut lints = match linter.run(
+            &run_planned,
+            &run_metadata,
+            self.run_mode,
+            &self.scheduler,
+            &self.cancelled,
+            Some(&self.recorder),
+        ) {
+            Some(lints) => lints,
+            None => return Ok(None),
+        };
+        if let Some(materialized) = &self.materialized {
+            materialized.restore_lint_paths(&mut lints);
+        }
+        artifacts::keep_artifacts(linter, &mut lints, &self.artifacts_dir);
+
+        // Messages on files that changed while the linter ran may be stale:
+        // flag them, or lint those files again.
+        let modified = match &self.snapshot {
+            Some(snapshot) => snapshot.modified(matches),
+            None => HashSet::new(),
+        };
+        let lints = if modified.is_empty() {
+            lints
+        } else if self.rerun_modified {
+            debug!(
+                "Files changed while linter {} ran, linting them again: {:?}",
+                linter.code, modified
+            );
+            let rerun_files = modified.iter().cloned().collect::<Vec<_>>();
+            let rerun_planned = PlannedLinter::new(linter, &rerun_files, planned.expanded);
+            let rerun_snapshot = FileSnapshot::take(&rerun_files);
+            let rerun_metadata = self
+                .change_metadata
+                .iter()
+                .filter(|(path, _)| modified.contains(*path))
+                .map(|(path, info)| (path.clone(), info.clone()))
+                .collect::<ChangeMetadata>();
+            match linter.run(
+                &rerun_planned,
+                &rerun_metadata,
+                self.run_mode,
+                &self.scheduler,
+                &self.cancelled,
+                Some(&self.recorder),
+            ) {
+                Some(rerun_lints) => {
+                    let mut lints = lints
+                        .into_iter()
+                        .filter(|lint| !snapshot::is_about(lint, &modified))
+                        .collect::<Vec<_>>();
+                    lints.extend(snapshot::flag_modified(
+                        rerun_lints,
+                        &linter.code,
+                        &rerun_snapshot.modified(&rerun_files),
+                    ));
+                    lints
+                }
+                None => snapshot::flag_modified(lints, &linter.code, &modified),
+            }
+        } else {
+            snapshot::flag_modified(lints, &linter.code, &modified)
+        };
+
+        let lints = match &self.diff_scope {
+            Some(diff_scope) => diff_scope.filter_lints(lints),
+            None => lints,
+        };
+        let lints = suppress::remove_suppressed(lints);
+        let lints = match self.min_severity {
+            Some(min_severity) => lints
+                .into_iter()
+                .filter(|lint| lint.severity.is_at_least(min_severity))
+                .collect::<Vec<_>>(),
+            None => lints,
+        };
+
+        if self.fail_fast
+            && lints
+                .iter()
+                .any(|lint| matches!(lint.severity, LintSeverity::Error))
+            && !self.cancelled.swap(true, Ordering::SeqCst)
+        {
+            debug!(
+                "Linter {} reported an error, cancelling remaining linters (--fail-fast)",
+                linter.code
+            );
+        }
+        Ok(Some(lints))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{plan::tests::setup, progress::ProgressOpt};
+
+    #[test]
+    fn executes_a_plan() -> Result<()> {
+        let (dir, linters, files) = setup(
+            "
+            [[linter]]
+            code = 'MARKERS'
+            kind = 'builtin'
+            builtin = 'conflict_markers'
+            include_patterns = ['*.txt']
+            ",
+            &[
+                ("clean.txt", "fine\n"),
+                ("conflicted.txt", "<<<<<<< HEAD\n"),
+            ],
+        )?;
+        let plan = Plan::new(&linters, &files, None, &BTreeSet::new(), BTreeMap::new());
+        let executor = Executor {
+            run_mode: RunMode {
+                applying_fixes: false,
+                changed_lines_only: false,
+            },
+            apply_suggested: false,
+            fail_fast: false,
+            rerun_modified: false,
+            min_severity: None,
+            render_opt: RenderOpt::Default,
+            scheduler: Scheduler::new(1),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            budget_exhausted: Arc::new(AtomicBool::new(false)),
+            recorder: Recorder::default(),
+            progress: Progress::new(ProgressOpt::Hidden, &["MARKERS".to_string()]),
+            materialized: None,
+            diff_scope: None,
+            change_metadata: ChangeMetadata::new(),
+            snapshot: Some(FileSnapshot::take(&files)),
+            stream_to: None,
+            artifacts_dir: dir.path().join("artifacts"),
+            hash_dispatched: false,
+        };
+        let executed = executor.execute(&linters, &plan)?;
+        let paths = executed.lints.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(paths, [Some(files[1].display().to_string())]);
+        assert_eq!(executed.processed_files["MARKERS"], files);
+        assert!(executed.cancelled.is_empty());
+
+        // Plans are for particular linters.
+        assert!(executor.execute(&[], &plan).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(target_os = "windows", ignore)] // requires printf
+    fn runs_the_planned_batches() -> Result<()> {
+        let (dir, linters, files) = setup(
+            "
+            [[linter]]
+            code = 'PLANNED'
+            include_patterns = ['*.txt']
+            command = ['false', '{{PATHSFILE}}']
+            ",
+            &[("a.txt", "")],
+        )?;
+        let mut plan = Plan::new(&linters, &files, None, &BTreeSet::new(), BTreeMap::new());
+        // What runs is what was planned, not the linter's command again.
+        plan.linters[0].batches[0].command = [
+            "printf",
+            r#"{"path": null, "line": null, "char": null, "code": "PLANNED", "severity": "advice", "name": "%s", "original": null, "replacement": null, "description": null}\n"#,
+            "{{PATHSFILE}}",
+        ]
+        .map(String::from)
+        .to_vec();
+        let executor = Executor {
+            run_mode: RunMode::default(),
+            apply_suggested: false,
+            fail_fast: false,
+            rerun_modified: false,
+            min_severity: None,
+            render_opt: RenderOpt::Default,
+            scheduler: Scheduler::new(1),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            budget_exhausted: Arc::new(AtomicBool::new(false)),
+            recorder: Recorder::default(),
+            progress: Progress::new(ProgressOpt::Hidden, &["PLANNED".to_string()]),
+            materialized: None,
+            diff_scope: None,
+            change_metadata: ChangeMetadata::new(),
+            snapshot: None,
+            stream_to: None,
+            artifacts_dir: dir.path().join("artifacts"),
+            hash_dispatched: false,
+        };
+        let executed = executor.execute(&linters, &plan)?;
+        let lints = &executed.lints[&None];
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].code, "PLANNED");
+        assert!(matches!(lints[0].severity, LintSeverity::Advice));
+
+        // So is a linter whose invocations couldn't be planned.
+        plan.linters[0].error = Some("can't run here".to_string());
+        let executed = executor.execute(&linters, &plan)?;
+        let description = executed.lints[&None][0].description.as_deref().unwrap();
+        assert!(description.contains("can't run here"), "{}", description);
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+//! The codes lintrunner exits with, so wrappers and hooks can tell what kind
+//! of failure a run had without matching its stderr.
+//!
+//! A run can both report findings and have a linter fail; the highest code
+//! that applies wins, so a run that exits 1 had findings and nothing worse.
+//! Subcommands that check something, like `config check`, exit 1 for the
+//! problems they find.
+
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    linter::INVALID_CONFIGURATION,
+};
+
+/// Nothing to report.
+pub const SUCCESS: i32 = 0;
+/// Linters reported findings (beyond their budgets, if any).
+pub const LINT_FINDINGS: i32 = 1;
+/// The arguments or the config are invalid, including a linter's config.
+/// Also what clap exits with for arguments it can't parse.
+pub const USAGE_ERROR: i32 = 2;
+/// A linter failed to run: it crashed, timed out, wrote too much, or its
+/// toolchain is broken.
+pub const LINTER_FAILURE: i32 = 3;
+/// lintrunner itself failed.
+pub const INTERNAL_ERROR: i32 = 4;
+/// lintrunner was interrupted by SIGINT, e.g. Ctrl-C.
+pub const INTERRUPTED: i32 = 130;
+
+/// The exit codes, for `--help`.
+pub const HELP: &str = "\
+EXIT CODES:
+    0      No findings
+    1      Lint findings
+    2      Invalid arguments or config
+    3      A linter failed to run
+    4      Internal error
+    130    Interrupted
+If several apply, the highest of 1-3 is used.";
+
+/// An error in how lintrunner was invoked or configured, rather than in
+/// lintrunner itself. Displays as the error it wraps.
+#[derive(Debug)]
+pub struct UsageError(pub anyhow::Error);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Marks the error of a result as a [`UsageError`].
+pub trait UsageResult<T> {
+    fn usage_error(self) -> Result<T>;
+}
+
+impl<T> UsageResult<T> for Result<T> {
+    fn usage_error(self) -> Result<T> {
+        self.map_err(|err| UsageError(err).into())
+    }
+}
+
+/// The exit code for a run that ended with `err`.
+pub fn for_error(err: &anyhow::Error) -> i32 {
+    if err.chain().any(|cause| cause.is::<UsageError>()) {
+        USAGE_ERROR
+    } else {
+        INTERNAL_ERROR
+    }
+}
+
+/// The exit code for the failures among `lints`, if any: errors that aren't
+/// about a file mean the linter failed, or was misconfigured.
+pub fn for_failures<'a>(lints: impl IntoIterator<Item = &'a LintMessage>) -> Option<i32> {
+    lints
+        .into_iter()
+        .filter(|lint| lint.path.is_none() && matches!(lint.severity, LintSeverity::Error))
+        .map(|lint| {
+            if lint.name == INVALID_CONFIGURATION {
+                USAGE_ERROR
+            } else {
+                LINTER_FAILURE
+            }
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn usage_errors_keep_their_messages() {
+        let err = Err::<(), _>(anyhow!("no such profile"))
+            .context("Couldn't load the config")
+            .usage_error()
+            .context("Couldn't start")
+            .unwrap_err();
+        assert_eq!(for_error(&err), USAGE_ERROR);
+        assert_eq!(
+            err.chain()
+                .map(|cause| cause.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "Couldn't start",
+                "Couldn't load the config",
+                "no such profile"
+            ]
+        );
+        assert_eq!(for_error(&anyhow!("oops")), INTERNAL_ERROR);
+    }
+}
@@ -0,0 +1,105 @@
+//! Apply patches suggested by a previous run of lintrunner, without
+//! re-running any linters.
+
+use std::{collections::HashSet, convert::TryFrom, path::Path};
+
+use anyhow::Result;
+use console::{style, Term};
+use log::debug;
+
+use crate::{
+    apply::{apply_patches, conflicting_patches_error, FileLocks, Patch, PatchOutcome},
+    io_limit,
+    lint_config::patterns_from_strs,
+    linter::matches_relative_path,
+    path::AbsPath,
+    persistent_data::PersistentDataStore,
+};
+
+/// Apply a subset of the patches recorded by a past run.
+///
+/// Only patches from linters in `only` (if provided) and on files matching
+/// one of `path_patterns` (if any are provided) are applied. Patterns are
+/// interpreted relative to `config_dir`, like linter include patterns.
+pub fn do_fix(
+    persistent_data_store: &PersistentDataStore,
+    invocation: Option<usize>,
+    only: Option<HashSet<String>>,
+    path_patterns: &[String],
+    apply_suggested: bool,
+    config_dir: &Path,
+) -> Result<i32> {
+    let stdout = Term::stdout();
+    let path_patterns = patterns_from_strs(path_patterns)?;
+    let lint_messages = persistent_data_store.past_lint_messages(invocation)?;
+
+    let mut patches: Vec<Patch> = Vec::new();
+    let mut num_stale = 0;
+    for lint_message in &lint_messages {
+        if !lint_message.should_apply_fix(apply_suggested) {
+            continue;
+        }
+        let (replacement, path) = match (&lint_message.replacement, &lint_message.path) {
+            (Some(replacement), Some(path)) => (replacement, path),
+            _ => continue,
+        };
+        if let Some(only) = &only {
+            if !only.contains(&lint_message.code) {
+                continue;
+            }
+        }
+
+        let path = match AbsPath::try_from(path) {
+            Ok(path) => path,
+            Err(_) => {
+                debug!("Skipping patch for missing file: {}", path);
+                num_stale += 1;
+                continue;
+            }
+        };
+        if !path_patterns.is_empty()
+            && !path_patterns
+                .iter()
+                .any(|pattern| matches_relative_path(config_dir, &path, pattern))
+        {
+            continue;
+        }
+
+        if patches.iter().any(|patch| patch.path == path) {
+            return Err(conflicting_patches_error(path.display()));
+        }
+        // The file may have been edited since the run that produced this
+        // patch; applying it then would clobber those edits, so it's checked
+        // against `original` first.
+        patches.push(Patch {
+            path,
+            original: lint_message.original.as_deref(),
+            replacement,
+        });
+    }
+
+    let outcomes = apply_patches(&patches, &FileLocks::default(), io_limit::io_threads())?;
+    let num_applied = outcomes
+        .iter()
+        .filter(|outcome| **outcome == PatchOutcome::Applied)
+        .count();
+    num_stale += outcomes.len() - num_applied;
+
+    stdout.write_line(&format!(
+        "{} Applied {} patch(es).",
+        style("ok").green(),
+        num_applied
+    ))?;
+    if num_stale > 0 {
+        stdout.write_line(&format!(
+            "{}",
+            style(format!(
+                "Skipped {} patch(es) for files that changed since they were linted. \
+                 Re-run lintrunner to get fresh suggestions.",
+                num_stale
+            ))
+            .yellow()
+        ))?;
+    }
+    Ok(0)
+}
@@ -1,8 +1,15 @@
-use std::{collections::HashSet, convert::TryFrom, process::Command};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    convert::TryFrom,
+    path::Path,
+    process::Command,
+};
 
 use crate::{
+    diff_file::parse_unified_diff,
     log_utils::{ensure_output, log_files},
     path::AbsPath,
+    pathsfile::{ChangeInfo, ChangeKind, ChangeMetadata},
     version_control::VersionControl,
 };
 use anyhow::{ensure, Context, Result};
@@ -13,12 +20,13 @@ pub struct Repo {
     root: AbsPath,
 }
 
-impl VersionControl for Repo {
-    fn new() -> Result<Repo> {
-        // Retrieve the git root based on the current working directory.
+impl Repo {
+    /// The repo that `dir` is in.
+    pub fn at(dir: &Path) -> Result<Repo> {
         let output = Command::new("git")
             .arg("rev-parse")
             .arg("--show-toplevel")
+            .current_dir(dir)
             .output()?;
         ensure!(output.status.success(), "Failed to determine git root");
         let root = std::str::from_utf8(&output.stdout)?.trim();
@@ -26,9 +34,20 @@ impl VersionControl for Repo {
             root: AbsPath::try_from(root)?,
         })
     }
+}
+
+impl VersionControl for Repo {
+    fn new() -> Result<Repo> {
+        // Retrieve the git root based on the current working directory.
+        Repo::at(Path::new("."))
+    }
 
     fn get_head(&self) -> Result<String> {
-        let output = Command::new("git").arg("rev-parse").arg("HEAD").output()?;
+        let output = Command::new("git")
+            .arg("rev-parse")
+            .arg("HEAD")
+            .current_dir(&self.root)
+            .output()?;
         ensure_output("git rev-parse", &output)?;
         let head = std::str::from_utf8(&output.stdout)?.trim();
         Ok(head.to_string())
@@ -135,7 +154,7 @@ impl VersionControl for Repo {
         let filtered_files = all_files
             .difference(&deleted_working_tree_files)
             .map(|f| format!("{}", self.root.join(f).display()))
-            .filter_map(|f| match AbsPath::try_from(&f) {
+            .filter_map(|f| match AbsPath::try_from_allowing_dangling(&f) {
                 Ok(abs_path) => Some(abs_path),
                 Err(_) => {
                     eprintln!("Failed to find file while gathering files to lint: {}", f);
@@ -166,39 +185,200 @@ impl VersionControl for Repo {
         files.sort();
         files
             .into_iter()
-            .map(AbsPath::try_from)
+            .map(|file| AbsPath::try_from_allowing_dangling(self.root.join(file)))
             .collect::<Result<_>>()
     }
+
+    fn get_root(&self) -> &AbsPath {
+        &self.root
+    }
+
+    fn get_nested_roots(&self) -> Result<Vec<AbsPath>> {
+        // git doesn't look inside other repos in its worktree, and lists
+        // them as untracked directories instead. Without --exclude-standard,
+        // that includes ignored ones.
+        let output = Command::new("git")
+            .args([
+                "ls-files",
+                "--others",
+                "--directory",
+                "--no-empty-directory",
+                "-z",
+            ])
+            .current_dir(&self.root)
+            .output()?;
+        ensure_output("git ls-files --others", &output)?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|path| path.ends_with('/'))
+            .map(|dir| self.root.join(dir))
+            .filter(|dir| dir.join(".git").exists())
+            .filter_map(|dir| AbsPath::try_from(dir).ok())
+            .collect())
+    }
+
+    fn get_user(&self) -> Result<String> {
+        let output = Command::new("git")
+            .args(["config", "user.email"])
+            .current_dir(&self.root)
+            .output()?;
+        ensure_output("git config user.email", &output)?;
+        Ok(std::str::from_utf8(&output.stdout)?.trim().to_string())
+    }
+
+    fn get_line_authors(&self, path: &AbsPath, user: &str) -> Result<HashMap<String, usize>> {
+        let output = Command::new("git")
+            .args(["blame", "--line-porcelain", "--"])
+            .arg(path.as_os_str())
+            .current_dir(&self.root)
+            .output()?;
+        ensure_output("git blame", &output)?;
+
+        let mut authors = HashMap::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if let Some(mail) = line.strip_prefix("author-mail ") {
+                let mail = mail.trim_start_matches('<').trim_end_matches('>');
+                let author = if mail == "not.committed.yet" {
+                    user
+                } else {
+                    mail
+                };
+                *authors.entry(author.to_string()).or_insert(0) += 1;
+            }
+        }
+        Ok(authors)
+    }
+
+    fn get_change_metadata(&self, relative_to: Option<&str>) -> Result<ChangeMetadata> {
+        // Cover the same changes as `get_changed_files`: everything since
+        // `relative_to` (or in the HEAD commit), plus the working tree.
+        let base = relative_to.unwrap_or("HEAD^");
+        let diff = |extra_args: &[&str]| -> Result<String> {
+            let output = Command::new("git")
+                .args([
+                    "diff",
+                    "--no-color",
+                    "--no-ext-diff",
+                    "--ignore-submodules",
+                    "-M",
+                ])
+                .args(extra_args)
+                .arg(base)
+                .current_dir(&self.root)
+                .output()?;
+            ensure_output("git diff", &output)?;
+            Ok(String::from_utf8(output.stdout)?)
+        };
+
+        let mut changed_lines: HashMap<String, BTreeSet<usize>> =
+            parse_unified_diff(&diff(&["-U0"])?)?
+                .into_iter()
+                .map(|file| (file.path, file.lines))
+                .collect();
+
+        // Output of --name-status looks like:
+        // M    foo/bar.baz
+        // R087 old/name.rs    new/name.rs
+        let mut metadata = ChangeMetadata::new();
+        for line in diff(&["--name-status"])?.lines() {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            let (kind, old_path, path) = match fields.as_slice() {
+                [status, old_path, path] if status.starts_with('R') => {
+                    (ChangeKind::Modified, Some(*old_path), *path)
+                }
+                [status, _, path] if status.starts_with('C') => (ChangeKind::Added, None, *path),
+                ["A", path] => (ChangeKind::Added, None, *path),
+                ["D", path] => (ChangeKind::Deleted, None, *path),
+                [_, path] => (ChangeKind::Modified, None, *path),
+                _ => continue,
+            };
+            // Files that no longer exist can't be linted, so there's nothing
+            // to attach their metadata to.
+            let abs_path = match AbsPath::try_from_allowing_dangling(self.root.join(path)) {
+                Ok(abs_path) => abs_path,
+                Err(_) => continue,
+            };
+            metadata.insert(
+                abs_path,
+                ChangeInfo {
+                    kind,
+                    old_path: old_path
+                        .map(|old_path| self.root.join(old_path).display().to_string()),
+                    changed_lines: changed_lines.remove(path).unwrap_or_default(),
+                },
+            );
+        }
+        Ok(metadata)
+    }
 }
 
-pub fn get_paths_from_cmd(paths_cmd: &str) -> Result<Vec<AbsPath>> {
-    debug!("Running paths_cmd: {}", paths_cmd);
-    if paths_cmd.is_empty() {
+/// A command to run to get the paths to lint, as specified by `--paths-cmd` or
+/// `--paths-cmd-arg`.
+#[derive(Debug)]
+pub struct PathsCmd {
+    /// The program and its arguments. These are passed to the program as-is,
+    /// without any shell interpretation.
+    pub argv: Vec<String>,
+    /// If set, the command's output is a NUL-delimited list of paths (e.g.
+    /// `git ls-files -z`). Otherwise, it is newline-delimited.
+    pub nul_delimited: bool,
+}
+
+impl PathsCmd {
+    /// Split a `--paths-cmd` string into arguments with POSIX shell quoting
+    /// rules. The command is still not run through a shell.
+    pub fn from_shell_words(paths_cmd: &str, nul_delimited: bool) -> Result<PathsCmd> {
+        let argv = shell_words::split(paths_cmd).context("failed to split paths_cmd")?;
+        Ok(PathsCmd {
+            argv,
+            nul_delimited,
+        })
+    }
+}
+
+fn parse_paths_cmd_output(output: &str, nul_delimited: bool) -> Vec<String> {
+    let files = if nul_delimited {
+        output
+            .split('\0')
+            .map(|s| s.to_string())
+            .collect::<HashSet<_>>()
+    } else {
+        output
+            .lines()
+            .map(|s| s.to_string())
+            .collect::<HashSet<_>>()
+    };
+    let mut files = files
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<String>>();
+    files.sort();
+    files
+}
+
+pub fn get_paths_from_cmd(paths_cmd: &PathsCmd) -> Result<Vec<AbsPath>> {
+    debug!("Running paths_cmd: {:?}", paths_cmd.argv);
+    if paths_cmd.argv.is_empty() {
         return Err(anyhow::Error::msg(
             "paths_cmd is empty. Please provide an executable command.",
         ));
     }
-    let argv = shell_words::split(paths_cmd).context("failed to split paths_cmd")?;
-    debug!("Parsed paths_cmd: {:?}", argv);
 
-    let output = Command::new(&argv[0])
-        .args(&argv[1..])
+    let output = Command::new(&paths_cmd.argv[0])
+        .args(&paths_cmd.argv[1..])
         .output()
         .context("failed to run provided paths_cmd")?;
 
     ensure!(
         output.status.success(),
-        format!("Failed to run provided paths_cmd: '{}'", paths_cmd)
+        format!(
+            "Failed to run provided paths_cmd: '{}'",
+            shell_words::join(&paths_cmd.argv)
+        )
     );
 
     let files = std::str::from_utf8(&output.stdout).context("failed to parse paths_cmd output")?;
-    let files = files
-        .lines()
-        .map(|s| s.to_string())
-        .collect::<HashSet<String>>();
-    let mut files = files.into_iter().collect::<Vec<String>>();
-    files.sort();
-    files
+    parse_paths_cmd_output(files, paths_cmd.nul_delimited)
         .into_iter()
         .map(AbsPath::try_from)
         .collect::<Result<_>>()
@@ -209,6 +389,18 @@ mod tests {
     use super::*;
     use crate::testing::GitCheckout;
 
+    #[test]
+    fn paths_cmd_output_delimiters() {
+        assert_eq!(
+            parse_paths_cmd_output("b.py\na b.py\n\nb.py\n", false),
+            vec!["a b.py", "b.py"]
+        );
+        assert_eq!(
+            parse_paths_cmd_output("with\nnewline.py\0a b.py\0", true),
+            vec!["a b.py", "with\nnewline.py"]
+        );
+    }
+
     // Should properly detect changes in the commit (and not check other files)
     #[test]
     fn doesnt_detect_unchanged() -> Result<()> {
@@ -285,6 +477,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn change_metadata() -> Result<()> {
+        let git = GitCheckout::new()?;
+        git.write_file("test_1.txt", "line 1\nline 2\nline 3")?;
+        git.write_file("test_2.txt", "a much longer line that stays the same\nb\nc")?;
+        git.add(".")?;
+        git.commit("commit 1")?;
+
+        git.write_file("test_1.txt", "line 4")?;
+        git.write_file("test_3.txt", "new")?;
+        git.add(".")?;
+        git.commit("commit 2")?;
+
+        let output = git.run("mv").arg("test_2.txt").arg("moved.txt").output()?;
+        assert!(output.status.success());
+
+        let metadata = git.change_metadata(None)?;
+        assert_eq!(metadata.len(), 3);
+        assert_eq!(metadata["test_1.txt"].kind, ChangeKind::Modified);
+        assert_eq!(
+            metadata["test_1.txt"].changed_lines,
+            [4].into_iter().collect()
+        );
+        assert_eq!(metadata["test_3.txt"].kind, ChangeKind::Added);
+        assert_eq!(metadata["moved.txt"].kind, ChangeKind::Modified);
+        assert!(metadata["moved.txt"]
+            .old_path
+            .as_ref()
+            .unwrap()
+            .ends_with("test_2.txt"));
+        assert!(metadata["moved.txt"].changed_lines.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn relative_revision() -> Result<()> {
         let git = GitCheckout::new()?;
@@ -362,8 +588,10 @@ mod tests {
 
     #[test]
     fn invalid_get_paths_from_cmd_fails() {
-        assert!(get_paths_from_cmd("asoidjfoaisdjf").is_err());
-        assert!(get_paths_from_cmd("false").is_err());
+        for paths_cmd in ["asoidjfoaisdjf", "false", ""] {
+            let paths_cmd = PathsCmd::from_shell_words(paths_cmd, false).unwrap();
+            assert!(get_paths_from_cmd(&paths_cmd).is_err());
+        }
     }
 
     #[test]
@@ -412,4 +640,36 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn finds_nested_roots() -> Result<()> {
+        let git = GitCheckout::new()?;
+        git.write_file(".gitignore", "ignored/")?;
+        git.add(".gitignore")?;
+        git.commit("ignore")?;
+        for dir in ["nested", "ignored", "not_a_repo"] {
+            std::fs::create_dir_all(git.root().join(dir))?;
+            std::fs::write(git.root().join(dir).join("a.txt"), "a")?;
+        }
+        for dir in ["nested", "ignored"] {
+            let status = Command::new("git")
+                .args(["init", "-q"])
+                .current_dir(git.root().join(dir))
+                .status()?;
+            assert!(status.success());
+        }
+
+        let repo = Repo::at(git.root())?;
+        let mut roots = repo.get_nested_roots()?;
+        roots.sort();
+        let root = git.root().canonicalize()?;
+        assert_eq!(
+            roots,
+            vec![
+                AbsPath::try_from(root.join("ignored"))?,
+                AbsPath::try_from(root.join("nested"))?,
+            ]
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,132 @@
+//! Skipping the paths git ignores, for `respect_gitignore`.
+//!
+//! Tracked files can still match a `.gitignore` (they were added before the
+//! pattern was, or with `git add -f`), and paths passed on the command line or
+//! by `--paths-cmd` needn't be tracked at all. Linters that respect gitignore
+//! skip the paths git would ignore: those matching a `.gitignore` in their
+//! directory or any directory above it up to the repo root,
+//! `.git/info/exclude`, or the global excludes file. Deeper `.gitignore`
+//! files take precedence, so one can `!`-include a path ignored above it.
+//!
+//! Ignore files are read with the `ignore` crate, once per directory, rather
+//! than by asking git about each path.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use ignore::{
+    gitignore::{gitconfig_excludes_path, Gitignore, GitignoreBuilder},
+    Match,
+};
+use log::debug;
+
+/// The ignore files of the directories paths were checked in, read as needed.
+#[derive(Default)]
+pub struct Gitignores {
+    /// By directory. `None` for directories without ignore files.
+    dirs: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+fn is_repo_root(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
+
+impl Gitignores {
+    /// Read the ignore files of `dir`. At the repo root, that includes
+    /// `.git/info/exclude` and the global excludes file, which are relative to
+    /// it.
+    fn read(dir: &Path) -> Option<Arc<Gitignore>> {
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut files = Vec::new();
+        if is_repo_root(dir) {
+            // Later files take precedence.
+            files.extend(gitconfig_excludes_path());
+            files.push(dir.join(".git/info/exclude"));
+        }
+        files.push(dir.join(".gitignore"));
+        let mut any = false;
+        for file in files.into_iter().filter(|file| file.is_file()) {
+            if let Some(err) = builder.add(&file) {
+                debug!("Failed to read '{}': {}", file.display(), err);
+            }
+            any = true;
+        }
+        if !any {
+            return None;
+        }
+        match builder.build() {
+            Ok(gitignore) => Some(Arc::new(gitignore)),
+            Err(err) => {
+                debug!(
+                    "Failed to read the ignore files in '{}': {}",
+                    dir.display(),
+                    err
+                );
+                None
+            }
+        }
+    }
+
+    fn in_dir(&self, dir: &Path) -> Option<Arc<Gitignore>> {
+        if let Some(gitignore) = self.dirs.lock().unwrap().get(dir) {
+            return gitignore.clone();
+        }
+        // Read without holding the lock. Two threads may both read a
+        // directory, which is harmless.
+        let gitignore = Gitignores::read(dir);
+        self.dirs
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), gitignore.clone());
+        gitignore
+    }
+
+    /// Whether git ignores `path`, a file, or a directory containing it.
+    pub fn ignores(&self, path: &Path) -> bool {
+        for dir in path.ancestors().skip(1) {
+            if let Some(gitignore) = self.in_dir(dir) {
+                match gitignore.matched_path_or_any_parents(path, false) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            if is_repo_root(dir) {
+                break;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deeper_ignore_files_take_precedence() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let root = root.path();
+        std::fs::create_dir_all(root.join(".git/info"))?;
+        std::fs::create_dir_all(root.join("build/keep"))?;
+        std::fs::create_dir_all(root.join("src/gen"))?;
+        std::fs::write(root.join(".git/info/exclude"), "*.local\n")?;
+        std::fs::write(root.join(".gitignore"), "build/\n*.log\n")?;
+        std::fs::write(root.join("src/.gitignore"), "gen/\n!debug.log\n")?;
+
+        let gitignores = Gitignores::default();
+        let ignored = |path: &str| gitignores.ignores(&root.join(path));
+        assert!(!ignored("src/main.py"));
+        assert!(ignored("build/out.py"));
+        assert!(ignored("build/keep/out.py"));
+        assert!(ignored("server.log"));
+        assert!(ignored("settings.local"));
+        assert!(ignored("src/gen/types.py"));
+        assert!(!ignored("src/debug.log"));
+        assert!(ignored("src/other.log"));
+        Ok(())
+    }
+}
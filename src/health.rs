@@ -0,0 +1,353 @@
+//! Checking that linters' toolchains work before running them.
+//!
+//! A linter whose executable is missing, or whose `health_check` command
+//! fails, would otherwise fail every one of its invocations, and only be
+//! reported after the rest of the run finished. Instead, every linter is
+//! probed before anything runs, and a broken one is reported once, without
+//! being run.
+//!
+//! Probe results are cached in the data dir for `health_cache_minutes` (10 by
+//! default), so that health checks don't slow down every run. A result is
+//! thrown away early if the linter's executable changes, and `lintrunner
+//! init`, which is usually what fixes a toolchain, clears the cache.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lint_message::LintMessage,
+    linter::Linter,
+    network::{self, NetworkPolicy},
+    persistent_data::PersistentDataStore,
+};
+
+/// How long probe results are reused, unless the config says otherwise.
+pub const DEFAULT_HEALTH_CACHE_MINUTES: u64 = 10;
+
+/// A health check that takes longer than this fails.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The cached result of probing one linter.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HealthRecord {
+    /// Identifies what was probed: the linter's executable, when it was last
+    /// modified, and the health check command. If any of them change, the
+    /// linter is probed again.
+    pub key: String,
+    /// When the probe ran, in seconds since the Unix epoch.
+    pub checked_at: u64,
+    /// Why the linter's toolchain is broken, if it is.
+    pub error: Option<String>,
+}
+
+/// Probe results by linter code.
+pub type HealthCache = BTreeMap<String, HealthRecord>;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Find `program` like running it would: relative to `cwd` if it's a path,
+/// otherwise on `PATH`.
+fn find_executable(program: &str, cwd: &Path) -> Option<PathBuf> {
+    let candidates = |path: PathBuf| {
+        let mut candidates = vec![path.clone()];
+        if cfg!(windows) && path.extension().is_none() {
+            candidates.push(path.with_extension("exe"));
+        }
+        candidates
+    };
+    if Path::new(program).components().count() > 1 {
+        return candidates(cwd.join(program))
+            .into_iter()
+            .find(|path| is_executable(path));
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| candidates(dir.join(program)))
+        .find(|path| is_executable(path))
+}
+
+/// Run `command` in `cwd`, failing unless it exits 0 in time. `command` should
+/// already be sandboxed as `network` requires; the environment is restricted
+/// here.
+fn run_health_check(
+    command: &[String],
+    cwd: &Path,
+    env: &[(&str, &str)],
+    network: NetworkPolicy,
+) -> Result<()> {
+    let (program, arguments) = command
+        .split_first()
+        .context("The health check command is empty")?;
+    let rendered = command.join(" ");
+    let mut health_check = Command::new(program);
+    health_check
+        .args(arguments)
+        .current_dir(cwd)
+        .envs(env.iter().copied());
+    if !network.allows_run() {
+        network::strip_env(&mut health_check);
+    }
+    let mut child = health_check
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run `{}`", rendered))?;
+    let start = Instant::now();
+    while child.try_wait()?.is_none() {
+        if start.elapsed() > HEALTH_CHECK_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!(
+                "`{}` didn't finish within {} seconds",
+                rendered,
+                HEALTH_CHECK_TIMEOUT.as_secs()
+            );
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.lines().find(|line| !line.trim().is_empty()) {
+            Some(line) => bail!("`{}` failed with {}: {}", rendered, output.status, line),
+            None => bail!("`{}` failed with {}", rendered, output.status),
+        }
+    }
+    Ok(())
+}
+
+/// The local program that runs `linter`'s command. With remote execution or
+/// a command wrapper, the linter's own executable may only exist wherever it
+/// is run, so that program is checked instead.
+fn probed_program(linter: &Linter) -> Option<&str> {
+    match &linter.remote {
+        Some(remote) => Some(remote.ssh_program()),
+        None => linter
+            .command_wrapper
+            .first()
+            .or_else(|| linter.commands.first())
+            .map(String::as_str),
+    }
+}
+
+/// What would be probed for `linter`: its executable, if it was found, and
+/// the cache key for that. `None` if the linter isn't probed at all.
+fn probe_target(linter: &Linter) -> Option<(Option<PathBuf>, String)> {
+    if linter.builtin.is_some() || linter.config_error.is_some() {
+        return None;
+    }
+    let program = probed_program(linter)?;
+    // E.g. `{{DOWNLOAD}}`, which is checked when the linter runs.
+    if program.contains("{{") {
+        return None;
+    }
+    let executable = find_executable(program, linter.get_config_dir());
+    let modified = executable
+        .as_ref()
+        .and_then(|path| path.metadata().ok())
+        .and_then(|metadata| metadata.modified().ok())
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_secs());
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(program.as_bytes());
+    hasher.update(format!("\0{:?}\0{:?}\0", executable, modified).as_bytes());
+    for arg in linter
+        .command_wrapper
+        .iter()
+        .chain(linter.health_check.iter().flatten())
+    {
+        hasher.update(arg.as_bytes());
+        hasher.update(b"\0");
+    }
+    Some((executable, hasher.finalize().to_hex().to_string()))
+}
+
+/// Probe `linter`, whose executable was found at `executable`.
+fn probe(linter: &Linter, executable: Option<&Path>) -> Option<String> {
+    if executable.is_none() {
+        return Some(format!(
+            "`{}` was not found",
+            probed_program(linter).unwrap_or_default()
+        ));
+    }
+    let health_check = match linter.wrap_linter_command(linter.health_check.clone()?) {
+        Ok(health_check) => health_check,
+        Err(err) => return Some(format!("{:#}", err)),
+    };
+    let lease = linter.remote.as_ref().map(|remote| remote.lease());
+    let health_check = match (&linter.remote, &lease) {
+        (Some(remote), Some(lease)) => remote.remote_command(
+            lease.host(),
+            linter.get_config_dir(),
+            &linter.controlled_env(),
+            &health_check,
+            None,
+            false,
+        ),
+        _ => health_check,
+    };
+    run_health_check(
+        &health_check,
+        linter.get_config_dir(),
+        &linter.controlled_env(),
+        linter.network,
+    )
+    .err()
+    .map(|err| format!("{:#}", err))
+}
+
+/// Probe every linter whose cached result is missing or older than
+/// `cache_for`, and mark the broken ones so they report it instead of
+/// running.
+pub fn preflight(
+    linters: &mut [Linter],
+    persistent_data_store: &PersistentDataStore,
+    cache_for: Duration,
+) -> Result<()> {
+    let mut cache = persistent_data_store.health_cache()?;
+    let now = now();
+
+    let targets = linters
+        .iter()
+        .enumerate()
+        .filter_map(|(i, linter)| {
+            probe_target(linter).map(|(executable, key)| (i, executable, key))
+        })
+        .collect::<Vec<_>>();
+    let checked = targets.iter().map(|(i, _, _)| *i).collect::<Vec<_>>();
+    let to_probe = targets
+        .into_iter()
+        .filter(|(i, _, key)| {
+            let code = &linters[*i].code;
+            let fresh = cache.get(code).is_some_and(|record| {
+                record.key == *key && now.saturating_sub(record.checked_at) < cache_for.as_secs()
+            });
+            if fresh {
+                debug!("Using cached health of linter {}", code);
+            }
+            !fresh
+        })
+        .collect::<Vec<_>>();
+
+    let linters_ref = &*linters;
+    let probed = thread::scope(|scope| {
+        to_probe
+            .into_iter()
+            .map(|(i, executable, key)| {
+                scope.spawn(move || {
+                    let linter = &linters_ref[i];
+                    debug!("Checking health of linter {}", linter.code);
+                    (i, key, probe(linter, executable.as_deref()))
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    let any_probed = !probed.is_empty();
+    for (i, key, error) in probed {
+        cache.insert(
+            linters[i].code.clone(),
+            HealthRecord {
+                key,
+                checked_at: now,
+                error,
+            },
+        );
+    }
+    if any_probed {
+        persistent_data_store.write_health_cache(&cache)?;
+    }
+
+    for i in checked {
+        let linter = &mut linters[i];
+        linter.health_error = cache
+            .get(&linter.code)
+            .and_then(|record| record.error.clone());
+    }
+    Ok(())
+}
+
+/// Reported instead of running linter `code`, whose toolchain is broken.
+pub fn broken_toolchain_message(code: &str, error: &str) -> LintMessage {
+    LintMessage::general_error(
+        code,
+        "Broken toolchain",
+        format!(
+            "This linter was not run because its toolchain doesn't work: {}\n\n\
+             Fix it (`lintrunner init` may help) and run lintrunner again.",
+            error
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(target_os = "windows", ignore)] // requires sh
+    fn health_checks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert!(find_executable("sh", dir.path()).is_some());
+        assert!(find_executable("idonotexist", dir.path()).is_none());
+        assert!(find_executable("./sh", dir.path()).is_none());
+
+        let command = |script: &str| vec!["sh".to_string(), "-c".to_string(), script.to_string()];
+        run_health_check(&command("exit 0"), dir.path(), &[], NetworkPolicy::Allowed)?;
+        let err = run_health_check(
+            &command("echo 'no module named mypy' >&2; exit 1"),
+            dir.path(),
+            &[],
+            NetworkPolicy::Allowed,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().ends_with(": no module named mypy"),
+            "{}",
+            err
+        );
+
+        // Health checks of linters that may not use the network don't get
+        // proxy settings.
+        let proxy = [("HTTPS_PROXY", "http://proxy:3128")];
+        let no_proxy = "test -z \"$HTTPS_PROXY\" && test \"$LINTRUNNER_NETWORK\" = none";
+        run_health_check(&command(no_proxy), dir.path(), &proxy, NetworkPolicy::Never)?;
+        assert!(run_health_check(
+            &command(no_proxy),
+            dir.path(),
+            &proxy,
+            NetworkPolicy::Allowed
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+//! Exporting metadata about past runs, for `lintrunner history export`.
+
+use std::io::Write;
+
+use anyhow::Result;
+use clap::ArgEnum;
+use serde::Serialize;
+
+use crate::{
+    lint_message::LintSeverity,
+    persistent_data::{PastRun, PersistentDataStore},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+pub enum HistoryFormat {
+    Csv,
+    Json,
+}
+
+/// One exported run.
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub args: Vec<String>,
+    pub duration_ms: Option<u64>,
+    pub exit_code: i32,
+    pub error: Option<String>,
+    /// Lint counts are missing if the run didn't record any lint messages,
+    /// e.g. because it wasn't a lint run.
+    pub num_lints: Option<usize>,
+    pub num_errors: Option<usize>,
+    pub num_warnings: Option<usize>,
+    pub num_advice: Option<usize>,
+}
+
+impl From<PastRun> for HistoryEntry {
+    fn from(run: PastRun) -> HistoryEntry {
+        let count = |pred: fn(&LintSeverity) -> bool| {
+            run.lint_messages
+                .as_ref()
+                .map(|messages| messages.iter().filter(|m| pred(&m.severity)).count())
+        };
+        HistoryEntry {
+            num_lints: run.lint_messages.as_ref().map(|messages| messages.len()),
+            num_errors: count(|s| matches!(s, LintSeverity::Error)),
+            num_warnings: count(|s| matches!(s, LintSeverity::Warning)),
+            num_advice: count(|s| matches!(s, LintSeverity::Advice)),
+            timestamp: run.run_info.timestamp,
+            args: run.run_info.args,
+            duration_ms: run.exit_info.duration_ms,
+            exit_code: run.exit_info.code,
+            error: run.exit_info.err,
+        }
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn optional_field<T: ToString>(field: &Option<T>) -> String {
+    field.as_ref().map(|f| f.to_string()).unwrap_or_default()
+}
+
+pub fn render_history_csv(stdout: &mut impl Write, entries: &[HistoryEntry]) -> Result<()> {
+    writeln!(
+        stdout,
+        "timestamp,args,duration_ms,exit_code,error,num_lints,num_errors,num_warnings,num_advice"
+    )?;
+    for entry in entries {
+        let fields = [
+            entry.timestamp.clone(),
+            shell_words::join(&entry.args),
+            optional_field(&entry.duration_ms),
+            entry.exit_code.to_string(),
+            optional_field(&entry.error),
+            optional_field(&entry.num_lints),
+            optional_field(&entry.num_errors),
+            optional_field(&entry.num_warnings),
+            optional_field(&entry.num_advice),
+        ];
+        let fields = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>();
+        writeln!(stdout, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Write one JSON object per line, like `--output json`.
+pub fn render_history_json(stdout: &mut impl Write, entries: &[HistoryEntry]) -> Result<()> {
+    for entry in entries {
+        writeln!(stdout, "{}", serde_json::to_string(entry)?)?;
+    }
+    Ok(())
+}
+
+pub fn do_history_export(
+    persistent_data_store: &PersistentDataStore,
+    format: HistoryFormat,
+) -> Result<i32> {
+    let entries = persistent_data_store
+        .past_runs_with_lint_messages()?
+        .into_iter()
+        .map(HistoryEntry::from)
+        .collect::<Vec<_>>();
+
+    let mut stdout = std::io::stdout().lock();
+    match format {
+        HistoryFormat::Csv => render_history_csv(&mut stdout, &entries)?,
+        HistoryFormat::Json => render_history_json(&mut stdout, &entries)?,
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_escapes_fields() -> Result<()> {
+        let entries = vec![HistoryEntry {
+            timestamp: "2024-01-01T00:00:00.000Z".to_string(),
+            args: vec!["lintrunner".to_string(), "--take=A,B".to_string()],
+            duration_ms: Some(1200),
+            exit_code: 1,
+            error: Some("it said \"no\"".to_string()),
+            num_lints: None,
+            num_errors: None,
+            num_warnings: None,
+            num_advice: None,
+        }];
+        let mut out = Vec::new();
+        render_history_csv(&mut out, &entries)?;
+        let out = String::from_utf8(out)?;
+        assert_eq!(
+            out.lines().nth(1).unwrap(),
+            "2024-01-01T00:00:00.000Z,\"lintrunner '--take=A,B'\",1200,1,\"it said \"\"no\"\"\",,,,"
+        );
+        Ok(())
+    }
+}
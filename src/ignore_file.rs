@@ -0,0 +1,68 @@
+//! Tools' own ignore files, for linters' `ignore_file` setting.
+//!
+//! Many tools skip the paths listed in an ignore file of their own, like
+//! `.clang-format-ignore`, `.prettierignore` or `.eslintignore`. Passed such a
+//! path anyway, some lint it regardless, and others complain in a way
+//! lintrunner can't parse. A linter that names its tool's ignore file is never
+//! passed the paths it ignores.
+//!
+//! Ignore files use gitignore syntax, which those tools' formats follow:
+//! one pattern per line, relative to the directory of the ignore file, with
+//! `#` comments and `!` to re-include a path an earlier line ignored.
+//!
+//! A `.lintrunnerignore` at the root of the repo is lintrunner's own ignore
+//! file: the paths it ignores are linted by no linter, so trees like vendored
+//! code can be left out once rather than in every linter's
+//! `exclude_patterns`.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use log::debug;
+
+use crate::path::AbsPath;
+
+/// The name of lintrunner's own ignore file, at the root of the repo.
+pub const LINTRUNNERIGNORE: &str = ".lintrunnerignore";
+
+/// Read the ignore file at `path`.
+pub fn read(path: &Path) -> Result<Gitignore> {
+    // Unwrap is fine here because `path` is a file, so it has a parent.
+    let mut builder = GitignoreBuilder::new(path.parent().unwrap());
+    if let Some(error) = builder.add(path) {
+        return Err(error).with_context(|| format!("Could not read '{}'", path.display()));
+    }
+    Ok(builder.build()?)
+}
+
+/// Whether `ignore_file` ignores `path`, or a directory containing it.
+pub fn ignores(ignore_file: &Gitignore, path: &Path) -> bool {
+    // Paths outside the ignore file's directory are none of its business.
+    path.starts_with(ignore_file.path())
+        && ignore_file
+            .matched_path_or_any_parents(path, false)
+            .is_ignore()
+}
+
+/// Remove the paths ignored by the `.lintrunnerignore` at `root`, if there is
+/// one, from `files`.
+pub fn remove_lintrunnerignored(files: &mut Vec<AbsPath>, root: &Path) -> Result<()> {
+    let path = root.join(LINTRUNNERIGNORE);
+    if !path.is_file() {
+        return Ok(());
+    }
+    let ignore_file = read(&path)?;
+    files.retain(|file| {
+        let ignored = ignores(&ignore_file, file);
+        if ignored {
+            debug!(
+                "Skipping '{}', which {} ignores",
+                file.display(),
+                path.display()
+            );
+        }
+        !ignored
+    });
+    Ok(())
+}
@@ -0,0 +1,167 @@
+//! Mapping the selected files to the build targets that own them, for
+//! `lintrunner impacted`.
+//!
+//! Files are selected exactly as they would be for linting, so one tool can
+//! answer both "what should be linted" and "what should be rebuilt".
+
+use std::{collections::BTreeSet, convert::TryFrom, process::Command};
+
+use anyhow::{bail, Context, Result};
+use clap::ArgEnum;
+use log::debug;
+
+use crate::{
+    get_version_control, log_utils::ensure_output, path::AbsPath, path_provider::bazel_label_path,
+    select_files, PathsOpt, RevisionOpt,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+pub enum BuildSystem {
+    Bazel,
+    Buck2,
+}
+
+impl BuildSystem {
+    fn program(&self) -> &'static str {
+        match self {
+            BuildSystem::Bazel => "bazel",
+            BuildSystem::Buck2 => "buck2",
+        }
+    }
+
+    fn command(&self) -> Command {
+        Command::new(self.program())
+    }
+
+    pub(crate) fn workspace_root(&self) -> Result<AbsPath> {
+        let mut command = self.command();
+        match self {
+            BuildSystem::Bazel => command.args(["info", "workspace"]),
+            BuildSystem::Buck2 => command.args(["root", "--kind", "project"]),
+        };
+        let output = command
+            .output()
+            .with_context(|| format!("Failed to run '{}'", self.program()))?;
+        ensure_output(self.program(), &output)?;
+        let root = std::str::from_utf8(&output.stdout)?.trim();
+        AbsPath::try_from(root)
+            .with_context(|| format!("'{}' reported an invalid root: '{}'", self.program(), root))
+    }
+
+    /// The source files of `targets`, relative to `root`.
+    pub(crate) fn sources(&self, root: &AbsPath, targets: &[String]) -> Result<Vec<String>> {
+        let set = targets.join(" ");
+        let mut command = self.command();
+        command.current_dir(root);
+        match self {
+            BuildSystem::Bazel => {
+                command.args([
+                    "query",
+                    "--output=label",
+                    &format!("kind(\"source file\", deps(set({}), 1))", set),
+                ]);
+            }
+            BuildSystem::Buck2 => {
+                command.args(["uquery", &format!("inputs(set({}))", set)]);
+            }
+        }
+        debug!("Finding target sources: {:?}", command);
+        let output = command
+            .output()
+            .with_context(|| format!("Failed to run '{}'", self.program()))?;
+        ensure_output(self.program(), &output)?;
+        let lines = std::str::from_utf8(&output.stdout)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+        Ok(match self {
+            BuildSystem::Bazel => lines
+                .filter_map(bazel_label_path)
+                .map(|path| path.display().to_string())
+                .collect(),
+            BuildSystem::Buck2 => lines.map(str::to_string).collect(),
+        })
+    }
+
+    /// The targets that own `files`, given relative to `root`.
+    fn owners(&self, root: &AbsPath, files: &[String]) -> Result<Vec<String>> {
+        let mut command = self.command();
+        command.current_dir(root);
+        match self {
+            BuildSystem::Bazel => {
+                let set = files
+                    .iter()
+                    .map(|file| format!("\"{}\"", file))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                command.args([
+                    "query",
+                    "--keep_going",
+                    "--output=label",
+                    &format!("same_pkg_direct_rdeps(set({}))", set),
+                ]);
+            }
+            BuildSystem::Buck2 => {
+                command.args(["uquery", "owner(%Ss)"]).args(files);
+            }
+        }
+        debug!("Finding owning targets: {:?}", command);
+        let output = command
+            .output()
+            .with_context(|| format!("Failed to run '{}'", self.program()))?;
+        // With --keep_going, bazel exits with 3 if some files aren't in any
+        // package, but still reports the targets it found.
+        let partial_success = *self == BuildSystem::Bazel && output.status.code() == Some(3);
+        if !partial_success {
+            ensure_output(self.program(), &output)?;
+        }
+        Ok(std::str::from_utf8(&output.stdout)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Print the build targets owning the files selected by `paths_opt`, one per
+/// line.
+pub fn do_impacted(
+    paths_opt: PathsOpt,
+    revision_opt: RevisionOpt,
+    scopes: Option<&[AbsPath]>,
+    build_system: BuildSystem,
+) -> Result<i32> {
+    let repo = get_version_control()?;
+    let selected = select_files(repo.as_ref(), paths_opt, revision_opt, scopes, false)?;
+    if selected.files.is_empty() {
+        return Ok(0);
+    }
+
+    let root = build_system.workspace_root()?;
+    let mut relative_files = Vec::new();
+    for file in &selected.files {
+        match file.strip_prefix(&*root) {
+            Ok(relative) => match relative.to_str() {
+                Some(relative) => relative_files.push(relative.to_string()),
+                None => bail!("Path is not valid UTF-8: '{}'", file.display()),
+            },
+            Err(_) => debug!(
+                "Skipping '{}', which is outside the workspace",
+                file.display()
+            ),
+        }
+    }
+    if relative_files.is_empty() {
+        return Ok(0);
+    }
+
+    let targets = build_system
+        .owners(&root, &relative_files)?
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+    for target in targets {
+        println!("{}", target);
+    }
+    Ok(0)
+}
@@ -0,0 +1,44 @@
+//! Whether lintrunner may ask the user questions or animate its output.
+//!
+//! Prompts are only shown when someone can answer them: not with
+//! `--non-interactive`, which is implied when stdin isn't a terminal, and not
+//! in CI. Otherwise they fail with a message saying which flag to pass
+//! instead, so that lintrunner never hangs waiting for input.
+
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use console::Term;
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Turn prompts off for the rest of the run.
+pub fn set_non_interactive(non_interactive: bool) {
+    NON_INTERACTIVE.store(non_interactive, Ordering::SeqCst);
+}
+
+/// Whether `--non-interactive` was passed, or implied because stdin isn't a
+/// terminal.
+pub fn is_non_interactive() -> bool {
+    NON_INTERACTIVE.load(Ordering::SeqCst) || !std::io::stdin().is_terminal()
+}
+
+/// Whether lintrunner runs in CI, by the common convention of setting `CI`
+/// (to anything but `false` or `0`).
+pub fn is_ci() -> bool {
+    std::env::var("CI").is_ok_and(|value| !matches!(value.as_str(), "" | "false" | "0"))
+}
+
+/// Whether someone is watching stderr: it is a terminal, and this isn't CI,
+/// where jobs may be given a terminal nobody looks at. Animations are only
+/// shown then.
+pub fn is_watched() -> bool {
+    Term::stderr().is_term() && !is_ci()
+}
+
+/// Whether the user can be asked a question.
+pub fn can_prompt() -> bool {
+    !is_non_interactive() && is_watched()
+}
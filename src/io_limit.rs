@@ -0,0 +1,99 @@
+//! A cap on how much filesystem IO lintrunner does at once.
+//!
+//! Gathering files stats and reads them from one thread per linter, and
+//! patches and paths files are written as linters finish. On a local disk that
+//! parallelism is cheap, but on a network filesystem (an NFS-mounted checkout,
+//! a devserver) thousands of concurrent calls make everything slower. So
+//! filesystem work goes through [`limited`], which lets at most `--io-threads`
+//! calls run at a time.
+
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+};
+
+/// How many filesystem operations may run at once by default.
+pub const DEFAULT_IO_THREADS: usize = 8;
+
+static IO_THREADS: AtomicUsize = AtomicUsize::new(DEFAULT_IO_THREADS);
+static IN_FLIGHT: Mutex<usize> = Mutex::new(0);
+static SLOT_FREED: Condvar = Condvar::new();
+
+thread_local! {
+    /// Whether this thread holds a slot, so that nested calls don't wait for
+    /// one.
+    static HOLDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Let at most `io_threads` filesystem operations run at once. Call once, at
+/// startup.
+pub fn set_io_threads(io_threads: usize) {
+    IO_THREADS.store(io_threads.max(1), Ordering::SeqCst);
+}
+
+/// How many filesystem operations may run at once.
+pub fn io_threads() -> usize {
+    IO_THREADS.load(Ordering::SeqCst)
+}
+
+/// Gives back its slot when dropped, including when unwinding.
+struct Slot;
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        HOLDING.with(|holding| holding.set(false));
+        *IN_FLIGHT.lock().unwrap() -= 1;
+        SLOT_FREED.notify_one();
+    }
+}
+
+/// Run `io` once fewer than `--io-threads` other operations are running.
+pub fn limited<T>(io: impl FnOnce() -> T) -> T {
+    if HOLDING.with(Cell::get) {
+        return io();
+    }
+    let slot = {
+        let mut in_flight = SLOT_FREED
+            .wait_while(IN_FLIGHT.lock().unwrap(), |in_flight| {
+                *in_flight >= io_threads()
+            })
+            .unwrap();
+        *in_flight += 1;
+        HOLDING.with(|holding| holding.set(true));
+        Slot
+    };
+    let result = io();
+    drop(slot);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn at_most_io_threads_run_at_once() {
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..4 * io_threads() {
+                scope.spawn(|| {
+                    limited(|| {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        most.fetch_max(now, Ordering::SeqCst);
+                        // Nested calls don't wait for a second slot.
+                        limited(|| thread::sleep(Duration::from_millis(5)));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                });
+            }
+        });
+        let most = most.into_inner();
+        assert!(most >= 1 && most <= io_threads(), "{}", most);
+    }
+}
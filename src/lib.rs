@@ -1,43 +1,126 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{Context, Result};
+use apply::{FileLocks, Patch, PatchOutcome};
+use budget::{check_budgets, covered_by_budget, Budget};
 use clap::ArgEnum;
-use console::{style, Term};
-use indicatif::{MultiProgress, ProgressBar};
-use linter::Linter;
-use log::debug;
+use columns::ColumnUnit;
+use console::Term;
+use content_provider::ContentProvider;
+use diff_file::DiffScope;
+use executor::{Executed, Executor};
+use exit_code::UsageResult;
+use linter::{Linter, RunMode, SkipReason};
+use log::{debug, info};
+use nested::NestedConfigs;
+use ownership::OwnershipOpt;
 use path::AbsPath;
+use path_provider::{PathProvider, ProviderContext};
+use pathsfile::{ChangeMetadata, PathsFileFormat};
 use persistent_data::PersistentDataStore;
-use render::{render_lint_messages, render_lint_messages_json};
-use std::collections::HashMap;
-use std::collections::HashSet;
+use plan::Plan;
+use progress::{Progress, ProgressOpt};
+use record::Recorder;
+use render::{
+    render_budget_overruns, render_lint_messages, render_lint_messages_grouped,
+    render_lint_messages_json, render_lint_messages_json_with_patches,
+    render_nothing_linted_notice, render_time_budget_notice, render_timing, GroupBy,
+};
+use scheduler::Scheduler;
+use serde::{Deserialize, Serialize};
+use snapshot::FileSnapshot;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fs::OpenOptions;
-use std::sync::{Arc, Mutex};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
+use stream::LintStream;
 use version_control::VersionControl;
 
+pub mod apply;
+pub mod artifacts;
+pub mod badge;
+pub mod bisect;
+pub mod budget;
+pub mod builtin;
+pub mod capabilities;
+pub mod cargo;
+pub mod columns;
+pub mod compdb;
+pub mod config_check;
+pub mod config_new;
+pub mod config_schema;
+pub mod content_provider;
+pub mod diff_file;
+pub mod download;
+pub mod executor;
+pub mod exit_code;
+pub mod fix;
 pub mod git;
+pub mod gitignore;
+pub mod health;
+pub mod history;
+pub mod ignore_file;
+pub mod impacted;
 pub mod init;
+pub mod interactive;
+pub mod io_limit;
 pub mod lint_config;
 pub mod lint_message;
 pub mod linter;
 pub mod log_utils;
+pub mod manifest;
+pub mod nested;
+pub mod network;
+pub mod ownership;
+pub mod patch;
 pub mod path;
+pub mod path_provider;
+pub mod pathsfile;
 pub mod persistent_data;
+pub mod plan;
+pub mod process;
+pub mod profile;
+pub mod progress;
+pub mod protected;
+pub mod publish;
 pub mod rage;
+pub mod record;
+pub mod remote;
 pub mod render;
 pub mod sapling;
+pub mod scheduler;
+pub mod shebang;
+pub mod snapshot;
+pub mod spool;
+pub mod stream;
+pub mod subdir;
+pub mod suppress;
+pub mod terminal;
+pub mod theme;
+pub mod tmpdir;
+pub mod toolchain;
+pub mod trend;
+pub mod triage;
+pub mod trust;
+pub mod vendored;
 pub mod version_control;
 
 #[cfg(test)]
 pub mod testing;
 
-use git::get_paths_from_cmd;
-use lint_message::LintMessage;
+use git::PathsCmd;
+use lint_message::{LintMessage, LintSeverity};
 use render::PrintedLintErrors;
 
+use crate::render::render_lint_messages_arc;
 use crate::render::render_lint_messages_oneline;
+use crate::render::render_lint_messages_summary_markdown;
 
-fn group_lints_by_file(
+pub(crate) fn group_lints_by_file(
     all_lints: &mut HashMap<Option<String>, Vec<LintMessage>>,
     lints: Vec<LintMessage>,
 ) {
@@ -47,32 +130,72 @@ fn group_lints_by_file(
     });
 }
 
-fn apply_patches(lint_messages: &[LintMessage]) -> Result<()> {
-    let mut patched_paths = HashSet::new();
+/// Order the messages on each file by linter, in the order of `linters`,
+/// keeping each linter's messages in the order it reported them.
+fn order_by_linter(all_lints: &mut HashMap<Option<String>, Vec<LintMessage>>, linters: &[Linter]) {
+    let mut ranks = HashMap::new();
+    for (rank, linter) in linters.iter().enumerate() {
+        ranks.entry(linter.code.as_str()).or_insert(rank);
+    }
+    for lint_messages in all_lints.values_mut() {
+        lint_messages.sort_by_key(|lint_message| {
+            ranks
+                .get(lint_message.code.as_str())
+                .copied()
+                .unwrap_or(usize::MAX)
+        });
+    }
+}
+
+/// Apply the fixes suggested in `lint_messages`. Returns the paths whose
+/// fixes were skipped because the file changed after the linter read it.
+fn apply_patches(
+    lint_messages: &[LintMessage],
+    apply_suggested: bool,
+    file_locks: &FileLocks,
+    snapshot: Option<&FileSnapshot>,
+) -> Result<HashSet<AbsPath>> {
+    let mut patches: Vec<Patch> = Vec::new();
     for lint_message in lint_messages {
+        if !lint_message.should_apply_fix(apply_suggested) {
+            continue;
+        }
         if let (Some(replacement), Some(path)) = (&lint_message.replacement, &lint_message.path) {
             let path = AbsPath::try_from(path)?;
-            if patched_paths.contains(&path) {
-                bail!(
-                    "Two different linters proposed changes for the same file:
-                    {}.\n This is not yet supported, file an issue if you want it.",
-                    path.display()
-                );
+            if patches.iter().any(|patch| patch.path == path) {
+                return Err(apply::conflicting_patches_error(path.display()));
             }
-            patched_paths.insert(path.clone());
-
-            std::fs::write(&path, replacement).context(format!(
-                "Failed to write apply patch to file: '{}'",
-                path.display()
-            ))?;
+            patches.push(Patch {
+                path,
+                original: lint_message.original.as_deref(),
+                replacement,
+            });
         }
     }
-    Ok(())
+    let outcomes = apply::apply_patches(&patches, file_locks, io_limit::io_threads())?;
+    // Our own fixes don't count as the files changing during the run.
+    if let Some(snapshot) = snapshot {
+        let applied = patches
+            .iter()
+            .zip(&outcomes)
+            .filter(|(_, outcome)| **outcome == PatchOutcome::Applied)
+            .map(|(patch, _)| patch.path.clone())
+            .collect::<Vec<_>>();
+        snapshot.update(&applied);
+    }
+    Ok(patches
+        .into_iter()
+        .zip(outcomes)
+        .filter(|(_, outcome)| *outcome == PatchOutcome::Stale)
+        .map(|(patch, _)| patch.path)
+        .collect())
 }
 
 pub fn do_init(
     linters: Vec<Linter>,
     dry_run: bool,
+    offline: bool,
+    pack: Option<&Path>,
     persistent_data_store: &PersistentDataStore,
     config_paths: &Vec<std::string::String>,
 ) -> Result<i32> {
@@ -81,24 +204,64 @@ pub fn do_init(
         linters.iter().map(|l| &l.code).collect::<Vec<_>>()
     );
 
-    for linter in linters {
-        linter.init(dry_run)?;
+    for linter in &linters {
+        linter.init(dry_run, offline)?;
+    }
+    if !dry_run {
+        persistent_data_store.clear_health_cache()?;
     }
     persistent_data_store.update_last_init(config_paths)?;
+    if let Some(archive) = pack {
+        let count = toolchain::pack(&linters, archive)
+            .with_context(|| format!("Failed to pack toolchains into {}", archive.display()))?;
+        println!("Packed {} toolchains into {}", count, archive.display());
+    }
     Ok(0)
 }
 
-fn remove_patchable_lints(lints: Vec<LintMessage>) -> Vec<LintMessage> {
-    lints
-        .into_iter()
-        .filter(|lint| lint.replacement.is_none())
-        .collect()
+/// Restore the toolchains in `archive`, made by `lintrunner init --pack`,
+/// instead of running init commands.
+pub fn do_init_from_archive(
+    archive: &Path,
+    persistent_data_store: &PersistentDataStore,
+    config_paths: &Vec<std::string::String>,
+) -> Result<i32> {
+    toolchain::restore(archive)
+        .with_context(|| format!("Failed to restore toolchains from {}", archive.display()))?;
+    persistent_data_store.clear_health_cache()?;
+    persistent_data_store.update_last_init(config_paths)?;
+    Ok(0)
+}
+
+/// Drop the lints whose fixes were applied. Lints whose fixes were stale are
+/// kept, along with a warning about it.
+fn remove_patchable_lints(
+    lints: Vec<LintMessage>,
+    apply_suggested: bool,
+    stale_paths: &HashSet<AbsPath>,
+) -> Vec<LintMessage> {
+    let mut remaining = Vec::new();
+    for lint in lints {
+        if !lint.should_apply_fix(apply_suggested) {
+            remaining.push(lint);
+            continue;
+        }
+        let stale = lint.path.as_ref().is_some_and(|path| {
+            AbsPath::try_from(path).is_ok_and(|path| stale_paths.contains(&path))
+        });
+        if stale {
+            let path = lint.path.clone().unwrap_or_default();
+            remaining.push(apply::stale_patch_message(&lint.code, &path));
+            remaining.push(lint);
+        }
+    }
+    remaining
 }
 
 fn get_paths_from_input(paths: Vec<String>) -> Result<Vec<AbsPath>> {
     let mut ret = Vec::new();
     for path in &paths {
-        let path = AbsPath::try_from(path)
+        let path = AbsPath::try_from_allowing_dangling(path)
             .with_context(|| format!("Failed to find provided file: '{}'", path))?;
         ret.push(path);
     }
@@ -127,8 +290,27 @@ pub enum PathsOpt {
     Auto,
     AllFiles,
     PathsFile(AbsPath),
-    PathsCmd(String),
+    PathsCmd(PathsCmd),
     Paths(Vec<String>),
+    /// Lint the files changed by a diff, reporting only lints on changed lines.
+    DiffFile(DiffScope),
+    /// Lint the files a custom provider provides.
+    Provider(Box<dyn PathProvider>),
+}
+
+impl PathsOpt {
+    /// The provider of these paths. `revision_opt` is only used for `Auto`.
+    pub fn into_provider(self, revision_opt: RevisionOpt) -> Box<dyn PathProvider> {
+        match self {
+            PathsOpt::Auto => Box::new(path_provider::ChangedFiles(revision_opt)),
+            PathsOpt::AllFiles => Box::new(path_provider::AllFiles),
+            PathsOpt::PathsFile(file) => Box::new(path_provider::PathsFile(file)),
+            PathsOpt::PathsCmd(paths_cmd) => Box::new(path_provider::CommandOutput(paths_cmd)),
+            PathsOpt::Paths(paths) => Box::new(path_provider::PathList(paths)),
+            PathsOpt::DiffFile(scope) => Box::new(path_provider::DiffFile(scope)),
+            PathsOpt::Provider(provider) => provider,
+        }
+    }
 }
 
 /// Represents the scope of revisions that the auto paths finder will look at to
@@ -142,11 +324,29 @@ pub enum RevisionOpt {
     MergeBaseWith(String),
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ArgEnum,
+    Default,
+    Serialize,
+    Deserialize
+)]
+#[serde(rename_all = "lowercase")]
 pub enum RenderOpt {
+    #[default]
     Default,
     Json,
     Oneline,
+    Vscode,
+    #[serde(rename = "summary-markdown")]
+    SummaryMarkdown,
+    Arc,
 }
 
 pub fn get_version_control() -> Result<Box<dyn VersionControl>> {
@@ -158,129 +358,517 @@ pub fn get_version_control() -> Result<Box<dyn VersionControl>> {
     Ok(Box::new(sapling::Repo::new()?))
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn do_lint(
-    linters: Vec<Linter>,
+/// The files selected by the paths options.
+pub struct SelectedFiles {
+    /// Sorted and deduplicated.
+    pub files: Vec<AbsPath>,
+    /// With --diff-file, the diff. Only lints on lines it changes are
+    /// reported.
+    pub diff_scope: Option<DiffScope>,
+    /// How the files changed, if requested and known.
+    pub change_metadata: ChangeMetadata,
+}
+
+/// Select the files to work on according to `paths_opt`, restricted to
+/// `scopes` if set. With `with_change_metadata`, also find out how they
+/// changed, where that's known.
+pub fn select_files(
+    repo: &dyn VersionControl,
     paths_opt: PathsOpt,
-    should_apply_patches: bool,
-    render_opt: RenderOpt,
-    enable_spinners: bool,
     revision_opt: RevisionOpt,
-    tee_json: Option<String>,
-    only_lint_under_config_dir: bool,
+    scopes: Option<&[AbsPath]>,
+    with_change_metadata: bool,
+) -> Result<SelectedFiles> {
+    select_provided_files(
+        repo,
+        paths_opt.into_provider(revision_opt),
+        scopes,
+        with_change_metadata,
+    )
+}
+
+/// Select the files `provider` provides, restricted to `scopes` if set.
+pub fn select_provided_files(
+    repo: &dyn VersionControl,
+    provider: Box<dyn PathProvider>,
+    scopes: Option<&[AbsPath]>,
+    with_change_metadata: bool,
+) -> Result<SelectedFiles> {
+    debug!(
+        "Selecting files with the '{}' path provider",
+        provider.name()
+    );
+    let context = ProviderContext {
+        repo,
+        scopes,
+        with_change_metadata,
+    };
+    let mut selected = provider.provide(&context)?;
+    if let Some(scopes) = scopes {
+        selected
+            .files
+            .retain(|path| scopes.iter().any(|scope| path.starts_with(scope)));
+    }
+    // Sort and unique the files so we pass a consistent ordering to linters
+    selected.files.sort();
+    selected.files.dedup();
+    Ok(selected)
+}
+
+/// Parse a duration like `600`, `600s`, `10m` or `1h`. A bare number is in
+/// seconds.
+pub fn parse_duration(duration: &str) -> Result<Duration> {
+    let duration = duration.trim();
+    let (number, unit_secs) = match duration.char_indices().last() {
+        Some((i, 's')) => (&duration[..i], 1),
+        Some((i, 'm')) => (&duration[..i], 60),
+        Some((i, 'h')) => (&duration[..i], 60 * 60),
+        _ => (duration, 1),
+    };
+    let invalid = || format!("Invalid duration '{}', expected e.g. 600s or 10m", duration);
+    let number: u64 = number.parse().with_context(invalid)?;
+    let secs = number.checked_mul(unit_secs).with_context(invalid)?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Compute the directories that files must be under to be linted, from
+/// `--dir` and `only_lint_under_config_dir`. `None` means no restriction.
+pub fn lint_scopes(dirs: Vec<AbsPath>, config_dir: Option<AbsPath>) -> Option<Vec<AbsPath>> {
+    match (dirs.is_empty(), config_dir) {
+        (true, None) => None,
+        (true, Some(config_dir)) => Some(vec![config_dir]),
+        (false, None) => Some(dirs),
+        // Intersect each --dir with the config dir.
+        (false, Some(config_dir)) => Some(
+            dirs.into_iter()
+                .filter_map(|dir| {
+                    if dir.starts_with(&config_dir) {
+                        Some(dir)
+                    } else if config_dir.starts_with(&dir) {
+                        Some(config_dir.clone())
+                    } else {
+                        debug!(
+                            "Ignoring --dir {}, since it is outside the config directory",
+                            dir.display()
+                        );
+                        None
+                    }
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// How [`do_lint`] lints.
+pub struct LintOptions {
+    /// Which files to lint.
+    pub paths_opt: PathsOpt,
+    /// Apply the fixes linters propose.
+    pub should_apply_patches: bool,
+    /// With `should_apply_patches`, also apply suggested fixes.
+    pub apply_suggested: bool,
+    pub render_opt: RenderOpt,
+    /// The width to wrap the default output at, if any.
+    pub wrap_width: Option<usize>,
+    pub progress_opt: ProgressOpt,
+    /// The revisions to look for changed files in, with `PathsOpt::Auto`.
+    pub revision_opt: RevisionOpt,
+    /// Also write the messages as JSON to this file.
+    pub tee_json: Option<String>,
+    /// Write fixes to this patch file instead of applying them.
+    pub patch_file: Option<String>,
+    /// Only lint files under the config's directory.
+    pub only_lint_under_config_dir: bool,
+    pub skip_vendored: bool,
+    pub ownership_opt: OwnershipOpt,
+    /// Only lint files under these directories, if any.
+    pub dirs: Vec<AbsPath>,
+    /// Cancel the remaining linters once one reports an error.
+    pub fail_fast: bool,
+    /// Fail if no files were linted.
+    pub strict_empty: bool,
+    /// Write a manifest of which files each linter processed to this file.
+    pub write_manifest: Option<String>,
+    /// Write a badge summarizing the run to this file.
+    pub badge: Option<String>,
+    /// Record each linter invocation to this file, for `lintrunner replay`.
+    pub record: Option<String>,
+    /// Lint the contents it provides instead of the working tree.
+    pub content_provider: Option<ContentProvider>,
+    /// Why the linters skipped before linting are skipped.
+    pub skip_reasons: BTreeMap<String, SkipReason>,
+    pub nested_configs: Option<NestedConfigs>,
+    /// Cancel the linters that are still running after this long.
+    pub time_budget: Option<Duration>,
+    /// Send each linter's messages here as soon as it finishes.
+    pub stream_to: Option<LintStream>,
+    /// Report how long each linter took.
+    pub timing: bool,
+    /// The unit to report columns in.
+    pub column_unit: ColumnUnit,
+    pub group_by: GroupBy,
+    pub budgets: Vec<Budget>,
+    /// How many linter invocations may run at once.
+    pub jobs: Option<usize>,
+    /// Drop messages less severe than this.
+    pub min_severity: Option<LintSeverity>,
+    /// How long the results of health checks are kept.
+    pub health_cache: Duration,
+    /// Lint files that changed while being linted again.
+    pub rerun_modified: bool,
+    /// Write the plan to this file instead of running the linters.
+    pub plan_json: Option<String>,
+}
+
+pub fn do_lint(
+    mut linters: Vec<Linter>,
+    options: LintOptions,
+    persistent_data_store: &PersistentDataStore,
 ) -> Result<i32> {
+    let LintOptions {
+        paths_opt,
+        should_apply_patches,
+        apply_suggested,
+        render_opt,
+        wrap_width,
+        progress_opt,
+        revision_opt,
+        tee_json,
+        patch_file,
+        only_lint_under_config_dir,
+        skip_vendored,
+        ownership_opt,
+        dirs,
+        fail_fast,
+        strict_empty,
+        write_manifest,
+        badge,
+        record,
+        content_provider,
+        mut skip_reasons,
+        nested_configs,
+        time_budget,
+        stream_to,
+        timing,
+        column_unit,
+        group_by,
+        budgets,
+        jobs,
+        min_severity,
+        health_cache,
+        rerun_modified,
+        plan_json,
+    } = options;
     debug!(
         "Running linters: {:?}",
         linters.iter().map(|l| &l.code).collect::<Vec<_>>()
     );
     let repo = get_version_control()?;
     let mut stdout = Term::stdout();
-    if linters.is_empty() {
+    if linters.is_empty() && nested_configs.is_none() {
         stdout.write_line("No linters ran.")?;
         return Ok(0);
     }
 
-    let config_dir = if only_lint_under_config_dir {
-        Some(AbsPath::try_from(linters[0].get_config_dir())?)
+    // Nested configs' linters have their own config dirs, so go by the root
+    // config's.
+    let config_dir = match &nested_configs {
+        Some(nested_configs) => {
+            // Unwrap is fine here because we know this path is absolute and won't be `/`
+            AbsPath::try_from(nested_configs.root_config_path.parent().unwrap())?
+        }
+        None => AbsPath::try_from(linters[0].get_config_dir())?,
+    };
+    // Run from inside a repo nested in the config's, lint the config's repo,
+    // which looks for changes in the nested one too.
+    let repo = if repo.get_root() != &config_dir && repo.get_root().starts_with(&config_dir) {
+        match git::Repo::at(&config_dir) {
+            Ok(outer) => Box::new(outer),
+            Err(_) => repo,
+        }
+    } else {
+        repo
+    };
+    let scope_dir = if only_lint_under_config_dir {
+        Some(config_dir.clone())
     } else {
         None
     };
-
-    let mut files = match paths_opt {
-        PathsOpt::Auto => {
-            let relative_to = match revision_opt {
-                RevisionOpt::Head => None,
-                RevisionOpt::Revision(revision) => Some(revision),
-                RevisionOpt::MergeBaseWith(merge_base_with) => {
-                    Some(repo.get_merge_base_with(&merge_base_with)?)
-                }
-            };
-            debug!("Relative to: {:?}", relative_to);
-            repo.get_changed_files(relative_to.as_deref())?
+    let scopes = lint_scopes(dirs, scope_dir);
+
+    // How changed files changed, for linters that take (or might take) a
+    // JSON paths file.
+    let wants_change_metadata = linters.iter().any(|linter| {
+        linter.pathsfile_format == PathsFileFormat::Json
+            || linter.probe_capabilities
+            || linter
+                .capabilities
+                .is_some_and(|capabilities| capabilities.json_pathsfile)
+    });
+    let provider = paths_opt.into_provider(revision_opt);
+    let lints_changes = provider.lists_changes();
+    let SelectedFiles {
+        mut files,
+        diff_scope,
+        change_metadata,
+    } = select_provided_files(
+        repo.as_ref(),
+        provider,
+        scopes.as_deref(),
+        wants_change_metadata,
+    )?;
+    if skip_vendored {
+        vendored::remove_vendored(&mut files, &config_dir);
+    }
+    ignore_file::remove_lintrunnerignored(&mut files, repo.get_root())?;
+    if ownership_opt.mine || ownership_opt.owner.is_some() {
+        ownership::retain_owned(&mut files, repo.as_ref(), &ownership_opt)?;
+    }
+    if let Some(nested_configs) = &nested_configs {
+        nested_configs
+            .add_linters(&files, &mut linters, &mut skip_reasons)
+            .usage_error()?;
+        if linters.is_empty() {
+            stdout.write_line("No linters ran.")?;
+            return Ok(0);
+        }
+    }
+    health::preflight(&mut linters, persistent_data_store, health_cache)?;
+    // Linters with a higher priority start first, and their messages are
+    // shown first. The sort is stable, so others keep the config's order.
+    linters.sort_by_key(|linter| Reverse(linter.priority));
+
+    // Linters whose own config files changed lint all files, since the
+    // change can affect their results anywhere. Provided contents only cover
+    // the selected files, so there is nothing more to lint with them.
+    let expanded_linters = if lints_changes && content_provider.is_none() {
+        linters
+            .iter()
+            .filter(|linter| linter.expand_on_config_change && linter.cache_inputs_among(&files))
+            .map(|linter| linter.code.clone())
+            .collect::<BTreeSet<_>>()
+    } else {
+        BTreeSet::new()
+    };
+    let all_files = if expanded_linters.is_empty() {
+        None
+    } else {
+        info!(
+            "Linting all files with {}, since their config files changed",
+            expanded_linters
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let mut all_files = select_provided_files(
+            repo.as_ref(),
+            Box::new(path_provider::AllFiles),
+            scopes.as_deref(),
+            false,
+        )?
+        .files;
+        if skip_vendored {
+            vendored::remove_vendored(&mut all_files, &config_dir);
         }
-        PathsOpt::PathsCmd(paths_cmd) => get_paths_from_cmd(&paths_cmd)?,
-        PathsOpt::Paths(paths) => get_paths_from_input(paths)?,
-        PathsOpt::PathsFile(file) => get_paths_from_file(file)?,
-        PathsOpt::AllFiles => repo.get_all_files(config_dir.as_ref())?,
+        ignore_file::remove_lintrunnerignored(&mut all_files, repo.get_root())?;
+        if ownership_opt.mine || ownership_opt.owner.is_some() {
+            ownership::retain_owned(&mut all_files, repo.as_ref(), &ownership_opt)?;
+        }
+        // Changed files that aren't committed yet are still linted.
+        all_files.extend(files.iter().cloned());
+        all_files.sort();
+        all_files.dedup();
+        Some(all_files)
     };
 
-    // Sort and unique the files so we pass a consistent ordering to linters
-    if let Some(config_dir) = config_dir {
-        files.retain(|path| path.starts_with(&config_dir));
+    let plan = Plan::new(
+        &linters,
+        &files,
+        all_files.as_deref(),
+        &expanded_linters,
+        skip_reasons,
+    );
+    if let Some(plan_json) = plan_json {
+        plan.write(&plan_json)?;
+        return Ok(0);
     }
-    files.sort();
-    files.dedup();
 
-    let files = Arc::new(files);
-
-    log_utils::log_files("Linting files: ", &files);
-
-    let mut thread_handles = Vec::new();
-    let spinners = Arc::new(MultiProgress::new());
+    let materialized = match content_provider {
+        Some(content_provider) => Some(content_provider.materialize(&files, &config_dir)?),
+        None => None,
+    };
 
-    // Too lazy to learn rust's fancy concurrent programming stuff, just spawn a thread per linter and join them.
-    let all_lints = Arc::new(Mutex::new(HashMap::new()));
+    // To tell which files change while they're being linted. Provided
+    // contents can't change.
+    let snapshot = match &materialized {
+        Some(_) => None,
+        None => Some(FileSnapshot::take(all_files.as_deref().unwrap_or(&files))),
+    };
 
-    for linter in linters {
-        let all_lints = Arc::clone(&all_lints);
-        let files = Arc::clone(&files);
-        let spinners = Arc::clone(&spinners);
+    log_utils::log_files("Linting files: ", &files);
 
-        let handle = thread::spawn(move || -> Result<()> {
-            let mut spinner = None;
-            if enable_spinners {
-                let _spinner = spinners.add(ProgressBar::new_spinner());
-                _spinner.set_message(format!("{} running...", linter.code));
-                _spinner.enable_steady_tick(100);
-                spinner = Some(_spinner);
+    // Set when the remaining linters should stop, e.g. due to --fail-fast.
+    let cancelled = Arc::new(AtomicBool::new(false));
+
+    // With --time-budget, cancel whatever is still running once the budget
+    // runs out. Dropping `budget_done` stops the timer early.
+    let budget_exhausted = Arc::new(AtomicBool::new(false));
+    let (budget_done, budget_done_rx) = mpsc::channel::<()>();
+    if let Some(time_budget) = time_budget {
+        let cancelled = Arc::clone(&cancelled);
+        let budget_exhausted = Arc::clone(&budget_exhausted);
+        thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = budget_done_rx.recv_timeout(time_budget) {
+                debug!("Time budget exhausted, cancelling remaining linters");
+                budget_exhausted.store(true, Ordering::SeqCst);
+                cancelled.store(true, Ordering::SeqCst);
             }
+        });
+    }
 
-            let lints = linter.run(&files);
+    // External files each linter's results depend on, for the manifest.
+    let cache_inputs = linters
+        .iter()
+        .filter(|linter| !linter.cache_inputs.is_empty())
+        .map(|linter| {
+            let paths = linter
+                .cache_inputs
+                .iter()
+                .map(|path| linter.get_config_dir().join(path))
+                .collect::<Vec<_>>();
+            (linter.code.clone(), paths)
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    // Hands out worker slots to linter invocations, fairly between linters.
+    let slots = jobs.unwrap_or_else(|| Scheduler::default_slots(linters.len()));
+    let executor = Executor {
+        run_mode: RunMode {
+            applying_fixes: should_apply_patches,
+            changed_lines_only: diff_scope.is_some(),
+        },
+        apply_suggested,
+        fail_fast,
+        rerun_modified,
+        min_severity,
+        render_opt,
+        scheduler: Scheduler::new(slots).with_priorities(
+            linters
+                .iter()
+                .map(|linter| (linter.code.as_str(), linter.priority)),
+        ),
+        cancelled,
+        budget_exhausted,
+        // Captures linter invocations, for --record and for the per-linter
+        // logs kept with the run. Only --record needs each one's environment.
+        recorder: Recorder::new(record.is_some()),
+        progress: Progress::new(
+            progress_opt,
+            &linters.iter().map(|l| l.code.clone()).collect::<Vec<_>>(),
+        ),
+        materialized,
+        diff_scope,
+        change_metadata,
+        snapshot,
+        stream_to,
+        artifacts_dir: persistent_data_store.artifacts_dir(),
+        hash_dispatched: write_manifest.is_some(),
+    };
+    let Executed {
+        lints: mut all_lints,
+        processed_files,
+        processed_hashes,
+        cancelled: cancelled_linters,
+        num_stale_patches,
+    } = executor.execute(&linters, &plan)?;
+    drop(budget_done);
+    order_by_linter(&mut all_lints, &linters);
+
+    // Report columns in the unit asked for. (The VS Code output is always in
+    // UTF-16 code units.)
+    let converted_lints;
+    let rendered_lints = if column_unit == ColumnUnit::Char {
+        &all_lints
+    } else {
+        let mut lints = all_lints.clone();
+        for messages in lints.values_mut() {
+            columns::convert_messages(messages, ColumnUnit::Char, column_unit);
+        }
+        converted_lints = lints;
+        &converted_lints
+    };
+    let nothing_linted = !plan.any_matched();
+    let mut skip_reasons = plan.skipped;
+    skip_reasons.extend(cancelled_linters);
+    for (code, reason) in skip_reasons.iter() {
+        debug!("Linter {} did not run: {}", code, reason);
+    }
 
-            // If we're applying patches later, don't consider lints that would
-            // be fixed by that.
-            let lints = if should_apply_patches {
-                apply_patches(&lints)?;
-                remove_patchable_lints(lints)
-            } else {
-                lints
-            };
+    // Flush the logger before rendering results.
+    log::logger().flush();
 
-            let mut all_lints = all_lints.lock().unwrap();
-            let is_success = lints.is_empty();
+    // Linting nothing is usually a mistake (wrong directory, bad
+    // --merge-base-with, etc.), so call it out rather than reporting success.
+    if nothing_linted {
+        if render_opt == RenderOpt::Default {
+            render_nothing_linted_notice(&mut stdout, !files.is_empty())?;
+        } else {
+            render_nothing_linted_notice(&mut theme::stderr(), !files.is_empty())?;
+        }
+    }
 
-            group_lints_by_file(&mut all_lints, lints);
+    let over_budget = skip_reasons
+        .iter()
+        .filter(|(_, reason)| **reason == SkipReason::TimeBudget)
+        .map(|(code, _)| code.clone())
+        .collect::<Vec<_>>();
 
-            let spinner_message = if is_success {
-                format!("{} {}", linter.code, style("success!").green())
+    let did_print = match render_opt {
+        RenderOpt::Default if nothing_linted => PrintedLintErrors::No,
+        RenderOpt::Default if group_by == GroupBy::Message => {
+            render_lint_messages_grouped(&mut stdout, rendered_lints, wrap_width)?
+        }
+        RenderOpt::Default => render_lint_messages(&mut stdout, rendered_lints, wrap_width)?,
+        RenderOpt::Json => render_lint_messages_json(&mut stdout, rendered_lints)?,
+        RenderOpt::Oneline => render_lint_messages_oneline(&mut stdout, rendered_lints)?,
+        RenderOpt::Arc => render_lint_messages_arc(&mut stdout, rendered_lints)?,
+        RenderOpt::SummaryMarkdown => {
+            render_lint_messages_summary_markdown(&mut stdout, rendered_lints)?
+        }
+        // Messages were already streamed out as each linter finished.
+        RenderOpt::Vscode => {
+            if all_lints.is_empty() {
+                PrintedLintErrors::No
             } else {
-                format!("{} {}", linter.code, style("failure").red())
-            };
-
-            if enable_spinners {
-                spinner.unwrap().finish_with_message(spinner_message);
+                PrintedLintErrors::Yes
             }
-            Ok(())
-        });
-        thread_handles.push(handle);
-    }
+        }
+    };
 
-    spinners.join()?;
-    for handle in thread_handles {
-        handle.join().unwrap()?;
+    // Report linters that didn't finish after the results, where they won't
+    // be missed.
+    if let Some(time_budget) = time_budget.filter(|_| !over_budget.is_empty()) {
+        if render_opt == RenderOpt::Default {
+            render_time_budget_notice(&mut stdout, &over_budget, time_budget)?;
+        } else {
+            render_time_budget_notice(&mut theme::stderr(), &over_budget, time_budget)?;
+        }
     }
 
-    // Unwrap is fine because all other owners hsould have been joined.
-    let all_lints = all_lints.lock().unwrap();
-
-    // Flush the logger before rendering results.
-    log::logger().flush();
+    let counts = trend::count_lints(processed_files.keys(), &all_lints);
+    let overruns = check_budgets(&budgets, &counts);
+    if !overruns.is_empty() {
+        if render_opt == RenderOpt::Default {
+            render_budget_overruns(&mut stdout, &overruns)?;
+        } else {
+            render_budget_overruns(&mut theme::stderr(), &overruns)?;
+        }
+    }
 
-    let did_print = match render_opt {
-        RenderOpt::Default => render_lint_messages(&mut stdout, &all_lints)?,
-        RenderOpt::Json => render_lint_messages_json(&mut stdout, &all_lints)?,
-        RenderOpt::Oneline => render_lint_messages_oneline(&mut stdout, &all_lints)?,
-    };
+    if timing {
+        render_timing(&mut theme::stderr(), &executor.scheduler.timings())?;
+    }
 
     if let Some(tee_json) = tee_json {
         let mut file = OpenOptions::new()
@@ -288,17 +876,65 @@ pub fn do_lint(
             .create_new(true)
             .open(tee_json)
             .context("Couldn't open file for --tee-json")?;
-        render_lint_messages_json(&mut file, &all_lints)?;
+        render_lint_messages_json_with_patches(&mut file, rendered_lints)?;
     }
 
-    if should_apply_patches {
-        stdout.write_line("Successfully applied all patches.")?;
+    if let Some(patch_file) = patch_file {
+        let patch = patch::consolidated_patch(&all_lints, &config_dir, apply_suggested)?;
+        std::fs::write(&patch_file, patch)
+            .with_context(|| format!("Failed to write patch file to '{}'", patch_file))?;
+    }
+
+    if let Some(write_manifest) = write_manifest {
+        manifest::write_manifest(
+            &write_manifest,
+            &processed_hashes,
+            &skip_reasons,
+            &cache_inputs,
+        )?;
+    }
+
+    if let Some(badge) = badge {
+        badge::write_badge(&badge, &all_lints)?;
     }
 
-    match did_print {
-        PrintedLintErrors::No => Ok(0),
-        PrintedLintErrors::Yes => Ok(1),
+    let recorded_run = executor.recorder.finish();
+    if let Some(record) = record {
+        recorded_run.write(&record)?;
     }
+    persistent_data_store.write_linter_logs(&recorded_run.invocations)?;
+
+    persistent_data_store.write_lint_messages(&all_lints)?;
+    persistent_data_store.record_trend(counts)?;
+
+    if should_apply_patches {
+        match num_stale_patches {
+            0 => stdout.write_line("Successfully applied all patches.")?,
+            num_stale => stdout.write_line(&format!(
+                "Applied patches, except for {} stale one(s).",
+                num_stale
+            ))?,
+        }
+    }
+
+    // Findings within their linters' budgets are expected, and don't fail the
+    // run by themselves.
+    let all_within_budget = overruns.is_empty()
+        && all_lints
+            .values()
+            .flatten()
+            .all(|message| covered_by_budget(&budgets, message));
+
+    let findings = match did_print {
+        _ if !overruns.is_empty() => exit_code::LINT_FINDINGS,
+        PrintedLintErrors::Yes if all_within_budget => exit_code::SUCCESS,
+        PrintedLintErrors::No if nothing_linted && strict_empty => exit_code::LINT_FINDINGS,
+        PrintedLintErrors::No => exit_code::SUCCESS,
+        PrintedLintErrors::Yes => exit_code::LINT_FINDINGS,
+    };
+    // Budgets are for findings, so they don't cover linters failing.
+    let failures = exit_code::for_failures(all_lints.values().flatten());
+    Ok(failures.map_or(findings, |failures| failures.max(findings)))
 }
 
 #[cfg(test)]
@@ -307,6 +943,47 @@ mod tests {
     use std::{convert::TryFrom, io::Write};
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn test_lint_scopes() -> Result<()> {
+        let root = tempfile::tempdir()?;
+        std::fs::create_dir_all(root.path().join("config/sub"))?;
+        std::fs::create_dir_all(root.path().join("other"))?;
+        let root_dir = AbsPath::try_from(root.path())?;
+        let config_dir = AbsPath::try_from(root.path().join("config"))?;
+        let sub_dir = AbsPath::try_from(root.path().join("config/sub"))?;
+        let other_dir = AbsPath::try_from(root.path().join("other"))?;
+
+        assert_eq!(lint_scopes(vec![], None), None);
+        assert_eq!(
+            lint_scopes(vec![], Some(config_dir.clone())),
+            Some(vec![config_dir.clone()])
+        );
+        assert_eq!(
+            lint_scopes(vec![other_dir.clone()], None),
+            Some(vec![other_dir.clone()])
+        );
+        assert_eq!(
+            lint_scopes(
+                vec![root_dir, sub_dir.clone(), other_dir],
+                Some(config_dir.clone())
+            ),
+            Some(vec![config_dir, sub_dir])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_duration() -> Result<()> {
+        assert_eq!(parse_duration("600")?, Duration::from_secs(600));
+        assert_eq!(parse_duration("600s")?, Duration::from_secs(600));
+        assert_eq!(parse_duration("10m")?, Duration::from_secs(600));
+        assert_eq!(parse_duration("1h")?, Duration::from_secs(3600));
+        assert!(parse_duration("ten minutes").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("6000000000000000000h").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_paths_file() -> Result<()> {
         let file1 = NamedTempFile::new()?;
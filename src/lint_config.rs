@@ -1,15 +1,42 @@
-use std::{collections::HashSet, fs};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
 
-use crate::{linter::Linter, path::AbsPath};
-use anyhow::{bail, ensure, Context, Result};
+use crate::{
+    budget::Budget,
+    builtin::builtin_from_config,
+    capabilities::Capabilities,
+    columns::ColumnUnit,
+    compdb::CompdbConfig,
+    download::DownloadConfig,
+    ignore_file,
+    linter::{IncludePattern, Linter, SkipReason},
+    network::NetworkPolicy,
+    path::AbsPath,
+    pathsfile::PathsFileFormat,
+    profile::Profile,
+    remote::RemoteExecConfig,
+    theme::ColorsConfig,
+    toolchain::{toolchain_dir, TOOLCHAIN_DIR_PLACEHOLDER},
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use figment::{
-    providers::{Format, Toml},
+    providers::{Format, Json, Serialized, Toml, Yaml},
+    value::Value,
     Figment,
 };
 use glob::Pattern;
-use log::debug;
+use log::{debug, warn};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+/// Replaced by the directory of the config file in a linter's `env` values.
+pub const CONFIG_DIR_PLACEHOLDER: &str = "{{CONFIG_DIR}}";
+
 #[derive(Serialize, Deserialize)]
 pub struct LintRunnerConfig {
     #[serde(rename = "linter")]
@@ -24,12 +51,112 @@ pub struct LintRunnerConfig {
     /// Supercedes command line argument.
     #[serde()]
     pub only_lint_under_config_dir: Option<bool>,
+
+    /// If set, files in vendored directories are never linted: directories
+    /// named `third_party` or `vendor`, directories containing a
+    /// `LICENSE.vendor` file, and git submodules. See [`crate::vendored`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub skip_vendored: bool,
+
+    /// The command used to get file contents for `--staged` and
+    /// `--contents-at`, instead of reading them from the working tree.
+    /// `{{REV}}` is replaced with the requested revision (empty for
+    /// `--staged`), and `{{PATH}}` with the path of the file relative to the
+    /// config file. The command should print the file's contents to stdout.
+    ///
+    /// Defaults to `['git', 'show', '{{REV}}:./{{PATH}}']`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_provider: Option<Vec<String>>,
+
+    /// Customizes the colors and styling of terminal output. See
+    /// [`ColorsConfig`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colors: Option<ColorsConfig>,
+
+    /// How to generate the compilation database for linters that set
+    /// `needs_compdb`. See [`CompdbConfig`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compdb: Option<CompdbConfig>,
+
+    /// The most messages each listed linter may report before the run
+    /// fails. See [`Budget`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub budgets: Vec<Budget>,
+
+    /// A command to prefix every linter invocation with, e.g. to run linters
+    /// through a scheduler or sandbox, or at a lower priority. Overridden by
+    /// `--wrapper`.
+    ///
+    /// # Examples
+    /// ```toml
+    /// command_wrapper = ['nice', '-n19']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_wrapper: Option<Vec<String>>,
+
+    /// Hosts to run linters on with `--remote-exec`. See
+    /// [`RemoteExecConfig`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_exec: Option<RemoteExecConfig>,
+
+    /// How many minutes to reuse the results of linter health checks for.
+    /// Defaults to 10. See [`crate::health`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_cache_minutes: Option<u64>,
+
+    /// Named sets of options, selected with `--profile`. See [`Profile`].
+    #[serde(
+        default,
+        rename = "profile",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// If set, config files with the same name in directories under this
+    /// one add linters that only lint files under their directory. See
+    /// [`crate::nested`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub nested_configs: bool,
+
+    /// If set, linters skip the paths git ignores, even if they're tracked,
+    /// or passed on the command line. Linters can override this with their
+    /// own `respect_gitignore`. See [`crate::gitignore`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub respect_gitignore: bool,
+
+    /// Glob patterns, relative to this config file, of config fragments to
+    /// read linters from. Fragments may only define linters and include
+    /// other fragments. A linter code may only be defined once across all
+    /// of them.
+    ///
+    /// # Examples
+    /// ```toml
+    /// include = ['tools/linters/*.toml']
+    /// ```
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// Linters whose configuration could not be parsed.
+    #[serde(skip)]
+    pub invalid_linters: Vec<InvalidLintConfig>,
+
+    /// Every config file that was read, including included fragments.
+    #[serde(skip)]
+    pub files: Vec<PathBuf>,
 }
 
 fn is_false(b: &bool) -> bool {
     !(*b)
 }
 
+fn is_default_column_unit(unit: &ColumnUnit) -> bool {
+    *unit == ColumnUnit::default()
+}
+
+fn is_default_network(network: &NetworkPolicy) -> bool {
+    *network == NetworkPolicy::default()
+}
+
 /// Represents a single linter, along with all the information necessary to invoke it.
 ///
 /// This goes in the linter configuration TOML file.
@@ -62,6 +189,11 @@ pub struct LintConfig {
     /// will be linted. Patterns should be specified relative to the location
     /// of the config file.
     ///
+    /// A pattern starting with `!` un-includes the paths it matches. For each
+    /// path, the last pattern that matches it decides whether it's included,
+    /// so later patterns can include some of those paths again.
+    /// `exclude_patterns` apply afterwards, and always win.
+    ///
     /// # Examples
     /// - Matching against everything:
     /// ```toml
@@ -75,8 +207,41 @@ pub struct LintConfig {
     /// ```toml
     /// include_patterns = ['include/caffe2/caffe2_operators.h', 'torch/csrc/jit/script_type.h']
     /// ```
+    /// - Matching everything under `src/` except generated files, but one:
+    /// ```toml
+    /// include_patterns = ['src/**', '!src/**/*_generated.py', 'src/api_generated.py']
+    /// ```
+    ///
+    /// Can be left out if the linter has `include_regexes` or
+    /// `match_shebang`.
+    #[serde(default)]
     pub include_patterns: Vec<String>,
 
+    /// Regular expressions, for paths globs can't describe. Paths relative to
+    /// the config file that match any of these are linted, on top of those
+    /// `include_patterns` matches. A regex matches if it's found anywhere in
+    /// the path, so anchor it with `^` and `$` to match the whole path.
+    ///
+    /// # Examples
+    /// - Matching migrations, which have a numeric prefix:
+    /// ```toml
+    /// include_regexes = ['^db/migrations/[0-9]+_\w+\.py$']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_regexes: Option<Vec<String>>,
+
+    /// Interpreter names. Files whose shebang runs one of them are linted, on
+    /// top of those `include_patterns` matches, e.g. for scripts without an
+    /// extension. Versions are ignored, and `env` is looked through, so
+    /// `python3` matches `#!/usr/bin/env python3.11`. See [`crate::shebang`].
+    ///
+    /// # Examples
+    /// ```toml
+    /// match_shebang = ['python3', 'bash']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_shebang: Option<Vec<String>>,
+
     /// A list of UNIX-style glob patterns. Paths matching any of these patterns
     /// will be never be linted, even if they match an include pattern.
     ///
@@ -84,6 +249,88 @@ pub struct LintConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exclude_patterns: Option<Vec<String>>,
 
+    /// Regular expressions like `include_regexes`. Paths matching any of these
+    /// are never linted, like those matching `exclude_patterns`.
+    ///
+    /// # Examples
+    /// - Excluding vendored copies of a library, whatever their version:
+    /// ```toml
+    /// exclude_regexes = ['^third_party/zlib-[0-9.]+/']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_regexes: Option<Vec<String>>,
+
+    /// The tool's own ignore file, relative to the config file, like
+    /// `.clang-format-ignore` or `.prettierignore`. Paths it ignores are
+    /// never passed to the linter, on top of `exclude_patterns`. It uses
+    /// gitignore syntax, relative to its own directory. See
+    /// [`crate::ignore_file`].
+    ///
+    /// # Examples
+    /// ```toml
+    /// ignore_file = '.clang-format-ignore'
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_file: Option<String>,
+
+    /// Whether to skip the paths git ignores. Defaults to the top-level
+    /// `respect_gitignore`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub respect_gitignore: Option<bool>,
+
+    /// Files larger than this many bytes are never passed to the linter, e.g.
+    /// so that a text linter doesn't choke on giant generated files. Skipped
+    /// files are listed with `-v`.
+    ///
+    /// # Examples
+    /// ```toml
+    /// max_file_size_bytes = 1048576  # 1 MiB
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size_bytes: Option<u64>,
+
+    /// If true, binary files are never passed to the linter. Like git, a
+    /// file is considered binary if its first 8000 bytes contain a NUL byte.
+    /// Skipped files are listed with `-v`.
+    #[serde(skip_serializing_if = "is_false", default = "bool::default")]
+    pub skip_binary_files: bool,
+
+    /// Names of groups the linter belongs to, for selecting linters with
+    /// `--tag` and `--skip-tag` instead of listing their codes.
+    ///
+    /// # Examples
+    /// ```toml
+    /// tags = ['python', 'slow']
+    /// ```
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// When to run the linter relative to the others: when worker slots are
+    /// scarce, linters with a higher priority get them first, so give slow
+    /// linters a high one to keep them off the end of the run. Messages on a
+    /// file are also shown in order of priority. Defaults to 0; linters with
+    /// the same priority keep the order of the config.
+    ///
+    /// # Examples
+    /// ```toml
+    /// priority = 10
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+
+    /// Whether the linter exists at all. A linter with `enabled = false` is
+    /// dropped when the config is loaded, so `.lintrunner.private.toml` can
+    /// turn off a linter of the repo's config for just you. Defaults to true.
+    ///
+    /// # Examples
+    /// ```toml
+    /// [[linter]]
+    /// code = 'MYPY'
+    /// enabled = false
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
     /// A list of arguments describing how the linter will be called. lintrunner
     /// will create a subprocess and invoke this command.
     ///
@@ -97,13 +344,111 @@ pub struct LintConfig {
     /// Commands are run with the current working directory set to the parent
     /// directory of the config file.
     ///
+    /// If the string `{{CRATE_DIR}}` is present, the command is instead run
+    /// once per Cargo crate containing files to lint, from the crate's
+    /// directory, with `{{CRATE_DIR}}` replaced by it. See [`crate::cargo`].
+    ///
     /// # Examples
     /// - Calling a Python script:
     /// ```toml
     /// command = ['python3', 'my_linter.py', '--', '@{{PATHSFILE}}']
     /// ```
+    ///
+    /// Not used by builtin linters.
+    #[serde(default)]
     pub command: Vec<String>,
 
+    /// The format of `{{PATHSFILE}}`: `'lines'` (the default), one path per
+    /// line, or `'json'`, which also includes how each file changed, the
+    /// lines changed and a hash of its contents. See [`crate::pathsfile`].
+    ///
+    /// # Examples
+    /// ```toml
+    /// pathsfile_format = 'json'
+    /// ```
+    #[serde(default, skip_serializing_if = "PathsFileFormat::is_lines")]
+    pub pathsfile_format: PathsFileFormat,
+
+    /// Optional protocol features this linter supports, so lintrunner can
+    /// pick the best way to invoke it. See [`crate::capabilities`].
+    ///
+    /// # Examples
+    /// ```toml
+    /// capabilities = { json_pathsfile = true, fix = true }
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Capabilities>,
+
+    /// If true, and `capabilities` isn't set, ask the linter for its
+    /// capabilities by running its command with `--lintrunner-capabilities`
+    /// before each run.
+    #[serde(skip_serializing_if = "is_false", default = "bool::default")]
+    pub probe_capabilities: bool,
+
+    /// If true, this linter needs a compilation database
+    /// (`compile_commands.json`). It is generated or refreshed before the
+    /// linter runs, with the command in the config's `[compdb]` section.
+    #[serde(skip_serializing_if = "is_false", default = "bool::default")]
+    pub needs_compdb: bool,
+
+    /// If true, the linter's stdout is a pseudo-terminal instead of a pipe,
+    /// for tools that only emit progress or color when writing to a
+    /// terminal. ANSI escape codes are stripped from the output, and only
+    /// lines that look like JSON lint messages are parsed. Unix only.
+    #[serde(skip_serializing_if = "is_false", default = "bool::default")]
+    pub use_pty: bool,
+
+    /// The most output, in bytes, the linter may write to stdout. A linter
+    /// that writes more is stopped and reported as failed. Output past 16 MiB
+    /// is spilled to a temporary file rather than kept in memory, so without
+    /// a limit, the only bound is disk space.
+    ///
+    /// # Examples
+    /// ```toml
+    /// max_output_bytes = 1073741824  # 1 GiB
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_bytes: Option<u64>,
+
+    /// How many seconds each invocation of the linter may run for. A linter
+    /// that runs longer is killed, along with any processes it started, and
+    /// reported as failed, instead of holding up the whole run.
+    ///
+    /// # Examples
+    /// ```toml
+    /// timeout_seconds = 600
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+
+    /// What the `char` columns in the linter's messages count: `'char'`
+    /// (Unicode code points, the default), `'byte'` (bytes of UTF-8, as
+    /// reported by e.g. Go and C tools) or `'utf16'` (UTF-16 code units, as
+    /// reported by JavaScript tools). Columns are converted to code points
+    /// when the messages are parsed. See [`crate::columns`].
+    #[serde(default, skip_serializing_if = "is_default_column_unit")]
+    pub column_unit: ColumnUnit,
+
+    /// Whether this linter runs `command` (the default), or is implemented by
+    /// lintrunner itself (`'builtin'`). See [`crate::builtin`].
+    #[serde(default, skip_serializing_if = "LinterKind::is_command")]
+    pub kind: LinterKind,
+
+    /// For builtin linters, which builtin to run.
+    ///
+    /// # Examples
+    /// ```toml
+    /// kind = 'builtin'
+    /// builtin = 'secrets'
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub builtin: Option<String>,
+
+    /// Options for a builtin linter. Which options are available depends on
+    /// the builtin.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<Value>,
+
     /// A list of arguments describing how to set up the right dependencies for
     /// this linter. This command will be run when `lintrunner init` is called.
     ///
@@ -116,6 +461,11 @@ pub struct LintConfig {
     /// Commands are run with the current working directory set to the parent
     /// directory of the config file.
     ///
+    /// `{{TOOLCHAIN_DIR}}`, here and in `command`, `health_check` and `env`,
+    /// is replaced by a directory in the lintrunner data directory for this
+    /// linter's toolchain, which `lintrunner init --pack` snapshots. See
+    /// [`crate::toolchain`].
+    ///
     /// # Examples
     /// - Calling a Python script:
     /// ```toml
@@ -129,17 +479,403 @@ pub struct LintConfig {
     /// meaning of their code.
     #[serde(skip_serializing_if = "is_false", default = "bool::default")]
     pub is_formatter: bool,
+
+    /// Names of environment variables whose values should be passed through
+    /// from the user's environment as-is.
+    ///
+    /// By default, linters are run with a fixed locale and output encoding
+    /// (`LANG` and `LC_ALL` set to `C.UTF-8`, `PYTHONIOENCODING` set to
+    /// `utf-8`), so that adapters behave the same on every machine. Only list
+    /// a variable here if the linter really needs the user's value.
+    ///
+    /// # Examples
+    /// ```toml
+    /// env_passthrough = ['LANG', 'LC_ALL']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_passthrough: Option<Vec<String>>,
+
+    /// Extra environment variables to run the linter's commands with. They
+    /// take precedence over both the controlled and the passed-through
+    /// variables. `{{CONFIG_DIR}}` in a value is replaced by the directory of
+    /// the config file.
+    ///
+    /// # Examples
+    /// ```toml
+    /// env = { MYPY_CACHE_DIR = '{{CONFIG_DIR}}/.mypy_cache' }
+    /// ```
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    /// A prebuilt binary for this linter, downloaded from a GitHub release
+    /// by `lintrunner init` and cached in the lintrunner data directory.
+    /// `{{DOWNLOAD}}` in `command` is replaced by the path to the binary.
+    ///
+    /// # Examples
+    /// ```toml
+    /// download = { repo = 'mvdan/sh', asset_pattern = 'shfmt_.*_linux_amd64', version = 'v3.8.0' }
+    /// command = ['{{DOWNLOAD}}', '--diff', '@{{PATHSFILE}}']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download: Option<DownloadConfig>,
+
+    /// A command that exits 0 if the linter's toolchain works, run before
+    /// the linter. If it fails, or the linter's executable doesn't exist, the
+    /// linter is reported as broken once instead of running. The result is
+    /// cached for `health_cache_minutes`. See [`crate::health`].
+    ///
+    /// # Examples
+    /// ```toml
+    /// health_check = ['python3', '-m', 'mypy', '--version']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<Vec<String>>,
+
+    /// Files outside of the linted sources whose contents affect the
+    /// linter's results, like the tool's own configuration, relative to the
+    /// config file. Their hashes are recorded in the `--write-manifest`
+    /// manifest, so that caches keyed on it are invalidated when they change,
+    /// even if no linted file did.
+    ///
+    /// # Examples
+    /// ```toml
+    /// cache_inputs = ['requirements.txt', '.clang-tidy']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_inputs: Option<Vec<String>>,
+
+    /// If true, the linter lints all files, as with `--all-files`, in runs
+    /// that lint the changed files and find one of its `cache_inputs` among
+    /// them. A change to the tool's configuration can change its results in
+    /// files that didn't change.
+    #[serde(skip_serializing_if = "is_false", default = "bool::default")]
+    pub expand_on_config_change: bool,
+
+    /// If true, the linter is passed directories instead of files: the
+    /// directory of each matched file, or the innermost of `directory_roots`
+    /// that contains it, each listed once. For tools that work on whole
+    /// packages, like `go vet` or `cargo clippy`.
+    ///
+    /// # Examples
+    /// ```toml
+    /// include_patterns = ['**/*.go']
+    /// command = ['sh', '-c', 'go vet $(cat "$1")', 'sh', '{{PATHSFILE}}']
+    /// pass_directories = true
+    /// ```
+    #[serde(skip_serializing_if = "is_false", default = "bool::default")]
+    pub pass_directories: bool,
+
+    /// With `pass_directories`, directories relative to the config file to
+    /// pass instead of the directories of matched files. Matched files
+    /// outside of all of them are still passed as their directory.
+    ///
+    /// # Examples
+    /// ```toml
+    /// directory_roots = ['services/api', 'services/worker']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory_roots: Option<Vec<String>>,
+
+    /// Glob patterns, relative to the config file, of files that help debug
+    /// the linter, like its logs. When the linter fails, they are copied into
+    /// the run's data dir, and the failure message and `lintrunner rage`
+    /// report point at them.
+    ///
+    /// # Examples
+    /// ```toml
+    /// artifacts = ['.mypy_cache/**/*.log']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifacts: Option<Vec<String>>,
+
+    /// When the linter may use the network: `'allowed'` (the default),
+    /// `'init-only'` (only its init command) or `'never'`. Where possible,
+    /// commands that may not use it are sandboxed without network access;
+    /// they also never see proxy variables. See [`crate::network`].
+    ///
+    /// # Examples
+    /// ```toml
+    /// network = 'init-only'
+    /// ```
+    #[serde(default, skip_serializing_if = "is_default_network")]
+    pub network: NetworkPolicy,
+
+    /// The operating systems the linter runs on: `'linux'`, `'macos'` or
+    /// `'windows'`. On others, it is skipped. Runs everywhere if unset.
+    ///
+    /// # Examples
+    /// ```toml
+    /// platforms = ['linux', 'macos']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<Vec<Platform>>,
+
+    /// If true, this linter's configuration is pinned by the lockfile written
+    /// by `lintrunner config lock`. Running with a configuration that differs
+    /// from the lockfile (e.g. because a local config weakened it) prints a
+    /// warning, or fails with `--enforce-protected`.
+    #[serde(skip_serializing_if = "is_false", default = "bool::default")]
+    pub protected: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinterKind {
+    #[default]
+    Command,
+    Builtin,
+}
+
+impl LinterKind {
+    fn is_command(&self) -> bool {
+        *self == LinterKind::Command
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Linux,
+    Macos,
+    Windows,
+}
+
+impl Platform {
+    /// The platform lintrunner is running on, if it is one of the above.
+    pub fn current() -> Option<Platform> {
+        match std::env::consts::OS {
+            "linux" => Some(Platform::Linux),
+            "macos" => Some(Platform::Macos),
+            "windows" => Some(Platform::Windows),
+            _ => None,
+        }
+    }
+}
+
+impl LintConfig {
+    /// Whether the linter runs on the platform lintrunner is running on.
+    pub fn runs_on_this_platform(&self) -> bool {
+        match &self.platforms {
+            Some(platforms) => {
+                Platform::current().is_some_and(|current| platforms.contains(&current))
+            }
+            None => true,
+        }
+    }
+}
+
+/// A `[[linter]]` entry that could not be parsed.
+#[derive(Clone)]
+pub struct InvalidLintConfig {
+    /// The linter's code, or a placeholder if we couldn't even find one.
+    pub code: String,
+    pub is_formatter: bool,
+    pub error: String,
+}
+
+fn linter_from_config(lint_config: &LintConfig, primary_config_path: &AbsPath) -> Result<Linter> {
+    let include_patterns = include_patterns_from_strs(&lint_config.include_patterns)?;
+    let exclude_patterns = if let Some(exclude_patterns) = &lint_config.exclude_patterns {
+        patterns_from_strs(exclude_patterns)?
+    } else {
+        Vec::new()
+    };
+    let include_regexes =
+        regexes_from_strs(lint_config.include_regexes.as_deref().unwrap_or_default())?;
+    let exclude_regexes =
+        regexes_from_strs(lint_config.exclude_regexes.as_deref().unwrap_or_default())?;
+    let ignore_file = match &lint_config.ignore_file {
+        Some(path) => {
+            // Unwrap is fine here because we know this path is absolute and won't be `/`
+            let path = primary_config_path.parent().unwrap().join(path);
+            Some(ignore_file::read(&path).with_context(|| {
+                format!(
+                    "Invalid linter configuration: '{}' has an ignore_file that can't be used.",
+                    lint_config.code
+                )
+            })?)
+        }
+        None => None,
+    };
+
+    ensure!(
+        lint_config.pass_directories || lint_config.directory_roots.is_none(),
+        "Invalid linter configuration: '{}' sets directory_roots, \
+         which requires pass_directories = true.",
+        lint_config.code
+    );
+    ensure!(
+        !lint_config.expand_on_config_change || lint_config.cache_inputs.is_some(),
+        "Invalid linter configuration: '{}' sets expand_on_config_change, \
+         which requires cache_inputs.",
+        lint_config.code
+    );
+
+    for tag in &lint_config.tags {
+        ensure!(
+            !tag.is_empty() && !tag.contains(','),
+            "Invalid linter configuration: '{}' has invalid tag '{}'.",
+            lint_config.code,
+            tag
+        );
+    }
+    for name in lint_config.env.keys() {
+        ensure!(
+            !name.is_empty() && !name.contains('=') && !name.contains('\0'),
+            "Invalid linter configuration: '{}' sets invalid environment variable name '{}'.",
+            lint_config.code,
+            name
+        );
+    }
+    // Unwrap is fine here because we know this path is absolute and won't be `/`
+    let config_dir = primary_config_path.parent().unwrap().to_string_lossy();
+    // Only looked up when used, since there may not be a data directory.
+    let toolchain_dir = if lint_config
+        .command
+        .iter()
+        .chain(lint_config.init_command.iter().flatten())
+        .chain(lint_config.health_check.iter().flatten())
+        .chain(lint_config.env.values())
+        .any(|arg| arg.contains(TOOLCHAIN_DIR_PLACEHOLDER))
+    {
+        toolchain_dir(&lint_config.code)?
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        String::new()
+    };
+    let expand = |args: &[String]| -> Vec<String> {
+        args.iter()
+            .map(|arg| arg.replace(TOOLCHAIN_DIR_PLACEHOLDER, &toolchain_dir))
+            .collect()
+    };
+    let env = lint_config
+        .env
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.clone(),
+                value
+                    .replace(CONFIG_DIR_PLACEHOLDER, &config_dir)
+                    .replace(TOOLCHAIN_DIR_PLACEHOLDER, &toolchain_dir),
+            )
+        })
+        .collect();
+
+    let builtin = match lint_config.kind {
+        LinterKind::Command => {
+            ensure!(
+                !lint_config.command.is_empty(),
+                "Invalid linter configuration: '{}' has an empty command list.",
+                lint_config.code
+            );
+            None
+        }
+        LinterKind::Builtin => {
+            let name = lint_config.builtin.as_deref().with_context(|| {
+                format!(
+                    "Invalid linter configuration: '{}' is a builtin linter, \
+                     but doesn't say which builtin to run.",
+                    lint_config.code
+                )
+            })?;
+            // Unwrap is fine here because we know this path is absolute and won't be `/`
+            let config_dir = primary_config_path.parent().unwrap();
+            Some(builtin_from_config(
+                name,
+                lint_config.options.as_ref(),
+                config_dir,
+            )?)
+        }
+    };
+
+    Ok(Linter {
+        code: lint_config.code.clone(),
+        include_patterns,
+        exclude_patterns,
+        include_regexes,
+        exclude_regexes,
+        match_shebang: lint_config.match_shebang.clone().unwrap_or_default(),
+        scope: None,
+        overridden_under: Vec::new(),
+        ignore_file,
+        respect_gitignore: lint_config.respect_gitignore,
+        gitignores: None,
+        max_file_size_bytes: lint_config.max_file_size_bytes,
+        skip_binary_files: lint_config.skip_binary_files,
+        priority: lint_config.priority.unwrap_or_default(),
+        commands: expand(&lint_config.command),
+        pathsfile_format: lint_config.pathsfile_format,
+        capabilities: lint_config.capabilities,
+        probe_capabilities: lint_config.probe_capabilities,
+        probed_capabilities: OnceLock::new(),
+        needs_compdb: lint_config.needs_compdb,
+        use_pty: lint_config.use_pty,
+        max_output_bytes: lint_config.max_output_bytes,
+        timeout: lint_config.timeout_seconds.map(Duration::from_secs),
+        column_unit: lint_config.column_unit,
+        init_commands: lint_config.init_command.as_deref().map(expand),
+        env_passthrough: lint_config.env_passthrough.clone().unwrap_or_default(),
+        env,
+        download: lint_config.download.clone(),
+        health_check: lint_config.health_check.as_deref().map(expand),
+        cache_inputs: lint_config.cache_inputs.clone().unwrap_or_default(),
+        expand_on_config_change: lint_config.expand_on_config_change,
+        pass_directories: lint_config.pass_directories,
+        directory_roots: lint_config.directory_roots.clone().unwrap_or_default(),
+        artifacts: lint_config.artifacts.clone().unwrap_or_default(),
+        network: lint_config.network,
+        health_error: None,
+        command_wrapper: Vec::new(),
+        remote: None,
+        builtin,
+        primary_config_path: primary_config_path.clone(),
+        config_error: None,
+    })
+}
+
+/// The `--tag` and `--skip-tag` options.
+#[derive(Clone, Default)]
+pub struct TagSelection {
+    /// Only run linters with at least one of these tags.
+    pub tags: Option<HashSet<String>>,
+    /// Don't run linters with any of these tags.
+    pub skip_tags: Option<HashSet<String>>,
 }
 
 /// Given options specified by the user, return a list of linters to run.
+///
+/// Unless `strict` is set, a linter with an invalid configuration doesn't fail
+/// the whole run; instead it is returned as a linter that reports its
+/// configuration error when run. Under `strict`, an invalid linter fails the
+/// run if it is still selected after `--take`, `--skip` and the tag options.
 pub fn get_linters_from_configs(
     linter_configs: &[LintConfig],
+    invalid_linter_configs: &[InvalidLintConfig],
     skipped_linters: Option<HashSet<String>>,
     taken_linters: Option<HashSet<String>>,
+    tag_selection: TagSelection,
     primary_config_path: &AbsPath,
-) -> Result<Vec<Linter>> {
+    strict: bool,
+) -> Result<(Vec<Linter>, BTreeMap<String, SkipReason>)> {
     let mut linters = Vec::new();
+    let mut skipped = BTreeMap::new();
     let mut all_linters: HashSet<String> = HashSet::new();
+    // Under --strict, the config errors to raise once linters are selected.
+    let mut config_errors: HashMap<String, anyhow::Error> = HashMap::new();
+    let linter_tags = linter_configs
+        .iter()
+        .map(|lint_config| (lint_config.code.as_str(), &lint_config.tags))
+        .collect::<HashMap<_, _>>();
+    let all_tags = linter_tags
+        .values()
+        .flat_map(|tags| tags.iter())
+        .collect::<BTreeSet<_>>();
+    let has_tag_in = |code: &str, selected: &HashSet<String>| {
+        linter_tags
+            .get(code)
+            .is_some_and(|tags| tags.iter().any(|tag| selected.contains(tag)))
+    };
 
     for lint_config in linter_configs {
         if all_linters.contains(&lint_config.code) {
@@ -150,27 +886,48 @@ pub fn get_linters_from_configs(
         }
         all_linters.insert(lint_config.code.clone());
 
-        let include_patterns = patterns_from_strs(&lint_config.include_patterns)?;
-        let exclude_patterns = if let Some(exclude_patterns) = &lint_config.exclude_patterns {
-            patterns_from_strs(exclude_patterns)?
-        } else {
-            Vec::new()
-        };
-
-        ensure!(
-            !lint_config.command.is_empty(),
-            "Invalid linter configuration: '{}' has an empty command list.",
-            lint_config.code
-        );
+        if !lint_config.runs_on_this_platform() {
+            skipped.insert(lint_config.code.clone(), SkipReason::Platform);
+            continue;
+        }
+        match linter_from_config(lint_config, primary_config_path) {
+            Ok(linter) => linters.push(linter),
+            Err(err) => {
+                linters.push(Linter::with_config_error(
+                    &lint_config.code,
+                    format!("{:#}", err),
+                    primary_config_path,
+                ));
+                if strict {
+                    config_errors.insert(lint_config.code.clone(), err);
+                }
+            }
+        }
+    }
 
-        linters.push(Linter {
-            code: lint_config.code.clone(),
-            include_patterns,
-            exclude_patterns,
-            commands: lint_config.command.clone(),
-            init_commands: lint_config.init_command.clone(),
-            primary_config_path: primary_config_path.clone(),
-        });
+    for invalid_config in invalid_linter_configs {
+        if all_linters.contains(&invalid_config.code) {
+            bail!(
+                "Invalid linter configuration: linter '{}' is defined multiple times.",
+                invalid_config.code
+            );
+        }
+        all_linters.insert(invalid_config.code.clone());
+        linters.push(Linter::with_config_error(
+            &invalid_config.code,
+            invalid_config.error.clone(),
+            primary_config_path,
+        ));
+        if strict {
+            config_errors.insert(
+                invalid_config.code.clone(),
+                anyhow!(
+                    "Invalid linter configuration for '{}': {}",
+                    invalid_config.code,
+                    invalid_config.error
+                ),
+            );
+        }
     }
 
     debug!("Found linters: {:?}", all_linters);
@@ -187,7 +944,13 @@ pub fn get_linters_from_configs(
             );
         }
 
-        linters.retain(|linter| taken_linters.contains(&linter.code));
+        linters.retain(|linter| {
+            let taken = taken_linters.contains(&linter.code);
+            if !taken {
+                skipped.insert(linter.code.clone(), SkipReason::NotTaken);
+            }
+            taken
+        });
     }
 
     // Apply --skip
@@ -201,45 +964,375 @@ pub fn get_linters_from_configs(
                 all_linters,
             );
         }
-        linters.retain(|linter| !skipped_linters.contains(&linter.code));
+        linters.retain(|linter| {
+            let skip = skipped_linters.contains(&linter.code);
+            if skip {
+                skipped.insert(linter.code.clone(), SkipReason::Skipped);
+            }
+            !skip
+        });
+    }
+
+    // Apply --tag
+    if let Some(tags) = tag_selection.tags {
+        debug!("Taking linters tagged: {:?}", tags);
+        for tag in &tags {
+            ensure!(
+                all_tags.contains(tag),
+                "Unknown tag specified in --tag: {}. These tags are used: {:?}",
+                tag,
+                all_tags,
+            );
+        }
+        linters.retain(|linter| {
+            let tagged = has_tag_in(&linter.code, &tags);
+            if !tagged {
+                skipped.insert(linter.code.clone(), SkipReason::NotTagged);
+            }
+            tagged
+        });
+    }
+
+    // Apply --skip-tag
+    if let Some(skip_tags) = tag_selection.skip_tags {
+        debug!("Skipping linters tagged: {:?}", skip_tags);
+        for tag in &skip_tags {
+            ensure!(
+                all_tags.contains(tag),
+                "Unknown tag specified in --skip-tag: {}. These tags are used: {:?}",
+                tag,
+                all_tags,
+            );
+        }
+        linters.retain(|linter| {
+            let skip = has_tag_in(&linter.code, &skip_tags);
+            if skip {
+                skipped.insert(linter.code.clone(), SkipReason::SkippedTag);
+            }
+            !skip
+        });
+    }
+
+    if let Some(err) = linters
+        .iter()
+        .find_map(|linter| config_errors.remove(&linter.code))
+    {
+        return Err(err);
+    }
+    Ok((linters, skipped))
+}
+
+/// The format of a config file, going by its extension: `.yaml` or `.yml` for
+/// YAML, `.json` for JSON, and TOML otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub fn of(path: &Path) -> ConfigFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Json => "JSON",
+        }
+    }
+
+    /// Parse the contents of a config file in this format. Whatever the
+    /// format, the result is a TOML value, so the same rules apply to all of
+    /// them: e.g. YAML and JSON configs can't have nulls.
+    pub fn parse(self, contents: &str) -> Result<toml::Value> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::from_str(contents)?,
+            ConfigFormat::Yaml => Figment::from(Yaml::string(contents)).extract()?,
+            ConfigFormat::Json => Figment::from(Json::string(contents)).extract()?,
+        })
+    }
+
+    /// Merge the config file at `path` into `config`.
+    fn merge_file(self, config: Figment, path: &Path) -> Figment {
+        match self {
+            ConfigFormat::Toml => config.merge(Toml::file(path)),
+            ConfigFormat::Yaml => config.merge(Yaml::file(path)),
+            ConfigFormat::Json => config.merge(Json::file(path)),
+        }
+    }
+
+    /// The linters defined in the config file at `path`, unparsed.
+    fn read_linters(self, path: &Path) -> Result<Vec<Value>> {
+        self.merge_file(Figment::new(), path)
+            .join(Serialized::default("linter", Vec::<Value>::new()))
+            .extract_inner::<Vec<Value>>("linter")
+            .with_context(|| format!("Config file at {} had invalid schema", path.display()))
+    }
+}
+
+/// The files matching the `include` patterns of the config file at `path`,
+/// which parsed to `value`, in sorted order.
+fn included_paths(path: &Path, value: &toml::Value) -> Result<Vec<PathBuf>> {
+    let patterns = match value.get("include") {
+        Some(patterns) => patterns
+            .clone()
+            .try_into::<Vec<String>>()
+            .with_context(|| {
+                format!("`include` in {} must be a list of strings", path.display())
+            })?,
+        None => return Ok(Vec::new()),
+    };
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut included = Vec::new();
+    for pattern in patterns {
+        let pattern = dir.join(&pattern);
+        let pattern = pattern.to_string_lossy();
+        let mut matches = glob::glob(&pattern)
+            .with_context(|| format!("Invalid include pattern '{}'", pattern))?
+            .filter_map(|path| path.ok())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            warn!(
+                "Include pattern '{}' in {} matched no files",
+                pattern,
+                path.display()
+            );
+        }
+        matches.sort();
+        included.extend(matches);
+    }
+    Ok(included)
+}
+
+/// Read the linters of the fragments included by the config file at `path`,
+/// and of the fragments they include, into `linters`, with the fragment each
+/// came from. Each fragment is only read once.
+fn read_included_linters(
+    path: &Path,
+    value: &toml::Value,
+    seen: &mut HashSet<PathBuf>,
+    linters: &mut Vec<(Value, PathBuf)>,
+) -> Result<()> {
+    for fragment in included_paths(path, value)? {
+        if !seen.insert(fragment.canonicalize()?) {
+            continue;
+        }
+        let fragment_str = fs::read_to_string(&fragment)
+            .with_context(|| format!("Could not read config file at {}", fragment.display()))?;
+        let format = ConfigFormat::of(&fragment);
+        let fragment_value = format
+            .parse(&fragment_str)
+            .with_context(|| format!("Config file at {} had invalid schema", fragment.display()))?;
+        if let Some(table) = fragment_value.as_table() {
+            if let Some(key) = table
+                .keys()
+                .find(|key| !matches!(key.as_str(), "linter" | "include"))
+            {
+                bail!(
+                    "Included config file at {} sets '{}', but included files \
+                     may only define linters and include other files",
+                    fragment.display(),
+                    key
+                );
+            }
+        }
+        let fragment_linters = format.read_linters(&fragment)?;
+        debug!(
+            "Read {} linters from included config {}",
+            fragment_linters.len(),
+            fragment.display()
+        );
+        linters.extend(
+            fragment_linters
+                .into_iter()
+                .map(|linter| (linter, fragment.clone())),
+        );
+        read_included_linters(&fragment, &fragment_value, seen, linters)?;
+    }
+    Ok(())
+}
+
+/// The code of the linter `value` defines, if it has one.
+fn linter_code(value: &Value) -> Option<String> {
+    value
+        .find_ref("code")
+        .and_then(|code| code.as_str())
+        .map(|code| code.to_string())
+}
+
+/// Add the linters of a later config file to `linters`. One with the code of a
+/// linter from an earlier file overrides the keys it sets, rather than being
+/// added; linters of the same file with the same code are left for validation
+/// to report.
+fn merge_linters(linters: &mut Vec<Value>, later: Vec<Value>) {
+    let earlier = linters.len();
+    for linter in later {
+        let overridden = linter_code(&linter).and_then(|code| {
+            linters[..earlier]
+                .iter()
+                .position(|earlier| linter_code(earlier).as_ref() == Some(&code))
+        });
+        match (overridden, linter) {
+            (Some(index), Value::Dict(_, keys)) => {
+                debug!(
+                    "Overriding {:?} of linter {:?}",
+                    keys.keys().collect::<Vec<_>>(),
+                    linter_code(&linters[index])
+                );
+                if let Value::Dict(_, base) = &mut linters[index] {
+                    base.extend(keys);
+                }
+            }
+            (_, linter) => linters.push(linter),
+        }
     }
-    Ok(linters)
 }
 
 impl LintRunnerConfig {
     pub fn new(paths: &Vec<std::string::String>) -> Result<LintRunnerConfig> {
         let mut config = Figment::new();
+        let mut seen = HashSet::new();
+        let mut linter_values = Vec::new();
         for path in paths {
             let config_str = fs::read_to_string(path)
                 .context(format!("Could not read config file at {}", path))?;
 
             // schema check
-            let _test_str = toml::from_str::<toml::Value>(&config_str)
+            let format = ConfigFormat::of(Path::new(path));
+            let value = format
+                .parse(&config_str)
                 .context(format!("Config file at {} had invalid schema", path))?;
 
-            config = config.merge(Toml::file(path));
+            config = format.merge_file(config, Path::new(path));
+
+            seen.insert(Path::new(path).canonicalize()?);
+            let mut included_linters = Vec::new();
+            read_included_linters(Path::new(path), &value, &mut seen, &mut included_linters)?;
+
+            let mut file_linters = format.read_linters(Path::new(path))?;
+            let mut defined_in = file_linters
+                .iter()
+                .filter_map(linter_code)
+                .map(|code| (code, path.clone()))
+                .collect::<BTreeMap<_, _>>();
+            for (value, fragment) in included_linters {
+                if let Some(code) = linter_code(&value) {
+                    if let Some(other) =
+                        defined_in.insert(code.clone(), fragment.display().to_string())
+                    {
+                        bail!(
+                            "Linter '{}' is defined in both {} and {}",
+                            code,
+                            other,
+                            fragment.display()
+                        );
+                    }
+                }
+                file_linters.push(value);
+            }
+            merge_linters(&mut linter_values, file_linters);
         }
+        // Some config has to define linters, even if others only override
+        // settings.
+        config
+            .extract_inner::<Vec<Value>>("linter")
+            .context("Config file had invalid schema")?;
+        linter_values.retain(|value| {
+            let enabled = value
+                .find_ref("enabled")
+                .and_then(|enabled| enabled.to_bool())
+                .unwrap_or(true);
+            if !enabled {
+                debug!("Linter {:?} is disabled", linter_code(value));
+            }
+            enabled
+        });
 
-        let config = config
+        // Parse each linter separately, so that one broken linter doesn't
+        // prevent the rest from running.
+        let mut lint_runner_config = config
+            .merge(Serialized::default("linter", Vec::<LintConfig>::new()))
             .extract::<LintRunnerConfig>()
             .context("Config file had invalid schema")?;
 
-        for linter in &config.linters {
-            if let Some(init_args) = &linter.init_command {
-                if init_args.iter().all(|arg| !arg.contains("{{DRYRUN}}")) {
-                    bail!(
-                        "Config for linter {} defines init args \
-                         but does not take a {{{{DRYRUN}}}} argument.",
-                        linter.code
-                    );
+        for (idx, value) in linter_values.iter().enumerate() {
+            let parsed = value
+                .deserialize::<LintConfig>()
+                .map_err(|err| err.to_string())
+                .and_then(|lint_config| {
+                    // Only builtin linters can do without a command.
+                    if lint_config.kind == LinterKind::Command
+                        && value.find_ref("command").is_none()
+                    {
+                        Err("missing field `command`".to_string())
+                    } else if value.find_ref("include_patterns").is_none()
+                        && value.find_ref("include_regexes").is_none()
+                        && value.find_ref("match_shebang").is_none()
+                    {
+                        Err("missing field `include_patterns`".to_string())
+                    } else {
+                        Ok(lint_config)
+                    }
+                });
+            match parsed {
+                Ok(lint_config) => lint_runner_config.linters.push(lint_config),
+                Err(err) => {
+                    let code = value
+                        .find_ref("code")
+                        .and_then(|code| code.as_str())
+                        .map(|code| code.to_string())
+                        .unwrap_or_else(|| format!("<linter #{}>", idx + 1));
+                    debug!("Invalid config for linter {}: {}", code, err);
+                    lint_runner_config.invalid_linters.push(InvalidLintConfig {
+                        code,
+                        is_formatter: value
+                            .find_ref("is_formatter")
+                            .and_then(|is_formatter| is_formatter.to_bool())
+                            .unwrap_or(false),
+                        error: err,
+                    });
                 }
             }
         }
-        Ok(config)
+
+        lint_runner_config.files = seen.into_iter().collect();
+        lint_runner_config.files.sort();
+        Ok(lint_runner_config)
     }
 }
 
-fn patterns_from_strs(pattern_strs: &[String]) -> Result<Vec<Pattern>> {
+fn include_patterns_from_strs(pattern_strs: &[String]) -> Result<Vec<IncludePattern>> {
+    pattern_strs
+        .iter()
+        .map(|pattern_str| {
+            IncludePattern::new(pattern_str).map_err(|err| {
+                anyhow::Error::msg(err)
+                    .context("Could not parse pattern from linter configuration.")
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn regexes_from_strs(regex_strs: &[String]) -> Result<Vec<Regex>> {
+    regex_strs
+        .iter()
+        .map(|regex_str| {
+            Regex::new(regex_str).map_err(|err| {
+                anyhow::Error::msg(err).context("Could not parse regex from linter configuration.")
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn patterns_from_strs(pattern_strs: &[String]) -> Result<Vec<Pattern>> {
     pattern_strs
         .iter()
         .map(|pattern_str| {
@@ -1,6 +1,8 @@
+use anyhow::{bail, ensure, Context, Result};
+use clap::ArgEnum;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Clone, Serialize, Copy)]
+#[derive(Debug, Deserialize, Clone, Serialize, Copy, PartialEq, Eq, ArgEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum LintSeverity {
     Error,
@@ -18,6 +20,76 @@ impl LintSeverity {
             Self::Disabled => "Disabled",
         }
     }
+
+    fn rank(self) -> u8 {
+        match self {
+            Self::Disabled => 0,
+            Self::Advice => 1,
+            Self::Warning => 2,
+            Self::Error => 3,
+        }
+    }
+
+    /// Whether this is as severe as `threshold`, or more.
+    pub fn is_at_least(self, threshold: LintSeverity) -> bool {
+        self.rank() >= threshold.rank()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Serialize, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FixKind {
+    /// The fix can be applied without review.
+    Safe,
+    /// The fix is likely right, but should be reviewed.
+    Suggested,
+}
+
+/// The newest version of the `LintMessage` schema this lintrunner understands.
+///
+/// Version 2 added `end_line`/`end_char`, `related` and `suggestions`. Every
+/// field added since version 1 is optional, so version 1 messages are still
+/// valid; linters only need to set `version` to use the new fields.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// A position in a file. Lines and columns are 1-based.
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
+pub struct RelatedLocation {
+    /// Path to the file, absolute or relative to the current working
+    /// directory, like [`LintMessage::path`].
+    pub path: String,
+    pub line: Option<usize>,
+    pub char: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_char: Option<usize>,
+    /// What this location has to do with the lint, e.g. "first defined here".
+    pub message: String,
+}
+
+/// Replace the text from `line`:`char` up to (but not including)
+/// `end_line`:`end_char` with `replacement`. Lines and columns are 1-based.
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
+pub struct TextEdit {
+    pub line: usize,
+    pub char: usize,
+    pub end_line: usize,
+    pub end_char: usize,
+    pub replacement: String,
+}
+
+/// One way of fixing a lint, as edits to the file the lint is in.
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
+pub struct Suggestion {
+    /// What the suggestion does, e.g. "Remove the unused import".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Non-overlapping edits to make.
+    pub edits: Vec<TextEdit>,
+    /// Like [`LintMessage::fix_kind`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix_kind: Option<FixKind>,
 }
 
 /// Represents a single lint message. This version of the struct is used as the
@@ -39,6 +111,15 @@ pub struct LintMessage {
     /// The column number that the lint message pertains to.
     pub char: Option<usize>,
 
+    /// With `line` and `char`, the end of the range the lint message pertains
+    /// to, exclusive. Since schema version 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+
+    /// See [`LintMessage::end_line`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_char: Option<usize>,
+
     /// Linter code (e.g. `FLAKE8`). Must match the code specified in the linter config.
     pub code: String,
 
@@ -60,4 +141,207 @@ pub struct LintMessage {
     /// encoded as a utf-8 string.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub replacement: Option<String>,
+
+    /// How confident the linter is in `replacement`. `lintrunner -a` only
+    /// applies safe fixes; suggested fixes also need `--apply-suggested`.
+    /// If not set, the fix is considered safe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix_kind: Option<FixKind>,
+
+    /// Other locations relevant to the lint, e.g. a previous definition.
+    /// Since schema version 2.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub related: Vec<RelatedLocation>,
+
+    /// Ways of fixing the lint, as edits rather than a whole `replacement`.
+    /// If there's no `replacement`, lintrunner computes it from the first
+    /// suggestion. Since schema version 2.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggestion>,
+
+    /// The version of the schema this message follows, or version 1 if
+    /// unset. Messages from a newer version than [`SCHEMA_VERSION`] are
+    /// rejected, rather than having the fields lintrunner doesn't know about
+    /// dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+}
+
+impl LintMessage {
+    /// A message from linter `code` about no file in particular, with no
+    /// description or fix. Set the rest with struct update syntax.
+    pub fn new(code: &str, severity: LintSeverity, name: &str) -> LintMessage {
+        LintMessage {
+            path: None,
+            line: None,
+            char: None,
+            end_line: None,
+            end_char: None,
+            code: code.to_string(),
+            severity,
+            name: name.to_string(),
+            description: None,
+            original: None,
+            replacement: None,
+            fix_kind: None,
+            related: Vec::new(),
+            suggestions: Vec::new(),
+            version: None,
+        }
+    }
+
+    /// An error reported for linter `code` as a whole, like it failing to
+    /// run.
+    pub fn general_error(code: &str, name: &str, description: String) -> LintMessage {
+        LintMessage {
+            description: Some(description),
+            ..LintMessage::new(code, LintSeverity::Error, name)
+        }
+    }
+
+    /// Fail if this message follows a newer schema than we understand.
+    pub fn check_version(&self) -> Result<()> {
+        if let Some(version) = self.version {
+            ensure!(
+                version <= SCHEMA_VERSION,
+                "Lint message uses schema version {}, but this lintrunner only \
+                 supports up to version {}. Try upgrading lintrunner.",
+                version,
+                SCHEMA_VERSION
+            );
+        }
+        Ok(())
+    }
+
+    /// If this message has suggestions but no `replacement`, fill in
+    /// `original` and `replacement` (and `fix_kind`) from the first
+    /// suggestion, reading the file at `path`. This lets everything that
+    /// works with replacements, like applying patches, work with suggestions.
+    pub fn resolve_suggestion(&mut self) -> Result<()> {
+        let (path, suggestion) = match (&self.path, self.suggestions.first()) {
+            (Some(path), Some(suggestion)) if self.replacement.is_none() => (path, suggestion),
+            _ => return Ok(()),
+        };
+        let original = match &self.original {
+            Some(original) => original.clone(),
+            None => std::fs::read_to_string(path).with_context(|| {
+                format!("Failed to read '{}' to apply a suggestion to it", path)
+            })?,
+        };
+        let replacement = apply_edits(&original, &suggestion.edits)
+            .with_context(|| format!("Invalid suggestion for '{}'", path))?;
+        self.fix_kind = suggestion.fix_kind.or(self.fix_kind);
+        self.original = Some(original);
+        self.replacement = Some(replacement);
+        Ok(())
+    }
+
+    /// Whether `replacement` should be applied when applying patches.
+    pub fn should_apply_fix(&self, apply_suggested: bool) -> bool {
+        self.replacement.is_some() && (apply_suggested || self.fix_kind != Some(FixKind::Suggested))
+    }
+}
+
+/// The byte offset of the 1-based `line` and `char` in `text`. A position
+/// just past the end of a line (or of the text) is allowed.
+fn byte_offset(text: &str, line: usize, char: usize) -> Result<usize> {
+    ensure!(
+        line >= 1 && char >= 1,
+        "Lines and columns are 1-based, got {}:{}",
+        line,
+        char
+    );
+    let mut line_start = 0;
+    for _ in 1..line {
+        match text[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => bail!("Line {} is past the end of the file", line),
+        }
+    }
+    let line_text = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut offsets = line_text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(std::iter::once(line_text.len()));
+    match offsets.nth(char - 1) {
+        Some(offset) => Ok(line_start + offset),
+        None => bail!("Column {} is past the end of line {}", char, line),
+    }
+}
+
+/// Apply non-overlapping `edits` to `text`.
+pub fn apply_edits(text: &str, edits: &[TextEdit]) -> Result<String> {
+    let mut ranges = edits
+        .iter()
+        .map(|edit| {
+            let start = byte_offset(text, edit.line, edit.char)?;
+            let end = byte_offset(text, edit.end_line, edit.end_char)?;
+            ensure!(
+                start <= end,
+                "Edit ends at {}:{}, before it starts at {}:{}",
+                edit.end_line,
+                edit.end_char,
+                edit.line,
+                edit.char
+            );
+            Ok((start, end, edit.replacement.as_str()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ranges.sort_by_key(|(start, end, _)| (*start, *end));
+    let mut result = String::with_capacity(text.len());
+    let mut copied_to = 0;
+    for (start, end, replacement) in ranges {
+        ensure!(start >= copied_to, "Edits overlap");
+        result.push_str(&text[copied_to..start]);
+        result.push_str(replacement);
+        copied_to = end;
+    }
+    result.push_str(&text[copied_to..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(line: usize, char: usize, end_line: usize, end_char: usize, text: &str) -> TextEdit {
+        TextEdit {
+            line,
+            char,
+            end_line,
+            end_char,
+            replacement: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn applies_edits() -> Result<()> {
+        let text = "import os, sys\nprint(sys.argv)\n";
+        assert_eq!(
+            apply_edits(text, &[edit(2, 1, 2, 6, "log"), edit(1, 10, 1, 15, "")])?,
+            "import os\nlog(sys.argv)\n"
+        );
+        // Inserting at the very end, and columns counted in characters.
+        assert_eq!(apply_edits("héllo", &[edit(1, 3, 1, 6, "y")])?, "héy");
+        assert_eq!(apply_edits("a\n", &[edit(2, 1, 2, 1, "b\n")])?, "a\nb\n");
+
+        assert!(apply_edits(text, &[edit(1, 1, 1, 5, ""), edit(1, 3, 1, 6, "")]).is_err());
+        assert!(apply_edits(text, &[edit(5, 1, 5, 1, "")]).is_err());
+        assert!(apply_edits(text, &[edit(1, 5, 1, 1, "")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_newer_schema_versions() {
+        let message = |version: u32| -> LintMessage {
+            serde_json::from_value(serde_json::json!({
+                "path": null, "line": null, "char": null, "code": "X",
+                "severity": "advice", "name": "x", "description": null,
+                "version": version,
+            }))
+            .unwrap()
+        };
+        assert!(message(SCHEMA_VERSION).check_version().is_ok());
+        assert!(message(SCHEMA_VERSION + 1).check_version().is_err());
+    }
 }
@@ -1,26 +1,201 @@
-use std::io::Write;
-use std::path::Path;
-use std::process::Command;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crate::{
+    builtin::BuiltinLinter,
+    capabilities::{self, Capabilities},
+    cargo::{group_by_crate, CRATE_DIR_PLACEHOLDER},
+    columns::{convert_messages, ColumnUnit},
+    download::DownloadConfig,
+    gitignore::Gitignores,
+    health, ignore_file, interactive, io_limit,
     lint_message::LintMessage,
-    log_utils::{ensure_output, log_files},
+    log_utils::log_files,
+    network::{self, NetworkPolicy},
     path::{path_relative_from, AbsPath},
+    pathsfile::{write_paths_file, ChangeMetadata, PathsFileFormat},
+    plan::{Batch, PlannedLinter},
+    process::{clean_pty_line, kill_process_group, own_process_group, register_group, Pty},
+    record::{RecordedInvocation, Recorder},
+    remote::RemotePool,
+    scheduler::Scheduler,
+    shebang,
+    spool::{SpooledOutput, SPILL_THRESHOLD},
 };
 use anyhow::{anyhow, bail, ensure, Context, Result};
 use glob::{MatchOptions, Pattern};
-use log::{debug, info};
+use ignore::gitignore::Gitignore;
+use log::{debug, info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 pub struct Linter {
     pub code: String,
-    pub include_patterns: Vec<Pattern>,
+    /// Linters with a higher priority get worker slots first, and their
+    /// messages are shown first.
+    pub priority: i64,
+    pub include_patterns: Vec<IncludePattern>,
     pub exclude_patterns: Vec<Pattern>,
+    /// Paths matching any of these are included too. See
+    /// [`matches_relative_regex`].
+    pub include_regexes: Vec<Regex>,
+    /// Paths matching any of these are excluded too.
+    pub exclude_regexes: Vec<Regex>,
+    /// Files with a shebang running one of these interpreters are included
+    /// too. See [`crate::shebang`].
+    pub match_shebang: Vec<String>,
+    /// If set, the linter comes from a nested config, and only lints files
+    /// under this directory. See [`crate::nested`].
+    pub scope: Option<PathBuf>,
+    /// Directories of nested configs that define their own linter with this
+    /// code, whose files this linter leaves to them.
+    pub overridden_under: Vec<PathBuf>,
+    /// The tool's own ignore file. Paths it ignores aren't passed to the
+    /// linter. See [`crate::ignore_file`].
+    pub ignore_file: Option<Gitignore>,
+    /// The linter's own `respect_gitignore`, if it has one.
+    pub respect_gitignore: Option<bool>,
+    /// If set, paths git ignores aren't passed to the linter. See
+    /// [`crate::gitignore`].
+    pub gitignores: Option<Arc<Gitignores>>,
+    /// Files larger than this many bytes aren't passed to the linter.
+    pub max_file_size_bytes: Option<u64>,
+    /// Whether binary files aren't passed to the linter. See [`is_binary`].
+    pub skip_binary_files: bool,
     pub commands: Vec<String>,
+    /// The format of the `{{PATHSFILE}}` passed to `commands`.
+    pub pathsfile_format: PathsFileFormat,
+    /// Protocol features the linter declared support for.
+    pub capabilities: Option<Capabilities>,
+    /// If set, and `capabilities` isn't, ask the linter for its capabilities.
+    pub probe_capabilities: bool,
+    /// What probing reported, so the linter is probed at most once per run
+    /// rather than once per invocation.
+    pub probed_capabilities: OnceLock<Capabilities>,
+    /// Whether the linter needs the compilation database generated first.
+    pub needs_compdb: bool,
+    /// Whether to run the linter with a pseudo-terminal as its stdout.
+    pub use_pty: bool,
+    /// Stop the linter if it writes more than this many bytes to stdout.
+    pub max_output_bytes: Option<u64>,
+    /// How long each invocation of the linter may run before it is killed.
+    pub timeout: Option<Duration>,
+    /// What the columns in the linter's messages count.
+    pub column_unit: ColumnUnit,
     pub init_commands: Option<Vec<String>>,
+    /// Environment variables to take from the user's environment instead of
+    /// the controlled values in [`CONTROLLED_ENV`].
+    pub env_passthrough: Vec<String>,
+    /// Extra environment variables from the config, with placeholders
+    /// replaced. They override both of the above.
+    pub env: Vec<(String, String)>,
+    /// A prebuilt binary that `init` fetches, referred to by `{{DOWNLOAD}}`.
+    pub download: Option<DownloadConfig>,
+    /// A command that exits 0 if the linter's toolchain works.
+    pub health_check: Option<Vec<String>>,
+    /// Files outside of the linted sources that the linter's results depend
+    /// on, relative to the config dir.
+    pub cache_inputs: Vec<String>,
+    /// Whether to lint all files when one of `cache_inputs` changed.
+    pub expand_on_config_change: bool,
+    /// Whether the linter is passed directories instead of files. See
+    /// [`Linter::directories_to_pass`].
+    pub pass_directories: bool,
+    /// With `pass_directories`, the directories to pass for the files in
+    /// them, relative to the config dir.
+    pub directory_roots: Vec<String>,
+    /// Glob patterns, relative to the config dir, of files to keep when the
+    /// linter fails. See [`crate::artifacts`].
+    pub artifacts: Vec<String>,
+    /// When the linter may use the network.
+    pub network: NetworkPolicy,
+    /// If set, the linter's toolchain is broken, so running it just reports
+    /// this error. Set by [`crate::health::preflight`].
+    pub health_error: Option<String>,
+    /// Prepended to every invocation of the linter's command, e.g.
+    /// `['nice', '-n19']`.
+    pub command_wrapper: Vec<String>,
+    /// If set, the linter's command runs on one of these hosts instead of
+    /// locally. See [`crate::remote`].
+    pub remote: Option<Arc<RemotePool>>,
+    /// If set, this linter is implemented by lintrunner itself, and
+    /// `commands` is unused.
+    pub builtin: Option<Box<dyn BuiltinLinter>>,
     pub primary_config_path: AbsPath,
+    /// If set, this linter's configuration is invalid. Running it just
+    /// reports this error.
+    pub config_error: Option<String>,
 }
 
-fn matches_relative_path(base: &Path, from: &Path, pattern: &Pattern) -> bool {
+/// Why a linter didn't run.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Excluded with `--skip`.
+    Skipped,
+    /// Not listed in `--take`.
+    NotTaken,
+    /// Has none of the tags in `--tag`.
+    NotTagged,
+    /// Has one of the tags in `--skip-tag`.
+    SkippedTag,
+    /// Its `platforms` don't include this one.
+    Platform,
+    /// None of the files being linted matched its patterns.
+    NoMatchingFiles,
+    /// Cancelled before finishing, by `--fail-fast`.
+    Cancelled,
+    /// Still running when `--time-budget` ran out.
+    TimeBudget,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SkipReason::Skipped => "excluded by --skip",
+            SkipReason::NotTaken => "not included in --take",
+            SkipReason::NotTagged => "not tagged with any of --tag",
+            SkipReason::SkippedTag => "tagged with one of --skip-tag",
+            SkipReason::Platform => "not run on this platform",
+            SkipReason::NoMatchingFiles => "no files matched its patterns",
+            SkipReason::Cancelled => "cancelled by --fail-fast",
+            SkipReason::TimeBudget => "not run due to --time-budget",
+        })
+    }
+}
+
+/// What will be done with a linter's results, for picking how to invoke
+/// linters that declare [`Capabilities`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunMode {
+    /// Fixes from this run are going to be applied.
+    pub applying_fixes: bool,
+    /// Only lints on changed lines are going to be reported.
+    pub changed_lines_only: bool,
+}
+
+// macOS doesn't ship a C.UTF-8 locale.
+#[cfg(target_os = "macos")]
+const UTF8_LOCALE: &str = "en_US.UTF-8";
+#[cfg(not(target_os = "macos"))]
+const UTF8_LOCALE: &str = "C.UTF-8";
+
+/// Environment variables that are set to fixed values when running linters,
+/// so that adapters don't break on machines with unusual locales or encodings.
+pub const CONTROLLED_ENV: &[(&str, &str)] = &[
+    ("LANG", UTF8_LOCALE),
+    ("LC_ALL", UTF8_LOCALE),
+    ("PYTHONIOENCODING", "utf-8"),
+];
+
+pub(crate) fn matches_relative_path(base: &Path, from: &Path, pattern: &Pattern) -> bool {
     // Unwrap ok because we already checked that both paths are absolute.
     let relative_path = path_relative_from(from, base).unwrap();
     pattern.matches_with(
@@ -36,50 +211,772 @@ fn matches_relative_path(base: &Path, from: &Path, pattern: &Pattern) -> bool {
     )
 }
 
+/// Whether `regex` matches `from`, relative to `base`, anywhere in it: it
+/// needs `^` and `$` to match the whole path. Separators are always `/`.
+pub(crate) fn matches_relative_regex(base: &Path, from: &Path, regex: &Regex) -> bool {
+    // Unwrap ok because we already checked that both paths are absolute.
+    let relative_path = path_relative_from(from, base).unwrap();
+    let relative_path = relative_path.to_str().unwrap();
+    if std::path::MAIN_SEPARATOR == '/' {
+        regex.is_match(relative_path)
+    } else {
+        regex.is_match(&relative_path.replace(std::path::MAIN_SEPARATOR, "/"))
+    }
+}
+
+/// Whether `file` looks binary: like git, whether its first 8000 bytes
+/// contain a NUL byte. Files that can't be read aren't.
+pub(crate) fn is_binary(file: &Path) -> bool {
+    let mut head = Vec::with_capacity(8000);
+    match File::open(file) {
+        Ok(file) => file.take(8000).read_to_end(&mut head).is_ok() && head.contains(&0),
+        Err(_) => false,
+    }
+}
+
+/// One of a linter's include patterns. A pattern starting with `!` takes
+/// back paths that include patterns before it matched.
+pub struct IncludePattern {
+    pub pattern: Pattern,
+    pub negated: bool,
+}
+
+impl IncludePattern {
+    pub fn new(pattern: &str) -> Result<IncludePattern, glob::PatternError> {
+        let (pattern, negated) = match pattern.strip_prefix('!') {
+            Some(pattern) => (pattern, true),
+            None => (pattern, false),
+        };
+        Ok(IncludePattern {
+            pattern: Pattern::new(pattern)?,
+            negated,
+        })
+    }
+}
+
+/// Whether `patterns` include `from`. The last pattern matching it decides,
+/// so `['src/**', '!src/gen/**', 'src/gen/api.py']` includes everything under
+/// `src/` except what's under `src/gen/`, but still includes
+/// `src/gen/api.py`. Paths no pattern matches aren't included.
+pub(crate) fn includes(base: &Path, from: &Path, patterns: &[IncludePattern]) -> bool {
+    patterns
+        .iter()
+        .rev()
+        .find(|include| matches_relative_path(base, from, &include.pattern))
+        .is_some_and(|include| !include.negated)
+}
+
+fn read_pipe(pipe: Option<impl Read + Send + 'static>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            // If reading fails, whatever we got so far is the best we can do.
+            drop(pipe.read_to_end(&mut buf));
+        }
+        buf
+    })
+}
+
+/// What a linter wrote, once it has exited.
+struct LinterOutput {
+    status: ExitStatus,
+    stdout: SpooledOutput,
+    stderr: Vec<u8>,
+}
+
+enum WaitOutcome {
+    Finished(LinterOutput),
+    Cancelled,
+    /// The linter wrote more than its `max_output_bytes` and was killed.
+    OutputLimitExceeded,
+    /// The linter ran for longer than its `timeout_seconds` and was killed.
+    TimedOut,
+}
+
+// Like `Child::wait_with_output`, but kills the child (and its process group)
+// if `cancelled` is set while we're waiting, if it writes more than
+// `output_limit` bytes to stdout, or if it runs for longer than `timeout`. If
+// the child's stdout is a pseudo-terminal, `pty_leader` is where to read it
+// from.
+fn wait_with_output_cancellable(
+    mut child: Child,
+    pty_leader: Option<File>,
+    output_limit: Option<u64>,
+    timeout: Option<Duration>,
+    cancelled: &AtomicBool,
+) -> Result<WaitOutcome> {
+    let start = Instant::now();
+    // Drain stdout/stderr concurrently so the child can't block on a full pipe.
+    // Reading a pty fails with EIO once the child is gone, which ends the
+    // read like EOF would.
+    let exceeded = Arc::new(AtomicBool::new(false));
+    let stdout = {
+        let exceeded = Arc::clone(&exceeded);
+        match pty_leader {
+            Some(pty_leader) => thread::spawn(move || {
+                SpooledOutput::collect(Some(pty_leader), output_limit, &exceeded)
+            }),
+            None => {
+                let pipe = child.stdout.take();
+                thread::spawn(move || SpooledOutput::collect(pipe, output_limit, &exceeded))
+            }
+        }
+    };
+    let stderr = read_pipe(child.stderr.take());
+
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if cancelled.load(Ordering::SeqCst) {
+            kill_process_group(&mut child)?;
+            return Ok(WaitOutcome::Cancelled);
+        }
+        if exceeded.load(Ordering::SeqCst) {
+            kill_process_group(&mut child)?;
+            return Ok(WaitOutcome::OutputLimitExceeded);
+        }
+        if timeout.is_some_and(|timeout| start.elapsed() > timeout) {
+            kill_process_group(&mut child)?;
+            return Ok(WaitOutcome::TimedOut);
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+
+    let stdout = stdout.join().unwrap()?;
+    // The limit may have been hit just as the linter exited.
+    if exceeded.load(Ordering::SeqCst) {
+        return Ok(WaitOutcome::OutputLimitExceeded);
+    }
+    Ok(WaitOutcome::Finished(LinterOutput {
+        status,
+        stdout,
+        stderr: stderr.join().unwrap(),
+    }))
+}
+
+/// Fail if a linter command exited unsuccessfully.
+fn ensure_linter_succeeded(success: bool, stdout: &str, stderr: &str) -> Result<()> {
+    if !success {
+        bail!(
+            "Linter command failed with non-zero exit code.\n\
+             STDERR:\n{}\n\nSTDOUT:{}\n",
+            stderr,
+            stdout,
+        );
+    }
+    Ok(())
+}
+
+/// Finish up parsed messages: convert their columns from the linter's
+/// `column_unit` to code points, and fill in replacements from suggestions.
+fn finish_messages(messages: &mut [LintMessage], column_unit: ColumnUnit) -> Result<()> {
+    convert_messages(messages, column_unit, ColumnUnit::Char);
+    for msg in messages {
+        msg.resolve_suggestion()?;
+    }
+    Ok(())
+}
+
+/// Parse lines of linter output into lint messages, according to the linter
+/// protocol.
+fn parse_lint_lines(lines: impl Iterator<Item = Result<String>>) -> Result<Vec<LintMessage>> {
+    let mut messages = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let msg: LintMessage = serde_json::from_str(&line).with_context(|| {
+            format!(
+                "Failed to deserialize output for lint adapter, line: {}",
+                line
+            )
+        })?;
+        msg.check_version()?;
+        messages.push(msg);
+    }
+    Ok(messages)
+}
+
+/// Whether output starting with `first_line` is a single JSON document (an
+/// array of messages, or an object with a `messages` array, possibly
+/// pretty-printed) rather than JSON Lines.
+fn is_json_document(first_line: Option<&str>) -> bool {
+    let first_line = match first_line {
+        Some(first_line) => first_line.trim_start(),
+        None => return false,
+    };
+    if first_line.starts_with('[') {
+        return true;
+    }
+    if !first_line.starts_with('{') {
+        return false;
+    }
+    match serde_json::from_str::<serde_json::Value>(first_line) {
+        // A message, as in JSON Lines.
+        Ok(serde_json::Value::Object(object)) => is_messages_wrapper(&object),
+        Ok(_) => false,
+        // Not a complete object on one line, so it's pretty-printed.
+        Err(_) => true,
+    }
+}
+
+/// Whether `object` wraps a list of messages, as in `{"messages": [...]}`.
+fn is_messages_wrapper(object: &serde_json::Map<String, serde_json::Value>) -> bool {
+    !object.contains_key("code") && object.get("messages").is_some_and(|m| m.is_array())
+}
+
+/// Parse linter output that is one or more JSON documents, each either a
+/// message, an array of messages, or an object with a `messages` array.
+fn parse_lint_document(reader: impl Read) -> Result<Vec<LintMessage>> {
+    let mut messages = Vec::new();
+    for value in serde_json::Deserializer::from_reader(reader).into_iter::<serde_json::Value>() {
+        let value = value.context("Failed to deserialize JSON output for lint adapter")?;
+        let items = match value {
+            serde_json::Value::Array(items) => items,
+            serde_json::Value::Object(mut object) if is_messages_wrapper(&object) => {
+                match object.remove("messages") {
+                    Some(serde_json::Value::Array(items)) => items,
+                    _ => unreachable!("checked by is_messages_wrapper"),
+                }
+            }
+            value => vec![value],
+        };
+        for item in items {
+            let msg: LintMessage = serde_json::from_value(item.clone()).with_context(|| {
+                format!(
+                    "Failed to deserialize output for lint adapter, message: {}",
+                    item
+                )
+            })?;
+            msg.check_version()?;
+            messages.push(msg);
+        }
+    }
+    Ok(messages)
+}
+
+/// Parse the output of a linter command into lint messages, according to the
+/// linter protocol.
+pub(crate) fn parse_linter_output(
+    success: bool,
+    stdout: &str,
+    stderr: &str,
+) -> Result<Vec<LintMessage>> {
+    ensure_linter_succeeded(success, stdout, stderr)?;
+    parse_messages(stdout)
+}
+
+/// Parse lint messages from `output`, as JSON Lines or JSON documents, the way
+/// the output of a linter command is parsed. Messages whose suggestions have
+/// to be resolved against their file read it from disk.
+///
+/// Malformed output is an error, never a panic.
+pub fn parse_messages(output: &str) -> Result<Vec<LintMessage>> {
+    let mut messages = if is_json_document(output.lines().find(|line| !line.trim().is_empty())) {
+        parse_lint_document(output.as_bytes())?
+    } else {
+        parse_lint_lines(output.lines().map(|line| Ok(line.to_string())))?
+    };
+    finish_messages(&mut messages, ColumnUnit::Char)?;
+    Ok(messages)
+}
+
+/// The lines of a linter's stdout that are meant for lintrunner.
+fn protocol_lines(
+    stdout: &mut SpooledOutput,
+    use_pty: bool,
+) -> Result<impl Iterator<Item = Result<String>> + '_> {
+    Ok(stdout.lines()?.filter_map(move |line| {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => return Some(Err(err.into())),
+        };
+        if use_pty {
+            clean_pty_line(&String::from_utf8_lossy(&line)).map(Ok)
+        } else {
+            Some(String::from_utf8(line).map_err(Into::into))
+        }
+    }))
+}
+
+/// Reported when a linter runs for longer than its `timeout_seconds`.
+fn timeout_message(code: &str, timeout: Duration) -> LintMessage {
+    LintMessage::general_error(
+        code,
+        "Linter timed out",
+        format!(
+            "The linter ran for longer than {} seconds, the limit set by its \
+             `timeout_seconds`, so it was stopped along with any processes it \
+             started, and its results were discarded. Check that it isn't stuck, \
+             or raise the limit.",
+            timeout.as_secs()
+        ),
+    )
+}
+
+/// Reported when a linter writes more than its `max_output_bytes`.
+fn output_limit_message(code: &str, limit: u64) -> LintMessage {
+    LintMessage::general_error(
+        code,
+        "Linter output too large",
+        format!(
+            "The linter wrote more than {} bytes to stdout, the limit set by its \
+             `max_output_bytes`, so it was stopped and its results were discarded. \
+             Check that it isn't stuck in a loop, or raise the limit.",
+            limit
+        ),
+    )
+}
+
+/// The general failure reported when running a linter fails.
+pub(crate) const LINTER_FAILED: &str = "Linter failed";
+
+/// The name of the message reported by linters whose config is invalid.
+pub(crate) const INVALID_CONFIGURATION: &str = "Invalid linter configuration";
+
+pub(crate) fn linter_failure_message(code: &str, err: &anyhow::Error) -> LintMessage {
+    LintMessage::general_error(
+        code,
+        LINTER_FAILED,
+        format!(
+            "Linter failed. This a bug, please file an issue against \
+                     the linter maintainer.\n\nCONTEXT:\n{}",
+            err
+        ),
+    )
+}
+
 impl Linter {
+    /// Create a placeholder for a linter whose configuration is invalid.
+    pub fn with_config_error(code: &str, error: String, primary_config_path: &AbsPath) -> Linter {
+        Linter {
+            code: code.to_string(),
+            include_patterns: Vec::new(),
+            exclude_patterns: Vec::new(),
+            include_regexes: Vec::new(),
+            exclude_regexes: Vec::new(),
+            match_shebang: Vec::new(),
+            scope: None,
+            overridden_under: Vec::new(),
+            ignore_file: None,
+            respect_gitignore: None,
+            gitignores: None,
+            max_file_size_bytes: None,
+            skip_binary_files: false,
+            priority: 0,
+            commands: Vec::new(),
+            pathsfile_format: PathsFileFormat::default(),
+            capabilities: None,
+            probe_capabilities: false,
+            probed_capabilities: OnceLock::new(),
+            needs_compdb: false,
+            use_pty: false,
+            max_output_bytes: None,
+            timeout: None,
+            column_unit: ColumnUnit::default(),
+            init_commands: None,
+            env_passthrough: Vec::new(),
+            env: Vec::new(),
+            download: None,
+            health_check: None,
+            cache_inputs: Vec::new(),
+            expand_on_config_change: false,
+            pass_directories: false,
+            directory_roots: Vec::new(),
+            artifacts: Vec::new(),
+            network: NetworkPolicy::default(),
+            health_error: None,
+            command_wrapper: Vec::new(),
+            remote: None,
+            builtin: None,
+            primary_config_path: primary_config_path.clone(),
+            config_error: Some(error),
+        }
+    }
+
+    /// The environment variables to set for this linter: the controlled ones,
+    /// minus any that it asked to pass through, then its own from the config.
+    pub(crate) fn controlled_env(&self) -> Vec<(&str, &str)> {
+        CONTROLLED_ENV
+            .iter()
+            .filter(|(name, _)| {
+                !self.env_passthrough.iter().any(|p| p == name)
+                    && !self.env.iter().any(|(own, _)| own == name)
+            })
+            .copied()
+            .chain(
+                self.env
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), value.as_str())),
+            )
+            .collect()
+    }
+
+    /// The capabilities this linter declared in its config, or reported when
+    /// probed. Linters that can't be probed are assumed to support nothing.
+    fn resolve_capabilities(&self, download_path: &str) -> Capabilities {
+        if let Some(capabilities) = self.capabilities {
+            return capabilities;
+        }
+        if !self.probe_capabilities {
+            return Capabilities::default();
+        }
+        *self
+            .probed_capabilities
+            .get_or_init(|| self.run_capabilities_probe(download_path))
+    }
+
+    fn run_capabilities_probe(&self, download_path: &str) -> Capabilities {
+        let command = self.wrap_linter_command(
+            self.commands
+                .iter()
+                .filter(|arg| !arg.contains("{{PATHSFILE}}"))
+                .map(|arg| arg.replace("{{DOWNLOAD}}", download_path)),
+        );
+        let command = match command {
+            Ok(command) => command,
+            Err(err) => {
+                warn!(
+                    "Not probing capabilities of linter '{}', assuming none: {:#}",
+                    self.code, err
+                );
+                return Capabilities::default();
+            }
+        };
+        let lease = self.remote.as_ref().map(|remote| remote.lease());
+        let command = match (&self.remote, &lease) {
+            (Some(remote), Some(lease)) => remote.remote_command(
+                lease.host(),
+                self.get_config_dir(),
+                &self.controlled_env(),
+                &command,
+                None,
+                false,
+            ),
+            _ => command,
+        };
+        match capabilities::probe(
+            &command,
+            self.get_config_dir(),
+            &self.controlled_env(),
+            self.network,
+        ) {
+            Ok(capabilities) => {
+                debug!("Linter {} capabilities: {:?}", self.code, capabilities);
+                capabilities
+            }
+            Err(err) => {
+                warn!(
+                    "Failed to probe capabilities of linter '{}', assuming none: {:#}",
+                    self.code, err
+                );
+                Capabilities::default()
+            }
+        }
+    }
+
+    /// `command` prefixed with the command wrapper, if any.
+    pub(crate) fn wrap_command(&self, command: impl IntoIterator<Item = String>) -> Vec<String> {
+        self.command_wrapper
+            .iter()
+            .cloned()
+            .chain(command)
+            .collect()
+    }
+
+    /// `command`, which runs the linter's own code, prefixed with the command
+    /// wrapper and kept off the network if the linter may not use it. The
+    /// sandbox goes inside the wrapper, which may need the network itself.
+    /// Fails if the linter may not use the network but that can't be
+    /// enforced, as on remote hosts, unless that's allowed.
+    pub(crate) fn wrap_linter_command(
+        &self,
+        command: impl IntoIterator<Item = String>,
+    ) -> Result<Vec<String>> {
+        if self.network.allows_run() {
+            return Ok(self.wrap_command(command));
+        }
+        if self.remote.is_some() {
+            ensure!(
+                network::allows_unsandboxed(),
+                "Linter '{}' may not use the network, which can't be enforced on remote \
+                 hosts. Pass --allow-unsandboxed-network to run it there anyway.",
+                self.code
+            );
+            return Ok(self.wrap_command(command));
+        }
+        Ok(self.wrap_command(network::isolate(command.into_iter().collect())?))
+    }
+
+    /// What to pass the linter for `files`. With `pass_directories`, that's
+    /// the innermost directory root containing each file, or else the file's
+    /// own directory, each listed once.
+    fn paths_to_pass(&self, files: Vec<AbsPath>) -> Result<Vec<AbsPath>> {
+        if !self.pass_directories {
+            return Ok(files);
+        }
+        // Roots that don't exist contain no files.
+        let roots = self
+            .directory_roots
+            .iter()
+            .filter_map(|root| self.get_config_dir().join(root).canonicalize().ok())
+            .collect::<Vec<_>>();
+        let mut dirs = BTreeSet::new();
+        for file in &files {
+            let dir = roots
+                .iter()
+                .filter(|root| file.starts_with(root))
+                .max_by_key(|root| root.components().count())
+                .map(PathBuf::as_path)
+                .or_else(|| file.parent());
+            if let Some(dir) = dir {
+                dirs.insert(dir.to_path_buf());
+            }
+        }
+        dirs.into_iter().map(AbsPath::try_from).collect()
+    }
+
     pub fn get_config_dir(&self) -> &Path {
         // Unwrap is fine here because we know this path is absolute and won't be `/`
         self.primary_config_path.parent().unwrap()
     }
 
-    fn get_matches(&self, files: &[AbsPath]) -> Vec<AbsPath> {
+    fn accepts_dangling_symlinks(&self) -> bool {
+        match &self.builtin {
+            Some(builtin) => builtin.accepts_dangling_symlinks(),
+            None => false,
+        }
+    }
+
+    /// Whether any of the linter's `cache_inputs` are among `files`, which
+    /// must be sorted.
+    pub fn cache_inputs_among(&self, files: &[AbsPath]) -> bool {
+        self.cache_inputs.iter().any(|input| {
+            AbsPath::try_from(self.get_config_dir().join(input))
+                .is_ok_and(|input| files.binary_search(&input).is_ok())
+        })
+    }
+
+    /// Return the subset of `files` that this linter should run on.
+    pub fn get_matches(&self, files: &[AbsPath]) -> Vec<AbsPath> {
         let config_dir = self.get_config_dir();
         files
             .iter()
             .filter(|name| {
-                self.include_patterns
-                    .iter()
-                    .any(|pattern| matches_relative_path(config_dir, name, pattern))
+                includes(config_dir, name, &self.include_patterns)
+                    || self
+                        .include_regexes
+                        .iter()
+                        .any(|regex| matches_relative_regex(config_dir, name, regex))
+                    || shebang::matches(name, &self.match_shebang)
             })
             .filter(|name| {
                 !self
                     .exclude_patterns
                     .iter()
                     .any(|pattern| matches_relative_path(config_dir, name, pattern))
+                    && !self
+                        .exclude_regexes
+                        .iter()
+                        .any(|regex| matches_relative_regex(config_dir, name, regex))
+            })
+            .filter(|name| {
+                self.scope
+                    .as_ref()
+                    .is_none_or(|scope| name.starts_with(scope))
+                    && !self
+                        .overridden_under
+                        .iter()
+                        .any(|dir| name.starts_with(dir))
+            })
+            .filter(|name| {
+                !self
+                    .ignore_file
+                    .as_ref()
+                    .is_some_and(|ignore_file| ignore_file::ignores(ignore_file, name))
+            })
+            .filter(|name| {
+                !self
+                    .gitignores
+                    .as_ref()
+                    .is_some_and(|gitignores| gitignores.ignores(name))
+            })
+            .filter(|name| {
+                io_limit::limited(|| {
+                    // Dangling symlinks only go to linters that check for them.
+                    (self.accepts_dangling_symlinks() || name.exists())
+                        && self.accepts_contents(name)
+                })
             })
             .cloned()
             .collect()
     }
 
-    fn run_command(&self, matched_files: Vec<AbsPath>) -> Result<Vec<LintMessage>> {
-        let tmp_file = tempfile::NamedTempFile::new()?;
-        for matched_file in &matched_files {
-            let name = matched_file
-                .to_str()
-                .ok_or_else(|| anyhow!("Could not convert path to string."))?;
-            writeln!(&tmp_file, "{}", name)?;
+    /// Whether the linter's `max_file_size_bytes` and `skip_binary_files`
+    /// let `file` through. Files that can't be read are let through, for the
+    /// linter to report.
+    fn accepts_contents(&self, file: &AbsPath) -> bool {
+        if let Some(max) = self.max_file_size_bytes {
+            if let Ok(metadata) = std::fs::metadata(file) {
+                if metadata.is_file() && metadata.len() > max {
+                    debug!(
+                        "Linter '{}' skipped '{}': larger than {} bytes",
+                        self.code,
+                        file.display(),
+                        max
+                    );
+                    return false;
+                }
+            }
+        }
+        if self.skip_binary_files && is_binary(file) {
+            debug!(
+                "Linter '{}' skipped '{}': binary file",
+                self.code,
+                file.display()
+            );
+            return false;
+        }
+        true
+    }
+
+    fn run_command(
+        &self,
+        batches: &[Batch],
+        metadata: &ChangeMetadata,
+        mode: RunMode,
+        scheduler: &Scheduler,
+        cancelled: &AtomicBool,
+        recorder: Option<&Recorder>,
+    ) -> Result<Option<Vec<LintMessage>>> {
+        if let [batch] = batches {
+            return self.run_invocation(batch, metadata, mode, scheduler, cancelled, recorder);
+        }
+
+        // Each batch, like each crate, runs as the scheduler allows.
+        let results = thread::scope(|scope| {
+            let handles = batches
+                .iter()
+                .map(|batch| {
+                    scope.spawn(move || {
+                        self.run_invocation(batch, metadata, mode, scheduler, cancelled, recorder)
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        let mut messages = Vec::new();
+        for result in results {
+            match result? {
+                Some(crate_messages) => messages.extend(crate_messages),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(messages))
+    }
+
+    /// Run the command of `batch` once, with its paths in `{{PATHSFILE}}`.
+    fn run_invocation(
+        &self,
+        batch: &Batch,
+        metadata: &ChangeMetadata,
+        mode: RunMode,
+        scheduler: &Scheduler,
+        cancelled: &AtomicBool,
+        recorder: Option<&Recorder>,
+    ) -> Result<Option<Vec<LintMessage>>> {
+        let _slot = match scheduler.acquire(&self.code, cancelled) {
+            Some(slot) => slot,
+            None => {
+                debug!("Linter {} was cancelled", self.code);
+                return Ok(None);
+            }
+        };
+        let cwd = batch.cwd.as_path();
+        let download_path = match &self.download {
+            Some(download) => {
+                let path = download.cached_path()?;
+                ensure!(
+                    path.exists(),
+                    "The binary for this linter hasn't been downloaded. \
+                     Run `lintrunner init` to download it."
+                );
+                path.display().to_string()
+            }
+            None => String::new(),
+        };
+
+        // Pick how to invoke the linter based on what it supports.
+        let capabilities = self.resolve_capabilities(&download_path);
+        let pathsfile_format = if capabilities.json_pathsfile {
+            PathsFileFormat::Json
+        } else {
+            self.pathsfile_format
+        };
+        let paths_on_stdin = capabilities.stdin
+            && !batch
+                .command
+                .iter()
+                .any(|arg| arg.contains("{{PATHSFILE}}"));
+        let mut invocation_env = Vec::new();
+        if capabilities.fix && mode.applying_fixes {
+            invocation_env.push(("LINTRUNNER_FIX", "1"));
         }
+        if capabilities.line_ranges
+            && pathsfile_format == PathsFileFormat::Json
+            && mode.changed_lines_only
+        {
+            invocation_env.push(("LINTRUNNER_LINE_RANGES", "1"));
+        }
+
+        let mut paths_file = Vec::new();
+        write_paths_file(&mut paths_file, pathsfile_format, &batch.paths, metadata)?;
+        let tmp_file = io_limit::limited(|| -> Result<_> {
+            let mut tmp_file = tempfile::NamedTempFile::new()?;
+            tmp_file.write_all(&paths_file)?;
+            Ok(tmp_file)
+        })?;
 
         let file_path = tmp_file
             .path()
             .to_str()
             .ok_or_else(|| anyhow!("tempfile corrupted"))?;
 
-        let (program, arguments) = self.commands.split_at(1);
-        let arguments: Vec<String> = arguments
+        let commands = batch
+            .command
             .iter()
             .map(|arg| arg.replace("{{PATHSFILE}}", file_path))
-            .collect();
+            .collect::<Vec<_>>();
+        // Remotely, the paths file is sent over stdin and the environment is
+        // set by the remote command.
+        let remote = self.remote.as_ref().filter(|_| batch.remote);
+        let lease = remote.map(|remote| remote.lease());
+        let commands = match (remote, &lease) {
+            (Some(remote), Some(lease)) => {
+                debug!("Running linter {} on {}", self.code, lease.host());
+                let env = self
+                    .controlled_env()
+                    .into_iter()
+                    .chain(invocation_env.iter().copied())
+                    .collect::<Vec<_>>();
+                remote.remote_command(
+                    lease.host(),
+                    cwd,
+                    &env,
+                    &commands,
+                    Some(file_path),
+                    paths_on_stdin,
+                )
+            }
+            _ => commands,
+        };
+        let send_paths_file = paths_on_stdin || remote.is_some();
+        let (program, arguments) = commands.split_at(1);
 
         debug!(
             "Running linter {}: {} {}",
@@ -91,95 +988,380 @@ impl Linter {
                 .collect::<Vec<_>>()
                 .join(" ")
         );
+        debug!(
+            "Linter {} invocation: {:?} paths file{}, env {:?}",
+            self.code,
+            pathsfile_format,
+            if paths_on_stdin { " on stdin" } else { "" },
+            invocation_env
+        );
 
         let start = std::time::Instant::now();
-        let command = Command::new(&program[0])
-            .args(&arguments)
-            .current_dir(self.get_config_dir())
-            .output()
-            .with_context(|| {
-                format!(
-                    "Failed to execute linter command {} with args: {:?}",
-                    program[0], arguments
-                )
-            })?;
-        debug!("Linter {} took: {:?}", self.code, start.elapsed());
-        ensure_output("Linter command", &command)?;
-
-        if !&command.status.success() {
-            let stderr = std::str::from_utf8(&command.stderr)?;
-            let stdout = std::str::from_utf8(&command.stdout)?;
-            bail!(
-                "Linter command failed with non-zero exit code.\n\
-                 STDERR:\n{}\n\nSTDOUT:{}\n",
-                stderr,
-                stdout,
-            );
+        let mut command = Command::new(&program[0]);
+        command
+            .args(arguments)
+            .current_dir(cwd)
+            .envs(self.controlled_env())
+            .envs(invocation_env.iter().copied())
+            .stdin(if send_paths_file {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stderr(Stdio::piped());
+        if !self.network.allows_run() {
+            network::strip_env(&mut command);
+        }
+        own_process_group(&mut command);
+        let pty_leader = if self.use_pty {
+            let pty = Pty::open().context("Failed to allocate a pty for the linter")?;
+            command.stdout(pty.follower);
+            Some(pty.leader)
+        } else {
+            command.stdout(Stdio::piped());
+            None
+        };
+        let child = command.spawn().with_context(|| {
+            format!(
+                "Failed to execute linter command {} with args: {:?}",
+                program[0], arguments
+            )
+        });
+        // Close our copy of the pty follower, so that reading the leader ends
+        // when the child exits.
+        drop(command);
+        let mut child = child?;
+        // Until the child has been waited for, which is before this returns.
+        let _registered = register_group(&child);
+        if let Some(mut stdin) = child.stdin.take() {
+            // Written from a thread so a linter that doesn't read all of its
+            // input can't block us. Dropping stdin closes it.
+            thread::spawn(move || drop(stdin.write_all(&paths_file)));
+        }
+        let mut output = match wait_with_output_cancellable(
+            child,
+            pty_leader,
+            self.max_output_bytes,
+            self.timeout,
+            cancelled,
+        )? {
+            WaitOutcome::Finished(output) => output,
+            WaitOutcome::Cancelled => {
+                debug!("Linter {} was cancelled", self.code);
+                return Ok(None);
+            }
+            WaitOutcome::OutputLimitExceeded => {
+                let limit = self.max_output_bytes.unwrap_or_default();
+                debug!("Linter {} wrote more than {} bytes", self.code, limit);
+                return Ok(Some(vec![output_limit_message(&self.code, limit)]));
+            }
+            WaitOutcome::TimedOut => {
+                let timeout = self.timeout.unwrap_or_default();
+                debug!("Linter {} timed out after {:?}", self.code, timeout);
+                return Ok(Some(vec![timeout_message(&self.code, timeout)]));
+            }
+        };
+        debug!(
+            "Linter {} took: {:?}, wrote {} bytes{}",
+            self.code,
+            start.elapsed(),
+            output.stdout.len(),
+            if output.stdout.is_spilled() {
+                " (spilled to disk)"
+            } else {
+                ""
+            }
+        );
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+        if let Some(recorder) = recorder {
+            recorder.record(RecordedInvocation {
+                code: self.code.clone(),
+                command: program.iter().chain(arguments.iter()).cloned().collect(),
+                cwd: cwd.display().to_string(),
+                env: if recorder.captures_env() {
+                    std::env::vars_os()
+                        .map(|(key, value)| {
+                            (
+                                key.to_string_lossy().into_owned(),
+                                value.to_string_lossy().into_owned(),
+                            )
+                        })
+                        .chain(
+                            self.controlled_env()
+                                .into_iter()
+                                .chain(invocation_env.iter().copied())
+                                .map(|(key, value)| (key.to_string(), value.to_string())),
+                        )
+                        .collect()
+                } else {
+                    BTreeMap::new()
+                },
+                paths: batch
+                    .paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect(),
+                stdout: self.recordable_stdout(&mut output.stdout)?,
+                stderr: stderr.clone(),
+                exit_code: output.status.code(),
+            });
+        }
+
+        if !output.status.success() {
+            ensure_linter_succeeded(false, &output.stdout.head(SPILL_THRESHOLD)?, &stderr)?;
+        }
+        // Parsed a line at a time, so output spilled to disk is never read
+        // into memory all at once.
+        // Output from a pty is always parsed as JSON Lines, after cleaning.
+        let first_line = output
+            .stdout
+            .lines()?
+            .filter_map(|line| line.ok())
+            .map(|line| String::from_utf8_lossy(&line).into_owned())
+            .find(|line| !line.trim().is_empty());
+        let mut messages = if !self.use_pty && is_json_document(first_line.as_deref()) {
+            parse_lint_document(output.stdout.reader()?)?
+        } else {
+            parse_lint_lines(protocol_lines(&mut output.stdout, self.use_pty)?)?
+        };
+        finish_messages(&mut messages, self.column_unit)?;
+        Ok(Some(messages))
+    }
+
+    /// The stdout to record for an invocation: what lintrunner parses,
+    /// truncated if it's too big to keep in memory.
+    fn recordable_stdout(&self, stdout: &mut SpooledOutput) -> Result<String> {
+        if !self.use_pty {
+            return Ok(stdout.head(SPILL_THRESHOLD)?);
         }
-        let stdout_str = std::str::from_utf8(&command.stdout)?;
+        let mut cleaned = String::new();
+        for line in protocol_lines(stdout, true)? {
+            if cleaned.len() >= SPILL_THRESHOLD {
+                cleaned.push_str("[more output not shown]\n");
+                break;
+            }
+            cleaned.push_str(&line?);
+            cleaned.push('\n');
+        }
+        Ok(cleaned)
+    }
+
+    fn run_builtin(
+        &self,
+        builtin: &dyn BuiltinLinter,
+        batches: &[Batch],
+        scheduler: &Scheduler,
+        cancelled: &AtomicBool,
+    ) -> Result<Option<Vec<LintMessage>>> {
+        let _slot = match scheduler.acquire(&self.code, cancelled) {
+            Some(slot) => slot,
+            None => {
+                debug!("Linter {} was cancelled", self.code);
+                return Ok(None);
+            }
+        };
+        let start = std::time::Instant::now();
         let mut messages = Vec::new();
-        for line in stdout_str.lines() {
-            if line.is_empty() {
-                continue;
+        for file in batches.iter().flat_map(|batch| &batch.paths) {
+            if cancelled.load(Ordering::SeqCst) {
+                debug!("Linter {} was cancelled", self.code);
+                return Ok(None);
             }
-            let msg = serde_json::from_str(line).with_context(|| {
-                format!(
-                    "Failed to deserialize output for lint adapter, line: {}",
-                    line
-                )
-            })?;
-            messages.push(msg);
+            messages.extend(
+                builtin
+                    .lint_file(&self.code, file)
+                    .with_context(|| format!("Failed to lint '{}'", file.display()))?,
+            );
         }
-        Ok(messages)
+        debug!("Linter {} took: {:?}", self.code, start.elapsed());
+        Ok(Some(messages))
     }
 
-    pub fn run(&self, files: &[AbsPath]) -> Vec<LintMessage> {
-        let matches = self.get_matches(files);
-        log_files(&format!("Linter '{}' matched files: ", self.code), &matches);
-        if matches.is_empty() {
-            return Vec::new();
+    /// The invocations running the linter on `matches` would take, without
+    /// running anything. Linters that won't run a command on them, because
+    /// there are none or the linter can't run, have none. A command that
+    /// needs a crate is run once per crate, from the crate's directory.
+    pub fn plan_batches(&self, matches: &[AbsPath]) -> Result<Vec<Batch>> {
+        if matches.is_empty() || self.config_error.is_some() || self.health_error.is_some() {
+            return Ok(Vec::new());
+        }
+        let config_dir = self.get_config_dir();
+        if self.builtin.is_some() {
+            return Ok(vec![Batch {
+                cwd: config_dir.to_path_buf(),
+                paths: matches.to_vec(),
+                command: Vec::new(),
+                remote: false,
+            }]);
+        }
+        let download_path = match &self.download {
+            Some(download) => download.cached_path()?.display().to_string(),
+            None => String::new(),
+        };
+        let batch = |files: &[AbsPath], crate_dir: Option<&Path>| -> Result<Batch> {
+            let crate_dir_str = crate_dir
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_default();
+            let command = self.commands.iter().map(|arg| {
+                arg.replace("{{DOWNLOAD}}", &download_path)
+                    .replace(CRATE_DIR_PLACEHOLDER, &crate_dir_str)
+            });
+            let paths = self.paths_to_pass(files.to_vec())?;
+            if self.pass_directories {
+                log_files(
+                    &format!("Linter '{}' is passed directories: ", self.code),
+                    &paths,
+                );
+            }
+            Ok(Batch {
+                cwd: crate_dir.unwrap_or(config_dir).to_path_buf(),
+                paths,
+                command: self.wrap_linter_command(command)?,
+                remote: self.remote.is_some(),
+            })
+        };
+        if !self
+            .commands
+            .iter()
+            .any(|arg| arg.contains(CRATE_DIR_PLACEHOLDER))
+        {
+            return Ok(vec![batch(matches, None)?]);
+        }
+        let (crates, outside) = group_by_crate(matches, config_dir);
+        if !outside.is_empty() {
+            log_files(
+                &format!("Linter '{}' skipped files not in any crate: ", self.code),
+                &outside,
+            );
+        }
+        crates
+            .iter()
+            .map(|(crate_dir, files)| batch(files, Some(crate_dir)))
+            .collect()
+    }
+
+    /// Run the linter as `planned`, on the files it matched. `metadata`
+    /// describes how the matches changed, for linters that take a JSON paths
+    /// file, and `mode` what will be done with the results. Each planned
+    /// batch waits for a slot from `scheduler`.
+    ///
+    /// Returns `None` if `cancelled` was set before the linter finished. If
+    /// `recorder` is set, the invocation is recorded to it.
+    pub fn run(
+        &self,
+        planned: &PlannedLinter,
+        metadata: &ChangeMetadata,
+        mode: RunMode,
+        scheduler: &Scheduler,
+        cancelled: &AtomicBool,
+        recorder: Option<&Recorder>,
+    ) -> Option<Vec<LintMessage>> {
+        if let Some(config_error) = &self.config_error {
+            return Some(vec![LintMessage::general_error(
+                &self.code,
+                INVALID_CONFIGURATION,
+                format!(
+                    "This linter was not run because its configuration is invalid. \
+                     Other linters are unaffected.\n\nCONTEXT:\n{}",
+                    config_error
+                ),
+            )]);
+        }
+        log_files(
+            &format!("Linter '{}' matched files: ", self.code),
+            &planned.files,
+        );
+        if planned.files.is_empty() {
+            return Some(Vec::new());
+        }
+        if let Some(health_error) = &self.health_error {
+            debug!(
+                "Not running linter {}, its toolchain is broken: {}",
+                self.code, health_error
+            );
+            return Some(vec![health::broken_toolchain_message(
+                &self.code,
+                health_error,
+            )]);
         }
         // Wrap the command in a Result to ensure uniform error handling.
         // This way, linters are guaranteed to exit cleanly, and any issue will
         // be reported using the same mechanism that we use to report regular
         // lint errors.
-        match self.run_command(matches) {
-            Err(e) => {
-                let err_lint = LintMessage {
-                    path: None,
-                    line: None,
-                    char: None,
-                    code: self.code.clone(),
-                    severity: crate::lint_message::LintSeverity::Error,
-                    name: "Linter failed".to_string(),
-                    description: Some(format!(
-                        "Linter failed. This a bug, please file an issue against \
-                                 the linter maintainer.\n\nCONTEXT:\n{}",
-                        e
-                    )),
-                    original: None,
-                    replacement: None,
-                };
-                vec![err_lint]
+        let res = match (&planned.error, &self.builtin) {
+            (Some(error), _) => Err(anyhow!("{}", error)),
+            (None, Some(builtin)) => {
+                self.run_builtin(builtin.as_ref(), &planned.batches, scheduler, cancelled)
             }
+            (None, None) => self.run_command(
+                &planned.batches,
+                metadata,
+                mode,
+                scheduler,
+                cancelled,
+                recorder,
+            ),
+        };
+        match res {
+            Err(e) => Some(vec![linter_failure_message(&self.code, &e)]),
             Ok(messages) => messages,
         }
     }
 
-    pub fn init(&self, dry_run: bool) -> Result<()> {
+    /// Run this linter's setup: fetch its `download`, if any, then run its
+    /// `init_command`. With `offline`, downloads that aren't already cached
+    /// are skipped with a warning, and init commands are told about it
+    /// through `LINTRUNNER_OFFLINE=1`. With `network = 'never'`, the init
+    /// command runs without network access, but the download still happens,
+    /// since lintrunner fetches and verifies it rather than the linter.
+    pub fn init(&self, dry_run: bool, offline: bool) -> Result<()> {
+        if let Some(config_error) = &self.config_error {
+            warn!(
+                "Skipping init for linter '{}' because its configuration is invalid: {}",
+                self.code, config_error
+            );
+            return Ok(());
+        }
+        if let Some(download) = &self.download {
+            info!("Downloading binary for linter: '{}'", self.code);
+            match download.ensure_downloaded(dry_run, offline) {
+                Ok(_) => {}
+                Err(err) if offline => warn!(
+                    "Skipping download for linter '{}': {:#}. \
+                     It will fail until `lintrunner init` is run while online.",
+                    self.code, err
+                ),
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("Failed to download binary for '{}'", self.code))
+                }
+            }
+        }
         match &self.init_commands {
             Some(init_commands) => {
-                info!("Initializing linter: '{}'", self.code);
                 if init_commands.is_empty() {
                     return Ok(());
                 }
+                // Checked here rather than at config load time, so that a
+                // broken init command doesn't block unrelated lint runs.
+                ensure!(
+                    init_commands.iter().any(|arg| arg.contains("{{DRYRUN}}")),
+                    "Config for linter {} defines init args \
+                     but does not take a {{{{DRYRUN}}}} argument.",
+                    self.code
+                );
+                info!("Initializing linter: '{}'", self.code);
 
                 let dry_run = if dry_run { "1" } else { "0" };
 
-                let init_commands: Vec<String> = init_commands
+                let mut init_commands: Vec<String> = init_commands
                     .iter()
                     .map(|arg| arg.replace("{{DRYRUN}}", dry_run))
                     .collect();
+                if !self.network.allows_init() {
+                    init_commands = network::isolate(init_commands)?;
+                }
                 info!("the init commands are {:?}", init_commands);
                 let (program, arguments) = init_commands.split_at(1);
                 debug!(
@@ -191,10 +1373,25 @@ impl Linter {
                         .collect::<Vec<_>>()
                         .join(" ")
                 );
-                let status = Command::new(&program[0])
+                let mut command = Command::new(&program[0]);
+                command
                     .args(arguments)
                     .current_dir(self.get_config_dir())
-                    .status()?;
+                    .envs(self.env.iter().map(|(name, value)| (name, value)));
+                if offline {
+                    command.env("LINTRUNNER_OFFLINE", "1");
+                }
+                if !self.network.allows_init() {
+                    network::strip_env(&mut command);
+                }
+                // Nobody would answer a prompt, so make sure it fails instead
+                // of hanging.
+                if !interactive::can_prompt() {
+                    command
+                        .stdin(Stdio::null())
+                        .env("LINTRUNNER_NON_INTERACTIVE", "1");
+                }
+                let status = command.status()?;
                 info!("the status is {:?}", status);
                 ensure!(
                     status.success(),
@@ -210,10 +1407,185 @@ impl Linter {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{convert::TryFrom, path::PathBuf};
 
     use super::*;
 
+    fn env_linter(env_passthrough: Vec<String>, config_path: &AbsPath) -> Linter {
+        // Reports the linter's locale and encoding as the lint name.
+        let script = r#"echo "{\"path\": null, \"line\": null, \"char\": null, \"code\": \"ENV\", \"severity\": \"advice\", \"name\": \"$LC_ALL $PYTHONIOENCODING\", \"original\": null, \"replacement\": null, \"description\": null}""#;
+        Linter {
+            code: "ENV".to_string(),
+            include_patterns: Vec::new(),
+            exclude_patterns: Vec::new(),
+            include_regexes: Vec::new(),
+            exclude_regexes: Vec::new(),
+            match_shebang: Vec::new(),
+            scope: None,
+            overridden_under: Vec::new(),
+            ignore_file: None,
+            respect_gitignore: None,
+            gitignores: None,
+            max_file_size_bytes: None,
+            skip_binary_files: false,
+            priority: 0,
+            commands: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            pathsfile_format: PathsFileFormat::default(),
+            capabilities: None,
+            probe_capabilities: false,
+            probed_capabilities: OnceLock::new(),
+            needs_compdb: false,
+            use_pty: false,
+            max_output_bytes: None,
+            timeout: None,
+            column_unit: ColumnUnit::default(),
+            init_commands: None,
+            env_passthrough,
+            env: Vec::new(),
+            download: None,
+            health_check: None,
+            cache_inputs: Vec::new(),
+            expand_on_config_change: false,
+            pass_directories: false,
+            directory_roots: Vec::new(),
+            artifacts: Vec::new(),
+            network: NetworkPolicy::default(),
+            health_error: None,
+            command_wrapper: Vec::new(),
+            remote: None,
+            builtin: None,
+            primary_config_path: config_path.clone(),
+            config_error: None,
+        }
+    }
+
+    /// `linter` planned to run on `path`, whether it matches or not.
+    fn planned(linter: &Linter, path: &AbsPath) -> PlannedLinter {
+        PlannedLinter {
+            code: linter.code.clone(),
+            expanded: false,
+            files: vec![path.clone()],
+            batches: linter.plan_batches(std::slice::from_ref(path)).unwrap(),
+            error: None,
+        }
+    }
+
+    #[test]
+    #[cfg_attr(target_os = "windows", ignore)] // requires sh
+    fn test_controlled_env() -> Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let path = AbsPath::try_from(file.path())?;
+
+        let linter = env_linter(Vec::new(), &path);
+        let messages = linter
+            .run(
+                &planned(&linter, &path),
+                &ChangeMetadata::new(),
+                RunMode::default(),
+                &Scheduler::new(1),
+                &AtomicBool::new(false),
+                None,
+            )
+            .unwrap();
+        assert_eq!(messages[0].name, format!("{} utf-8", UTF8_LOCALE));
+
+        // Passed-through variables keep the user's value (here, unset).
+        let linter = env_linter(vec!["PYTHONIOENCODING".to_string()], &path);
+        let messages = linter
+            .run(
+                &planned(&linter, &path),
+                &ChangeMetadata::new(),
+                RunMode::default(),
+                &Scheduler::new(1),
+                &AtomicBool::new(false),
+                None,
+            )
+            .unwrap();
+        let expected = std::env::var("PYTHONIOENCODING").unwrap_or_default();
+        assert_eq!(
+            messages[0].name.trim_end(),
+            format!("{} {}", UTF8_LOCALE, expected).trim_end()
+        );
+
+        // Variables from the config override both.
+        let mut linter = env_linter(vec!["PYTHONIOENCODING".to_string()], &path);
+        linter.env = vec![("PYTHONIOENCODING".to_string(), "latin-1".to_string())];
+        let messages = linter
+            .run(
+                &planned(&linter, &path),
+                &ChangeMetadata::new(),
+                RunMode::default(),
+                &Scheduler::new(1),
+                &AtomicBool::new(false),
+                None,
+            )
+            .unwrap();
+        assert_eq!(messages[0].name, format!("{} latin-1", UTF8_LOCALE));
+        Ok(())
+    }
+
+    #[test]
+    fn recorded_env_only_when_asked() -> Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let path = AbsPath::try_from(file.path())?;
+        let linter = env_linter(Vec::new(), &path);
+
+        for capture_env in [false, true] {
+            let recorder = Recorder::new(capture_env);
+            linter
+                .run(
+                    &planned(&linter, &path),
+                    &ChangeMetadata::new(),
+                    RunMode::default(),
+                    &Scheduler::new(1),
+                    &AtomicBool::new(false),
+                    Some(&recorder),
+                )
+                .unwrap();
+            let invocations = recorder.finish().invocations;
+            assert_eq!(invocations.len(), 1);
+            assert_eq!(
+                invocations[0].env.get("LC_ALL").map(String::as_str),
+                capture_env.then_some(UTF8_LOCALE)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_paths_to_pass() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let config_path = root.join(".lintrunner.toml");
+        std::fs::write(&config_path, "")?;
+        let mut files = Vec::new();
+        for file in [
+            "top.go",
+            "pkg/a.go",
+            "pkg/b.go",
+            "svc/api/main.go",
+            "svc/api/v1/h.go",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, "")?;
+            files.push(AbsPath::try_from(path)?);
+        }
+
+        let mut linter = env_linter(Vec::new(), &AbsPath::try_from(config_path)?);
+        assert_eq!(linter.paths_to_pass(files.clone())?, files);
+
+        linter.pass_directories = true;
+        linter.directory_roots = vec!["svc".to_string(), "svc/api".to_string(), "gone".to_string()];
+        let dirs = linter.paths_to_pass(files)?;
+        let expected = [root.clone(), root.join("pkg"), root.join("svc/api")];
+        assert_eq!(
+            dirs.iter().map(|dir| dir.to_path_buf()).collect::<Vec<_>>(),
+            expected
+        );
+        Ok(())
+    }
+
     // Check that `*` does not match across path segments.
     #[test]
     fn test_glob_with_separator() -> Result<()> {
@@ -224,4 +1596,113 @@ mod tests {
         ));
         Ok(())
     }
+
+    #[test]
+    fn test_negated_include_patterns() -> Result<()> {
+        let patterns = ["src/**", "!src/gen/**", "src/gen/api.py"]
+            .iter()
+            .map(|pattern| IncludePattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let included = |path: &str| includes(Path::new(""), Path::new(path), &patterns);
+        assert!(included("src/main.py"));
+        assert!(!included("src/gen/types.py"));
+        assert!(included("src/gen/api.py"));
+        assert!(!included("docs/index.md"));
+        // Negations alone include nothing.
+        let negated = [IncludePattern::new("!src/gen/**")?];
+        assert!(!includes(Path::new(""), Path::new("src/main.py"), &negated));
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_and_exclude_regexes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let config_path = root.join(".lintrunner.toml");
+        std::fs::write(&config_path, "")?;
+        let mut files = Vec::new();
+        for file in [
+            "db/0001_init.py",
+            "db/helpers.py",
+            "vendor/zlib-1.3/zlib.c",
+            "vendor/zlib/zlib.c",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, "")?;
+            files.push(AbsPath::try_from(path)?);
+        }
+
+        let mut linter = env_linter(Vec::new(), &AbsPath::try_from(config_path)?);
+        linter.include_patterns = vec![IncludePattern::new("vendor/**")?];
+        linter.include_regexes = vec![Regex::new(r"^db/[0-9]+_\w+\.py$")?];
+        linter.exclude_regexes = vec![Regex::new(r"-[0-9.]+/")?];
+        assert_eq!(
+            linter.get_matches(&files),
+            [files[0].clone(), files[3].clone()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_file_size_and_binary_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let config_path = root.join(".lintrunner.toml");
+        std::fs::write(&config_path, "")?;
+        let mut files = Vec::new();
+        for (file, contents) in [
+            ("small.txt", &b"hello\n"[..]),
+            ("big.txt", &[b'a'; 100][..]),
+            ("image.png", &b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"[..]),
+        ] {
+            let path = root.join(file);
+            std::fs::write(&path, contents)?;
+            files.push(AbsPath::try_from(path)?);
+        }
+
+        let mut linter = env_linter(Vec::new(), &AbsPath::try_from(config_path)?);
+        linter.include_patterns = vec![IncludePattern::new("**")?];
+        assert_eq!(linter.get_matches(&files), files);
+        linter.max_file_size_bytes = Some(50);
+        assert_eq!(
+            linter.get_matches(&files),
+            [files[0].clone(), files[2].clone()]
+        );
+        linter.skip_binary_files = true;
+        assert_eq!(linter.get_matches(&files), [files[0].clone()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_output_shapes() -> Result<()> {
+        let message = |name: &str| {
+            format!(
+                r#"{{"path": null, "line": null, "char": null, "code": "X", "severity": "advice", "name": "{}", "original": null, "replacement": null, "description": null}}"#,
+                name
+            )
+        };
+        let names = |stdout: &str| -> Result<Vec<String>> {
+            Ok(parse_linter_output(true, stdout, "")?
+                .into_iter()
+                .map(|message| message.name)
+                .collect())
+        };
+
+        let lines = format!("{}\n\n{}\n", message("a"), message("b"));
+        assert_eq!(names(&lines)?, vec!["a", "b"]);
+
+        let array = format!("[\n  {},\n  {}\n]\n", message("a"), message("b"));
+        assert_eq!(names(&array)?, vec!["a", "b"]);
+
+        let wrapper = format!(
+            "{{\n  \"version\": 1,\n  \"messages\": [{}]\n}}\n",
+            message("a")
+        );
+        assert_eq!(names(&wrapper)?, vec!["a"]);
+
+        assert_eq!(names("[]")?, Vec::<String>::new());
+        assert!(parse_linter_output(true, "[{\"code\": \"X\"}]", "").is_err());
+        Ok(())
+    }
 }
@@ -1,9 +1,11 @@
 use anyhow::{bail, Result};
-use console::{style, Term};
+use console::style;
 use fern::colors::{Color, ColoredLevelConfig};
 use std::path::Path;
 use std::process::Output;
 
+use crate::theme::ThemeExt;
+
 use log::Level::Trace;
 use log::{debug, log_enabled, trace, LevelFilter};
 
@@ -33,12 +35,11 @@ pub fn ensure_output(program_name: &str, output: &Output) -> Result<()> {
     Ok(())
 }
 
-pub fn setup_logger(log_level: LevelFilter, log_file: &Path, force_color: bool) -> Result<()> {
+pub fn setup_logger(log_level: LevelFilter, log_file: &Path) -> Result<()> {
     let builder = fern::Dispatch::new();
 
-    let isatty = Term::stderr().features().is_attended();
-    if isatty || force_color {
-        // Use colors in our terminal output if we're on a tty
+    if console::colors_enabled_stderr() {
+        // Use colors in our terminal output, as decided by `ColorChoice::apply`.
         let log_colors = ColoredLevelConfig::new()
             .trace(Color::Cyan)
             .debug(Color::Blue)
@@ -51,11 +52,11 @@ pub fn setup_logger(log_level: LevelFilter, log_file: &Path, force_color: bool)
                     .format(move |out, message, record| {
                         out.finish(format_args!(
                             "{}{} {} {}{} {}",
-                            style("[").dim(),
+                            style("[").for_stderr().theme_dim(),
                             chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
                             log_colors.color(record.level()),
                             record.target(),
-                            style("]").dim(),
+                            style("]").for_stderr().theme_dim(),
                             message
                         ))
                     })
@@ -1,27 +1,79 @@
-use std::{collections::HashSet, convert::TryFrom, io::Write, path::Path};
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use chrono::SecondsFormat;
 use clap::Parser;
 
 use itertools::Itertools;
 use lintrunner::{
-    do_init, do_lint,
+    bisect::{do_bisect_lint, BisectOpts, FindingFilter},
+    budget::do_budget_tighten,
+    columns::ColumnUnit,
+    compdb::ensure_compdb,
+    config_check::{do_config_check, CheckFormat},
+    config_new::do_config_new,
+    config_schema::do_config_schema,
+    content_provider::{ContentProvider, DEFAULT_CONTENT_PROVIDER},
+    diff_file::DiffScope,
+    do_init, do_init_from_archive, do_lint,
+    exit_code::{self, UsageError, UsageResult},
+    fix::do_fix,
+    git::PathsCmd,
+    gitignore::Gitignores,
+    health,
+    history::{do_history_export, HistoryFormat},
+    impacted::{do_impacted, BuildSystem},
     init::check_init_changed,
-    lint_config::{get_linters_from_configs, LintRunnerConfig},
+    interactive,
+    io_limit::{self, DEFAULT_IO_THREADS},
+    lint_config::{get_linters_from_configs, LintRunnerConfig, TagSelection},
+    lint_message::LintSeverity,
+    lint_scopes,
     log_utils::setup_logger,
+    nested::{NestedConfigs, Selection},
+    network,
+    ownership::OwnershipOpt,
+    parse_duration,
     path::AbsPath,
+    path_provider::{self, Registry},
     persistent_data::{ExitInfo, PersistentDataStore, RunInfo},
-    rage::do_rage,
-    render::print_error,
-    PathsOpt, RenderOpt, RevisionOpt,
+    profile::{Profile, PROFILE_ENV},
+    progress::{ProgressOpt, ProgressStyle},
+    protected::{check_protected, write_lockfile},
+    publish::{do_publish_gerrit, do_publish_github_checks, GerritOpts, GithubChecksOpts},
+    rage::{do_rage, parse_since, RageSelection},
+    record::do_replay,
+    remote::RemotePool,
+    render::{print_error, wrap_width, GroupBy},
+    stream::{LintStream, StreamTarget},
+    subdir::{self, find_config, RelativeTo},
+    terminal,
+    theme::{init_theme, ColorChoice},
+    tmpdir, toolchain,
+    trend::{do_trend, TrendFormat},
+    triage::do_triage,
+    trust::{check_trusted, do_trust},
+    LintOptions, PathsOpt, RenderOpt, RevisionOpt,
 };
-use log::debug;
+use log::{debug, info};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const DEFAULT_CONFIGS: &str = ".lintrunner.toml, .lintrunner.private.toml";
 
 #[derive(Debug, Parser)]
-#[clap(version, name = "lintrunner", infer_subcommands(true))]
+#[clap(
+    version,
+    name = "lintrunner",
+    infer_subcommands(true),
+    after_help = exit_code::HELP
+)]
 struct Args {
     /// Verbose mode (-v, or -vv to show full list of paths being linted)
     #[clap(short, long, parse(from_occurrences), global = true)]
@@ -35,28 +87,99 @@ struct Args {
         global = true,
         alias = "config",
         multiple = true,
-        default_value = ".lintrunner.toml, .lintrunner.private.toml"
+        default_value = DEFAULT_CONFIGS
     )]
     configs: String,
 
-    /// If set, any suggested patches will be applied
+    /// What relative paths on the command line (paths to lint, --dir and
+    /// --paths-from) are resolved against: the current directory, or the
+    /// directory of the config.
+    #[clap(long, arg_enum, default_value_t = RelativeTo::Cwd, global = true)]
+    relative_to: RelativeTo,
+
+    /// If set, any safe patches will be applied
     #[clap(short, long, global = true)]
     apply_patches: bool,
 
-    /// Shell command that returns new-line separated paths to lint
+    /// When applying patches, also apply fixes that linters marked as
+    /// suggested rather than safe.
+    #[clap(long, global = true)]
+    apply_suggested: bool,
+
+    /// Command that returns new-line separated paths to lint. The command is
+    /// split into arguments using shell quoting rules, but is not run through
+    /// a shell.
     ///
     /// Example: To run on all files in the repo, use `--paths-cmd='git grep -Il .'`.
-    #[clap(long, conflicts_with = "paths-from", global = true)]
+    #[clap(long, conflicts_with_all = &["paths-from", "paths-cmd-arg"], global = true)]
     paths_cmd: Option<String>,
 
-    /// File with new-line separated paths to lint
+    /// Like --paths-cmd, but specifies the command one argument at a time,
+    /// with no quoting rules applied. Repeat it for each argument.
+    ///
+    /// Example: `--paths-cmd-arg git --paths-cmd-arg=ls-files --paths-cmd-arg=-z`
+    #[clap(
+        long,
+        conflicts_with = "paths-from",
+        allow_hyphen_values = true,
+        global = true
+    )]
+    paths_cmd_arg: Vec<String>,
+
+    /// Treat the output of --paths-cmd as NUL-delimited instead of
+    /// newline-delimited, e.g. for `git ls-files -z`.
+    #[clap(long, global = true)]
+    paths_cmd_nul: bool,
+
+    /// Command to prefix every linter invocation with, e.g.
+    /// `--wrapper='srun --partition=lint'`. The command is split into
+    /// arguments using shell quoting rules. Overrides the config's
+    /// `command_wrapper`; `--wrapper=''` runs linters directly.
+    #[clap(long, global = true)]
+    wrapper: Option<String>,
+
+    /// If files change while they're being linted (e.g. an editor saved
+    /// them), lint them again instead of warning that their messages may be
+    /// stale.
+    #[clap(long, global = true)]
+    rerun_modified: bool,
+
+    /// Run linters over SSH on the hosts in the config's `[remote_exec]`
+    /// section, instead of locally. Experimental.
+    #[clap(long, global = true)]
+    remote_exec: bool,
+
+    /// Run linters with `network = 'never'` or `'init-only'` even where they
+    /// can't be kept off the network: on machines without a network sandbox,
+    /// and with --remote-exec. They then only have proxy variables removed.
+    /// Without this, those linters fail instead.
+    #[clap(long, global = true)]
+    allow_unsandboxed_network: bool,
+
+    /// Unified diff whose changes should be linted, instead of asking the
+    /// version control system. The diff must already be applied to the
+    /// working tree. Only lint messages on lines the diff changes are reported.
+    #[clap(long, conflicts_with_all = &["paths-cmd", "paths-cmd-arg", "paths-from"], global = true)]
+    diff_file: Option<String>,
+
+    /// File with new-line separated paths to lint, or `-` to read them from
+    /// stdin.
     #[clap(long, global = true)]
     paths_from: Option<String>,
 
+    /// Get the paths to lint from a path provider, given as `NAME` or
+    /// `NAME:ARG`: `changed[:REVISION]`, `all`, `paths-file:FILE`, `stdin`,
+    /// `paths-cmd:COMMAND`, `diff-file:FILE` or
+    /// `build-target:BUILD_SYSTEM:TARGETS`.
+    ///
+    /// Example: `--paths-provider=build-target:bazel://src/...`
+    #[clap(long, conflicts_with_all=&["paths", "paths-cmd", "paths-cmd-arg", "paths-from", "diff-file", "revision", "merge-base-with", "all-files"], global = true)]
+    paths_provider: Option<String>,
+
     /// Lint all files that differ between the working directory and the
     /// specified revision. This argument can be any <tree-ish> that is accepted
     /// by `git diff-tree`
-    #[clap(long, short, conflicts_with_all=&["paths", "paths-cmd", "paths-from"], global = true)]
+    #[clap(long, short, conflicts_with_all=&["paths", "paths-cmd", "paths-cmd-arg", "paths-from", "diff-file"], global = true)]
     revision: Option<String>,
 
     /// Lint all files that differ between the merge base of HEAD with the
@@ -64,7 +187,7 @@ struct Args {
     /// accepted by `git diff-tree`
     ///
     /// Example: lintrunner -m master
-    #[clap(long, short, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "revision"], global = true)]
+    #[clap(long, short, conflicts_with_all=&["paths", "paths-cmd", "paths-cmd-arg", "paths-from", "diff-file", "revision"], global = true)]
     merge_base_with: Option<String>,
 
     /// Comma-separated list of linters to skip (e.g. --skip CLANGFORMAT,NOQA).
@@ -79,11 +202,58 @@ struct Args {
     #[clap(long, global = true)]
     take: Option<String>,
 
+    /// Comma-separated list of tags; only run linters tagged with one of
+    /// them (e.g. --tag python,fast).
+    #[clap(long, global = true)]
+    tag: Option<String>,
+
+    /// Comma-separated list of tags; don't run linters tagged with any of
+    /// them (e.g. --skip-tag slow).
+    #[clap(long, global = true)]
+    skip_tag: Option<String>,
+
     /// With 'default' show lint issues in human-readable format, for interactive use.
     /// With 'json', show lint issues as machine-readable JSON (one per line)
     /// With 'oneline', show lint issues in compact format (one per line)
-    #[clap(long, arg_enum, default_value_t = RenderOpt::Default, global=true)]
-    output: RenderOpt,
+    /// With 'vscode', stream lint issues as JSON lines shaped for VS Code
+    /// diagnostics, as each linter completes
+    /// With 'summary-markdown', show a Markdown summary to post as a PR comment
+    /// With 'arc', show lint issues as JSON lines for a custom arcanist linter
+    ///
+    /// Defaults to 'default', or to the output of the --profile.
+    #[clap(long, arg_enum, global = true)]
+    output: Option<RenderOpt>,
+
+    /// Use the options of this profile from the config, e.g. `--profile ci`
+    /// for a `[profile.ci]` section. Options given on the command line
+    /// override the profile's. Defaults to LINTRUNNER_PROFILE, if set.
+    #[clap(long, global = true)]
+    profile: Option<String>,
+
+    /// How many linter invocations may run at once. Defaults to one per
+    /// core, but at least one per linter.
+    #[clap(long, short, global = true)]
+    jobs: Option<usize>,
+
+    /// Don't report messages less severe than this.
+    #[clap(long, arg_enum, global = true)]
+    min_severity: Option<LintSeverity>,
+
+    /// Don't wrap long messages to the terminal's width in the default
+    /// output, e.g. when it is consumed by a log viewer that wraps itself.
+    #[clap(long, global = true)]
+    no_wrap: bool,
+
+    /// How to show progress while linters run. 'plain' is meant for screen
+    /// readers: instead of redrawn spinners, each change is announced as a
+    /// sentence on its own line, with a periodic status summary.
+    #[clap(long, arg_enum, default_value_t = ProgressStyle::Spinners, global = true)]
+    progress: ProgressStyle,
+
+    /// With `--progress plain`, how often to print a status summary, in
+    /// seconds.
+    #[clap(long, default_value_t = 10, global = true)]
+    progress_interval: u64,
 
     #[clap(subcommand)]
     cmd: Option<SubCommand>,
@@ -91,14 +261,27 @@ struct Args {
     /// Paths to lint. lintrunner will still respect the inclusions and
     /// exclusions defined in .lintrunner.toml; manually specifying a path will
     /// not override them.
-    #[clap(conflicts_with_all = &["paths-cmd", "paths-from"], global = true)]
+    #[clap(conflicts_with_all = &["paths-cmd", "paths-cmd-arg", "paths-from", "diff-file"], global = true)]
     paths: Vec<String>,
 
+    /// Never prompt: fail with a message saying what to pass instead, and run
+    /// init commands with no stdin. Implied when stdin isn't a terminal, and
+    /// in CI.
+    #[clap(long, global = true)]
+    non_interactive: bool,
+
     /// If set, always output with ANSI colors, even if we detect the output is
-    /// not a user-attended terminal.
+    /// not a user-attended terminal. Same as `--color=always`.
     #[clap(long, global = true)]
     force_color: bool,
 
+    /// When to use ANSI colors. With 'auto', NO_COLOR, CLICOLOR and
+    /// CLICOLOR_FORCE are honored before checking for a terminal. Colors themselves can be customized with the
+    /// config's `[colors]` section or the LINTRUNNER_COLORS environment
+    /// variable.
+    #[clap(long, arg_enum, default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+
     /// If set, use ths provided path to store any metadata generated by
     /// lintrunner. By default, this is a platform-specific location for
     /// application data (e.g. $XDG_DATA_HOME for UNIX systems.)
@@ -106,16 +289,144 @@ struct Args {
     data_path: Option<String>,
 
     /// If set, output json to the provided path as well as the terminal.
+    /// Messages that suggest a replacement also include a `patch` field with
+    /// byte offsets, content hashes, and a unified diff.
     #[clap(long, global = true)]
     tee_json: Option<String>,
 
+    /// If set, write all suggested fixes to the provided path as a single
+    /// unified diff, with paths relative to the config file's directory,
+    /// instead of applying them. The patch can be applied with `git apply`.
+    #[clap(long, conflicts_with = "apply-patches", global = true)]
+    patch_file: Option<String>,
+
     /// Run lintrunner on all files in the repo. This could take a while!
-    #[clap(long, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "revision", "merge-base-with"], global = true)]
+    #[clap(long, conflicts_with_all=&["paths", "paths-cmd", "paths-cmd-arg", "paths-from", "diff-file", "revision", "merge-base-with"], global = true)]
     all_files: bool,
 
     /// If set, will only lint files under the directory where the configuration file is located and its subdirectories.
     #[clap(long, global = true)]
     only_lint_under_config_dir: bool,
+
+    /// Only lint files under this directory. Can be specified multiple times.
+    /// Applies to every way of selecting files, including --all-files.
+    #[clap(long = "dir", global = true)]
+    dirs: Vec<String>,
+
+    /// Only lint files in which you last changed most of the lines,
+    /// according to blame. Combine with --all-files to clean up your own
+    /// code.
+    #[clap(long, global = true)]
+    mine: bool,
+
+    /// Only lint files that CODEOWNERS assigns to this owner, e.g.
+    /// `@org/team`.
+    #[clap(long, global = true)]
+    owner: Option<String>,
+
+    /// Cancel any linters still running after this long, e.g. `600s` or `10m`,
+    /// and report partial results. Useful for hooks, where unbounded latency
+    /// isn't acceptable.
+    #[clap(long, global = true)]
+    time_budget: Option<String>,
+
+    /// After linting, print how long each linter waited for a worker slot
+    /// and spent running, and when it finished.
+    #[clap(long, global = true)]
+    timing: bool,
+
+    /// Directory to put this run's temporary files in, instead of the
+    /// system's, e.g. when `/tmp` is small or mounted `noexec`. Can also be set
+    /// with LINTRUNNER_TMPDIR. A directory is created in it for the run and
+    /// removed when lintrunner exits.
+    #[clap(long, global = true, value_name = "DIR")]
+    tmpdir: Option<PathBuf>,
+
+    /// How many filesystem operations (stats, reads and writes of the files
+    /// being linted, paths files and patches) may run at once. Lower it on
+    /// network filesystems, where many concurrent calls slow everything down.
+    #[clap(long, global = true, value_name = "N", default_value_t = DEFAULT_IO_THREADS)]
+    io_threads: usize,
+
+    /// What the columns in the default, oneline and JSON outputs count:
+    /// Unicode code points (the default), bytes of UTF-8, or UTF-16 code
+    /// units. The VS Code output always uses UTF-16, as LSP does.
+    #[clap(long, arg_enum, default_value = "char", global = true)]
+    column_unit: ColumnUnit,
+
+    /// How the default output arranges lint messages: by file, or by
+    /// message, with identical messages across files folded into one entry
+    /// listing where they were found.
+    #[clap(long, arg_enum, default_value = "file", global = true)]
+    group_by: GroupBy,
+
+    /// Stream lint messages as JSON lines to `unix:/path/to.sock` or
+    /// `tcp:host:port` as each linter finishes, e.g. for live dashboards.
+    #[clap(long, global = true)]
+    stream_to: Option<String>,
+
+    /// Fail if the configuration of any linter marked `protected` doesn't
+    /// match the committed .lintrunner.lock, instead of just warning.
+    #[clap(long, global = true)]
+    enforce_protected: bool,
+
+    /// Stop all remaining linters as soon as one reports an error (including
+    /// a linter failure). Useful for hooks, where any error fails the hook.
+    #[clap(long, global = true, overrides_with = "keep-going")]
+    fail_fast: bool,
+
+    /// Run every linter to completion, even if some report errors. This is
+    /// the default; pass it to override an earlier --fail-fast.
+    #[clap(long, global = true, overrides_with = "fail-fast")]
+    keep_going: bool,
+
+    /// Exit with a non-zero code if no files were linted, either because no
+    /// files were selected or because none matched any linter.
+    #[clap(long, global = true)]
+    strict_empty: bool,
+
+    /// If set, write a JSON manifest to the provided path recording which
+    /// files each linter processed, along with a hash of the contents it was
+    /// given.
+    #[clap(long, global = true)]
+    write_manifest: Option<String>,
+
+    /// Write what would be run as JSON to the provided path, or `-` for
+    /// stdout, instead of running any linters: the files each linter matches,
+    /// and the commands they would be run with.
+    #[clap(long, global = true)]
+    plan_json: Option<String>,
+
+    /// If set, write an SVG badge to the provided path showing whether the
+    /// run passed, or how many errors and warnings it found.
+    #[clap(long, global = true)]
+    badge: Option<String>,
+
+    /// Fail the whole run if any linter's configuration is invalid. By
+    /// default, invalid linters are reported as failures and the rest still run.
+    #[clap(long, global = true)]
+    strict: bool,
+    /// If set, record each linter invocation (command, environment, paths,
+    /// output and exit code) to the provided path. The recording can be
+    /// re-rendered with `lintrunner replay`.
+    #[clap(long, global = true)]
+    record: Option<String>,
+    /// Lint the staged contents of files, rather than what's in the working
+    /// tree. Contents are read with the config's `content_provider`.
+    #[clap(long, conflicts_with = "contents-at", global = true)]
+    staged: bool,
+
+    /// Lint the contents of files at the given revision, rather than what's
+    /// in the working tree. Contents are read with the config's
+    /// `content_provider`.
+    #[clap(long, global = true)]
+    contents_at: Option<String>,
+
+    /// Forbid network access by lintrunner itself, e.g. for air-gapped
+    /// machines. `init` uses only already-downloaded binaries, and `rage`
+    /// prints reports instead of uploading them.
+    #[clap(long, global = true)]
+    offline: bool,
 }
 
 #[derive(Debug, Parser)]
@@ -125,6 +436,20 @@ enum SubCommand {
         /// If set, do not actually execute initialization commands, just print them
         #[clap(long, short)]
         dry_run: bool,
+        /// After initializing, snapshot the linters' toolchains in the data
+        /// directory (downloaded binaries, and whatever init commands put in
+        /// `{{TOOLCHAIN_DIR}}`) into this archive: a `.tar`, `.tar.gz` or
+        /// `.tar.zst`.
+        #[clap(long, value_name = "ARCHIVE", conflicts_with = "dry-run")]
+        pack: Option<PathBuf>,
+        /// Instead of running init commands, restore the toolchains in an
+        /// archive made with --pack.
+        #[clap(
+            long,
+            value_name = "ARCHIVE",
+            conflicts_with_all = &["dry-run", "pack"]
+        )]
+        from_archive: Option<PathBuf>,
     },
     /// Run and accept changes for formatting linters only. Equivalent to
     /// `lintrunner --apply-patches --take <formatters>`.
@@ -133,31 +458,251 @@ enum SubCommand {
     /// Run linters. This is the default if no subcommand is provided.
     Lint,
 
+    /// Apply patches suggested by a previous run, without re-running linters.
+    Fix {
+        /// Comma-separated list of linters whose patches should be applied
+        /// (e.g. --only CLANGFORMAT,RUSTFMT). By default, all patches are applied.
+        #[clap(long)]
+        only: Option<String>,
+        /// Only apply patches to files matching this glob pattern, relative to
+        /// the config file. Can be specified multiple times.
+        #[clap(long = "path")]
+        path_patterns: Vec<String>,
+        /// Choose a specific invocation to take patches from. 0 is the most
+        /// recent run. By default, the most recent lint run is used.
+        #[clap(long, short)]
+        invocation: Option<usize>,
+    },
+
+    /// Re-render the results of a run recorded with `--record`, without
+    /// running any linters.
+    Replay {
+        /// Path to the recording.
+        recording: String,
+    },
+
     /// Show the list of available linters, based on this repo's .lintrunner.toml.
     List,
 
+    /// Print the build targets that own the files that would be linted, one
+    /// per line. Files are selected the same way as for linting.
+    Impacted {
+        /// The build system to ask for owning targets.
+        #[clap(long, arg_enum)]
+        build_system: BuildSystem,
+    },
+
+    /// Find the commit that introduced a finding, by linting past revisions
+    /// in temporary git worktrees with the current config.
+    BisectLint {
+        /// The oldest revision to lint.
+        #[clap(long)]
+        from: String,
+        /// The newest revision to lint.
+        #[clap(long, default_value = "HEAD")]
+        to: String,
+        /// Lint every this many commits, then bisect between the last one
+        /// without the finding and the first one with it.
+        #[clap(long, default_value_t = 10)]
+        step: usize,
+        /// The code of the linter that reports the finding.
+        #[clap(long)]
+        code: String,
+        /// Only look for messages with this name.
+        #[clap(long)]
+        name: Option<String>,
+        /// Only look for messages about this path, relative to the repo root.
+        #[clap(long)]
+        path: Option<String>,
+    },
+
+    /// Inspect the lintrunner config.
+    Config {
+        #[clap(subcommand)]
+        cmd: ConfigSubCommand,
+    },
+
+    /// Inspect past invocations of lintrunner.
+    History {
+        #[clap(subcommand)]
+        cmd: HistorySubCommand,
+    },
+
+    /// Show how lint counts changed over past runs, per linter.
+    Trend {
+        /// Only show these linters.
+        #[clap(long = "code")]
+        codes: Vec<String>,
+        /// Only show the most recent runs.
+        #[clap(long)]
+        last: Option<usize>,
+        #[clap(long, arg_enum, default_value_t = TrendFormat::Text)]
+        format: TrendFormat,
+    },
+
+    /// Go through the findings of the last run one at a time, opening them
+    /// in $EDITOR, applying their fixes or suppressing them.
+    Triage {
+        /// Triage the findings in this file of JSON lint messages (as written
+        /// by --output=json or --tee-json) instead.
+        #[clap(long)]
+        report: Option<String>,
+    },
+
+    /// Trust the configs in use, so that their commands run without asking:
+    /// the repo they're committed in, or the current contents of those that
+    /// aren't committed.
+    Trust {
+        /// Stop trusting them instead.
+        #[clap(long)]
+        revoke: bool,
+    },
+
+    /// Publish the findings of the last run to a code review tool.
+    Publish {
+        #[clap(subcommand)]
+        cmd: PublishSubCommand,
+    },
+
+    /// Manage the finding budgets set in the config.
+    Budget {
+        #[clap(subcommand)]
+        cmd: BudgetSubCommand,
+    },
+
     /// Create a bug report for a past invocation of lintrunner.
     Rage {
         /// Choose a specific invocation to report on. 0 is the most recent run.
         #[clap(long, short)]
         invocation: Option<usize>,
+        /// Report on the most recent run
+        #[clap(long, action, conflicts_with_all = &["invocation", "last-failed", "since"])]
+        last: bool,
+        /// Report on the most recent run that failed
+        #[clap(long, action, conflicts_with_all = &["invocation", "since"])]
+        last_failed: bool,
+        /// Report on every run since this time, given as an RFC 3339 timestamp
+        /// or a YYYY-MM-DD date
+        #[clap(long, conflicts_with = "invocation")]
+        since: Option<String>,
         /// Set to upload the report to github gist (if available)
         #[clap(long, short, action)]
         gist: bool,
         /// Set to upload the report to pastry (if available)
         #[clap(long, short, action)]
         pastry: bool,
+        /// Write a .tar.gz bundle with the report, full linter logs, the
+        /// effective config and environment info, for attaching to issues
+        #[clap(long, action, conflicts_with_all = &["gist", "pastry"])]
+        all: bool,
     },
 }
 
-fn do_main() -> Result<i32> {
-    let args = Args::parse();
+#[derive(Debug, Parser)]
+enum BudgetSubCommand {
+    /// Lower each budget to the number of messages its linter reported in
+    /// the last run. Run `lintrunner --all-files` first.
+    Tighten,
+}
 
-    if args.force_color {
-        console::set_colors_enabled(true);
-        console::set_colors_enabled_stderr(true);
-    }
-    let log_level = match (args.verbose, args.output != RenderOpt::Default) {
+#[derive(Debug, Parser)]
+enum PublishSubCommand {
+    /// Post the findings as a GitHub Check Run on a commit, with an
+    /// annotation per finding and suggested changes for fixes.
+    GithubChecks {
+        /// The environment variable holding the GitHub token. It needs
+        /// permission to write checks.
+        #[clap(long, default_value = "GITHUB_TOKEN")]
+        token_env: String,
+        /// Publish the findings in this file of JSON lint messages (as written
+        /// by --output=json or --tee-json) instead.
+        #[clap(long)]
+        report: Option<String>,
+        /// The name of the check run.
+        #[clap(long, default_value = "lintrunner")]
+        name: String,
+        /// The repo to publish to, as owner/name. Defaults to
+        /// $GITHUB_REPOSITORY.
+        #[clap(long)]
+        repo: Option<String>,
+        /// The commit to attach the check run to. Defaults to $GITHUB_SHA,
+        /// then to HEAD. In pull request workflows, pass the head commit of
+        /// the pull request.
+        #[clap(long)]
+        sha: Option<String>,
+    },
+
+    /// Post the findings as Gerrit robot comments on a patchset, with fix
+    /// suggestions for fixes.
+    Gerrit {
+        /// The base URL of the Gerrit server, like https://review.example.com.
+        #[clap(long)]
+        url: String,
+        /// The change to comment on. Defaults to $GERRIT_CHANGE_NUMBER.
+        #[clap(long)]
+        change: Option<String>,
+        /// The patchset to comment on. Defaults to $GERRIT_PATCHSET_NUMBER.
+        #[clap(long)]
+        patchset: Option<String>,
+        /// The environment variable holding the Gerrit user name.
+        #[clap(long, default_value = "GERRIT_USER")]
+        user_env: String,
+        /// The environment variable holding the user's Gerrit HTTP password.
+        #[clap(long, default_value = "GERRIT_PASSWORD")]
+        password_env: String,
+        /// Publish the findings in this file of JSON lint messages (as written
+        /// by --output=json or --tee-json) instead.
+        #[clap(long)]
+        report: Option<String>,
+        /// The robot id to post the comments as.
+        #[clap(long, default_value = "lintrunner")]
+        robot_id: String,
+    },
+}
+
+#[derive(Debug, Parser)]
+enum HistorySubCommand {
+    /// Print metadata about past runs (timestamp, args, duration, exit code
+    /// and lint counts), most recent first.
+    Export {
+        #[clap(long, arg_enum, default_value_t = HistoryFormat::Json)]
+        format: HistoryFormat,
+    },
+}
+
+#[derive(Debug, Parser)]
+enum ConfigSubCommand {
+    /// Check the config files for mistakes. Errors are what will break
+    /// runs: files that don't parse, duplicate linter codes, invalid
+    /// patterns, init commands without {{DRYRUN}} and missing command
+    /// executables. Warnings are likely mistakes, like patterns that match no
+    /// files or formatters that overlap. Exits non-zero if there are errors.
+    #[clap(alias = "validate")]
+    Check {
+        #[clap(long, arg_enum, default_value_t = CheckFormat::Text)]
+        format: CheckFormat,
+    },
+    /// Record the configuration of linters marked `protected` in
+    /// .lintrunner.lock, to be committed next to the config.
+    Lock,
+    /// Print a JSON Schema of the config format, for editors and CI to
+    /// validate config files with.
+    Schema,
+    /// Write a new config, with linters for the languages in the repo,
+    /// asking which ones to add.
+    New {
+        /// Don't ask; add the linters for the languages found.
+        #[clap(long)]
+        yes: bool,
+
+        /// Overwrite the config if it already exists.
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+fn log_level_for(verbose: u8, output: RenderOpt) -> log::LevelFilter {
+    match (verbose, output != RenderOpt::Default) {
         // Default
         (0, false) => log::LevelFilter::Info,
         // If just json is asked for, suppress most output except hard errors.
@@ -169,34 +714,111 @@ fn do_main() -> Result<i32> {
 
         // Any higher verbosity goes to trace.
         (_, _) => log::LevelFilter::Trace,
+    }
+}
+
+fn do_main() -> Result<i32> {
+    let start = std::time::Instant::now();
+    let args = Args::parse();
+
+    let color = if args.force_color {
+        ColorChoice::Always
+    } else {
+        args.color
     };
+    color.apply();
+    interactive::set_non_interactive(args.non_interactive);
+    tmpdir::init(args.tmpdir.as_deref()).usage_error()?;
+    if args.io_threads == 0 {
+        return Err(UsageError(anyhow!("`--io-threads` must be at least 1.")).into());
+    }
+    io_limit::set_io_threads(args.io_threads);
+    network::set_allow_unsandboxed(args.allow_unsandboxed_network);
+    // Replaying doesn't need a config or a repo, so handle it up front.
+    if let Some(SubCommand::Replay { recording }) = &args.cmd {
+        init_theme(None)?;
+        return do_replay(
+            recording,
+            args.output.unwrap_or_default(),
+            wrap_width(args.no_wrap),
+        );
+    }
+    // Neither does printing the config schema.
+    if let Some(SubCommand::Config {
+        cmd: ConfigSubCommand::Schema,
+    }) = &args.cmd
+    {
+        return do_config_schema();
+    }
+    // Nor does writing a new one, since there isn't one yet.
+    if let Some(SubCommand::Config {
+        cmd: ConfigSubCommand::New { yes, force },
+    }) = &args.cmd
+    {
+        init_theme(None)?;
+        let path = args.configs.split(',').next().unwrap_or_default().trim();
+        return do_config_new(Path::new(path), *yes, *force).usage_error();
+    }
+
+    let log_level = log_level_for(args.verbose, args.output.unwrap_or_default());
 
     let run_info = RunInfo {
         args: std::env::args().collect(),
         timestamp: chrono::Local::now().to_rfc3339_opts(SecondsFormat::Millis, true),
     };
     // clone split by commas and trim whitespace
-    let config_paths: Vec<String> = args
+    let mut config_paths: Vec<String> = args
         .configs
         .split(',')
         .map(|path| path.trim().to_string())
         .collect_vec();
+    // Without --configs, look for the config above the current directory
+    // too, so that lintrunner can be run from anywhere in the repo.
+    let mut found_above = false;
+    if args.configs == DEFAULT_CONFIGS {
+        let cwd = std::env::current_dir()?;
+        if let Some(found) = find_config(&cwd, &config_paths[0]) {
+            // Unwrap ok because the config was found in a directory.
+            let dir = found.parent().unwrap();
+            if dir != cwd {
+                config_paths = config_paths
+                    .iter()
+                    .map(|path| dir.join(path).to_string_lossy().into_owned())
+                    .collect();
+                found_above = true;
+            }
+        }
+    }
     // check if first config path exists
     let primary_config_path = AbsPath::try_from(config_paths[0].clone())
-        .with_context(|| format!("Could not read lintrunner config at: '{}'", config_paths[0]))?;
+        .with_context(|| format!("Could not read lintrunner config at: '{}'", config_paths[0]))
+        .usage_error()?;
 
+    // Unwrap is fine here because we know this path is absolute and won't be `/`
+    let config_dir = primary_config_path.parent().unwrap();
     let persistent_data_store = PersistentDataStore::new(&primary_config_path, run_info)?;
 
-    setup_logger(
-        log_level,
-        &persistent_data_store.log_file(),
-        args.force_color,
-    )?;
+    setup_logger(log_level, &persistent_data_store.log_file())?;
 
     debug!("Version: {VERSION}");
     debug!("Passed args: {:?}", std::env::args());
     debug!("Computed args: {:?}", args);
 
+    if found_above {
+        let resolved_against = match args.relative_to {
+            RelativeTo::Cwd => {
+                "the current directory; pass `--relative-to=config` to resolve them \
+                 against the config's"
+            }
+            RelativeTo::Config => "the config's directory",
+        };
+        info!(
+            "Using the config at {}. Relative paths on the command line are resolved against {}.",
+            primary_config_path.display(),
+            resolved_against
+        );
+    }
+
     // report config paths which do not exist
     for path in &config_paths {
         match AbsPath::try_from(path) {
@@ -210,19 +832,86 @@ fn do_main() -> Result<i32> {
         .filter(|path| Path::new(&path).exists())
         .collect();
     let cmd = args.cmd.unwrap_or(SubCommand::Lint);
-    let lint_runner_config = LintRunnerConfig::new(&config_paths)?;
-    let skipped_linters = args.skip.map(|linters| {
-        linters
-            .split(',')
-            .map(|linter_name| linter_name.to_string())
-            .collect::<HashSet<_>>()
-    });
-    let taken_linters = args.take.map(|linters| {
-        linters
-            .split(',')
-            .map(|linter_name| linter_name.to_string())
-            .collect::<HashSet<_>>()
+    // Checking reports configs that fail to load, so it loads them itself.
+    if let SubCommand::Config {
+        cmd: ConfigSubCommand::Check { format },
+    } = cmd
+    {
+        return do_config_check(&config_paths, format);
+    }
+    let mut lint_runner_config = LintRunnerConfig::new(&config_paths).usage_error()?;
+    init_theme(lint_runner_config.colors.as_ref()).usage_error()?;
+
+    let profile_name = args.profile.clone().or_else(|| {
+        std::env::var(PROFILE_ENV)
+            .ok()
+            .filter(|name| !name.is_empty())
     });
+    let profile = match &profile_name {
+        Some(name) => lint_runner_config
+            .profiles
+            .get(name)
+            .cloned()
+            .with_context(|| {
+                format!(
+                    "No profile named '{}' in the config. Available profiles: {}",
+                    name,
+                    lint_runner_config.profiles.keys().join(", ")
+                )
+            })
+            .usage_error()?,
+        None => Profile::default(),
+    };
+    if let Some(name) = &profile_name {
+        profile
+            .apply(&mut lint_runner_config)
+            .with_context(|| format!("Couldn't apply profile '{}'", name))
+            .usage_error()?;
+    }
+    let output = args.output.or(profile.output).unwrap_or_default();
+    if Some(output) != args.output {
+        log::set_max_level(log_level_for(args.verbose, output));
+    }
+    let jobs = args.jobs.or(profile.jobs);
+    let min_severity = args.min_severity.or(profile.min_severity);
+    let health_cache = Duration::from_secs(
+        60 * lint_runner_config
+            .health_cache_minutes
+            .unwrap_or(health::DEFAULT_HEALTH_CACHE_MINUTES),
+    );
+
+    let skipped_linters = args
+        .skip
+        .map(|linters| {
+            linters
+                .split(',')
+                .map(|linter_name| linter_name.to_string())
+                .collect::<HashSet<_>>()
+        })
+        .or_else(|| profile.skip.map(HashSet::from_iter));
+    let taken_linters = args
+        .take
+        .map(|linters| {
+            linters
+                .split(',')
+                .map(|linter_name| linter_name.to_string())
+                .collect::<HashSet<_>>()
+        })
+        .or_else(|| profile.take.map(HashSet::from_iter));
+    let selection = Selection {
+        taken_linters,
+        skipped_linters,
+        tag_selection: TagSelection {
+            tags: args
+                .tag
+                .map(|tags| tags.split(',').map(|tag| tag.to_string()).collect())
+                .or_else(|| profile.tag.map(HashSet::from_iter)),
+            skip_tags: args
+                .skip_tag
+                .map(|tags| tags.split(',').map(|tag| tag.to_string()).collect())
+                .or_else(|| profile.skip_tag.map(HashSet::from_iter)),
+        },
+    };
 
     // If we are formatting, the universe of linters to select from should be
     // restricted to only formatters.
@@ -231,28 +920,122 @@ fn do_main() -> Result<i32> {
     // but if we want to fix it we should impl Cow for LintConfig and use that
     // instead.).
     let mut placeholder = Vec::new();
-    let all_linters = if let SubCommand::Format = &cmd {
+    let mut invalid_placeholder = Vec::new();
+    let (all_linters, invalid_linters) = if let SubCommand::Format = &cmd {
         let iter = lint_runner_config
             .linters
             .iter()
             .filter(|l| l.is_formatter)
             .cloned();
         placeholder.extend(iter);
-        &placeholder
+        let invalid_iter = lint_runner_config
+            .invalid_linters
+            .iter()
+            .filter(|l| l.is_formatter)
+            .cloned();
+        invalid_placeholder.extend(invalid_iter);
+        (&placeholder, &invalid_placeholder)
     } else {
         // If we're not formatting, all linters defined in the config are
         // eligible to run.
-        &lint_runner_config.linters
+        (
+            &lint_runner_config.linters,
+            &lint_runner_config.invalid_linters,
+        )
     };
 
-    let linters = get_linters_from_configs(
+    // Linters selected by code or tag may be in nested configs instead.
+    let root_selection = if lint_runner_config.nested_configs {
+        selection.restricted_to(all_linters, invalid_linters)
+    } else {
+        selection.clone()
+    };
+    let (mut linters, skip_reasons) = get_linters_from_configs(
         all_linters,
-        skipped_linters,
-        taken_linters,
+        invalid_linters,
+        root_selection.skipped_linters,
+        root_selection.taken_linters,
+        root_selection.tag_selection,
         &primary_config_path,
-    )?;
+        args.strict,
+    )
+    .usage_error()?;
+    let command_wrapper = match &args.wrapper {
+        Some(wrapper) => shell_words::split(wrapper)
+            .context("failed to split --wrapper")
+            .usage_error()?,
+        None => lint_runner_config
+            .command_wrapper
+            .clone()
+            .unwrap_or_default(),
+    };
+    let remote = if args.remote_exec {
+        let config = lint_runner_config
+            .remote_exec
+            .as_ref()
+            .context("--remote-exec needs a [remote_exec] section in the config")
+            .usage_error()?;
+        Some(Arc::new(RemotePool::new(config).usage_error()?))
+    } else {
+        None
+    };
+    // Shared, so each directory's ignore files are only read once.
+    let gitignores = Arc::new(Gitignores::default());
+    for linter in &mut linters {
+        linter.command_wrapper = command_wrapper.clone();
+        linter.remote = remote.clone();
+        if linter
+            .respect_gitignore
+            .unwrap_or(lint_runner_config.respect_gitignore)
+        {
+            linter.gitignores = Some(Arc::clone(&gitignores));
+        }
+    }
+    let nested_configs = if lint_runner_config.nested_configs {
+        Some(NestedConfigs {
+            root_config_path: primary_config_path.clone(),
+            root_codes: all_linters
+                .iter()
+                .map(|linter| linter.code.clone())
+                .chain(invalid_linters.iter().map(|linter| linter.code.clone()))
+                .collect(),
+            selection,
+            formatters_only: matches!(cmd, SubCommand::Format),
+            strict: args.strict,
+            command_wrapper,
+            remote,
+            respect_gitignore: lint_runner_config.respect_gitignore,
+            gitignores,
+        })
+    } else {
+        None
+    };
 
-    let enable_spinners = args.verbose == 0 && args.output == RenderOpt::Default;
+    let time_budget = args
+        .time_budget
+        .as_deref()
+        .map(parse_duration)
+        .transpose()
+        .usage_error()?;
+    let stream_target = args
+        .stream_to
+        .as_deref()
+        .map(StreamTarget::parse)
+        .transpose()
+        .usage_error()?;
+
+    // Spinners redraw lines, so they would garble verbose logging. Plain
+    // progress is just more lines on stderr, so it's fine with either.
+    let progress_opt = match args.progress {
+        _ if output != RenderOpt::Default => ProgressOpt::Hidden,
+        ProgressStyle::Spinners if args.verbose > 0 => ProgressOpt::Hidden,
+        // Nobody watches spinners in CI, and they garble its logs.
+        ProgressStyle::Spinners if !interactive::is_watched() => ProgressOpt::Hidden,
+        ProgressStyle::Spinners => ProgressOpt::Spinners,
+        ProgressStyle::Plain => ProgressOpt::Plain {
+            interval: Duration::from_secs(args.progress_interval.max(1)),
+        },
+    };
 
     let revision_opt = if let Some(revision) = args.revision {
         RevisionOpt::Revision(revision)
@@ -269,80 +1052,350 @@ fn do_main() -> Result<i32> {
         RevisionOpt::Head
     };
 
-    let only_lint_under_config_dir = if lint_runner_config.only_lint_under_config_dir.is_some() {
-        lint_runner_config.only_lint_under_config_dir.unwrap()
-    } else {
-        args.only_lint_under_config_dir
-    };
+    let only_lint_under_config_dir = lint_runner_config
+        .only_lint_under_config_dir
+        .unwrap_or(args.only_lint_under_config_dir);
+
+    let dirs = args
+        .dirs
+        .iter()
+        .map(|dir| {
+            AbsPath::try_from(subdir::resolve(dir, args.relative_to, config_dir))
+                .with_context(|| format!("Failed to find `--dir` '{}'", dir))
+        })
+        .collect::<Result<Vec<_>>>()
+        .usage_error()?;
 
-    let paths_opt = if let Some(paths_file) = args.paths_from {
-        let path_file = AbsPath::try_from(&paths_file)
-            .with_context(|| format!("Failed to find `--paths-from` file '{}'", paths_file))?;
+    let paths_opt = if let Some(spec) = args.paths_provider {
+        PathsOpt::Provider(Registry::with_builtins().create(&spec).usage_error()?)
+    } else if args.paths_from.as_deref() == Some("-") {
+        PathsOpt::Provider(Box::new(path_provider::Stdin))
+    } else if let Some(paths_file) = args.paths_from {
+        let path_file =
+            AbsPath::try_from(subdir::resolve(&paths_file, args.relative_to, config_dir))
+                .with_context(|| format!("Failed to find `--paths-from` file '{}'", paths_file))
+                .usage_error()?;
         PathsOpt::PathsFile(path_file)
+    } else if let Some(diff_file) = args.diff_file {
+        PathsOpt::DiffFile(DiffScope::from_file(&diff_file).usage_error()?)
     } else if let Some(paths_cmd) = args.paths_cmd {
-        PathsOpt::PathsCmd(paths_cmd)
+        PathsOpt::PathsCmd(
+            PathsCmd::from_shell_words(&paths_cmd, args.paths_cmd_nul).usage_error()?,
+        )
+    } else if !args.paths_cmd_arg.is_empty() {
+        PathsOpt::PathsCmd(PathsCmd {
+            argv: args.paths_cmd_arg,
+            nul_delimited: args.paths_cmd_nul,
+        })
     } else if !args.paths.is_empty() {
-        PathsOpt::Paths(args.paths)
+        PathsOpt::Paths(
+            args.paths
+                .iter()
+                .map(|path| subdir::resolve(path, args.relative_to, config_dir))
+                .collect(),
+        )
     } else if args.all_files {
         PathsOpt::AllFiles
     } else {
         PathsOpt::Auto
     };
 
+    let content_rev = if args.staged {
+        Some(String::new())
+    } else {
+        args.contents_at
+    };
+    let content_provider = match content_rev {
+        Some(rev) => {
+            let command = lint_runner_config
+                .content_provider
+                .clone()
+                .unwrap_or_else(|| {
+                    DEFAULT_CONTENT_PROVIDER
+                        .iter()
+                        .map(|arg| arg.to_string())
+                        .collect()
+                });
+            if command.is_empty() {
+                return Err(UsageError(anyhow!("`content_provider` is empty.")).into());
+            }
+            if args.apply_patches || matches!(cmd, SubCommand::Format) {
+                return Err(UsageError(anyhow!(
+                    "Patches can't be applied when linting --staged or --contents-at, \
+                     since they are computed from contents that aren't in the working tree."
+                ))
+                .into());
+            }
+            Some(ContentProvider { command, rev })
+        }
+        None => None,
+    };
+
+    persistent_data_store.write_effective_config(&lint_runner_config)?;
     let res = match cmd {
-        SubCommand::Init { dry_run } => {
+        SubCommand::Init {
+            dry_run,
+            pack,
+            from_archive,
+        } => {
+            check_trusted(&lint_runner_config.files).usage_error()?;
+            for archive in pack.iter().chain(&from_archive) {
+                toolchain::check_archive_type(archive).usage_error()?;
+            }
+            if let Some(archive) = from_archive {
+                return do_init_from_archive(&archive, &persistent_data_store, &config_paths);
+            }
             // Just run initialization commands, don't actually lint.
-            do_init(linters, dry_run, &persistent_data_store, &config_paths)
-        }
-        SubCommand::Format => {
-            check_init_changed(&persistent_data_store, &lint_runner_config)?;
-            do_lint(
+            do_init(
                 linters,
-                paths_opt,
-                true, // always apply patches when we use the format command
-                args.output,
-                enable_spinners,
-                revision_opt,
-                args.tee_json,
-                only_lint_under_config_dir,
+                dry_run,
+                args.offline,
+                pack.as_deref(),
+                &persistent_data_store,
+                &config_paths,
             )
         }
-        SubCommand::Lint => {
+        SubCommand::Format | SubCommand::Lint => {
             // Default command is to just lint.
-            check_init_changed(&persistent_data_store, &lint_runner_config)?;
+            check_trusted(&lint_runner_config.files).usage_error()?;
+            check_init_changed(&persistent_data_store, &lint_runner_config).usage_error()?;
+            check_protected(
+                config_dir,
+                &lint_runner_config.linters,
+                args.enforce_protected,
+            )
+            .usage_error()?;
+            ensure_compdb(
+                &linters,
+                lint_runner_config.compdb.as_ref(),
+                config_dir,
+                &persistent_data_store,
+            )
+            .usage_error()?;
             do_lint(
                 linters,
-                paths_opt,
-                args.apply_patches,
-                args.output,
-                enable_spinners,
-                revision_opt,
-                args.tee_json,
-                only_lint_under_config_dir,
+                LintOptions {
+                    paths_opt,
+                    // Always apply patches when we use the format command, unless
+                    // they should go to a patch file instead.
+                    should_apply_patches: match cmd {
+                        SubCommand::Format => args.patch_file.is_none(),
+                        _ => args.apply_patches,
+                    },
+                    apply_suggested: args.apply_suggested,
+                    render_opt: output,
+                    wrap_width: wrap_width(args.no_wrap),
+                    progress_opt,
+                    revision_opt,
+                    tee_json: args.tee_json,
+                    patch_file: args.patch_file,
+                    only_lint_under_config_dir,
+                    skip_vendored: lint_runner_config.skip_vendored,
+                    ownership_opt: OwnershipOpt {
+                        mine: args.mine,
+                        owner: args.owner.clone(),
+                    },
+                    dirs,
+                    fail_fast: args.fail_fast,
+                    strict_empty: args.strict_empty,
+                    write_manifest: args.write_manifest,
+                    badge: args.badge,
+                    record: args.record,
+                    content_provider,
+                    skip_reasons,
+                    nested_configs,
+                    time_budget,
+                    stream_to: stream_target
+                        .as_ref()
+                        .map(LintStream::connect)
+                        .transpose()
+                        .usage_error()?,
+                    timing: args.timing,
+                    column_unit: args.column_unit,
+                    group_by: args.group_by,
+                    budgets: lint_runner_config.budgets.clone(),
+                    jobs,
+                    min_severity,
+                    health_cache,
+                    rerun_modified: args.rerun_modified,
+                    plan_json: args.plan_json,
+                },
+                &persistent_data_store,
+            )
+        }
+        SubCommand::Fix {
+            only,
+            path_patterns,
+            invocation,
+        } => {
+            let only = only.map(|linters| {
+                linters
+                    .split(',')
+                    .map(|linter_name| linter_name.to_string())
+                    .collect::<HashSet<_>>()
+            });
+            do_fix(
+                &persistent_data_store,
+                invocation,
+                only,
+                &path_patterns,
+                args.apply_suggested,
+                config_dir,
             )
         }
+        SubCommand::Config {
+            cmd: ConfigSubCommand::Lock,
+        } => write_lockfile(config_dir, &lint_runner_config.linters),
+        SubCommand::Config {
+            cmd:
+                ConfigSubCommand::Check { .. } | ConfigSubCommand::Schema | ConfigSubCommand::New { .. },
+        } => unreachable!("handled before loading the config"),
+        SubCommand::History {
+            cmd: HistorySubCommand::Export { format },
+        } => do_history_export(&persistent_data_store, format),
+        SubCommand::Trend {
+            codes,
+            last,
+            format,
+        } => do_trend(&persistent_data_store, &codes, last, format),
+        SubCommand::Triage { report } => {
+            do_triage(&persistent_data_store, report, wrap_width(args.no_wrap))
+        }
+        SubCommand::Trust { revoke } => do_trust(&lint_runner_config.files, revoke),
+        SubCommand::Publish {
+            cmd:
+                PublishSubCommand::GithubChecks {
+                    token_env,
+                    report,
+                    name,
+                    repo,
+                    sha,
+                },
+        } => do_publish_github_checks(
+            &persistent_data_store,
+            GithubChecksOpts {
+                token_env,
+                report,
+                name,
+                repo,
+                sha,
+            },
+            args.offline,
+        ),
+        SubCommand::Publish {
+            cmd:
+                PublishSubCommand::Gerrit {
+                    url,
+                    change,
+                    patchset,
+                    user_env,
+                    password_env,
+                    report,
+                    robot_id,
+                },
+        } => do_publish_gerrit(
+            &persistent_data_store,
+            GerritOpts {
+                url,
+                change,
+                patchset,
+                user_env,
+                password_env,
+                report,
+                robot_id,
+            },
+            args.offline,
+        ),
+        SubCommand::Budget {
+            cmd: BudgetSubCommand::Tighten,
+        } => do_budget_tighten(&persistent_data_store, &config_paths),
         SubCommand::Rage {
             invocation,
+            last,
+            last_failed,
+            since,
             gist,
             pastry,
-        } => do_rage(&persistent_data_store, invocation, gist, pastry),
+            all,
+        } => {
+            let selection = if let Some(invocation) = invocation {
+                RageSelection::Invocation(invocation)
+            } else if last {
+                RageSelection::Invocation(0)
+            } else if last_failed {
+                RageSelection::LastFailed
+            } else if let Some(since) = since {
+                RageSelection::Since(parse_since(&since).usage_error()?)
+            } else {
+                RageSelection::Interactive
+            };
+            do_rage(
+                &persistent_data_store,
+                selection,
+                gist,
+                pastry,
+                all,
+                args.offline,
+            )
+        }
+        SubCommand::Replay { .. } => unreachable!("handled above"),
+        SubCommand::Impacted { build_system } => {
+            let scope_dir = if only_lint_under_config_dir {
+                Some(AbsPath::try_from(config_dir)?)
+            } else {
+                None
+            };
+            do_impacted(
+                paths_opt,
+                revision_opt,
+                lint_scopes(dirs, scope_dir).as_deref(),
+                build_system,
+            )
+        }
+        SubCommand::BisectLint {
+            from,
+            to,
+            step,
+            code,
+            name,
+            path,
+        } => do_bisect_lint(
+            &BisectOpts {
+                from,
+                to,
+                step,
+                finding: FindingFilter { code, name, path },
+            },
+            &config_paths,
+            &lint_runner_config.files,
+        ),
         SubCommand::List => {
             println!("Available linters:");
             for linter in &lint_runner_config.linters {
-                println!("  {}", linter.code);
+                let mut line = format!("  {}", linter.code);
+                if !linter.tags.is_empty() {
+                    line.push_str(&format!(" [{}]", linter.tags.join(", ")));
+                }
+                if let Some(reason) = skip_reasons.get(&linter.code) {
+                    line.push_str(&format!(" (not run: {})", reason));
+                }
+                println!("{}", line);
             }
             Ok(0)
         }
     };
 
+    let duration_ms = Some(start.elapsed().as_millis() as u64);
     let exit_info = match &res {
         Ok(code) => ExitInfo {
             code: *code,
             err: None,
+            duration_ms,
         },
         Err(err) => ExitInfo {
-            code: 1,
+            code: exit_code::for_error(err),
             err: Some(err.to_string()),
+            duration_ms,
         },
     };
 
@@ -353,19 +1406,22 @@ fn do_main() -> Result<i32> {
 }
 
 fn main() {
+    terminal::install();
+    let tmpdir = tmpdir::Guard;
     let code = match do_main() {
         Ok(code) => code,
         Err(err) => {
             print_error(&err)
                 .context("failed to print exit error")
                 .unwrap();
-            1
+            exit_code::for_error(&err)
         }
     };
 
     // Flush the output before exiting, in case there is anything left in the buffers.
     drop(std::io::stdout().flush());
     drop(std::io::stderr().flush());
+    drop(tmpdir);
 
     // exit() abruptly ends the process while running no destructors. We should
     // make sure that nothing is alive before running this.
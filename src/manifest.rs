@@ -0,0 +1,100 @@
+//! A record of exactly which files each linter processed during a run, for
+//! provenance audits and downstream caching layers.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{linter::SkipReason, path::AbsPath};
+
+#[derive(Serialize)]
+struct ManifestEntry {
+    path: String,
+    /// blake3 hash of the contents the linter was given: the file as it was
+    /// when the linter was dispatched, or its provided contents with
+    /// `--staged` or `--contents-at`.
+    blake3: String,
+}
+
+#[derive(Serialize)]
+struct CacheInputEntry {
+    path: String,
+    /// blake3 hash of the file contents, or null if the file doesn't exist.
+    blake3: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Manifest {
+    lintrunner_version: &'static str,
+    /// Map of linter code to the files it processed, in sorted order.
+    linters: BTreeMap<String, Vec<ManifestEntry>>,
+    /// Map of linter code to why that linter didn't run.
+    skipped: BTreeMap<String, SkipReason>,
+    /// Map of linter code to the files outside of `linters` that its results
+    /// depend on (its `cache_inputs`), for linters that declare any.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    cache_inputs: BTreeMap<String, Vec<CacheInputEntry>>,
+}
+
+/// The blake3 hash of the contents of `path`, for the manifest.
+pub fn hash_file(path: &Path) -> Result<String> {
+    let contents = std::fs::read(path)
+        .with_context(|| format!("Failed to hash file for manifest: '{}'", path.display()))?;
+    Ok(blake3::hash(&contents).to_string())
+}
+
+/// Write a JSON manifest to `manifest_path`, given a map of linter code to the
+/// files that linter processed with the hash of their contents at the time
+/// (see [`hash_file`]), of linter code to why it was skipped, and of linter
+/// code to its cache inputs.
+pub fn write_manifest(
+    manifest_path: &str,
+    processed_files: &BTreeMap<String, Vec<(AbsPath, String)>>,
+    skip_reasons: &BTreeMap<String, SkipReason>,
+    cache_inputs: &BTreeMap<String, Vec<PathBuf>>,
+) -> Result<()> {
+    let linters = processed_files
+        .iter()
+        .map(|(code, files)| {
+            let entries = files
+                .iter()
+                .map(|(file, hash)| ManifestEntry {
+                    path: file.to_string_lossy().to_string(),
+                    blake3: hash.clone(),
+                })
+                .collect();
+            (code.clone(), entries)
+        })
+        .collect();
+
+    let cache_inputs = cache_inputs
+        .iter()
+        .filter(|(code, _)| processed_files.contains_key(*code))
+        .map(|(code, paths)| {
+            let entries = paths
+                .iter()
+                .map(|path| CacheInputEntry {
+                    path: path.to_string_lossy().to_string(),
+                    blake3: std::fs::read(path)
+                        .ok()
+                        .map(|contents| blake3::hash(&contents).to_string()),
+                })
+                .collect();
+            (code.clone(), entries)
+        })
+        .collect();
+
+    let manifest = Manifest {
+        lintrunner_version: env!("CARGO_PKG_VERSION"),
+        linters,
+        skipped: skip_reasons.clone(),
+        cache_inputs,
+    };
+    std::fs::write(manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .with_context(|| format!("Failed to write manifest to '{}'", manifest_path))?;
+    Ok(())
+}
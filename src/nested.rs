@@ -0,0 +1,239 @@
+//! Per-directory configs deeper in the tree, for `nested_configs = true`.
+//!
+//! With nested configs on, a config file with the same name as the root one
+//! (usually `.lintrunner.toml`) in a directory under the root config's
+//! directory adds linters that only lint files under that directory, like
+//! nested eslint or clang-format configs. Their patterns and commands are
+//! relative to their own directory. A nested linter with the same code as one
+//! from a config above it takes over the files under its directory, so a
+//! subproject can swap in its own flags. Only linters, and the fragments they
+//! `include`, are read from nested configs.
+//!
+//! Nested configs are found while gathering the paths to lint: each
+//! directory between a linted file and the root config is checked for one.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    convert::TryFrom,
+    ffi::OsString,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::Result;
+use log::{debug, warn};
+
+use crate::{
+    gitignore::Gitignores,
+    lint_config::{
+        get_linters_from_configs, InvalidLintConfig, LintConfig, LintRunnerConfig, TagSelection,
+    },
+    linter::{Linter, SkipReason},
+    path::AbsPath,
+    remote::RemotePool,
+    trust::check_trusted,
+};
+
+/// The `--take`, `--skip`, `--tag` and `--skip-tag` options.
+#[derive(Clone, Default)]
+pub struct Selection {
+    pub taken_linters: Option<HashSet<String>>,
+    pub skipped_linters: Option<HashSet<String>>,
+    pub tag_selection: TagSelection,
+}
+
+impl Selection {
+    /// The selection, minus codes and tags that none of `linters` have, so
+    /// that selecting the linters of another config isn't an error.
+    pub fn restricted_to(
+        &self,
+        linters: &[LintConfig],
+        invalid_linters: &[InvalidLintConfig],
+    ) -> Selection {
+        let codes = linters
+            .iter()
+            .map(|linter| &linter.code)
+            .chain(invalid_linters.iter().map(|linter| &linter.code))
+            .collect::<HashSet<_>>();
+        let tags = linters
+            .iter()
+            .flat_map(|linter| &linter.tags)
+            .collect::<HashSet<_>>();
+        let restrict = |selected: &Option<HashSet<String>>, known: &HashSet<&String>| {
+            selected.as_ref().map(|selected| {
+                selected
+                    .iter()
+                    .filter(|name| known.contains(name))
+                    .cloned()
+                    .collect()
+            })
+        };
+        Selection {
+            taken_linters: restrict(&self.taken_linters, &codes),
+            skipped_linters: restrict(&self.skipped_linters, &codes),
+            tag_selection: TagSelection {
+                tags: restrict(&self.tag_selection.tags, &tags),
+                skip_tags: restrict(&self.tag_selection.skip_tags, &tags),
+            },
+        }
+    }
+}
+
+/// How to pick and set up the linters of nested configs, the same way as
+/// those of the root config.
+pub struct NestedConfigs {
+    /// The root config.
+    pub root_config_path: AbsPath,
+    /// The codes of the root config's linters.
+    pub root_codes: HashSet<String>,
+    pub selection: Selection,
+    /// Only take formatters, for `lintrunner format`.
+    pub formatters_only: bool,
+    pub strict: bool,
+    pub command_wrapper: Vec<String>,
+    pub remote: Option<Arc<RemotePool>>,
+    /// The root config's `respect_gitignore`, for linters without their own.
+    pub respect_gitignore: bool,
+    pub gitignores: Arc<Gitignores>,
+}
+
+impl NestedConfigs {
+    /// The nested configs in the directories between `files` and the root
+    /// config, shallowest first.
+    fn discover(&self, files: &[AbsPath]) -> Vec<PathBuf> {
+        // Unwrap is fine here because we know this path is absolute and won't be `/`
+        let root_dir = self.root_config_path.parent().unwrap();
+        let file_name = self
+            .root_config_path
+            .file_name()
+            .map(OsString::from)
+            .unwrap_or_default();
+        let mut checked = HashSet::new();
+        let mut found = BTreeSet::new();
+        for file in files {
+            for dir in file.ancestors().skip(1) {
+                if dir == root_dir || !dir.starts_with(root_dir) || !checked.insert(dir) {
+                    break;
+                }
+                let config = dir.join(&file_name);
+                if config.is_file() {
+                    found.insert((dir.components().count(), config));
+                }
+            }
+        }
+        found.into_iter().map(|(_, config)| config).collect()
+    }
+
+    /// Add the linters of the nested configs that apply to `files` to
+    /// `linters`, and hand each nested directory's files to the nested
+    /// linters with the same code as a linter above it.
+    pub fn add_linters(
+        &self,
+        files: &[AbsPath],
+        linters: &mut Vec<Linter>,
+        skip_reasons: &mut BTreeMap<String, SkipReason>,
+    ) -> Result<()> {
+        let configs = self.discover(files);
+        debug!("Found nested configs: {:?}", configs);
+
+        let mut known_codes = self.root_codes.clone();
+        let mut files_read = Vec::new();
+        let mut nested_linters = Vec::new();
+        let mut nested_skip_reasons = BTreeMap::new();
+        for path in configs {
+            let config = LintRunnerConfig::new(&vec![path.display().to_string()])?;
+            files_read.extend(config.files.iter().cloned());
+            known_codes.extend(config.linters.iter().map(|linter| linter.code.clone()));
+            known_codes.extend(
+                config
+                    .invalid_linters
+                    .iter()
+                    .map(|linter| linter.code.clone()),
+            );
+
+            let (all_linters, invalid_linters) = if self.formatters_only {
+                (
+                    config
+                        .linters
+                        .iter()
+                        .filter(|linter| linter.is_formatter)
+                        .cloned()
+                        .collect(),
+                    config
+                        .invalid_linters
+                        .iter()
+                        .filter(|linter| linter.is_formatter)
+                        .cloned()
+                        .collect(),
+                )
+            } else {
+                (config.linters, config.invalid_linters)
+            };
+            let selection = self.selection.restricted_to(&all_linters, &invalid_linters);
+            let config_path = AbsPath::try_from(path.as_path())?;
+            let (mut config_linters, skipped) = get_linters_from_configs(
+                &all_linters,
+                &invalid_linters,
+                selection.skipped_linters,
+                selection.taken_linters,
+                selection.tag_selection,
+                &config_path,
+                self.strict,
+            )?;
+            for linter in &mut config_linters {
+                linter.scope = Some(linter.get_config_dir().to_path_buf());
+                linter.command_wrapper = self.command_wrapper.clone();
+                linter.remote = self.remote.clone();
+                if linter.respect_gitignore.unwrap_or(self.respect_gitignore) {
+                    linter.gitignores = Some(Arc::clone(&self.gitignores));
+                }
+            }
+            nested_linters.extend(config_linters);
+            nested_skip_reasons.extend(skipped);
+        }
+        // Nested configs run commands just like the root one.
+        if !files_read.is_empty() {
+            check_trusted(&files_read)?;
+        }
+
+        let selected = [
+            ("--take", &self.selection.taken_linters),
+            ("--skip", &self.selection.skipped_linters),
+        ];
+        for (option, codes) in selected {
+            for code in codes.iter().flatten() {
+                if !known_codes.contains(code) {
+                    warn!(
+                        "Unknown linter specified in {}: {}. It isn't defined in the config, \
+                         or in a nested config of the linted files.",
+                        option, code
+                    );
+                }
+            }
+        }
+
+        linters.extend(nested_linters);
+        for (code, reason) in nested_skip_reasons {
+            skip_reasons.entry(code).or_insert(reason);
+        }
+        // A code only counts as skipped if no config's linter with it runs.
+        skip_reasons.retain(|code, _| !linters.iter().any(|linter| linter.code == *code));
+        // A nested linter takes over the files under its directory from
+        // linters with the same code in configs above it.
+        let scopes = linters
+            .iter()
+            .filter_map(|linter| Some((linter.code.clone(), linter.scope.clone()?)))
+            .collect::<Vec<_>>();
+        for linter in linters.iter_mut() {
+            let config_dir = linter.get_config_dir().to_path_buf();
+            linter.overridden_under = scopes
+                .iter()
+                .filter(|(code, scope)| {
+                    *code == linter.code && *scope != config_dir && scope.starts_with(&config_dir)
+                })
+                .map(|(_, scope)| scope.clone())
+                .collect();
+        }
+        Ok(())
+    }
+}
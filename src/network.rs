@@ -0,0 +1,166 @@
+//! Keeping linters off the network, for their `network` setting.
+//!
+//! A linter can be denied the network while it runs (`network =
+//! 'init-only'`) or always (`network = 'never'`), so that linting a malicious
+//! change can't send anything anywhere. Where the platform allows it, the
+//! linter's commands run in a sandbox without network access: a new network
+//! namespace with `unshare` on Linux, `sandbox-exec` on macOS. Either way,
+//! proxy variables are removed from their environment, and
+//! `LINTRUNNER_NETWORK=none` tells them that the network is off limits.
+//!
+//! Where there is no sandbox, and on remote hosts, the policy can't be
+//! enforced, so those linters fail rather than run with the network, unless
+//! `--allow-unsandboxed-network` says that removing proxy variables will do.
+
+use std::{
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once, OnceLock,
+    },
+};
+
+use anyhow::{bail, Result};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+/// When a linter may use the network.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NetworkPolicy {
+    /// Always.
+    #[default]
+    Allowed,
+    /// Only in its init command, e.g. to install its toolchain.
+    InitOnly,
+    /// Never.
+    Never,
+}
+
+impl NetworkPolicy {
+    /// Whether the linter's init command may use the network.
+    pub fn allows_init(self) -> bool {
+        self != NetworkPolicy::Never
+    }
+
+    /// Whether the linter's command may use the network.
+    pub fn allows_run(self) -> bool {
+        self == NetworkPolicy::Allowed
+    }
+}
+
+/// Removed from the environment of commands that may not use the network.
+const PROXY_VARS: &[&str] = &[
+    "http_proxy",
+    "https_proxy",
+    "ftp_proxy",
+    "all_proxy",
+    "HTTP_PROXY",
+    "HTTPS_PROXY",
+    "FTP_PROXY",
+    "ALL_PROXY",
+];
+
+/// Prefixes that run a command without network access, in order of
+/// preference. The first one that works on this machine is used.
+#[cfg(target_os = "linux")]
+const SANDBOXES: &[&[&str]] = &[
+    &["unshare", "--user", "--map-current-user", "--net", "--"],
+    // For util-linux older than 2.38.
+    &["unshare", "--user", "--map-root-user", "--net", "--"],
+];
+#[cfg(target_os = "macos")]
+const SANDBOXES: &[&[&str]] = &[&[
+    "sandbox-exec",
+    "-p",
+    "(version 1) (allow default) (deny network*)",
+]];
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const SANDBOXES: &[&[&str]] = &[];
+
+fn sandbox_works(prefix: &[&str]) -> bool {
+    Command::new(prefix[0])
+        .args(&prefix[1..])
+        .arg("true")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The prefix that runs a command without network access on this machine,
+/// if any. Probed once per run.
+pub fn sandbox() -> Option<&'static [String]> {
+    static SANDBOX: OnceLock<Option<Vec<String>>> = OnceLock::new();
+    SANDBOX
+        .get_or_init(|| {
+            let sandbox = SANDBOXES
+                .iter()
+                .find(|prefix| sandbox_works(prefix))
+                .map(|prefix| prefix.iter().map(|arg| arg.to_string()).collect());
+            debug!("Network sandbox: {:?}", sandbox);
+            sandbox
+        })
+        .as_deref()
+}
+
+static ALLOW_UNSANDBOXED: AtomicBool = AtomicBool::new(false);
+
+/// Let linters that may not use the network run where that can't be
+/// enforced, for `--allow-unsandboxed-network`. Call once, at startup.
+pub fn set_allow_unsandboxed(allow: bool) {
+    ALLOW_UNSANDBOXED.store(allow, Ordering::SeqCst);
+}
+
+/// Whether linters that may not use the network may run where that can't be
+/// enforced.
+pub fn allows_unsandboxed() -> bool {
+    ALLOW_UNSANDBOXED.load(Ordering::SeqCst)
+}
+
+/// `command`, made to run without network access. Fails if that isn't
+/// possible on this machine, unless unsandboxed runs are allowed.
+pub fn isolate(command: Vec<String>) -> Result<Vec<String>> {
+    match sandbox() {
+        Some(sandbox) => Ok(sandbox.iter().cloned().chain(command).collect()),
+        None if allows_unsandboxed() => {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| {
+                warn!(
+                    "Can't sandbox network access on this machine, so linters that may \
+                     not use the network only have proxy variables removed."
+                )
+            });
+            Ok(command)
+        }
+        None => bail!(
+            "Can't sandbox network access on this machine to keep the linter off the \
+             network. Pass --allow-unsandboxed-network to run it with just its proxy \
+             variables removed."
+        ),
+    }
+}
+
+/// Remove proxy variables from `command`'s environment, and tell it that it
+/// may not use the network.
+pub fn strip_env(command: &mut Command) {
+    for var in PROXY_VARS {
+        command.env_remove(var);
+    }
+    command.env("LINTRUNNER_NETWORK", "none");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policies() {
+        let policy: NetworkPolicy = serde_json::from_str("\"init-only\"").unwrap();
+        assert_eq!(policy, NetworkPolicy::InitOnly);
+        assert!(policy.allows_init() && !policy.allows_run());
+        assert!(!NetworkPolicy::Never.allows_init());
+        assert!(NetworkPolicy::default().allows_run());
+    }
+}
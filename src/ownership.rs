@@ -0,0 +1,194 @@
+//! Restricting the files to lint to those owned by someone, for `--mine` and
+//! `--owner`.
+//!
+//! `--mine` keeps the files where the current user last changed most of the
+//! lines, according to blame. `--owner` keeps the files a CODEOWNERS file
+//! assigns to the given owner.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use log::debug;
+
+use crate::{path::AbsPath, version_control::VersionControl};
+
+/// Where GitHub looks for a CODEOWNERS file, in order.
+const CODEOWNERS_PATHS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Whom to restrict the files to.
+#[derive(Debug, Default)]
+pub struct OwnershipOpt {
+    /// Only files mostly authored by the current user.
+    pub mine: bool,
+    /// Only files CODEOWNERS assigns to this owner.
+    pub owner: Option<String>,
+}
+
+/// The rules of a CODEOWNERS file.
+pub struct CodeOwners {
+    rules: Vec<Rule>,
+}
+
+/// A CODEOWNERS rule. Patterns work like in gitignore.
+struct Rule {
+    pattern: Pattern,
+    /// Whether the pattern only matches directories (it ends with `/`).
+    directory: bool,
+    owners: Vec<String>,
+}
+
+impl Rule {
+    fn new(pattern: &str, owners: Vec<String>) -> Result<Rule> {
+        let anchored = pattern.starts_with('/');
+        let directory = pattern.ends_with('/');
+        let trimmed = pattern.trim_start_matches('/').trim_end_matches('/');
+        // Patterns without a slash in the middle match at any depth.
+        let glob = if anchored || trimmed.contains('/') {
+            trimmed.to_string()
+        } else {
+            format!("**/{}", trimmed)
+        };
+        Ok(Rule {
+            pattern: Pattern::new(&glob)
+                .with_context(|| format!("Invalid CODEOWNERS pattern: '{}'", pattern))?,
+            directory,
+            owners,
+        })
+    }
+
+    /// Whether the rule matches `path`, relative to the repository root. A
+    /// rule matching a directory matches everything under it.
+    fn matches(&self, path: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let mut candidates = path.ancestors().filter(|p| !p.as_os_str().is_empty());
+        if self.directory {
+            candidates.next();
+        }
+        candidates.any(|candidate| self.pattern.matches_path_with(candidate, options))
+    }
+}
+
+impl CodeOwners {
+    pub fn parse(contents: &str) -> Result<CodeOwners> {
+        let mut rules = Vec::new();
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut parts = line.split_whitespace();
+            let pattern = match parts.next() {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            rules.push(Rule::new(pattern, parts.map(str::to_string).collect())?);
+        }
+        Ok(CodeOwners { rules })
+    }
+
+    /// Read the CODEOWNERS file of the repository at `root`, if it has one.
+    pub fn find(root: &Path) -> Result<Option<CodeOwners>> {
+        for path in CODEOWNERS_PATHS {
+            let path = root.join(path);
+            if path.is_file() {
+                debug!("Reading code owners from {}", path.display());
+                let contents = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read '{}'", path.display()))?;
+                return CodeOwners::parse(&contents).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// The owners of `path`, relative to the repository root. The last
+    /// matching rule wins.
+    pub fn owners(&self, path: &Path) -> &[String] {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(path))
+            .map(|rule| rule.owners.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether `owner` is one of `owners`. The leading `@` of users and teams is
+/// optional.
+fn is_owner(owners: &[String], owner: &str) -> bool {
+    let owner = owner.trim_start_matches('@');
+    owners
+        .iter()
+        .any(|candidate| candidate.trim_start_matches('@') == owner)
+}
+
+/// Keep only the files owned as `opt` asks.
+pub fn retain_owned(
+    files: &mut Vec<AbsPath>,
+    repo: &dyn VersionControl,
+    opt: &OwnershipOpt,
+) -> Result<()> {
+    let root = repo.get_root();
+    if let Some(owner) = &opt.owner {
+        let codeowners = CodeOwners::find(root)?.with_context(|| {
+            format!(
+                "--owner needs a CODEOWNERS file, but none was found in {}",
+                root.display()
+            )
+        })?;
+        files.retain(|file| {
+            file.strip_prefix(root)
+                .is_ok_and(|path| is_owner(codeowners.owners(path), owner))
+        });
+    }
+    if opt.mine {
+        let user = repo.get_user()?;
+        let mut mine = Vec::new();
+        for file in files.drain(..) {
+            // E.g. files that were never committed, which blame can't handle.
+            let authors = match repo.get_line_authors(&file, &user) {
+                Ok(authors) => authors,
+                Err(err) => {
+                    debug!("Couldn't blame {}: {}", file.display(), err);
+                    continue;
+                }
+            };
+            let total = authors.values().sum::<usize>();
+            let by_user = authors.get(&user).copied().unwrap_or_default();
+            if total > 0 && by_user * 2 > total {
+                mine.push(file);
+            }
+        }
+        *files = mine;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codeowners_last_match_wins() -> Result<()> {
+        let codeowners = CodeOwners::parse(
+            "\
+# Default owners
+*       @org/everyone
+*.rs    @org/rust   # Rust code
+/docs/  docs@example.com
+src/lint_config.rs @alice
+",
+        )?;
+        let owners = |path: &str| codeowners.owners(Path::new(path)).to_vec();
+        assert_eq!(owners("README.md"), vec!["@org/everyone"]);
+        assert_eq!(owners("src/lib.rs"), vec!["@org/rust"]);
+        assert_eq!(owners("src/lint_config.rs"), vec!["@alice"]);
+        assert_eq!(owners("docs/guide/intro.md"), vec!["docs@example.com"]);
+        // Anchored to the root.
+        assert_eq!(owners("src/docs/x.md"), vec!["@org/everyone"]);
+
+        assert!(is_owner(&owners("src/lib.rs"), "org/rust"));
+        assert!(!is_owner(&owners("src/lib.rs"), "@org/everyone"));
+        Ok(())
+    }
+}
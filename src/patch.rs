@@ -0,0 +1,270 @@
+//! Structured descriptions of the patches suggested by linters.
+//!
+//! Linters suggest fixes by providing the full original and replacement text
+//! of a file. That is easy for adapters to produce, but external tools that
+//! want to apply fixes (e.g. bots applying them server-side) need something
+//! they can verify and apply without re-deriving positions themselves.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use similar::{DiffTag, DiffableStr, TextDiff};
+
+use crate::{
+    apply::conflicting_patches_error, lint_message::LintMessage, path::path_relative_from,
+};
+
+/// A single edit, replacing the bytes `start..end` of the original text with
+/// `text`.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ByteEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Metadata describing how to get from a message's `original` to its
+/// `replacement`.
+#[derive(Debug, Serialize)]
+pub struct PatchMetadata {
+    /// blake3 hash of the original text. Consumers should check that the file
+    /// still hashes to this value before applying any edits.
+    pub original_blake3: String,
+
+    /// blake3 hash of the replacement text, which the file should hash to
+    /// after applying all edits.
+    pub replacement_blake3: String,
+
+    /// Non-overlapping edits against the original text, in ascending order.
+    pub edits: Vec<ByteEdit>,
+
+    /// The patch as a unified diff.
+    pub unified_diff: String,
+}
+
+impl PatchMetadata {
+    pub fn new(path: &str, original: &str, replacement: &str) -> PatchMetadata {
+        let diff = TextDiff::from_lines(original, replacement);
+        PatchMetadata {
+            original_blake3: blake3::hash(original.as_bytes()).to_string(),
+            replacement_blake3: blake3::hash(replacement.as_bytes()).to_string(),
+            edits: byte_edits(original, replacement),
+            unified_diff: diff
+                .unified_diff()
+                .context_radius(3)
+                .header(&format!("a/{path}"), &format!("b/{path}"))
+                .to_string(),
+        }
+    }
+}
+
+/// Compute line-granular edits that turn `original` into `replacement`: the
+/// 0-based range of lines of `original` each replaces, and the text to put in
+/// their place.
+pub fn line_edits(original: &str, replacement: &str) -> Vec<(Range<usize>, String)> {
+    let diff = TextDiff::from_lines(original, replacement);
+    let new_lines = diff.new_slices();
+    diff.ops()
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| (op.old_range(), new_lines[op.new_range()].concat()))
+        .collect()
+}
+
+/// Compute line-granular edits, expressed as byte offsets into `original`.
+pub fn byte_edits(original: &str, replacement: &str) -> Vec<ByteEdit> {
+    // line_offsets[i] is the byte offset at which line i of the original
+    // starts, with lines split the same way as for diffing.
+    let mut line_offsets = vec![0];
+    let mut offset = 0;
+    for line in original.tokenize_lines() {
+        offset += line.len();
+        line_offsets.push(offset);
+    }
+
+    line_edits(original, replacement)
+        .into_iter()
+        .map(|(lines, text)| ByteEdit {
+            start: line_offsets[lines.start],
+            end: line_offsets[lines.end],
+            text,
+        })
+        .collect()
+}
+
+/// The lines a fix changes: a non-empty range of lines of `original`, and
+/// the range of lines of `replacement` to put in their place, both 0-based.
+/// Pure insertions are anchored to the line before them (or after, at the
+/// start of the file), since review tools replace at least one line.
+pub fn changed_lines(original: &str, replacement: &str) -> Option<(Range<usize>, Range<usize>)> {
+    let diff = TextDiff::from_lines(original, replacement);
+    let ops = diff.ops();
+    let first = ops.iter().find(|op| op.tag() != DiffTag::Equal)?;
+    let last = ops.iter().rev().find(|op| op.tag() != DiffTag::Equal)?;
+    let (mut old_start, mut old_end) = (first.old_range().start, last.old_range().end);
+    let (mut new_start, mut new_end) = (first.new_range().start, last.new_range().end);
+    if old_start == old_end {
+        if old_start > 0 {
+            old_start -= 1;
+            new_start -= 1;
+        } else if original.lines().next().is_some() {
+            old_end += 1;
+            new_end += 1;
+        } else {
+            return None;
+        }
+    }
+    Some((old_start..old_end, new_start..new_end))
+}
+
+/// The lines of `text` in `range`, with their line endings.
+pub fn lines_of(text: &str, range: Range<usize>) -> String {
+    text.split_inclusive('\n')
+        .skip(range.start)
+        .take(range.len())
+        .collect()
+}
+
+/// Combine the fixes in `lint_messages` into a single unified diff that can
+/// be applied with `git apply` from `base_dir`.
+pub fn consolidated_patch(
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+    base_dir: &Path,
+    apply_suggested: bool,
+) -> Result<String> {
+    // Keyed by the path in the diff, so the patch is in a stable order.
+    let mut diffs = BTreeMap::new();
+    for lint_message in lint_messages.values().flatten() {
+        if !lint_message.should_apply_fix(apply_suggested) {
+            continue;
+        }
+        let (path, replacement) = match (&lint_message.path, &lint_message.replacement) {
+            (Some(path), Some(replacement)) => (path, replacement),
+            _ => continue,
+        };
+        let original = match &lint_message.original {
+            Some(original) => original.clone(),
+            None => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read file: '{}'", path))?,
+        };
+        let abs_path = std::fs::canonicalize(path)
+            .with_context(|| format!("Failed to find file: '{}'", path))?;
+        let diff_path = path_relative_from(&abs_path, base_dir)
+            .unwrap_or(abs_path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if diffs.contains_key(&diff_path) {
+            return Err(conflicting_patches_error(&diff_path));
+        }
+        let diff = PatchMetadata::new(&diff_path, &original, replacement).unified_diff;
+        diffs.insert(diff_path, diff);
+    }
+    Ok(diffs.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint_message::LintSeverity;
+
+    fn apply(original: &str, edits: &[ByteEdit]) -> String {
+        let mut result = original.to_string();
+        // Apply back to front so earlier offsets stay valid.
+        for edit in edits.iter().rev() {
+            result.replace_range(edit.start..edit.end, &edit.text);
+        }
+        result
+    }
+
+    #[test]
+    fn edits_reproduce_replacement() {
+        let original = "foo  \nbar\nbaz\nqux  \n";
+        let replacement = "foo\nbar\nbaz\nqux\nnew\n";
+        let edits = byte_edits(original, replacement);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            edits[0],
+            ByteEdit {
+                start: 0,
+                end: 6,
+                text: "foo\n".to_string()
+            }
+        );
+        assert_eq!(apply(original, &edits), replacement);
+    }
+
+    #[test]
+    fn edits_handle_missing_trailing_newline() {
+        let original = "a\nb";
+        let replacement = "a\nc\n";
+        assert_eq!(
+            apply(original, &byte_edits(original, replacement)),
+            replacement
+        );
+    }
+
+    #[test]
+    fn consolidated_patch_is_sorted_and_relative() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let base_dir = std::fs::canonicalize(dir.path())?;
+        let mut lint_messages = HashMap::new();
+        for name in ["b.txt", "a.txt"] {
+            let path = base_dir.join(name);
+            std::fs::write(&path, "old\n")?;
+            let path = path.to_str().unwrap().to_string();
+            lint_messages.insert(
+                Some(path.clone()),
+                vec![LintMessage {
+                    path: Some(path),
+                    original: Some("old\n".to_string()),
+                    replacement: Some("new\n".to_string()),
+                    ..LintMessage::new("TEST", LintSeverity::Warning, "test")
+                }],
+            );
+        }
+
+        let patch = consolidated_patch(&lint_messages, &base_dir, false)?;
+        assert_eq!(
+            patch,
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-old\n+new\n\
+             --- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-old\n+new\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn consolidated_patch_rejects_conflicting_fixes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let base_dir = std::fs::canonicalize(dir.path())?;
+        let path = base_dir.join("a.txt");
+        std::fs::write(&path, "old\n")?;
+        let path = path.to_str().unwrap().to_string();
+        let messages = ["FIRST", "SECOND"]
+            .iter()
+            .map(|code| LintMessage {
+                path: Some(path.clone()),
+                original: Some("old\n".to_string()),
+                replacement: Some(format!("{}\n", code)),
+                ..LintMessage::new(code, LintSeverity::Warning, "test")
+            })
+            .collect();
+        let lint_messages = HashMap::from([(Some(path), messages)]);
+
+        let err = consolidated_patch(&lint_messages, &base_dir, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            conflicting_patches_error("a.txt").to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn no_edits_for_identical_text() {
+        assert!(byte_edits("same\n", "same\n").is_empty());
+    }
+}
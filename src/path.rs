@@ -1,4 +1,5 @@
 use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::{
     convert::TryFrom,
     fmt,
@@ -65,6 +66,37 @@ impl TryFrom<&str> for AbsPath {
     }
 }
 
+impl AbsPath {
+    /// Like `AbsPath::try_from`, but a symlink whose target doesn't exist is
+    /// kept (with its parent directory canonicalized) instead of being an
+    /// error, so that it can be reported by the `broken_symlinks` builtin.
+    pub fn try_from_allowing_dangling(p: impl AsRef<Path>) -> Result<AbsPath> {
+        let p = p.as_ref();
+        match p.canonicalize() {
+            Ok(inner) => Ok(AbsPath { inner }),
+            Err(err) => {
+                let is_symlink = p
+                    .symlink_metadata()
+                    .map(|metadata| metadata.file_type().is_symlink())
+                    .unwrap_or(false);
+                match (is_symlink, p.parent(), p.file_name()) {
+                    (true, Some(parent), Some(name)) => {
+                        let parent = if parent.as_os_str().is_empty() {
+                            Path::new(".")
+                        } else {
+                            parent
+                        };
+                        Ok(AbsPath {
+                            inner: parent.canonicalize()?.join(name),
+                        })
+                    }
+                    _ => Err(err.into()),
+                }
+            }
+        }
+    }
+}
+
 impl Deref for AbsPath {
     type Target = Path;
 
@@ -79,6 +111,21 @@ impl AsRef<Path> for AbsPath {
     }
 }
 
+impl Serialize for AbsPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.inner.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AbsPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = PathBuf::deserialize(deserializer)?;
+        AbsPath::try_from_allowing_dangling(&path).map_err(|err| {
+            serde::de::Error::custom(format!("invalid path '{}': {}", path.display(), err))
+        })
+    }
+}
+
 // This routine is adapted from the *old* Path's `path_relative_from`
 // function, which works differently from the new `relative_from` function.
 // In particular, this handles the case on unix where both paths are
@@ -0,0 +1,469 @@
+//! Where the set of files to work on comes from.
+//!
+//! Each way of picking files, like asking version control what changed,
+//! reading `--paths-from` or running `--paths-cmd`, is a [`PathProvider`].
+//! The engine only sees the files a provider returns, so a new source of
+//! files, for a library user or a future subcommand, is a new provider rather
+//! than a change to linting. Providers can also be picked by name from a
+//! [`Registry`], as `--paths-provider=NAME[:ARG]` does.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ArgEnum;
+use log::{debug, warn};
+
+use crate::{
+    diff_file::DiffScope,
+    get_paths_from_file, get_paths_from_input,
+    git::{self, get_paths_from_cmd, PathsCmd},
+    impacted::BuildSystem,
+    path::AbsPath,
+    pathsfile::ChangeMetadata,
+    version_control::VersionControl,
+    RevisionOpt, SelectedFiles,
+};
+
+/// What a provider has to work with.
+pub struct ProviderContext<'a> {
+    pub repo: &'a dyn VersionControl,
+    /// If set, only files under these directories are wanted. Providers may
+    /// use this to avoid listing other files, but needn't: the files they
+    /// return are restricted to the scopes afterwards either way.
+    pub scopes: Option<&'a [AbsPath]>,
+    /// Whether to find out how the files changed, for providers that know.
+    pub with_change_metadata: bool,
+}
+
+impl ProviderContext<'_> {
+    /// The repos checked out inside `repo` that the scopes reach into, and the
+    /// ones inside those. `repo` doesn't track their files, so providers that
+    /// ask version control have to ask each of them too.
+    pub fn nested_repos(&self) -> Vec<git::Repo> {
+        nested_repos(self.repo, self.scopes)
+    }
+}
+
+fn nested_repos(repo: &dyn VersionControl, scopes: Option<&[AbsPath]>) -> Vec<git::Repo> {
+    let roots = repo.get_nested_roots().unwrap_or_else(|err| {
+        debug!("Couldn't look for nested repos: {:#}", err);
+        Vec::new()
+    });
+    let mut nested = Vec::new();
+    for root in roots {
+        let in_scope = scopes.is_none_or(|scopes| {
+            scopes
+                .iter()
+                .any(|scope| root.starts_with(scope) || scope.starts_with(&root))
+        });
+        if !in_scope {
+            continue;
+        }
+        match git::Repo::at(&root) {
+            Ok(inner) => {
+                debug!("Found nested repo '{}'", root.display());
+                let deeper = nested_repos(&inner, scopes);
+                nested.push(inner);
+                nested.extend(deeper);
+            }
+            Err(err) => debug!("Skipping nested repo '{}': {:#}", root.display(), err),
+        }
+    }
+    nested
+}
+
+/// A source of files to work on.
+pub trait PathProvider {
+    /// The name of the provider, for logs.
+    fn name(&self) -> &str;
+
+    /// Whether the files are the ones changed relative to some revision, so
+    /// that linters whose config files are among them should lint everything.
+    fn lists_changes(&self) -> bool {
+        false
+    }
+
+    /// The files to work on. They needn't be sorted or deduplicated.
+    fn provide(self: Box<Self>, context: &ProviderContext) -> Result<SelectedFiles>;
+}
+
+fn just_files(files: Vec<AbsPath>) -> SelectedFiles {
+    SelectedFiles {
+        files,
+        diff_scope: None,
+        change_metadata: ChangeMetadata::new(),
+    }
+}
+
+/// The files version control says changed since a revision, plus those
+/// changed in the working tree.
+pub struct ChangedFiles(pub RevisionOpt);
+
+impl PathProvider for ChangedFiles {
+    fn name(&self) -> &str {
+        "changed"
+    }
+
+    fn lists_changes(&self) -> bool {
+        true
+    }
+
+    fn provide(self: Box<Self>, context: &ProviderContext) -> Result<SelectedFiles> {
+        let relative_to = self.relative_to(context.repo)?;
+        debug!("Relative to: {:?}", relative_to);
+        let mut selected = changed_in(
+            context.repo,
+            relative_to.as_deref(),
+            context.with_change_metadata,
+        )?;
+        for nested in context.nested_repos() {
+            let root = nested.get_root().display();
+            let changed = self
+                .relative_to(&nested)
+                .and_then(|relative_to| {
+                    debug!("Relative to in '{}': {:?}", root, relative_to);
+                    changed_in(
+                        &nested,
+                        relative_to.as_deref(),
+                        context.with_change_metadata,
+                    )
+                })
+                .or_else(|err| {
+                    if matches!(self.0, RevisionOpt::Head) {
+                        return Err(err);
+                    }
+                    // The revision is usually one of the outer repo's, which
+                    // means nothing here.
+                    warn!(
+                        "Only linting changes since HEAD in nested repo '{}': {:#}",
+                        root, err
+                    );
+                    changed_in(&nested, None, context.with_change_metadata)
+                });
+            match changed {
+                Ok(changed) => {
+                    selected.files.extend(changed.files);
+                    selected.change_metadata.extend(changed.change_metadata);
+                }
+                Err(err) => warn!("Skipping nested repo '{}': {:#}", root, err),
+            }
+        }
+        Ok(selected)
+    }
+}
+
+impl ChangedFiles {
+    /// The revision of `repo` the changes are relative to, or `None` for HEAD.
+    fn relative_to(&self, repo: &dyn VersionControl) -> Result<Option<String>> {
+        Ok(match &self.0 {
+            RevisionOpt::Head => None,
+            RevisionOpt::Revision(revision) => Some(revision.clone()),
+            RevisionOpt::MergeBaseWith(merge_base_with) => {
+                Some(repo.get_merge_base_with(merge_base_with)?)
+            }
+        })
+    }
+}
+
+/// The files changed in `repo` since `relative_to`.
+fn changed_in(
+    repo: &dyn VersionControl,
+    relative_to: Option<&str>,
+    with_change_metadata: bool,
+) -> Result<SelectedFiles> {
+    let mut selected = just_files(repo.get_changed_files(relative_to)?);
+    if with_change_metadata {
+        selected.change_metadata = repo.get_change_metadata(relative_to).unwrap_or_else(|err| {
+            debug!("Couldn't get change metadata: {:#}", err);
+            ChangeMetadata::new()
+        });
+    }
+    Ok(selected)
+}
+
+/// All files tracked by version control.
+pub struct AllFiles;
+
+impl PathProvider for AllFiles {
+    fn name(&self) -> &str {
+        "all"
+    }
+
+    fn provide(self: Box<Self>, context: &ProviderContext) -> Result<SelectedFiles> {
+        let mut files = match context.scopes {
+            Some(scopes) => {
+                let mut files = Vec::new();
+                for scope in scopes {
+                    files.extend(context.repo.get_all_files(Some(scope))?);
+                }
+                files
+            }
+            None => context.repo.get_all_files(None)?,
+        };
+        for nested in context.nested_repos() {
+            match nested.get_all_files(None) {
+                Ok(more) => files.extend(more),
+                Err(err) => warn!(
+                    "Skipping nested repo '{}': {:#}",
+                    nested.get_root().display(),
+                    err
+                ),
+            }
+        }
+        Ok(just_files(files))
+    }
+}
+
+/// Paths given explicitly, e.g. on the command line.
+pub struct PathList(pub Vec<String>);
+
+impl PathProvider for PathList {
+    fn name(&self) -> &str {
+        "paths"
+    }
+
+    fn provide(self: Box<Self>, _context: &ProviderContext) -> Result<SelectedFiles> {
+        Ok(just_files(get_paths_from_input(self.0)?))
+    }
+}
+
+/// The paths listed in a file, one per line.
+pub struct PathsFile(pub AbsPath);
+
+impl PathProvider for PathsFile {
+    fn name(&self) -> &str {
+        "paths-file"
+    }
+
+    fn provide(self: Box<Self>, _context: &ProviderContext) -> Result<SelectedFiles> {
+        Ok(just_files(get_paths_from_file(self.0)?))
+    }
+}
+
+/// The paths read from stdin, one per line.
+pub struct Stdin;
+
+impl PathProvider for Stdin {
+    fn name(&self) -> &str {
+        "stdin"
+    }
+
+    fn provide(self: Box<Self>, _context: &ProviderContext) -> Result<SelectedFiles> {
+        let mut input = String::new();
+        std::io::stdin()
+            .read_to_string(&mut input)
+            .context("Failed to read paths from stdin")?;
+        let paths = input.lines().map(str::to_string).collect::<Vec<_>>();
+        Ok(just_files(get_paths_from_input(paths)?))
+    }
+}
+
+/// The paths printed by a command.
+pub struct CommandOutput(pub PathsCmd);
+
+impl PathProvider for CommandOutput {
+    fn name(&self) -> &str {
+        "paths-cmd"
+    }
+
+    fn provide(self: Box<Self>, _context: &ProviderContext) -> Result<SelectedFiles> {
+        Ok(just_files(get_paths_from_cmd(&self.0)?))
+    }
+}
+
+/// The files changed by a diff. Only lints on the lines it changes are
+/// reported.
+pub struct DiffFile(pub DiffScope);
+
+impl PathProvider for DiffFile {
+    fn name(&self) -> &str {
+        "diff-file"
+    }
+
+    fn provide(self: Box<Self>, context: &ProviderContext) -> Result<SelectedFiles> {
+        let scope = self.0;
+        let change_metadata = if context.with_change_metadata {
+            scope.change_metadata()
+        } else {
+            ChangeMetadata::new()
+        };
+        Ok(SelectedFiles {
+            files: scope.files(),
+            diff_scope: Some(scope),
+            change_metadata,
+        })
+    }
+}
+
+/// The source files of build targets, as the build system reports them.
+/// Generated sources, and sources in other repositories, are left out.
+pub struct BuildTargets {
+    pub build_system: BuildSystem,
+    pub targets: Vec<String>,
+}
+
+impl PathProvider for BuildTargets {
+    fn name(&self) -> &str {
+        "build-target"
+    }
+
+    fn provide(self: Box<Self>, _context: &ProviderContext) -> Result<SelectedFiles> {
+        let root = self.build_system.workspace_root()?;
+        let mut files = Vec::new();
+        for source in self.build_system.sources(&root, &self.targets)? {
+            let path = root.join(&source);
+            if path.is_file() {
+                files.push(AbsPath::try_from(path)?);
+            } else {
+                debug!("Skipping '{}', which isn't a file", source);
+            }
+        }
+        Ok(just_files(files))
+    }
+}
+
+/// The path of the file a bazel label like `//pkg:file.py` names, relative to
+/// the workspace root, or `None` for labels in other repositories.
+pub(crate) fn bazel_label_path(label: &str) -> Option<PathBuf> {
+    let label = label
+        .strip_prefix("@@//")
+        .or_else(|| label.strip_prefix("@//"))
+        .or_else(|| label.strip_prefix("//"))?;
+    let (package, name) = label.split_once(':')?;
+    Some(Path::new(package).join(name))
+}
+
+/// Makes a provider from the argument after the `:` in
+/// `--paths-provider=NAME:ARG`, or an empty string if there's none.
+pub type ProviderFactory = Box<dyn Fn(&str) -> Result<Box<dyn PathProvider>>>;
+
+/// Path providers by name.
+#[derive(Default)]
+pub struct Registry {
+    factories: BTreeMap<String, ProviderFactory>,
+}
+
+impl Registry {
+    /// A registry with lintrunner's own providers.
+    pub fn with_builtins() -> Registry {
+        let mut registry = Registry::default();
+        registry.register("changed", |arg| {
+            Ok(Box::new(ChangedFiles(if arg.is_empty() {
+                RevisionOpt::Head
+            } else {
+                RevisionOpt::Revision(arg.to_string())
+            })))
+        });
+        registry.register("all", |_| Ok(Box::new(AllFiles)));
+        registry.register("paths-file", |arg| {
+            let path = AbsPath::try_from(arg)
+                .with_context(|| format!("Failed to find paths file '{}'", arg))?;
+            Ok(Box::new(PathsFile(path)))
+        });
+        registry.register("stdin", |_| Ok(Box::new(Stdin)));
+        registry.register("paths-cmd", |arg| {
+            Ok(Box::new(CommandOutput(PathsCmd::from_shell_words(
+                arg, false,
+            )?)))
+        });
+        registry.register("diff-file", |arg| {
+            Ok(Box::new(DiffFile(DiffScope::from_file(arg)?)))
+        });
+        registry.register("build-target", |arg| {
+            let (build_system, targets) = arg.split_once(':').ok_or_else(|| {
+                anyhow!("Expected `build-target:BUILD_SYSTEM:TARGETS`, e.g. `build-target:bazel://src/...`")
+            })?;
+            let build_system = BuildSystem::from_str(build_system, true)
+                .map_err(|err| anyhow!("Unknown build system '{}': {}", build_system, err))?;
+            Ok(Box::new(BuildTargets {
+                build_system,
+                targets: targets.split_whitespace().map(str::to_string).collect(),
+            }))
+        });
+        registry
+    }
+
+    /// Add a provider, replacing any with the same name.
+    pub fn register(
+        &mut self,
+        name: &str,
+        factory: impl Fn(&str) -> Result<Box<dyn PathProvider>> + 'static,
+    ) {
+        self.factories.insert(name.to_string(), Box::new(factory));
+    }
+
+    /// The names of the registered providers, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.factories.keys().map(String::as_str)
+    }
+
+    /// Make the provider `spec` names, as `NAME` or `NAME:ARG`.
+    pub fn create(&self, spec: &str) -> Result<Box<dyn PathProvider>> {
+        let (name, arg) = spec.split_once(':').unwrap_or((spec, ""));
+        match self.factories.get(name) {
+            Some(factory) => factory(arg)
+                .with_context(|| format!("Invalid arguments for path provider '{}'", name)),
+            None => bail!(
+                "Unknown path provider '{}'. Known providers: {}",
+                name,
+                self.names().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bazel_labels_are_workspace_paths() {
+        assert_eq!(
+            bazel_label_path("//src/lib:util.py"),
+            Some(PathBuf::from("src/lib/util.py"))
+        );
+        assert_eq!(
+            bazel_label_path("//:setup.py"),
+            Some(PathBuf::from("setup.py"))
+        );
+        assert_eq!(
+            bazel_label_path("@//src:main.cc"),
+            Some(PathBuf::from("src/main.cc"))
+        );
+        assert_eq!(bazel_label_path("@rules_python//python:defs.bzl"), None);
+    }
+
+    struct Fixed;
+
+    impl PathProvider for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn provide(self: Box<Self>, _context: &ProviderContext) -> Result<SelectedFiles> {
+            Ok(just_files(Vec::new()))
+        }
+    }
+
+    #[test]
+    fn registry_creates_providers_by_name() {
+        let mut registry = Registry::with_builtins();
+        registry.register("fixed", |_| Ok(Box::new(Fixed)));
+        assert!(registry.names().any(|name| name == "fixed"));
+
+        assert_eq!(registry.create("fixed").unwrap().name(), "fixed");
+        let changed = registry.create("changed:origin/main").unwrap();
+        assert_eq!(changed.name(), "changed");
+        assert!(changed.lists_changes());
+        assert!(registry.create("build-target:make://all").is_err());
+        let err = registry.create("nope").err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("Known providers: all, build-target"));
+    }
+}
@@ -0,0 +1,162 @@
+//! Writing the `{{PATHSFILE}}` passed to linters.
+//!
+//! By default it lists one path per line. Linters can opt into a JSON format
+//! instead (`pathsfile_format = 'json'`), which also says how each file
+//! changed, so that adapters can skip work without asking the VCS themselves:
+//!
+//! ```json
+//! {
+//!   "version": 2,
+//!   "files": [
+//!     {
+//!       "path": "/repo/src/lib.rs",
+//!       "change": "modified",
+//!       "old_path": "/repo/src/old.rs",
+//!       "changed_lines": [[3, 5], [10, 10]],
+//!       "hash": "af1349b9..."
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `change`, `old_path` and `changed_lines` are only known when linting
+//! changed files (the default, or with `--revision`/`--merge-base-with`), and
+//! are `null` otherwise. `changed_lines` are inclusive, 1-based ranges of
+//! lines added or changed. `hash` is the BLAKE3 hash of the file's contents.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::Write,
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{io_limit, path::AbsPath};
+
+const JSON_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathsFileFormat {
+    /// One path per line.
+    #[default]
+    Lines,
+    /// A JSON object with metadata for each path.
+    Json,
+}
+
+impl PathsFileFormat {
+    pub fn is_lines(&self) -> bool {
+        *self == PathsFileFormat::Lines
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    /// Modified, or renamed (in which case `old_path` is set).
+    Modified,
+    Deleted,
+}
+
+/// How a file changed, as reported by the VCS.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangeInfo {
+    pub kind: ChangeKind,
+    /// For renamed files, the absolute path the file used to be at.
+    pub old_path: Option<String>,
+    /// Lines added or changed.
+    pub changed_lines: BTreeSet<usize>,
+}
+
+/// How each changed file changed.
+pub type ChangeMetadata = HashMap<AbsPath, ChangeInfo>;
+
+#[derive(Serialize)]
+struct JsonPathsFile<'a> {
+    version: u32,
+    files: Vec<JsonEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    path: &'a str,
+    change: Option<ChangeKind>,
+    old_path: Option<String>,
+    changed_lines: Option<Vec<(usize, usize)>>,
+    hash: Option<String>,
+}
+
+/// Collapse line numbers into inclusive ranges of consecutive lines.
+fn line_ranges(lines: &BTreeSet<usize>) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &line in lines {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == line => *end = line,
+            _ => ranges.push((line, line)),
+        }
+    }
+    ranges
+}
+
+fn path_str(path: &AbsPath) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| anyhow!("Could not convert path to string."))
+}
+
+/// Write `paths` to `out` in `format`. `metadata` is only used by the JSON
+/// format.
+pub fn write_paths_file(
+    out: &mut impl Write,
+    format: PathsFileFormat,
+    paths: &[AbsPath],
+    metadata: &ChangeMetadata,
+) -> Result<()> {
+    match format {
+        PathsFileFormat::Lines => {
+            for path in paths {
+                writeln!(out, "{}", path_str(path)?)?;
+            }
+        }
+        PathsFileFormat::Json => {
+            let files = paths
+                .iter()
+                .map(|path| {
+                    let info = metadata.get(path);
+                    Ok(JsonEntry {
+                        path: path_str(path)?,
+                        change: info.map(|info| info.kind),
+                        old_path: info.and_then(|info| info.old_path.clone()),
+                        changed_lines: info.map(|info| line_ranges(&info.changed_lines)),
+                        // Dangling symlinks have no contents to hash.
+                        hash: io_limit::limited(|| std::fs::read(path))
+                            .ok()
+                            .map(|contents| blake3::hash(&contents).to_hex().to_string()),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            serde_json::to_writer(
+                &mut *out,
+                &JsonPathsFile {
+                    version: JSON_VERSION,
+                    files,
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_line_ranges() {
+        let lines = [1, 2, 3, 7, 9, 10].into_iter().collect();
+        assert_eq!(line_ranges(&lines), vec![(1, 3), (7, 7), (9, 10)]);
+        assert!(line_ranges(&BTreeSet::new()).is_empty());
+    }
+}
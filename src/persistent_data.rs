@@ -8,19 +8,32 @@
 
 use anyhow::{anyhow, bail, Context, Result};
 use directories::ProjectDirs;
-use figment::providers::{Format, Toml};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryFrom,
     fmt::Write,
     path::{Path, PathBuf},
 };
 
-use crate::{lint_config::LintRunnerConfig, path::AbsPath};
+use crate::{
+    health::HealthCache, lint_config::LintRunnerConfig, lint_message::LintMessage, path::AbsPath,
+    record::RecordedInvocation, render::render_lint_messages_json, trend::SeverityCounts,
+    trend::TrendPoint,
+};
 
 const CONFIG_DATA_NAME: &str = ".lintrunner.toml";
 const RUNS_DIR_NAME: &str = "runs";
+const LINT_MESSAGES_NAME: &str = "lint_messages.json";
+const LINTER_LOGS_DIR_NAME: &str = "linter_logs";
+const ARTIFACTS_DIR_NAME: &str = "artifacts";
 const MAX_RUNS_TO_STORE: usize = 10;
+const COMPDB_HASH_NAME: &str = "compdb_hash";
+const TREND_NAME: &str = "trend.jsonl";
+const MAX_TREND_POINTS: usize = 1000;
+const HEALTH_NAME: &str = "health.json";
+const EFFECTIVE_CONFIG_NAME: &str = "effective_config.json";
 
 /// Single way to interact with persistent data for a given run of lintrunner.
 /// This is scoped to a single .lintrunner.toml config.
@@ -41,6 +54,17 @@ pub struct RunInfo {
 pub struct ExitInfo {
     pub code: i32,
     pub err: Option<String>,
+    /// How long the run took. Missing for runs recorded by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+/// A past run, as returned by [`PersistentDataStore::past_runs_with_lint_messages`].
+pub struct PastRun {
+    pub run_info: RunInfo,
+    pub exit_info: ExitInfo,
+    /// The lint messages the run produced, if it recorded any.
+    pub lint_messages: Option<Vec<LintMessage>>,
 }
 
 impl RunInfo {
@@ -52,6 +76,38 @@ impl RunInfo {
     }
 }
 
+fn read_lint_messages(messages_path: &Path) -> Result<Vec<LintMessage>> {
+    let messages = std::fs::read_to_string(messages_path).with_context(|| {
+        format!(
+            "couldn't read lint messages from {}",
+            messages_path.display()
+        )
+    })?;
+    messages
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str(line).context("couldn't deserialize lint message"))
+        .collect()
+}
+
+/// Every file under `dir`, sorted. Empty if `dir` doesn't exist.
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
 impl PersistentDataStore {
     pub fn new(
         primary_config_path: &AbsPath,
@@ -123,6 +179,144 @@ impl PersistentDataStore {
         Ok(())
     }
 
+    /// Keep the config this run was started with, so that `lintrunner rage
+    /// --all` bundles the config a past run used rather than the current one.
+    pub fn write_effective_config(&self, config: &LintRunnerConfig) -> Result<()> {
+        let run_path = self.runs_dir.join(self.cur_run_info.dir_name());
+        debug!("Writing effective config to {}", run_path.display());
+        std::fs::write(
+            run_path.join(EFFECTIVE_CONFIG_NAME),
+            serde_json::to_string_pretty(config)?,
+        )?;
+        Ok(())
+    }
+
+    /// Record the lint messages produced by this run, so that later
+    /// invocations (e.g. `lintrunner fix`) can act on them without re-running
+    /// linters.
+    pub fn write_lint_messages(
+        &self,
+        lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+    ) -> Result<()> {
+        let run_path = self.runs_dir.join(self.cur_run_info.dir_name());
+        debug!("Writing lint messages to {}", run_path.display());
+
+        // Paths reported by linters may be relative to the current working
+        // directory, which might be different for the invocation that reads
+        // them back. Store absolute paths wherever we can.
+        let mut absolute_lint_messages = HashMap::new();
+        for (path, messages) in lint_messages {
+            let path = path.as_ref().map(|path| match AbsPath::try_from(path) {
+                Ok(abs_path) => abs_path.to_string_lossy().to_string(),
+                Err(_) => path.clone(),
+            });
+            let messages = messages
+                .iter()
+                .cloned()
+                .map(|mut message| {
+                    message.path = path.clone();
+                    message
+                })
+                .collect::<Vec<_>>();
+            absolute_lint_messages.insert(path, messages);
+        }
+
+        let mut file = std::fs::File::create(run_path.join(LINT_MESSAGES_NAME))?;
+        render_lint_messages_json(&mut file, &absolute_lint_messages)?;
+        Ok(())
+    }
+
+    /// Keep the full output of each linter invocation with this run, so that
+    /// `lintrunner rage --all` can include it untruncated. A linter run once
+    /// logs to `{code}.txt`, and one run several times, like once per crate,
+    /// to `{code}-1.txt`, `{code}-2.txt` and so on.
+    pub fn write_linter_logs(&self, invocations: &[RecordedInvocation]) -> Result<()> {
+        let logs_path = self
+            .runs_dir
+            .join(self.cur_run_info.dir_name())
+            .join(LINTER_LOGS_DIR_NAME);
+        debug!("Writing linter logs to {}", logs_path.display());
+        std::fs::create_dir_all(&logs_path)?;
+
+        let mut runs_per_code: HashMap<&str, usize> = HashMap::new();
+        for invocation in invocations {
+            *runs_per_code.entry(&invocation.code).or_default() += 1;
+        }
+        let mut logged_per_code: HashMap<&str, usize> = HashMap::new();
+        for invocation in invocations {
+            let mut log = String::new();
+            write!(
+                log,
+                "code: {}\n\
+                 command: {}\n\
+                 cwd: {}\n\
+                 exit code: {:?}\n\
+                 paths linted: {}\n\n\
+                 ========= BEGIN STDOUT =========\n\
+                 {}\n\
+                 ========= BEGIN STDERR =========\n\
+                 {}",
+                invocation.code,
+                invocation
+                    .command
+                    .iter()
+                    .map(|x| format!("'{x}'"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                invocation.cwd,
+                invocation.exit_code,
+                invocation.paths.len(),
+                invocation.stdout,
+                invocation.stderr,
+            )?;
+            let logged = logged_per_code.entry(&invocation.code).or_default();
+            *logged += 1;
+            let file_name = if runs_per_code[invocation.code.as_str()] == 1 {
+                format!("{}.txt", invocation.code)
+            } else {
+                format!("{}-{}.txt", invocation.code, logged)
+            };
+            std::fs::write(logs_path.join(file_name), log)?;
+        }
+        Ok(())
+    }
+
+    /// Where this run keeps the artifacts of failed linters, in a
+    /// subdirectory per linter. See [`crate::artifacts`].
+    pub fn artifacts_dir(&self) -> PathBuf {
+        self.runs_dir
+            .join(self.cur_run_info.dir_name())
+            .join(ARTIFACTS_DIR_NAME)
+    }
+
+    /// The directory holding everything stored for `run_info`.
+    pub fn run_dir(&self, run_info: &RunInfo) -> PathBuf {
+        self.runs_dir.join(run_info.dir_name())
+    }
+
+    /// Retrieve the lint messages recorded by a past run. If `invocation` is
+    /// not specified, use the most recent run that recorded any.
+    pub fn past_lint_messages(&self, invocation: Option<usize>) -> Result<Vec<LintMessage>> {
+        let run_dirs = self.past_run_dirs()?;
+        let messages_path = match invocation {
+            Some(invocation) => match run_dirs.get(invocation) {
+                Some(dir) => dir.join(LINT_MESSAGES_NAME),
+                None => bail!(
+                    "Tried to request run #{invocation}, but didn't find it. \
+                     (lintrunner only stores the last {MAX_RUNS_TO_STORE} runs)"
+                ),
+            },
+            None => run_dirs
+                .iter()
+                .map(|dir| dir.join(LINT_MESSAGES_NAME))
+                .find(|path| path.exists())
+                .ok_or_else(|| anyhow!("Couldn't find a past run that produced lint messages"))?,
+        };
+        debug!("Reading lint messages from {}", messages_path.display());
+
+        read_lint_messages(&messages_path)
+    }
+
     pub fn get_run_report(&self, run_info: &RunInfo) -> Result<String> {
         let run_path = self.runs_dir.join(run_info.dir_name());
         debug!("Generating run report from {}", run_path.display());
@@ -161,6 +355,14 @@ impl PersistentDataStore {
         } else {
             writeln!(ret, "EXIT INFO MISSING")?;
         }
+        let artifacts = list_files(&run_path.join(ARTIFACTS_DIR_NAME));
+        if !artifacts.is_empty() {
+            writeln!(ret, "artifacts of failed linters:")?;
+            for artifact in artifacts {
+                writeln!(ret, "  {}", artifact.display())?;
+            }
+            writeln!(ret)?;
+        }
         writeln!(ret, "========= BEGIN LOGS =========")?;
         ret.write_str(&log)?;
 
@@ -205,6 +407,16 @@ impl PersistentDataStore {
     }
 
     pub fn past_runs(&self) -> Result<Vec<(RunInfo, ExitInfo)>> {
+        Ok(self
+            .past_runs_with_lint_messages()?
+            .into_iter()
+            .map(|run| (run.run_info, run.exit_info))
+            .collect())
+    }
+
+    /// Like [`PersistentDataStore::past_runs`], but also reads the lint
+    /// messages each run produced.
+    pub fn past_runs_with_lint_messages(&self) -> Result<Vec<PastRun>> {
         let run_dirs = self.past_run_dirs()?;
 
         let mut ret = Vec::new();
@@ -222,7 +434,17 @@ impl PersistentDataStore {
 
             let run_info: RunInfo = serde_json::from_str(&run_data?)?;
             let exit_info: ExitInfo = serde_json::from_str(&exit_data?)?;
-            ret.push((run_info, exit_info));
+            let messages_path = dir.join(LINT_MESSAGES_NAME);
+            let lint_messages = if messages_path.exists() {
+                Some(read_lint_messages(&messages_path)?)
+            } else {
+                None
+            };
+            ret.push(PastRun {
+                run_info,
+                exit_info,
+                lint_messages,
+            });
         }
         Ok(ret)
     }
@@ -248,19 +470,90 @@ impl PersistentDataStore {
             CONFIG_DATA_NAME
         );
 
-        let mut config_contents = figment::Figment::new();
-
-        for path in config_paths {
-            config_contents = config_contents.join(Toml::file(path));
-        }
-
-        let config_contents = config_contents.extract::<LintRunnerConfig>()?;
+        let config_contents = LintRunnerConfig::new(config_paths)?;
         let path = self.relative_path(CONFIG_DATA_NAME);
         let serialized_contents = serde_json::to_string_pretty(&config_contents)?;
         std::fs::write(path, serialized_contents)?;
         Ok(())
     }
 
+    /// The hash of the compilation database inputs when it was last
+    /// generated. See [`crate::compdb`].
+    pub fn compdb_hash(&self) -> Result<Option<String>> {
+        let path = self.relative_path(COMPDB_HASH_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(std::fs::read_to_string(path)?))
+    }
+
+    pub fn update_compdb_hash(&self, hash: &str) -> Result<()> {
+        std::fs::write(self.relative_path(COMPDB_HASH_NAME), hash)?;
+        Ok(())
+    }
+
+    /// The cached results of linter health checks. See [`crate::health`].
+    pub fn health_cache(&self) -> Result<HealthCache> {
+        let path = self.relative_path(HEALTH_NAME);
+        if !path.exists() {
+            return Ok(HealthCache::new());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        // A corrupt cache just means probing again.
+        Ok(serde_json::from_str(&contents).unwrap_or_default())
+    }
+
+    pub fn write_health_cache(&self, cache: &HealthCache) -> Result<()> {
+        std::fs::write(
+            self.relative_path(HEALTH_NAME),
+            serde_json::to_string(cache)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn clear_health_cache(&self) -> Result<()> {
+        let path = self.relative_path(HEALTH_NAME);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Append this run's lint counts to the trend data, dropping the oldest
+    /// points past [`MAX_TREND_POINTS`].
+    pub fn record_trend(&self, counts: BTreeMap<String, SeverityCounts>) -> Result<()> {
+        let mut points = self.trend_points()?;
+        points.push(TrendPoint {
+            timestamp: self.cur_run_info.timestamp.clone(),
+            counts,
+        });
+        points.drain(..points.len().saturating_sub(MAX_TREND_POINTS));
+
+        let path = self.relative_path(TREND_NAME);
+        debug!("Writing trend data to {}", path.display());
+        let mut contents = String::new();
+        for point in &points {
+            contents.push_str(&serde_json::to_string(point)?);
+            contents.push('\n');
+        }
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+
+    /// The lint counts of past runs, oldest first. See [`crate::trend`].
+    pub fn trend_points(&self) -> Result<Vec<TrendPoint>> {
+        let path = self.relative_path(TREND_NAME);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = std::fs::read_to_string(&path)?;
+        // A line may be cut short if lintrunner was killed while writing it.
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
     fn relative_path(&self, path: impl AsRef<Path>) -> PathBuf {
         self.data_dir.join(path)
     }
@@ -304,7 +597,11 @@ mod tests {
             };
             let store = PersistentDataStore::new(&config, run_info).unwrap();
             store
-                .write_run_info(ExitInfo { code: 0, err: None })
+                .write_run_info(ExitInfo {
+                    code: 0,
+                    err: None,
+                    duration_ms: None,
+                })
                 .unwrap()
         }
 
@@ -315,4 +612,45 @@ mod tests {
         // Try to clean up
         std::fs::remove_dir_all(store.data_dir).unwrap();
     }
+
+    #[test]
+    fn linter_logs_are_kept_per_invocation() {
+        let f = NamedTempFile::new().unwrap();
+        let config = AbsPath::try_from(f.path()).unwrap();
+
+        let run_info = RunInfo {
+            timestamp: "0".to_string(),
+            args: vec!["foo".to_string(), "bar".to_string()],
+        };
+        let store = PersistentDataStore::new(&config, run_info).unwrap();
+        let invocation = |code: &str, stdout: &str| RecordedInvocation {
+            code: code.to_string(),
+            command: vec!["true".to_string()],
+            cwd: ".".to_string(),
+            env: Default::default(),
+            paths: Vec::new(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: Some(0),
+        };
+        store
+            .write_linter_logs(&[
+                invocation("CRATES", "first crate"),
+                invocation("ONCE", "only"),
+                invocation("CRATES", "second crate"),
+            ])
+            .unwrap();
+
+        let logs_path = store
+            .runs_dir
+            .join(store.cur_run_info.dir_name())
+            .join(LINTER_LOGS_DIR_NAME);
+        let log = |name: &str| std::fs::read_to_string(logs_path.join(name)).unwrap();
+        assert!(log("CRATES-1.txt").contains("first crate"));
+        assert!(log("CRATES-2.txt").contains("second crate"));
+        assert!(log("ONCE.txt").contains("only"));
+        assert!(!logs_path.join("CRATES.txt").exists());
+
+        std::fs::remove_dir_all(store.data_dir).unwrap();
+    }
 }
@@ -0,0 +1,266 @@
+//! Planning a lint run, separately from executing it.
+//!
+//! Once the files are selected and the linters set up, [`Plan::new`] works
+//! out what each linter will be given: the files it matches, and the
+//! invocations of its command they make up. Nothing is run while planning, so
+//! a plan can be looked at with `--plan-json`, checked in tests, or run by
+//! something other than the local [`crate::executor::Executor`], which runs
+//! the planned invocations as they are.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::PathBuf,
+    thread,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    linter::{Linter, SkipReason},
+    path::AbsPath,
+};
+
+/// One invocation of a linter's command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Batch {
+    /// The directory the command runs in.
+    pub cwd: PathBuf,
+    /// The paths passed to the command, in its `{{PATHSFILE}}`.
+    pub paths: Vec<AbsPath>,
+    /// The command, wrapped and sandboxed as it will run. `{{PATHSFILE}}` is
+    /// left in, since the paths file is only written when the batch runs.
+    /// Empty for builtin linters.
+    pub command: Vec<String>,
+    /// Whether the command runs on a remote host with `--remote-exec`, over
+    /// ssh to whichever host is free when the batch runs.
+    #[serde(default)]
+    pub remote: bool,
+}
+
+/// What a linter will be given.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedLinter {
+    pub code: String,
+    /// Whether the linter lints all files, since its config files changed.
+    pub expanded: bool,
+    /// The files the linter matches.
+    pub files: Vec<AbsPath>,
+    pub batches: Vec<Batch>,
+    /// Why the linter's invocations couldn't be planned, in which case it has
+    /// none and fails when run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl PlannedLinter {
+    /// Plan running `linter` on the files it matches among `files`.
+    pub fn new(linter: &Linter, files: &[AbsPath], expanded: bool) -> PlannedLinter {
+        let matches = linter.get_matches(files);
+        let (batches, error) = match linter.plan_batches(&matches) {
+            Ok(batches) => (batches, None),
+            Err(err) => (Vec::new(), Some(format!("{:#}", err))),
+        };
+        PlannedLinter {
+            code: linter.code.clone(),
+            expanded,
+            files: matches,
+            batches,
+            error,
+        }
+    }
+}
+
+/// What a lint run will do.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    /// The selected files.
+    pub files: Vec<AbsPath>,
+    /// In the same order as the linters the plan was made for.
+    pub linters: Vec<PlannedLinter>,
+    /// Why each linter that won't run is skipped.
+    pub skipped: BTreeMap<String, SkipReason>,
+}
+
+impl Plan {
+    /// Plan running `linters` on `files`, or on `all_files` for the linters
+    /// in `expanded_linters`. `skipped` are the linters skipped before
+    /// planning, to which those that match no files are added.
+    pub fn new(
+        linters: &[Linter],
+        files: &[AbsPath],
+        all_files: Option<&[AbsPath]>,
+        expanded_linters: &BTreeSet<String>,
+        mut skipped: BTreeMap<String, SkipReason>,
+    ) -> Plan {
+        // Matching every file against every linter's patterns adds up, so
+        // each linter is matched on its own thread.
+        let planned = thread::scope(|scope| {
+            let handles = linters
+                .iter()
+                .map(|linter| {
+                    scope.spawn(move || {
+                        let expanded = expanded_linters.contains(&linter.code);
+                        let linter_files = match all_files {
+                            Some(all_files) if expanded => all_files,
+                            _ => files,
+                        };
+                        PlannedLinter::new(linter, linter_files, expanded)
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // Linters of nested configs can share a code, which isn't skipped
+        // for lack of files as long as one of them matched some.
+        let matched_codes = planned
+            .iter()
+            .filter(|planned| !planned.files.is_empty())
+            .map(|planned| planned.code.clone())
+            .collect::<HashSet<_>>();
+        for (linter, planned) in linters.iter().zip(&planned) {
+            if planned.files.is_empty() && linter.config_error.is_none() {
+                skipped.insert(linter.code.clone(), SkipReason::NoMatchingFiles);
+            }
+        }
+        skipped.retain(|code, reason| {
+            *reason != SkipReason::NoMatchingFiles || !matched_codes.contains(code)
+        });
+
+        Plan {
+            files: files.to_vec(),
+            linters: planned,
+            skipped,
+        }
+    }
+
+    /// Whether any linter matched any file.
+    pub fn any_matched(&self) -> bool {
+        self.linters.iter().any(|linter| !linter.files.is_empty())
+    }
+
+    /// Write the plan as JSON to `path`, or to stdout if it's `-`.
+    pub fn write(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        if path == "-" {
+            println!("{}", json);
+            return Ok(());
+        }
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write plan to '{}'", path))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::convert::TryFrom;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::lint_config::{get_linters_from_configs, LintRunnerConfig, TagSelection};
+
+    /// The linters of `config`, and `files` with `contents`, in a temporary
+    /// directory.
+    pub(crate) fn setup(
+        config: &str,
+        files: &[(&str, &str)],
+    ) -> Result<(TempDir, Vec<Linter>, Vec<AbsPath>)> {
+        let dir = TempDir::new()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        std::fs::write(&config_path, config)?;
+        let config_path_str = config_path.display().to_string();
+        let config = LintRunnerConfig::new(&vec![config_path_str])?;
+        let (linters, _) = get_linters_from_configs(
+            &config.linters,
+            &config.invalid_linters,
+            None,
+            None,
+            TagSelection::default(),
+            &AbsPath::try_from(config_path)?,
+            false,
+        )?;
+        let mut paths = Vec::new();
+        for (file, contents) in files {
+            let path = dir.path().join(file);
+            std::fs::write(&path, contents)?;
+            paths.push(AbsPath::try_from(path)?);
+        }
+        Ok((dir, linters, paths))
+    }
+
+    #[test]
+    fn plans_without_running_anything() -> Result<()> {
+        let (dir, linters, files) = setup(
+            "
+            [[linter]]
+            code = 'PY'
+            include_patterns = ['*.py']
+            command = ['no-such-linter', '--strict', '@{{PATHSFILE}}']
+
+            [[linter]]
+            code = 'MARKERS'
+            kind = 'builtin'
+            builtin = 'conflict_markers'
+            include_patterns = ['*.md']
+
+            [[linter]]
+            code = 'RS'
+            include_patterns = ['*.rs']
+            command = ['rustfmt']
+            ",
+            &[("a.py", ""), ("b.md", ""), ("c.py", "")],
+        )?;
+        let selected = files[..1].to_vec();
+        let plan = Plan::new(
+            &linters,
+            &files[..1],
+            None,
+            &BTreeSet::new(),
+            BTreeMap::new(),
+        );
+        assert_eq!(plan.linters[0].files, selected);
+        assert_eq!(
+            plan.linters[0].batches,
+            [Batch {
+                cwd: dir.path().canonicalize()?,
+                paths: selected,
+                command: ["no-such-linter", "--strict", "@{{PATHSFILE}}"]
+                    .map(String::from)
+                    .to_vec(),
+                remote: false,
+            }]
+        );
+        assert!(plan.linters[1].batches.is_empty());
+        assert!(plan.any_matched());
+        assert_eq!(
+            plan.skipped.into_iter().collect::<Vec<_>>(),
+            [
+                ("MARKERS".to_string(), SkipReason::NoMatchingFiles),
+                ("RS".to_string(), SkipReason::NoMatchingFiles),
+            ]
+        );
+
+        // The builtin is given all files once its config changed.
+        let expanded = BTreeSet::from(["MARKERS".to_string()]);
+        let plan = Plan::new(
+            &linters,
+            &files[..1],
+            Some(&files),
+            &expanded,
+            BTreeMap::new(),
+        );
+        assert!(plan.linters[1].expanded);
+        assert_eq!(plan.linters[1].files, files[1..2]);
+        assert_eq!(plan.linters[1].batches[0].command, Vec::<String>::new());
+
+        // Plans round-trip through JSON.
+        let json = serde_json::to_string(&plan)?;
+        assert_eq!(serde_json::from_str::<Plan>(&json)?, plan);
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+//! Process management for linter commands.
+//!
+//! On Unix, every linter is started in its own process group, so that when
+//! it is cancelled, the whole group is killed, including any processes the
+//! linter started itself. Being in their own group also means a Ctrl-C in the
+//! terminal doesn't reach linters, so the groups of running linters are
+//! registered, and lintrunner kills them itself when it is interrupted or
+//! killed. Linters with `use_pty = true` get a pseudo-terminal as their stdout,
+//! for tools that only show progress or color on a terminal.
+
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::{fs::File, process::Child, process::Command};
+
+use anyhow::Result;
+#[cfg(unix)]
+use log::debug;
+
+/// How many process groups can be registered at once: far more linters than
+/// run at a time.
+#[cfg(unix)]
+const MAX_GROUPS: usize = 1024;
+
+/// The process groups of running linters. They can be read from a signal
+/// handler, where nothing can be locked, so this is a fixed set of slots,
+/// with zero meaning a free one.
+#[cfg(unix)]
+static GROUPS: [AtomicI32; MAX_GROUPS] = [const { AtomicI32::new(0) }; MAX_GROUPS];
+
+/// Keeps a process group registered until dropped.
+pub struct Registered {
+    #[cfg(unix)]
+    slot: Option<usize>,
+}
+
+impl Drop for Registered {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(slot) = self.slot {
+            GROUPS[slot].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Register the process group `child` leads, started with
+/// [`own_process_group`], to be killed by [`kill_registered_groups`] until the
+/// returned guard is dropped. Drop it only once the child has been waited for,
+/// so that its pid can't have been reused.
+pub fn register_group(child: &Child) -> Registered {
+    #[cfg(unix)]
+    {
+        let Ok(pgid) = libc::pid_t::try_from(child.id()) else {
+            return Registered { slot: None };
+        };
+        let slot = GROUPS.iter().position(|slot| {
+            slot.compare_exchange(0, pgid, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        });
+        if slot.is_none() {
+            debug!(
+                "Too many linters running to register process group {}",
+                pgid
+            );
+        }
+        Registered { slot }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = child;
+        Registered {}
+    }
+}
+
+/// SIGKILL every registered process group, and wait for the linters leading
+/// them to exit, so that they are done with the run's temporary files. Only
+/// does what is safe in a signal handler.
+pub fn kill_registered_groups() {
+    #[cfg(unix)]
+    {
+        for slot in &GROUPS {
+            let pgid = slot.load(Ordering::SeqCst);
+            if pgid > 0 {
+                unsafe {
+                    libc::kill(-pgid, libc::SIGKILL);
+                }
+            }
+        }
+        for slot in &GROUPS {
+            let pgid = slot.load(Ordering::SeqCst);
+            if pgid > 0 {
+                // Fails if the thread running the linter reaped it first.
+                let mut status = 0;
+                unsafe {
+                    libc::waitpid(pgid, &mut status, 0);
+                }
+            }
+        }
+    }
+}
+
+/// Put the process started by `command` in a new process group of its own.
+pub fn own_process_group(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
+
+/// Kill `child` along with the rest of its process group, and wait for it.
+pub fn kill_process_group(child: &mut Child) -> Result<()> {
+    #[cfg(unix)]
+    {
+        // The child is the leader of its group, so the group id is its pid.
+        // Failure just means the group is already gone.
+        if let Ok(pid) = libc::pid_t::try_from(child.id()) {
+            unsafe {
+                libc::kill(-pid, libc::SIGKILL);
+            }
+        }
+    }
+    // Kill the child directly too, in case it wasn't a group leader.
+    drop(child.kill());
+    child.wait()?;
+    Ok(())
+}
+
+/// A pseudo-terminal: the child writes to `follower`, we read from `leader`.
+pub struct Pty {
+    pub leader: File,
+    pub follower: File,
+}
+
+impl Pty {
+    #[cfg(unix)]
+    pub fn open() -> Result<Pty> {
+        use std::os::unix::io::FromRawFd;
+
+        let mut leader = 0;
+        let mut follower = 0;
+        let ret = unsafe {
+            libc::openpty(
+                &mut leader,
+                &mut follower,
+                std::ptr::null_mut(),
+                std::ptr::null(),
+                std::ptr::null(),
+            )
+        };
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // The fds were just created and are owned by nothing else.
+        unsafe {
+            Ok(Pty {
+                leader: File::from_raw_fd(leader),
+                follower: File::from_raw_fd(follower),
+            })
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn open() -> Result<Pty> {
+        anyhow::bail!("`use_pty` is only supported on Unix")
+    }
+}
+
+/// Clean up a line a linter wrote to a terminal: drop ANSI escape codes and
+/// anything overwritten using carriage returns (e.g. progress bars). Returns
+/// `None` unless what's left looks like a JSON object, as the rest is output
+/// meant for a human.
+pub fn clean_pty_line(line: &str) -> Option<String> {
+    let line = console::strip_ansi_codes(line);
+    line.rsplit('\r')
+        .find(|part| !part.is_empty())
+        .filter(|part| part.trim_start().starts_with('{'))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn groups_are_registered_until_dropped() -> Result<()> {
+        let registered_groups = || {
+            GROUPS
+                .iter()
+                .map(|slot| slot.load(Ordering::SeqCst))
+                .filter(|pgid| *pgid > 0)
+                .collect::<Vec<_>>()
+        };
+        let mut command = Command::new("sleep");
+        command.arg("37");
+        own_process_group(&mut command);
+        let mut child = command.spawn()?;
+        let pgid = child.id() as libc::pid_t;
+
+        let registered = register_group(&child);
+        assert!(registered_groups().contains(&pgid));
+        kill_process_group(&mut child)?;
+        drop(registered);
+        assert!(!registered_groups().contains(&pgid));
+        Ok(())
+    }
+
+    #[test]
+    fn cleans_pty_lines() {
+        assert_eq!(clean_pty_line("\x1b[32mChecking\x1b[0m..."), None);
+        assert_eq!(clean_pty_line("10%\r50%\r100%\r"), None);
+        assert_eq!(
+            clean_pty_line("50%\r{\"a\": 1}"),
+            Some("{\"a\": 1}".to_string())
+        );
+        assert_eq!(
+            clean_pty_line("  {\"b\": \"\x1b[1mx\x1b[0m\"}"),
+            Some("  {\"b\": \"x\"}".to_string())
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Named sets of options, selected with `--profile`.
+//!
+//! Teams tend to wrap lintrunner in scripts that pass the same options for
+//! each situation (a fast pre-commit run, a thorough CI run). A profile puts
+//! those options in the config instead:
+//!
+//! ```toml
+//! [profile.precommit]
+//! skip_tag = ['slow']
+//! min_severity = 'warning'
+//!
+//! [profile.ci]
+//! output = 'json'
+//! jobs = 4
+//!
+//! [profile.ci.linter.MYPY]
+//! timeout_seconds = 1800
+//! command = ['python3', 'tools/mypy_linter.py', '--strict', '@{{PATHSFILE}}']
+//! ```
+//!
+//! Options given on the command line override the profile's. The profile is
+//! taken from `LINTRUNNER_PROFILE` if `--profile` isn't passed.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{lint_config::LintRunnerConfig, lint_message::LintSeverity, RenderOpt};
+
+/// The environment variable to select a profile with, if `--profile` isn't
+/// passed.
+pub const PROFILE_ENV: &str = "LINTRUNNER_PROFILE";
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Linters to run, like `--take`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub take: Option<Vec<String>>,
+    /// Linters not to run, like `--skip`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip: Option<Vec<String>>,
+    /// Tags of linters to run, like `--tag`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Vec<String>>,
+    /// Tags of linters not to run, like `--skip-tag`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_tag: Option<Vec<String>>,
+    /// How to show results, like `--output`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<RenderOpt>,
+    /// How many linter invocations may run at once, like `--jobs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+    /// Drop messages less severe than this, like `--min-severity`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<LintSeverity>,
+    /// Fields of linters to override, by linter code. Each replaces the
+    /// linter's own value of that field.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub linter: BTreeMap<String, toml::value::Table>,
+}
+
+impl Profile {
+    /// Replace the fields of `config`'s linters that the profile overrides.
+    pub fn apply(&self, config: &mut LintRunnerConfig) -> Result<()> {
+        for (code, overrides) in &self.linter {
+            ensure!(
+                !overrides.contains_key("code"),
+                "Can't override the code of linter '{}'",
+                code
+            );
+            let lint_config = match config.linters.iter_mut().find(|l| &l.code == code) {
+                Some(lint_config) => lint_config,
+                // Reported as invalid when linting, with or without the profile.
+                None if config.invalid_linters.iter().any(|l| &l.code == code) => continue,
+                None => bail!("Can't override linter '{}': it isn't in the config", code),
+            };
+            let mut value = toml::Value::try_from(&*lint_config)?;
+            // Unwrap ok because a struct serializes to a table.
+            let table = value.as_table_mut().unwrap();
+            table.extend(overrides.clone());
+            *lint_config = value
+                .try_into()
+                .with_context(|| format!("Invalid overrides for linter '{}'", code))?;
+        }
+        Ok(())
+    }
+}
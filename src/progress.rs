@@ -0,0 +1,239 @@
+//! Reporting the progress of linters while they run.
+//!
+//! By default this is a spinner per linter. `--progress plain` is meant for
+//! screen readers and other consumers that can't follow redrawn lines:
+//! progress is reported as complete sentences, one per line, with a periodic
+//! summary of which linters are still running.
+
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use clap::ArgEnum;
+use console::style;
+use indicatif::{MultiProgress, ProgressBar};
+
+/// How progress is shown, for `--progress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum ProgressStyle {
+    Spinners,
+    Plain,
+}
+
+pub enum ProgressOpt {
+    Hidden,
+    Spinners,
+    /// Plain-text progress, with a summary every `interval`.
+    Plain {
+        interval: Duration,
+    },
+}
+
+fn linters_noun(count: usize) -> &'static str {
+    if count == 1 {
+        "linter"
+    } else {
+        "linters"
+    }
+}
+
+fn issues_noun(count: usize) -> &'static str {
+    if count == 1 {
+        "issue"
+    } else {
+        "issues"
+    }
+}
+
+struct PlainState {
+    total: usize,
+    running: Mutex<BTreeSet<String>>,
+    // Set once all linters are done, to stop the summary thread.
+    done: Mutex<bool>,
+    done_cvar: Condvar,
+}
+
+impl PlainState {
+    fn summary(&self) -> String {
+        let running = self.running.lock().unwrap();
+        format!(
+            "Status: {} of {} {} finished. Still running: {}.",
+            self.total - running.len(),
+            self.total,
+            linters_noun(self.total),
+            running.iter().cloned().collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+pub struct Progress {
+    multi: Option<MultiProgress>,
+    plain: Option<Arc<PlainState>>,
+    summary_thread: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+/// Progress for a single linter.
+pub struct LinterProgress {
+    code: String,
+    kind: LinterProgressKind,
+}
+
+enum LinterProgressKind {
+    Hidden,
+    Spinner(ProgressBar),
+    Plain(Arc<PlainState>),
+}
+
+impl Progress {
+    pub fn new(opt: ProgressOpt, linter_codes: &[String]) -> Progress {
+        match opt {
+            ProgressOpt::Hidden => Progress {
+                multi: None,
+                plain: None,
+                summary_thread: Mutex::new(None),
+            },
+            ProgressOpt::Spinners => Progress {
+                multi: Some(MultiProgress::new()),
+                plain: None,
+                summary_thread: Mutex::new(None),
+            },
+            ProgressOpt::Plain { interval } => {
+                eprintln!(
+                    "Starting {} {}: {}.",
+                    linter_codes.len(),
+                    linters_noun(linter_codes.len()),
+                    linter_codes.join(", ")
+                );
+                let state = Arc::new(PlainState {
+                    total: linter_codes.len(),
+                    running: Mutex::new(BTreeSet::new()),
+                    done: Mutex::new(false),
+                    done_cvar: Condvar::new(),
+                });
+                let summary_state = Arc::clone(&state);
+                let summary_thread = thread::spawn(move || {
+                    let mut done = summary_state.done.lock().unwrap();
+                    loop {
+                        let (guard, timeout) = summary_state
+                            .done_cvar
+                            .wait_timeout(done, interval)
+                            .unwrap();
+                        done = guard;
+                        if *done {
+                            return;
+                        }
+                        if timeout.timed_out() {
+                            eprintln!("{}", summary_state.summary());
+                        }
+                    }
+                });
+                Progress {
+                    multi: None,
+                    plain: Some(state),
+                    summary_thread: Mutex::new(Some(summary_thread)),
+                }
+            }
+        }
+    }
+
+    pub fn start_linter(&self, code: &str) -> LinterProgress {
+        let kind = if let Some(multi) = &self.multi {
+            let spinner = multi.add(ProgressBar::new_spinner());
+            spinner.set_message(format!("{} running...", code));
+            spinner.enable_steady_tick(100);
+            LinterProgressKind::Spinner(spinner)
+        } else if let Some(state) = &self.plain {
+            state.running.lock().unwrap().insert(code.to_string());
+            eprintln!("Linter {} started.", code);
+            LinterProgressKind::Plain(Arc::clone(state))
+        } else {
+            LinterProgressKind::Hidden
+        };
+        LinterProgress {
+            code: code.to_string(),
+            kind,
+        }
+    }
+
+    /// Wait for the spinners to finish drawing. Must be called from the
+    /// thread that created this, while linters run on other threads.
+    pub fn join(&self) -> Result<()> {
+        if let Some(multi) = &self.multi {
+            multi.join()?;
+        }
+        Ok(())
+    }
+
+    /// Called once every linter is done.
+    pub fn finish(&self) {
+        if let Some(state) = &self.plain {
+            *state.done.lock().unwrap() = true;
+            state.done_cvar.notify_all();
+            if let Some(summary_thread) = self.summary_thread.lock().unwrap().take() {
+                drop(summary_thread.join());
+            }
+            if state.total == 1 {
+                eprintln!("The linter finished.");
+            } else {
+                eprintln!("All {} linters finished.", state.total);
+            }
+        }
+    }
+}
+
+impl LinterProgress {
+    fn announce(&self, sentence: String) {
+        if let LinterProgressKind::Plain(state) = &self.kind {
+            state.running.lock().unwrap().remove(&self.code);
+            eprintln!("{}", sentence);
+        }
+    }
+
+    /// The linter finished and reported `num_issues` lint messages.
+    pub fn finish(self, num_issues: usize) {
+        match &self.kind {
+            LinterProgressKind::Hidden => {}
+            LinterProgressKind::Spinner(spinner) => {
+                let message = if num_issues == 0 {
+                    format!("{} {}", self.code, style("success!").for_stderr().green())
+                } else {
+                    format!("{} {}", self.code, style("failure").for_stderr().red())
+                };
+                spinner.finish_with_message(message);
+            }
+            LinterProgressKind::Plain(_) => {
+                let sentence = if num_issues == 0 {
+                    format!("Linter {} finished with no issues.", self.code)
+                } else {
+                    format!(
+                        "Linter {} finished and reported {} {}.",
+                        self.code,
+                        num_issues,
+                        issues_noun(num_issues)
+                    )
+                };
+                self.announce(sentence);
+            }
+        }
+    }
+
+    pub fn cancel(self) {
+        match &self.kind {
+            LinterProgressKind::Hidden => {}
+            LinterProgressKind::Spinner(spinner) => {
+                spinner.finish_with_message(format!(
+                    "{} {}",
+                    self.code,
+                    style("cancelled").for_stderr().yellow()
+                ));
+            }
+            LinterProgressKind::Plain(_) => {
+                self.announce(format!("Linter {} was cancelled.", self.code));
+            }
+        }
+    }
+}
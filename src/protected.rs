@@ -0,0 +1,109 @@
+//! Protected linters: linters marked `protected = true` have their
+//! configuration pinned in a lockfile committed next to the config, so a
+//! local change that weakens them (e.g. in `.lintrunner.private.toml`) doesn't
+//! go unnoticed.
+//!
+//! `lintrunner config lock` writes the lockfile. When linting, any protected
+//! linter whose effective configuration doesn't match it is reported, and
+//! with `--enforce-protected` the run fails.
+
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lint_config::LintConfig,
+    theme::{self, ThemeExt},
+};
+
+pub const LOCKFILE_NAME: &str = ".lintrunner.lock";
+
+#[derive(Serialize, Deserialize, Default)]
+struct Lockfile {
+    /// Map of linter code to the blake3 hash of its configuration.
+    protected: BTreeMap<String, String>,
+}
+
+fn config_hash(lint_config: &LintConfig) -> Result<String> {
+    let serialized = serde_json::to_string(lint_config)?;
+    Ok(blake3::hash(serialized.as_bytes()).to_string())
+}
+
+/// Write the lockfile for the protected linters in `linters` to `config_dir`.
+pub fn write_lockfile(config_dir: &Path, linters: &[LintConfig]) -> Result<i32> {
+    let mut lockfile = Lockfile::default();
+    for linter in linters.iter().filter(|linter| linter.protected) {
+        lockfile
+            .protected
+            .insert(linter.code.clone(), config_hash(linter)?);
+    }
+    let path = config_dir.join(LOCKFILE_NAME);
+    let mut contents = serde_json::to_string_pretty(&lockfile)?;
+    contents.push('\n');
+    std::fs::write(&path, contents)
+        .with_context(|| format!("Failed to write lockfile '{}'", path.display()))?;
+    println!(
+        "Locked {} protected linter(s) in {}",
+        lockfile.protected.len(),
+        path.display()
+    );
+    Ok(0)
+}
+
+/// The codes of protected linters whose configuration doesn't match the
+/// lockfile in `config_dir`, including locked linters that are gone, e.g.
+/// because a local config set `enabled = false`.
+fn changed_protected_linters(config_dir: &Path, linters: &[LintConfig]) -> Result<Vec<String>> {
+    let path = config_dir.join(LOCKFILE_NAME);
+    let lockfile = match std::fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse lockfile '{}'", path.display()))?,
+        Err(_) => Lockfile::default(),
+    };
+    let mut changed = Vec::new();
+    for linter in linters.iter().filter(|linter| linter.protected) {
+        if lockfile.protected.get(&linter.code) != Some(&config_hash(linter)?) {
+            changed.push(linter.code.clone());
+        }
+    }
+    for code in lockfile.protected.keys() {
+        if !linters.iter().any(|linter| &linter.code == code) {
+            changed.push(code.clone());
+        }
+    }
+    Ok(changed)
+}
+
+/// Warn about protected linters whose configuration differs from the
+/// lockfile, or fail if `enforce` is set.
+pub fn check_protected(config_dir: &Path, linters: &[LintConfig], enforce: bool) -> Result<()> {
+    let changed = changed_protected_linters(config_dir, linters)?;
+    if changed.is_empty() {
+        return Ok(());
+    }
+    if enforce {
+        bail!(
+            "The configuration of protected linters {} doesn't match {}. \
+             Protected linters can't be changed locally; if the change is \
+             intended, run `lintrunner config lock` and commit the result.",
+            changed.join(", "),
+            LOCKFILE_NAME
+        );
+    }
+    writeln!(
+        theme::stderr(),
+        "{}",
+        style(format!(
+            "WARNING: The configuration of protected linters {} doesn't match {}. \
+             They may have been weakened by a local config change. If the change is \
+             intended, run `lintrunner config lock` and commit the result.",
+            changed.join(", "),
+            LOCKFILE_NAME
+        ))
+        .theme_bold()
+        .yellow(),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,583 @@
+//! Publishing the findings of a run to code review tools, for `lintrunner
+//! publish`.
+//!
+//! `github-checks` posts them as a Check Run on a commit. Each finding in a
+//! file of the repo becomes an annotation on its lines, and findings with a
+//! fix carry it as a suggested-change block. The run's summary is the same
+//! Markdown as `--output summary-markdown`.
+//!
+//! `gerrit` posts them as robot comments on a patchset, in a review whose
+//! message sums them up. Findings with a fix carry it as a fix suggestion,
+//! which reviewers can preview and apply in Gerrit's UI.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use log::{debug, info};
+use serde::Serialize;
+
+use crate::{
+    download::github_api_url,
+    get_version_control, group_lints_by_file,
+    lint_message::{LintMessage, LintSeverity},
+    patch::{changed_lines, lines_of},
+    persistent_data::PersistentDataStore,
+    render::render_lint_messages_summary_markdown,
+    triage::read_report,
+};
+
+/// The most annotations the Checks API accepts per request.
+const MAX_ANNOTATIONS_PER_REQUEST: usize = 50;
+/// Annotation messages are cut at this many bytes, under the API's 64KB limit.
+const MAX_ANNOTATION_MESSAGE_BYTES: usize = 60_000;
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct Annotation {
+    path: String,
+    start_line: usize,
+    end_line: usize,
+    annotation_level: &'static str,
+    title: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_details: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CheckRunOutput<'a> {
+    title: &'a str,
+    summary: &'a str,
+    annotations: &'a [Annotation],
+}
+
+fn annotation_level(severity: LintSeverity) -> &'static str {
+    match severity {
+        LintSeverity::Error => "failure",
+        LintSeverity::Warning => "warning",
+        LintSeverity::Advice | LintSeverity::Disabled => "notice",
+    }
+}
+
+/// The lines a fix changes, as a 1-based inclusive range of lines of
+/// `original`, and a suggested-change block that replaces them.
+fn suggestion(original: &str, replacement: &str) -> Option<(usize, usize, String)> {
+    let (old, new) = changed_lines(original, replacement)?;
+    let mut suggested = lines_of(replacement, new);
+    if !suggested.is_empty() && !suggested.ends_with('\n') {
+        suggested.push('\n');
+    }
+    let fence = if suggested.contains("```") {
+        "````"
+    } else {
+        "```"
+    };
+    Some((
+        old.start + 1,
+        old.end,
+        format!("{fence}suggestion\n{suggested}{fence}"),
+    ))
+}
+
+fn truncate(text: &mut String, max_bytes: usize) {
+    if text.len() > max_bytes {
+        let mut end = max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n[truncated]");
+    }
+}
+
+/// The annotation for `lint_message`, if it is about a file under `root`.
+fn annotation(lint_message: &LintMessage, root: &Path) -> Option<Annotation> {
+    let path = Path::new(lint_message.path.as_deref()?);
+    let path = path.strip_prefix(root).ok()?.to_str()?.to_string();
+    let line = lint_message.line.unwrap_or(1).max(1);
+    let mut annotation = Annotation {
+        path,
+        start_line: line,
+        end_line: lint_message.end_line.unwrap_or(line).max(line),
+        annotation_level: annotation_level(lint_message.severity),
+        title: format!("{} ({})", lint_message.name, lint_message.code),
+        message: lint_message
+            .description
+            .clone()
+            .unwrap_or_else(|| lint_message.name.clone()),
+        raw_details: None,
+    };
+    if let Some((start_line, end_line, block)) = lint_message
+        .original
+        .as_deref()
+        .zip(lint_message.replacement.as_deref())
+        .and_then(|(original, replacement)| suggestion(original, replacement))
+    {
+        annotation.start_line = start_line;
+        annotation.end_line = end_line;
+        annotation.raw_details = Some(block);
+    }
+    truncate(&mut annotation.message, MAX_ANNOTATION_MESSAGE_BYTES);
+    if let Some(raw_details) = &mut annotation.raw_details {
+        truncate(raw_details, MAX_ANNOTATION_MESSAGE_BYTES);
+    }
+    Some(annotation)
+}
+
+/// Quote `value` for a curl config file.
+pub(crate) fn curl_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Send `body` as JSON to `url` with curl, and return the response.
+/// `curl_config` are extra lines of curl config, like headers. They are
+/// passed in a file so that credentials don't show up in the process list.
+fn http_request(
+    method: &str,
+    url: &str,
+    curl_config: &[String],
+    body: &serde_json::Value,
+) -> Result<Vec<u8>> {
+    debug!("{} {}", method, url);
+    let mut config = tempfile::NamedTempFile::new()?;
+    writeln!(
+        config,
+        "header = {}",
+        curl_quote("Content-Type: application/json")
+    )?;
+    for line in curl_config {
+        writeln!(config, "{}", line)?;
+    }
+    let mut child = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--request", method])
+        .arg("--config")
+        .arg(config.path())
+        .args(["--data-binary", "@-", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl; is it installed?")?;
+    child
+        .stdin
+        .take()
+        .context("Failed to open curl's stdin")?
+        .write_all(serde_json::to_string(body)?.as_bytes())?;
+    let output = child.wait_with_output()?;
+    ensure!(
+        output.status.success(),
+        "{} {} failed: {}",
+        method,
+        url,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(output.stdout)
+}
+
+/// Send `body` to the GitHub API, and parse the response.
+fn github_request(
+    method: &str,
+    url: &str,
+    token: &str,
+    body: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    let curl_config = [
+        format!(
+            "header = {}",
+            curl_quote("Accept: application/vnd.github+json")
+        ),
+        format!(
+            "header = {}",
+            curl_quote(&format!("Authorization: Bearer {}", token))
+        ),
+    ];
+    let response = http_request(method, url, &curl_config, body)?;
+    serde_json::from_slice(&response).with_context(|| format!("Unexpected response from {}", url))
+}
+
+pub struct GithubChecksOpts {
+    /// The environment variable holding the API token.
+    pub token_env: String,
+    /// A file of JSON lint messages to publish, instead of the last run's.
+    pub report: Option<String>,
+    /// The check run's name.
+    pub name: String,
+    /// `owner/name`. Defaults to `$GITHUB_REPOSITORY`.
+    pub repo: Option<String>,
+    /// The commit to attach the check run to. Defaults to `$GITHUB_SHA`, then
+    /// to HEAD.
+    pub sha: Option<String>,
+}
+
+pub fn do_publish_github_checks(
+    persistent_data_store: &PersistentDataStore,
+    opts: GithubChecksOpts,
+    offline: bool,
+) -> Result<i32> {
+    ensure!(!offline, "Can't publish to GitHub with --offline.");
+    let token = std::env::var(&opts.token_env)
+        .with_context(|| format!("${} must hold a GitHub token", opts.token_env))?;
+    let repo = match opts
+        .repo
+        .or_else(|| std::env::var("GITHUB_REPOSITORY").ok())
+    {
+        Some(repo) => repo,
+        None => bail!("Pass --repo, or set $GITHUB_REPOSITORY, to say which repo to publish to."),
+    };
+    let version_control = get_version_control()?;
+    let sha = match opts.sha.or_else(|| std::env::var("GITHUB_SHA").ok()) {
+        Some(sha) => sha,
+        None => version_control.get_head()?,
+    };
+    let root = version_control.get_root();
+
+    let lint_messages = match &opts.report {
+        Some(report) => read_report(report)?,
+        None => persistent_data_store.past_lint_messages(None)?,
+    };
+    let annotations = lint_messages
+        .iter()
+        .filter_map(|lint_message| annotation(lint_message, root))
+        .collect::<Vec<_>>();
+
+    let mut summary = Vec::new();
+    let mut grouped = HashMap::new();
+    group_lints_by_file(&mut grouped, lint_messages.clone());
+    render_lint_messages_summary_markdown(&mut summary, &grouped)?;
+    let summary = String::from_utf8(summary)?;
+    let errors = lint_messages
+        .iter()
+        .filter(|lint_message| lint_message.severity == LintSeverity::Error)
+        .count();
+    let (conclusion, title) = if lint_messages.is_empty() {
+        ("success", "No lint issues".to_string())
+    } else if errors > 0 {
+        (
+            "failure",
+            format!("{} issues, {} errors", lint_messages.len(), errors),
+        )
+    } else {
+        ("neutral", format!("{} issues", lint_messages.len()))
+    };
+
+    let mut batches = annotations.chunks(MAX_ANNOTATIONS_PER_REQUEST);
+    let output = |annotations| CheckRunOutput {
+        title: &title,
+        summary: &summary,
+        annotations,
+    };
+    let created = github_request(
+        "POST",
+        &format!("{}/repos/{}/check-runs", github_api_url(), repo),
+        &token,
+        &serde_json::json!({
+            "name": opts.name,
+            "head_sha": sha,
+            "status": "completed",
+            "conclusion": conclusion,
+            "output": output(batches.next().unwrap_or_default()),
+        }),
+    )?;
+    let id = created
+        .get("id")
+        .and_then(|id| id.as_u64())
+        .context("GitHub didn't return the check run's id")?;
+    // Further annotations are added by updating the check run.
+    for batch in batches {
+        github_request(
+            "PATCH",
+            &format!("{}/repos/{}/check-runs/{}", github_api_url(), repo, id),
+            &token,
+            &serde_json::json!({ "output": output(batch) }),
+        )?;
+    }
+    info!(
+        "Published {} annotations to check run {} on {}",
+        annotations.len(),
+        id,
+        sha
+    );
+    match created.get("html_url").and_then(|url| url.as_str()) {
+        Some(url) => println!("{}", url),
+        None => println!("Published check run {}", id),
+    }
+    Ok(0)
+}
+
+/// A Gerrit comment range: 1-based lines, 0-based characters.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+struct GerritRange {
+    start_line: usize,
+    start_character: usize,
+    end_line: usize,
+    end_character: usize,
+}
+
+/// The range of `original` a fix replaces, and what to put there, for a
+/// Gerrit fix suggestion. Ranges end at the start of the line after the
+/// changed ones, except at the end of the file, where that line doesn't
+/// exist.
+fn fix_replacement(original: &str, replacement: &str) -> Option<(GerritRange, String)> {
+    let (old, new) = changed_lines(original, replacement)?;
+    let lines = original.lines().collect::<Vec<_>>();
+    let mut replaced = lines_of(replacement, new);
+    if old.end < lines.len() {
+        let range = GerritRange {
+            start_line: old.start + 1,
+            start_character: 0,
+            end_line: old.end + 1,
+            end_character: 0,
+        };
+        return Some((range, replaced));
+    }
+    if original.ends_with('\n') && replaced.ends_with('\n') {
+        replaced.pop();
+    }
+    let (start_line, start_character) = if replaced.is_empty() && old.start > 0 {
+        // Deleting the last lines also deletes the newline before them.
+        (old.start, lines[old.start - 1].chars().count())
+    } else {
+        (old.start + 1, 0)
+    };
+    let range = GerritRange {
+        start_line,
+        start_character,
+        end_line: old.end,
+        end_character: lines[old.end - 1].chars().count(),
+    };
+    Some((range, replaced))
+}
+
+/// The robot comment for `lint_message`, and the repo-relative path it is
+/// on, if it is about a file under `root`.
+fn robot_comment(
+    lint_message: &LintMessage,
+    root: &Path,
+    robot_id: &str,
+    robot_run_id: &str,
+) -> Option<(String, serde_json::Value)> {
+    let path = Path::new(lint_message.path.as_deref()?);
+    let path = path.strip_prefix(root).ok()?.to_str()?.to_string();
+    let mut message = format!("{} ({})", lint_message.name, lint_message.code);
+    if let Some(description) = &lint_message.description {
+        message.push_str("\n\n");
+        message.push_str(description);
+    }
+    truncate(&mut message, MAX_ANNOTATION_MESSAGE_BYTES);
+    let mut comment = serde_json::json!({
+        "robot_id": robot_id,
+        "robot_run_id": robot_run_id,
+        "message": message,
+        "properties": {
+            "code": lint_message.code,
+            "severity": lint_message.severity.label(),
+        },
+    });
+    if let Some(line) = lint_message.line {
+        comment["line"] = line.max(1).into();
+    }
+    if let Some((range, replacement)) = lint_message
+        .original
+        .as_deref()
+        .zip(lint_message.replacement.as_deref())
+        .and_then(|(original, replacement)| fix_replacement(original, replacement))
+    {
+        comment["fix_suggestions"] = serde_json::json!([{
+            "description": format!("Apply the fix from {}", lint_message.code),
+            "replacements": [{
+                "path": path,
+                "range": range,
+                "replacement": replacement,
+            }],
+        }]);
+    }
+    Some((path, comment))
+}
+
+/// The message of the review: how many findings there are, and the ones
+/// that can't be put on a file, like linter failures.
+fn gerrit_review_message(lint_messages: &[LintMessage], general: &[&LintMessage]) -> String {
+    let errors = lint_messages
+        .iter()
+        .filter(|lint_message| lint_message.severity == LintSeverity::Error)
+        .count();
+    let mut message = if lint_messages.is_empty() {
+        "lintrunner found no lint issues.".to_string()
+    } else {
+        format!(
+            "lintrunner found {} issues, {} of them errors.",
+            lint_messages.len(),
+            errors
+        )
+    };
+    if !general.is_empty() {
+        message.push_str("\n\nNot about a file of this change:\n");
+        for lint_message in general {
+            let description = lint_message
+                .description
+                .as_deref()
+                .and_then(|description| description.lines().next())
+                .unwrap_or_default();
+            message.push_str(&format!(
+                "\n* {} ({}) {}",
+                lint_message.name, lint_message.code, description
+            ));
+        }
+    }
+    truncate(&mut message, MAX_ANNOTATION_MESSAGE_BYTES);
+    message
+}
+
+pub struct GerritOpts {
+    /// The base URL of the Gerrit server.
+    pub url: String,
+    /// The change to comment on. Defaults to `$GERRIT_CHANGE_NUMBER`.
+    pub change: Option<String>,
+    /// The patchset to comment on. Defaults to `$GERRIT_PATCHSET_NUMBER`.
+    pub patchset: Option<String>,
+    /// The environment variables holding the HTTP credentials.
+    pub user_env: String,
+    pub password_env: String,
+    /// A file of JSON lint messages to publish, instead of the last run's.
+    pub report: Option<String>,
+    /// The robot id the comments are posted under.
+    pub robot_id: String,
+}
+
+pub fn do_publish_gerrit(
+    persistent_data_store: &PersistentDataStore,
+    opts: GerritOpts,
+    offline: bool,
+) -> Result<i32> {
+    ensure!(!offline, "Can't publish to Gerrit with --offline.");
+    let change = match opts
+        .change
+        .or_else(|| std::env::var("GERRIT_CHANGE_NUMBER").ok())
+    {
+        Some(change) => change,
+        None => {
+            bail!("Pass --change, or set $GERRIT_CHANGE_NUMBER, to say which change to comment on.")
+        }
+    };
+    let patchset = match opts
+        .patchset
+        .or_else(|| std::env::var("GERRIT_PATCHSET_NUMBER").ok())
+    {
+        Some(patchset) => patchset,
+        None => bail!(
+            "Pass --patchset, or set $GERRIT_PATCHSET_NUMBER, to say which patchset to comment on."
+        ),
+    };
+    let user = std::env::var(&opts.user_env)
+        .with_context(|| format!("${} must hold a Gerrit user name", opts.user_env))?;
+    let password = std::env::var(&opts.password_env)
+        .with_context(|| format!("${} must hold a Gerrit HTTP password", opts.password_env))?;
+    let version_control = get_version_control()?;
+    let root = version_control.get_root();
+
+    let lint_messages = match &opts.report {
+        Some(report) => read_report(report)?,
+        None => persistent_data_store.past_lint_messages(None)?,
+    };
+    let robot_run_id = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let mut robot_comments: BTreeMap<String, Vec<serde_json::Value>> = BTreeMap::new();
+    let mut general = Vec::new();
+    for lint_message in &lint_messages {
+        match robot_comment(lint_message, root, &opts.robot_id, &robot_run_id) {
+            Some((path, comment)) => robot_comments.entry(path).or_default().push(comment),
+            None => general.push(lint_message),
+        }
+    }
+    let comments = robot_comments.values().map(Vec::len).sum::<usize>();
+
+    let url = format!(
+        "{}/a/changes/{}/revisions/{}/review",
+        opts.url.trim_end_matches('/'),
+        change,
+        patchset
+    );
+    let curl_config = [format!(
+        "user = {}",
+        curl_quote(&format!("{}:{}", user, password))
+    )];
+    let response = http_request(
+        "POST",
+        &url,
+        &curl_config,
+        &serde_json::json!({
+            "message": gerrit_review_message(&lint_messages, &general),
+            "tag": "autogenerated:lintrunner",
+            "robot_comments": robot_comments,
+        }),
+    )?;
+    // Gerrit prefixes JSON responses with a line against XSSI.
+    let response = String::from_utf8_lossy(&response);
+    let response = response.strip_prefix(")]}'").unwrap_or(&response);
+    serde_json::from_str::<serde_json::Value>(response)
+        .with_context(|| format!("Unexpected response from {}", url))?;
+    info!(
+        "Published {} robot comments to change {} patchset {}",
+        comments, change, patchset
+    );
+    println!(
+        "Published {} robot comments to {}/c/{}/{}",
+        comments,
+        opts.url.trim_end_matches('/'),
+        change,
+        patchset
+    );
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggestions_cover_changed_lines() {
+        let original = "a\nb\nc\nd\n";
+        assert_eq!(
+            suggestion(original, "a\nB\nC\nd\n"),
+            Some((2, 3, "```suggestion\nB\nC\n```".to_string()))
+        );
+        // Deleted lines are replaced with nothing.
+        assert_eq!(
+            suggestion(original, "a\nd\n"),
+            Some((2, 3, "```suggestion\n```".to_string()))
+        );
+        // Insertions are anchored to the line before.
+        assert_eq!(
+            suggestion(original, "a\nb\nx\nc\nd\n"),
+            Some((2, 2, "```suggestion\nb\nx\n```".to_string()))
+        );
+        assert_eq!(suggestion(original, original), None);
+    }
+
+    #[test]
+    fn fix_replacements_cover_changed_lines() {
+        let range = |start_line, start_character, end_line, end_character| GerritRange {
+            start_line,
+            start_character,
+            end_line,
+            end_character,
+        };
+        let original = "a\nb\nc\n";
+        assert_eq!(
+            fix_replacement(original, "a\nB\nc\n"),
+            Some((range(2, 0, 3, 0), "B\n".to_string()))
+        );
+        // The last line has no line after it to end the range at.
+        assert_eq!(
+            fix_replacement(original, "a\nb\nC\nD\n"),
+            Some((range(3, 0, 3, 1), "C\nD".to_string()))
+        );
+        assert_eq!(
+            fix_replacement(original, "a\n"),
+            Some((range(1, 1, 3, 1), String::new()))
+        );
+    }
+}
@@ -1,8 +1,13 @@
+use crate::interactive;
 use crate::persistent_data::{PersistentDataStore, RunInfo};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
 use console::style;
 use dialoguer::{theme::ColorfulTheme, Select};
+use flate2::{write::GzEncoder, Compression};
+use std::fmt::Write as _;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::process::Stdio;
 
@@ -11,6 +16,12 @@ fn select_past_runs(persistent_data_store: &PersistentDataStore) -> Result<Optio
     if runs.is_empty() {
         return Ok(None);
     }
+    if !interactive::can_prompt() {
+        bail!(
+            "Can't ask which run to report in non-interactive mode. \
+             Pick one with --invocation, --last, --last-failed or --since."
+        );
+    }
     let items: Vec<String> = runs
         .iter()
         .map(|(run_info, exit_info)| {
@@ -48,36 +59,218 @@ fn upload(report: String, cmd: &mut Command) -> Result<()> {
     Ok(())
 }
 
+// Environment variables that commonly affect how linters behave.
+const ENV_VARS_TO_REPORT: &[&str] = &["PATH", "LANG", "LC_ALL", "PYTHONIOENCODING", "SHELL"];
+
+fn environment_info() -> Result<String> {
+    let mut ret = String::new();
+    writeln!(ret, "lintrunner version: {}", env!("CARGO_PKG_VERSION"))?;
+    writeln!(
+        ret,
+        "platform: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    )?;
+    writeln!(ret, "cwd: {}", std::env::current_dir()?.display())?;
+    for var in ENV_VARS_TO_REPORT {
+        match std::env::var(var) {
+            Ok(value) => writeln!(ret, "{}={}", var, value)?,
+            Err(_) => writeln!(ret, "{} is not set", var)?,
+        }
+    }
+    Ok(ret)
+}
+
+fn append_bytes(
+    archive: &mut tar::Builder<impl Write>,
+    path: impl AsRef<Path>,
+    contents: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, path, contents)?;
+    Ok(())
+}
+
+/// Package everything we know about `run` into a `.tar.gz` in the current
+/// directory, returning its path.
+///
+/// The bundle contains the report printed by plain `lintrunner rage`, the
+/// files stored for the run (including the full output of each linter and the
+/// config the run was started with), and information about the environment.
+fn write_bundle(
+    persistent_data_store: &PersistentDataStore,
+    run: &RunInfo,
+    report: &str,
+) -> Result<PathBuf> {
+    let run_dir = persistent_data_store.run_dir(run);
+    // Unwrap ok because run dirs always have a name.
+    let name = format!(
+        "lintrunner_rage_{}",
+        run_dir.file_name().unwrap().to_string_lossy()
+    );
+    let bundle_path = PathBuf::from(format!("{}.tar.gz", name));
+    let file = std::fs::File::create(&bundle_path)
+        .with_context(|| format!("Failed to create {}", bundle_path.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let root = Path::new(&name);
+    append_bytes(&mut archive, root.join("report.txt"), report.as_bytes())?;
+    append_bytes(
+        &mut archive,
+        root.join("environment.txt"),
+        environment_info()?.as_bytes(),
+    )?;
+    archive
+        .append_dir_all(root.join("run"), &run_dir)
+        .context("Failed to add run data to bundle")?;
+
+    archive.into_inner()?.finish()?;
+    Ok(bundle_path)
+}
+
+/// Which past run(s) `lintrunner rage` reports on.
+pub enum RageSelection {
+    /// Ask the user to pick one.
+    Interactive,
+    /// A specific run. 0 is the most recent.
+    Invocation(usize),
+    /// The most recent run that exited with a non-zero code.
+    LastFailed,
+    /// Every run started at or after this time, most recent first.
+    Since(DateTime<FixedOffset>),
+}
+
+/// Parse the argument to `--since`: either an RFC 3339 timestamp (like the
+/// ones lintrunner records) or a date, meaning midnight local time.
+pub fn parse_since(since: &str) -> Result<DateTime<FixedOffset>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(since) {
+        return Ok(timestamp);
+    }
+    let date = NaiveDate::parse_from_str(since, "%Y-%m-%d").with_context(|| {
+        format!(
+            "Invalid --since '{}': expected an RFC 3339 timestamp or YYYY-MM-DD",
+            since
+        )
+    })?;
+    // Unwrap ok because midnight always exists.
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    match Local.from_local_datetime(&midnight).earliest() {
+        Some(timestamp) => Ok(timestamp.fixed_offset()),
+        None => bail!("Invalid --since '{}': no such local time", since),
+    }
+}
+
+fn select_runs(
+    persistent_data_store: &PersistentDataStore,
+    selection: RageSelection,
+) -> Result<Vec<RunInfo>> {
+    let runs = match selection {
+        RageSelection::Interactive => select_past_runs(persistent_data_store)?
+            .into_iter()
+            .collect(),
+        RageSelection::Invocation(invocation) => {
+            vec![persistent_data_store.past_run(invocation)?]
+        }
+        RageSelection::LastFailed => {
+            match persistent_data_store
+                .past_runs()?
+                .into_iter()
+                .find(|(_, exit_info)| exit_info.code != 0)
+            {
+                Some((run_info, _)) => vec![run_info],
+                None => bail!("Couldn't find a past run that failed"),
+            }
+        }
+        RageSelection::Since(since) => {
+            let runs = persistent_data_store
+                .past_runs()?
+                .into_iter()
+                .map(|(run_info, _)| run_info)
+                .filter(|run_info| {
+                    DateTime::parse_from_rfc3339(&run_info.timestamp)
+                        .map(|timestamp| timestamp >= since)
+                        .unwrap_or(false)
+                })
+                .collect::<Vec<_>>();
+            if runs.is_empty() {
+                bail!("Couldn't find a past run since {}", since.to_rfc3339());
+            }
+            runs
+        }
+    };
+    Ok(runs)
+}
+
 pub fn do_rage(
     persistent_data_store: &PersistentDataStore,
-    invocation: Option<usize>,
+    selection: RageSelection,
     gist: bool,
     pastry: bool,
+    all: bool,
+    offline: bool,
 ) -> Result<i32> {
-    let run = match invocation {
-        Some(invocation) => Some(persistent_data_store.past_run(invocation)?),
-        None => select_past_runs(persistent_data_store)?,
-    };
+    let runs = select_runs(persistent_data_store, selection)?;
+    if runs.is_empty() {
+        println!("{}", style("Nothing selected, exiting.").yellow());
+        return Ok(0);
+    }
 
-    match run {
-        Some(run) => {
-            let report = persistent_data_store
-                .get_run_report(&run)
-                .context("getting selected run report")?;
-            if gist {
-                upload(
-                    report.clone(),
-                    Command::new("gh").args(["gist", "create", "-"]),
-                )?;
-            } else if pastry {
-                upload(report.clone(), &mut Command::new("pastry"))?;
-            } else {
-                print!("{}", report);
-            }
-        }
-        None => {
-            println!("{}", style("Nothing selected, exiting.").yellow());
+    let mut reports = Vec::new();
+    for run in &runs {
+        let report = persistent_data_store
+            .get_run_report(run)
+            .context("getting selected run report")?;
+        if all {
+            let bundle_path = write_bundle(persistent_data_store, run, &report)?;
+            println!("Wrote rage bundle to {}", bundle_path.display());
         }
+        reports.push(report);
+    }
+    if all {
+        return Ok(0);
+    }
+
+    let report = reports.join("\n");
+    if (gist || pastry) && offline {
+        eprintln!(
+            "{}",
+            style("Not uploading the report because of --offline; printing it instead.")
+                .for_stderr()
+                .yellow()
+        );
+        print!("{}", report);
+    } else if gist {
+        upload(report, Command::new("gh").args(["gist", "create", "-"]))?;
+    } else if pastry {
+        upload(report, &mut Command::new("pastry"))?;
+    } else {
+        print!("{}", report);
     }
     Ok(0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_since_formats() -> Result<()> {
+        let timestamp = parse_since("2024-01-02T03:04:05.000+01:00")?;
+        assert_eq!(timestamp.to_rfc3339(), "2024-01-02T03:04:05+01:00");
+
+        let date = parse_since("2024-01-02")?;
+        assert_eq!(
+            date.with_timezone(&Local).naive_local(),
+            NaiveDate::from_ymd_opt(2024, 1, 2)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        );
+
+        assert!(parse_since("yesterday").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Recording linter invocations with `--record`, and re-rendering them with
+//! `lintrunner replay`.
+//!
+//! A recording captures exactly what each linter was given and what it
+//! produced, so adapter bugs can be reproduced without the user's checkout.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use console::Term;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    exit_code, group_lints_by_file,
+    linter::{linter_failure_message, parse_linter_output},
+    render::{
+        render_lint_messages, render_lint_messages_arc, render_lint_messages_json,
+        render_lint_messages_oneline, render_lint_messages_summary_markdown,
+        render_lint_messages_vscode, render_linter_done_vscode, PrintedLintErrors,
+    },
+    RenderOpt,
+};
+
+/// A single linter invocation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedInvocation {
+    pub code: String,
+    /// The command as it was run, with `{{PATHSFILE}}` substituted.
+    pub command: Vec<String>,
+    pub cwd: String,
+    /// Empty unless the run was started with `--record`.
+    pub env: BTreeMap<String, String>,
+    /// The contents of `{{PATHSFILE}}`, one path per entry.
+    pub paths: Vec<String>,
+    pub stdout: String,
+    pub stderr: String,
+    /// `None` if the linter was killed by a signal.
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedRun {
+    pub lintrunner_version: String,
+    pub invocations: Vec<RecordedInvocation>,
+}
+
+/// Collects invocations from the linter threads during a run.
+#[derive(Default)]
+pub struct Recorder {
+    invocations: Mutex<Vec<RecordedInvocation>>,
+    capture_env: bool,
+}
+
+impl Recorder {
+    /// `capture_env` says whether to keep each invocation's environment.
+    /// Only `--record` needs it, and copying it per invocation isn't free.
+    pub fn new(capture_env: bool) -> Recorder {
+        Recorder {
+            invocations: Mutex::default(),
+            capture_env,
+        }
+    }
+
+    pub fn captures_env(&self) -> bool {
+        self.capture_env
+    }
+
+    pub fn record(&self, invocation: RecordedInvocation) {
+        self.invocations.lock().unwrap().push(invocation);
+    }
+
+    /// Take everything recorded so far.
+    pub fn finish(&self) -> RecordedRun {
+        let mut invocations = std::mem::take(&mut *self.invocations.lock().unwrap());
+        // Linters finish in arbitrary order; sort so recordings are stable.
+        invocations.sort_by(|a, b| a.code.cmp(&b.code));
+        RecordedRun {
+            lintrunner_version: env!("CARGO_PKG_VERSION").to_string(),
+            invocations,
+        }
+    }
+}
+
+impl RecordedRun {
+    pub fn write(&self, path: &str) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write recording to '{}'", path))
+    }
+}
+
+/// Re-render the results of a run recorded with `--record`.
+pub fn do_replay(
+    recording_path: &str,
+    render_opt: RenderOpt,
+    wrap_width: Option<usize>,
+) -> Result<i32> {
+    let contents = fs::read_to_string(recording_path)
+        .with_context(|| format!("Failed to read recording at '{}'", recording_path))?;
+    let run: RecordedRun = serde_json::from_str(&contents)
+        .with_context(|| format!("'{}' is not a valid lintrunner recording", recording_path))?;
+
+    let mut stdout = Term::stdout();
+    let mut all_lints = HashMap::new();
+    for invocation in run.invocations {
+        let lints = parse_linter_output(
+            invocation.exit_code == Some(0),
+            &invocation.stdout,
+            &invocation.stderr,
+        )
+        .unwrap_or_else(|err| vec![linter_failure_message(&invocation.code, &err)]);
+
+        if render_opt == RenderOpt::Vscode {
+            let mut stdout = std::io::stdout().lock();
+            render_lint_messages_vscode(&mut stdout, &lints)?;
+            render_linter_done_vscode(&mut stdout, &invocation.code, lints.len())?;
+        }
+        group_lints_by_file(&mut all_lints, lints);
+    }
+
+    let did_print = match render_opt {
+        RenderOpt::Default => render_lint_messages(&mut stdout, &all_lints, wrap_width)?,
+        RenderOpt::Json => render_lint_messages_json(&mut stdout, &all_lints)?,
+        RenderOpt::Oneline => render_lint_messages_oneline(&mut stdout, &all_lints)?,
+        RenderOpt::Arc => render_lint_messages_arc(&mut stdout, &all_lints)?,
+        RenderOpt::SummaryMarkdown => {
+            render_lint_messages_summary_markdown(&mut stdout, &all_lints)?
+        }
+        RenderOpt::Vscode if all_lints.is_empty() => PrintedLintErrors::No,
+        RenderOpt::Vscode => PrintedLintErrors::Yes,
+    };
+
+    let findings = match did_print {
+        PrintedLintErrors::No => exit_code::SUCCESS,
+        PrintedLintErrors::Yes => exit_code::LINT_FINDINGS,
+    };
+    let failures = exit_code::for_failures(all_lints.values().flatten());
+    Ok(failures.map_or(findings, |failures| failures.max(findings)))
+}
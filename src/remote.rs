@@ -0,0 +1,207 @@
+//! Running linters on other machines, for `--remote-exec` (experimental).
+//!
+//! With a `[remote_exec]` section in the config, `--remote-exec` runs each
+//! linter invocation over SSH on one of a pool of hosts, so that huge runs
+//! can fan out across a farm:
+//!
+//! ```toml
+//! [remote_exec]
+//! hosts = ['lint1.example.com', 'lint2.example.com']
+//! ```
+//!
+//! The checkout must be at the same path on every host (e.g. on a shared
+//! filesystem), and the linters' toolchains must be installed there. Each
+//! invocation goes to the host running the fewest invocations. Its paths file
+//! is sent over SSH, and the linter's output and exit status come back the
+//! same way, so lint messages, fixes and failures are handled just like for
+//! local runs.
+//!
+//! Only SSH is supported for now, not Remote Execution API services.
+
+use std::{path::Path, sync::Mutex};
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteExecConfig {
+    /// The hosts to run linters on.
+    pub hosts: Vec<String>,
+    /// How to run a command on a host; the host and the command to run are
+    /// appended. Defaults to `['ssh', '-o', 'BatchMode=yes']`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_command: Option<Vec<String>>,
+}
+
+/// The hosts linters run on, and how busy each of them is.
+pub struct RemotePool {
+    ssh_command: Vec<String>,
+    hosts: Vec<String>,
+    running: Mutex<Vec<usize>>,
+}
+
+/// A host picked to run one invocation. The host counts as busy until this
+/// is dropped.
+pub struct HostLease<'a> {
+    pool: &'a RemotePool,
+    index: usize,
+}
+
+impl HostLease<'_> {
+    pub fn host(&self) -> &str {
+        &self.pool.hosts[self.index]
+    }
+}
+
+impl Drop for HostLease<'_> {
+    fn drop(&mut self) {
+        self.pool.running.lock().unwrap()[self.index] -= 1;
+    }
+}
+
+impl RemotePool {
+    pub fn new(config: &RemoteExecConfig) -> Result<RemotePool> {
+        ensure!(
+            !config.hosts.is_empty(),
+            "The [remote_exec] section of the config doesn't list any hosts."
+        );
+        let ssh_command = config.ssh_command.clone().unwrap_or_else(|| {
+            vec![
+                "ssh".to_string(),
+                "-o".to_string(),
+                "BatchMode=yes".to_string(),
+            ]
+        });
+        ensure!(
+            !ssh_command.is_empty(),
+            "The ssh_command of the [remote_exec] section is empty."
+        );
+        Ok(RemotePool {
+            ssh_command,
+            hosts: config.hosts.clone(),
+            running: Mutex::new(vec![0; config.hosts.len()]),
+        })
+    }
+
+    /// The local program used to reach the hosts.
+    pub fn ssh_program(&self) -> &str {
+        &self.ssh_command[0]
+    }
+
+    /// Pick the host running the fewest invocations.
+    pub fn lease(&self) -> HostLease<'_> {
+        let mut running = self.running.lock().unwrap();
+        let index = (0..running.len())
+            .min_by_key(|&i| running[i])
+            .unwrap_or_default();
+        running[index] += 1;
+        HostLease { pool: self, index }
+    }
+
+    /// The local command that runs `command` on `host`, from `cwd`, with
+    /// `env` set.
+    ///
+    /// With `paths_file`, the command reads the paths file from its stdin,
+    /// writes it to a temporary file on the host, and replaces `paths_file`
+    /// in `command` with the path of that file. With `paths_on_stdin`, the
+    /// paths file is also the linter's stdin.
+    ///
+    /// Without `paths_file`, arguments appended to the returned command are
+    /// passed on to `command`.
+    pub fn remote_command(
+        &self,
+        host: &str,
+        cwd: &Path,
+        env: &[(&str, &str)],
+        command: &[String],
+        paths_file: Option<&str>,
+        paths_on_stdin: bool,
+    ) -> Vec<String> {
+        let quote = |arg: &str| shell_words::quote(arg).into_owned();
+        let mut invocation = vec!["env".to_string()];
+        invocation.extend(
+            env.iter()
+                .map(|(name, value)| quote(&format!("{name}={value}"))),
+        );
+        let script = match paths_file {
+            Some(paths_file) => {
+                invocation.extend(command.iter().map(|arg| {
+                    if !arg.contains(paths_file) {
+                        return quote(arg);
+                    }
+                    arg.split(paths_file)
+                        .map(|part| {
+                            if part.is_empty() {
+                                String::new()
+                            } else {
+                                quote(part)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\"$f\"")
+                }));
+                if paths_on_stdin {
+                    invocation.push("< \"$f\"".to_string());
+                }
+                format!(
+                    "f=$(mktemp) || exit 1; cat > \"$f\"; cd {} && {}; s=$?; rm -f \"$f\"; exit $s",
+                    quote(&cwd.display().to_string()),
+                    invocation.join(" ")
+                )
+            }
+            None => {
+                invocation.extend(command.iter().map(|arg| quote(arg)));
+                format!(
+                    "cd {} && exec {}",
+                    quote(&cwd.display().to_string()),
+                    invocation.join(" ")
+                )
+            }
+        };
+        self.ssh_command
+            .iter()
+            .cloned()
+            .chain([host.to_string(), script])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_remote_commands() -> Result<()> {
+        let pool = RemotePool::new(&RemoteExecConfig {
+            hosts: vec!["a".to_string(), "b".to_string()],
+            ssh_command: None,
+        })?;
+        let first = pool.lease();
+        let second = pool.lease();
+        assert_eq!((first.host(), second.host()), ("a", "b"));
+        drop(second);
+        assert_eq!(pool.lease().host(), "b");
+
+        let command = ["flake8".to_string(), "@/tmp/paths file".to_string()];
+        assert_eq!(
+            pool.remote_command(
+                "a",
+                Path::new("/src/my repo"),
+                &[("LANG", "C.UTF-8")],
+                &command,
+                Some("/tmp/paths file"),
+                false
+            ),
+            vec![
+                "ssh",
+                "-o",
+                "BatchMode=yes",
+                "a",
+                "f=$(mktemp) || exit 1; cat > \"$f\"; cd '/src/my repo' && \
+                 env 'LANG=C.UTF-8' flake8 @\"$f\"; s=$?; rm -f \"$f\"; exit $s"
+            ]
+        );
+        Ok(())
+    }
+}
@@ -1,23 +1,88 @@
 use std::fmt;
 use std::io::Write;
-use std::{cmp, collections::HashMap, fs};
+use std::{
+    cmp,
+    collections::{BTreeMap, HashMap},
+    fs,
+    time::Duration,
+};
 
 use anyhow::{anyhow, Result};
+use clap::ArgEnum;
 use console::{style, Style, Term};
 use itertools::Itertools;
+use serde::Serialize;
 use similar::{ChangeTag, DiffableStr, TextDiff};
 use textwrap::indent;
 
-use crate::lint_message::{LintMessage, LintSeverity};
-use crate::path::get_display_path;
+use crate::badge::plural;
+use crate::budget::BudgetOverrun;
+use crate::columns::{convert_message, ColumnUnit, LineCache};
+use crate::lint_message::{FixKind, LintMessage, LintSeverity};
+use crate::patch::{changed_lines, line_edits, lines_of, PatchMetadata};
+use crate::path::{get_display_path, AbsPath};
+use crate::scheduler::LinterTiming;
+use crate::theme::{theme, ThemeExt};
 
 static CONTEXT_LINES: usize = 3;
 
+/// Width to wrap at when output isn't going to a terminal.
+const DEFAULT_WRAP_WIDTH: usize = 78;
+/// Don't wrap narrower than this, even on tiny terminals.
+const MIN_WRAP_WIDTH: usize = 40;
+
+/// The width to wrap human-readable output at: the terminal's width if
+/// stdout is a terminal, otherwise a fixed default. `None` if `no_wrap` is set.
+pub fn wrap_width(no_wrap: bool) -> Option<usize> {
+    if no_wrap {
+        return None;
+    }
+    let width = match Term::stdout().size_checked() {
+        Some((_rows, cols)) => cols as usize,
+        None => DEFAULT_WRAP_WIDTH,
+    };
+    Some(cmp::max(width, MIN_WRAP_WIDTH))
+}
+
+// Wrap options that only break lines between words, so identifiers, flags
+// and URLs in messages stay intact even if they overflow.
+fn wrap_options<'a>(
+    width: usize,
+    initial_indent: &'a str,
+    subsequent_indent: &'a str,
+) -> textwrap::Options<'a> {
+    textwrap::Options::new(width)
+        .initial_indent(initial_indent)
+        .subsequent_indent(subsequent_indent)
+        .break_words(false)
+        .word_splitter(textwrap::WordSplitter::NoHyphenation)
+}
+
+/// How the default output arranges lint messages.
+#[derive(ArgEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GroupBy {
+    /// A section per file, with that file's messages.
+    #[default]
+    File,
+    /// An entry per distinct message, listing everywhere it was reported.
+    Message,
+}
+
 pub enum PrintedLintErrors {
     Yes,
     No,
 }
 
+/// The messages in `lint_messages`, file by file in sorted order, so that
+/// the output doesn't depend on the order linters finished in.
+fn sorted_messages(
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> impl Iterator<Item = &LintMessage> {
+    let mut paths = lint_messages.keys().collect::<Vec<_>>();
+    paths.sort();
+    paths.into_iter().flat_map(|path| &lint_messages[path])
+}
+
 pub fn render_lint_messages_oneline(
     stdout: &mut impl Write,
     lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
@@ -25,7 +90,7 @@ pub fn render_lint_messages_oneline(
     let mut printed = false;
     let current_dir = std::env::current_dir()?;
 
-    for lint_message in lint_messages.values().flatten() {
+    for lint_message in sorted_messages(lint_messages) {
         printed = true;
         let display_path = match &lint_message.path {
             None => "[General linter failure]".to_string(),
@@ -76,7 +141,7 @@ pub fn render_lint_messages_json(
     lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
 ) -> Result<PrintedLintErrors> {
     let mut printed = false;
-    for lint_message in lint_messages.values().flatten() {
+    for lint_message in sorted_messages(lint_messages) {
         printed = true;
         writeln!(stdout, "{}", serde_json::to_string(lint_message)?)?;
     }
@@ -88,9 +153,363 @@ pub fn render_lint_messages_json(
     }
 }
 
+#[derive(Serialize)]
+struct LintMessageWithPatch<'a> {
+    #[serde(flatten)]
+    message: &'a LintMessage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    patch: Option<PatchMetadata>,
+}
+
+/// Like [`render_lint_messages_json`], but messages that suggest a replacement
+/// also carry a `patch` field describing it (see [`PatchMetadata`]).
+pub fn render_lint_messages_json_with_patches(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let mut printed = false;
+    for lint_message in sorted_messages(lint_messages) {
+        printed = true;
+        let patch = match (
+            &lint_message.path,
+            &lint_message.original,
+            &lint_message.replacement,
+        ) {
+            (Some(path), Some(original), Some(replacement)) => {
+                Some(PatchMetadata::new(path, original, replacement))
+            }
+            _ => None,
+        };
+        let message = LintMessageWithPatch {
+            message: lint_message,
+            patch,
+        };
+        writeln!(stdout, "{}", serde_json::to_string(&message)?)?;
+    }
+
+    if printed {
+        Ok(PrintedLintErrors::Yes)
+    } else {
+        Ok(PrintedLintErrors::No)
+    }
+}
+
+/// A lint message in the shape arcanist's `ArcanistLintMessage::newFromDictionary`
+/// takes, so that a custom arcanist linter can hand lintrunner's findings to
+/// `arc lint`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArcLintMessage<'a> {
+    /// Relative to the current directory, or null for general linter
+    /// failures.
+    path: Option<String>,
+    line: Option<usize>,
+    char: Option<usize>,
+    code: &'a str,
+    severity: &'static str,
+    name: &'a str,
+    description: Option<&'a str>,
+    /// The changed lines of a fix, starting at `line`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    original: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replacement: Option<String>,
+    /// General failures aren't on any line, so arc must not filter them out
+    /// as unchanged.
+    bypass_changed_line_filtering: bool,
+}
+
+fn arc_severity(severity: LintSeverity) -> &'static str {
+    match severity {
+        LintSeverity::Error => "error",
+        LintSeverity::Warning => "warning",
+        LintSeverity::Advice => "advice",
+        LintSeverity::Disabled => "disabled",
+    }
+}
+
+fn arc_lint_message<'a>(
+    lint_message: &'a LintMessage,
+    current_dir: &std::path::Path,
+) -> ArcLintMessage<'a> {
+    let mut message = ArcLintMessage {
+        path: lint_message
+            .path
+            .as_deref()
+            .map(|path| get_display_path(path, current_dir)),
+        line: lint_message.line,
+        char: lint_message.char,
+        code: &lint_message.code,
+        severity: arc_severity(lint_message.severity),
+        name: &lint_message.name,
+        description: lint_message.description.as_deref(),
+        original: None,
+        replacement: None,
+        bypass_changed_line_filtering: lint_message.path.is_none(),
+    };
+    // arc applies fixes by replacing `original` at `line` and `char`, so
+    // narrow whole-file fixes down to the lines they change.
+    if let Some((original, replacement)) = lint_message
+        .original
+        .as_deref()
+        .zip(lint_message.replacement.as_deref())
+    {
+        if let Some((old, new)) = changed_lines(original, replacement) {
+            message.line = Some(old.start + 1);
+            message.char = Some(1);
+            message.original = Some(lines_of(original, old));
+            message.replacement = Some(lines_of(replacement, new));
+        }
+    }
+    message
+}
+
+/// Print each lint message as a line of JSON for arcanist (see
+/// [`ArcLintMessage`]).
+pub fn render_lint_messages_arc(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let mut printed = false;
+    let current_dir = std::env::current_dir()?;
+    for lint_message in sorted_messages(lint_messages) {
+        printed = true;
+        let message = arc_lint_message(lint_message, &current_dir);
+        writeln!(stdout, "{}", serde_json::to_string(&message)?)?;
+    }
+
+    if printed {
+        Ok(PrintedLintErrors::Yes)
+    } else {
+        Ok(PrintedLintErrors::No)
+    }
+}
+
+/// A single event in the `--output vscode` stream. Each event is written as one
+/// line of JSON, so consumers can process results as each linter completes.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum VscodeEvent<'a> {
+    /// A lint message, shaped after VS Code's `Diagnostic`.
+    Diagnostic {
+        /// `file://` URI of the file, or null for general linter failures.
+        uri: Option<String>,
+        range: VscodeRange,
+        severity: &'static str,
+        code: &'a str,
+        source: &'static str,
+        name: &'a str,
+        message: &'a str,
+        /// Edits that apply the linter's suggested replacement.
+        fixes: Vec<VscodeEdit>,
+        /// Other locations relevant to the diagnostic, shaped after VS Code's
+        /// `DiagnosticRelatedInformation`.
+        #[serde(rename = "relatedInformation", skip_serializing_if = "Vec::is_empty")]
+        related_information: Vec<VscodeRelatedInformation>,
+    },
+    /// Emitted once a linter has finished and all of its diagnostics have been
+    /// written.
+    LinterDone { code: &'a str, count: usize },
+}
+
+/// A zero-based position, matching VS Code's `Position`.
+#[derive(Serialize, Clone, Copy)]
+struct VscodePosition {
+    line: usize,
+    character: usize,
+}
+
+#[derive(Serialize)]
+struct VscodeRange {
+    start: VscodePosition,
+    end: VscodePosition,
+}
+
+#[derive(Serialize)]
+struct VscodeLocation {
+    uri: String,
+    range: VscodeRange,
+}
+
+#[derive(Serialize)]
+struct VscodeRelatedInformation {
+    location: VscodeLocation,
+    message: String,
+}
+
+// Convert a 1-based LintMessage position to a 0-based VS Code one. A missing
+// start is the start of the file, and a missing end is the start.
+fn vscode_range(
+    line: Option<usize>,
+    char: Option<usize>,
+    end_line: Option<usize>,
+    end_char: Option<usize>,
+) -> VscodeRange {
+    let start = VscodePosition {
+        line: line.unwrap_or(1).saturating_sub(1),
+        character: char.unwrap_or(1).saturating_sub(1),
+    };
+    let end = match end_line {
+        Some(end_line) => VscodePosition {
+            line: end_line.saturating_sub(1),
+            character: end_char.unwrap_or(1).saturating_sub(1),
+        },
+        None => start,
+    };
+    VscodeRange { start, end }
+}
+
+#[derive(Serialize)]
+struct VscodeEdit {
+    range: VscodeRange,
+    #[serde(rename = "newText")]
+    new_text: String,
+}
+
+fn vscode_severity(severity: LintSeverity) -> &'static str {
+    match severity {
+        LintSeverity::Error => "error",
+        LintSeverity::Warning => "warning",
+        LintSeverity::Advice => "information",
+        LintSeverity::Disabled => "hint",
+    }
+}
+
+fn file_uri(path: &str) -> String {
+    let abs_path = match AbsPath::try_from(path) {
+        Ok(abs_path) => abs_path.to_string_lossy().to_string(),
+        Err(_) => path.to_string(),
+    };
+    let mut uri = String::from("file://");
+    if !abs_path.starts_with('/') {
+        // Windows paths like `C:\foo` need a leading slash.
+        uri.push('/');
+    }
+    for byte in abs_path.replace('\\', "/").bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+// Compute line-granular edits that turn `original` into `replacement`.
+fn vscode_edits(original: &str, replacement: &str) -> Vec<VscodeEdit> {
+    line_edits(original, replacement)
+        .into_iter()
+        .map(|(lines, new_text)| VscodeEdit {
+            range: VscodeRange {
+                start: VscodePosition {
+                    line: lines.start,
+                    character: 0,
+                },
+                end: VscodePosition {
+                    line: lines.end,
+                    character: 0,
+                },
+            },
+            new_text,
+        })
+        .collect()
+}
+
+pub fn render_lint_messages_vscode(
+    stdout: &mut impl Write,
+    lint_messages: &[LintMessage],
+) -> Result<PrintedLintErrors> {
+    let mut line_cache = LineCache::default();
+    for lint_message in lint_messages {
+        // VS Code counts columns in UTF-16 code units.
+        let mut lint_message = lint_message.clone();
+        convert_message(
+            &mut lint_message,
+            ColumnUnit::Char,
+            ColumnUnit::Utf16,
+            &mut line_cache,
+        );
+        let lint_message = &lint_message;
+        // LintMessage lines and columns are 1-based, VS Code's are 0-based.
+        let range = vscode_range(
+            lint_message.line,
+            lint_message.char,
+            lint_message.end_line,
+            lint_message.end_char,
+        );
+        // Suggestions have precise edits, so prefer them over diffing the
+        // replacement they were resolved to.
+        let fixes = match (
+            lint_message.suggestions.first(),
+            &lint_message.original,
+            &lint_message.replacement,
+        ) {
+            (Some(suggestion), _, _) => suggestion
+                .edits
+                .iter()
+                .map(|edit| VscodeEdit {
+                    range: vscode_range(
+                        Some(edit.line),
+                        Some(edit.char),
+                        Some(edit.end_line),
+                        Some(edit.end_char),
+                    ),
+                    new_text: edit.replacement.clone(),
+                })
+                .collect(),
+            (None, Some(original), Some(replacement)) => vscode_edits(original, replacement),
+            _ => Vec::new(),
+        };
+        let related_information = lint_message
+            .related
+            .iter()
+            .map(|related| VscodeRelatedInformation {
+                location: VscodeLocation {
+                    uri: file_uri(&related.path),
+                    range: vscode_range(
+                        related.line,
+                        related.char,
+                        related.end_line,
+                        related.end_char,
+                    ),
+                },
+                message: related.message.clone(),
+            })
+            .collect();
+        let event = VscodeEvent::Diagnostic {
+            uri: lint_message.path.as_deref().map(file_uri),
+            range,
+            severity: vscode_severity(lint_message.severity),
+            code: &lint_message.code,
+            source: "lintrunner",
+            name: &lint_message.name,
+            message: lint_message.description.as_deref().unwrap_or(""),
+            fixes,
+            related_information,
+        };
+        writeln!(stdout, "{}", serde_json::to_string(&event)?)?;
+    }
+
+    if lint_messages.is_empty() {
+        Ok(PrintedLintErrors::No)
+    } else {
+        Ok(PrintedLintErrors::Yes)
+    }
+}
+
+pub fn render_linter_done_vscode(stdout: &mut impl Write, code: &str, count: usize) -> Result<()> {
+    let event = VscodeEvent::LinterDone { code, count };
+    writeln!(stdout, "{}", serde_json::to_string(&event)?)?;
+    Ok(())
+}
+
+/// Render lint messages for humans. Descriptions are wrapped at `wrap_width`
+/// columns (see [`wrap_width`]), or left as-is if it is `None`.
 pub fn render_lint_messages(
     stdout: &mut impl Write,
     lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+    wrap_width: Option<usize>,
 ) -> Result<PrintedLintErrors> {
     if lint_messages.is_empty() {
         writeln!(stdout, "{} No lint issues.", style("ok").green())?;
@@ -98,10 +517,6 @@ pub fn render_lint_messages(
         return Ok(PrintedLintErrors::No);
     }
 
-    let wrap_78_indent_4 = textwrap::Options::new(78)
-        .initial_indent(spaces(4))
-        .subsequent_indent(spaces(4));
-
     // Always render messages in sorted order.
     let mut paths: Vec<&Option<String>> = lint_messages.keys().collect();
     paths.sort();
@@ -123,19 +538,46 @@ pub fn render_lint_messages(
                 write!(
                     stdout,
                     "{} Lint for {}:\n\n",
-                    style(">>>").bold(),
-                    style(path_to_print).underlined()
+                    style(">>>").theme_bold(),
+                    style(path_to_print).theme_underlined()
                 )?;
             }
         }
 
         for lint_message in lint_messages {
-            write_summary_line(stdout, lint_message)?;
+            write_summary_line(stdout, lint_message, wrap_width)?;
 
-            // Write the description.
             if let Some(description) = &lint_message.description {
-                for line in textwrap::wrap(description, &wrap_78_indent_4) {
-                    writeln!(stdout, "{}", line)?;
+                write_description(stdout, description, wrap_width)?;
+            }
+
+            for related in &lint_message.related {
+                let location = match (related.line, related.char) {
+                    (Some(line), Some(char)) => format!(":{}:{}", line, char),
+                    (Some(line), None) => format!(":{}", line),
+                    _ => String::new(),
+                };
+                writeln!(
+                    stdout,
+                    "{}{} {}{}: {}",
+                    spaces(4),
+                    style("note").theme_dim(),
+                    get_display_path(&related.path, &current_dir),
+                    location,
+                    related.message
+                )?;
+            }
+            // The first suggestion is shown as the diff below; mention the
+            // alternatives.
+            for suggestion in lint_message.suggestions.iter().skip(1) {
+                if let Some(description) = &suggestion.description {
+                    writeln!(
+                        stdout,
+                        "{}{} {}",
+                        spaces(4),
+                        style("alternative fix:").theme_dim(),
+                        description
+                    )?;
                 }
             }
 
@@ -143,7 +585,8 @@ pub fn render_lint_messages(
             if let (Some(original), Some(replacement)) =
                 (&lint_message.original, &lint_message.replacement)
             {
-                write_context_diff(stdout, original, replacement)?;
+                let suggested = lint_message.fix_kind == Some(FixKind::Suggested);
+                write_context_diff(stdout, original, replacement, suggested)?;
             } else if let (Some(highlight_line), Some(path)) = (&lint_message.line, path) {
                 // Otherwise, write the context code snippet.
                 write_context(stdout, path, highlight_line)?;
@@ -154,6 +597,369 @@ pub fn render_lint_messages(
     Ok(PrintedLintErrors::Yes)
 }
 
+/// Render lint messages for humans, like [`render_lint_messages`], but with
+/// messages that only differ in where they are (same linter, severity, name
+/// and description) folded into one entry that lists their locations. A sweep
+/// that finds the same problem in hundreds of files then takes a few lines.
+///
+/// Largest groups come first. Fixes aren't shown as diffs; locations that
+/// have one are marked.
+pub fn render_lint_messages_grouped(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+    wrap_width: Option<usize>,
+) -> Result<PrintedLintErrors> {
+    if lint_messages.is_empty() {
+        writeln!(stdout, "{} No lint issues.", style("ok").green())?;
+
+        return Ok(PrintedLintErrors::No);
+    }
+
+    let mut groups: BTreeMap<_, Vec<&LintMessage>> = BTreeMap::new();
+    for lint_message in lint_messages.values().flatten() {
+        let key = (
+            &lint_message.code,
+            lint_message.severity.label(),
+            &lint_message.name,
+            &lint_message.description,
+        );
+        groups.entry(key).or_default().push(lint_message);
+    }
+    let mut groups = groups.into_values().collect::<Vec<_>>();
+    // Stable, so groups of the same size stay in key order.
+    groups.sort_by_key(|group| cmp::Reverse(group.len()));
+
+    let current_dir = std::env::current_dir()?;
+    for mut group in groups {
+        group
+            .sort_by_key(|lint_message| (&lint_message.path, lint_message.line, lint_message.char));
+        let files = group
+            .iter()
+            .map(|lint_message| &lint_message.path)
+            .dedup()
+            .count();
+
+        stdout.write_all(b"\n\n")?;
+        writeln!(
+            stdout,
+            "{} {} in {}:\n",
+            style(">>>").theme_bold(),
+            plural(group.len(), "occurrence"),
+            plural(files, "file")
+        )?;
+        write_summary_line(stdout, group[0], wrap_width)?;
+        if let Some(description) = &group[0].description {
+            write_description(stdout, description, wrap_width)?;
+        }
+        writeln!(stdout)?;
+        for lint_message in group {
+            let mut location = match &lint_message.path {
+                None => "[General linter failure]".to_string(),
+                Some(path) => get_display_path(path, &current_dir),
+            };
+            if let Some(line) = lint_message.line {
+                location.push_str(&format!(":{}", line));
+                if let Some(char) = lint_message.char {
+                    location.push_str(&format!(":{}", char));
+                }
+            }
+            if lint_message.replacement.is_some() {
+                writeln!(
+                    stdout,
+                    "{}{} {}",
+                    spaces(4),
+                    location,
+                    style("(fixable)").theme_dim()
+                )?;
+            } else {
+                writeln!(stdout, "{}{}", spaces(4), location)?;
+            }
+        }
+    }
+
+    Ok(PrintedLintErrors::Yes)
+}
+
+/// Stop listing findings in the Markdown summary past this many bytes, to
+/// stay under the size limit of PR comments (65536 characters on GitHub).
+const MAX_MARKDOWN_BYTES: usize = 60_000;
+
+/// Escape the characters that Markdown (and the HTML it allows) would
+/// interpret in `text`.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '\\' | '`' | '*' | '_' | '[' | ']' | '|' | '#' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A code fence longer than any run of backticks in `content`.
+fn code_fence(content: &str) -> String {
+    let longest = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(cmp::max(3, longest + 1))
+}
+
+/// One finding of the Markdown summary, as a list item.
+fn markdown_finding(lint_message: &LintMessage, current_dir: &std::path::Path) -> String {
+    let path = lint_message
+        .path
+        .as_deref()
+        .map(|path| get_display_path(path, current_dir));
+    let mut location = path.clone().unwrap_or_else(|| "(no file)".to_string());
+    if let Some(line) = lint_message.line {
+        location.push_str(&format!(":{}", line));
+        if let Some(char) = lint_message.char {
+            location.push_str(&format!(":{}", char));
+        }
+    }
+    let mut item = format!(
+        "- **{}** `{}` {}\n",
+        lint_message.severity.label(),
+        location.replace('`', "'"),
+        escape_markdown(&lint_message.name)
+    );
+    if let Some(description) = &lint_message.description {
+        item.push('\n');
+        for line in description.trim_end().lines() {
+            item.push_str(&format!("  {}\n", escape_markdown(line)));
+        }
+    }
+    if let (Some(original), Some(replacement)) = (&lint_message.original, &lint_message.replacement)
+    {
+        let diff = PatchMetadata::new(path.as_deref().unwrap_or("file"), original, replacement)
+            .unified_diff;
+        let fence = code_fence(&diff);
+        let label = if lint_message.fix_kind == Some(FixKind::Suggested) {
+            "Suggested fix"
+        } else {
+            "Fix"
+        };
+        item.push_str(&format!("\n  {}:\n\n  {}diff\n", label, fence));
+        for line in diff.lines() {
+            item.push_str(&format!("  {}\n", line));
+        }
+        item.push_str(&format!("  {}\n", fence));
+    }
+    item
+}
+
+/// Render a Markdown summary of lint messages, to post as a PR comment: a
+/// table of counts per linter, then a collapsed section per linter listing
+/// its findings, with the diffs of their fixes.
+pub fn render_lint_messages_summary_markdown(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    writeln!(stdout, "## Lint results\n")?;
+    if lint_messages.is_empty() {
+        writeln!(stdout, "No lint issues.")?;
+        return Ok(PrintedLintErrors::No);
+    }
+
+    let mut by_linter: BTreeMap<&str, Vec<&LintMessage>> = BTreeMap::new();
+    for lint_message in lint_messages.values().flatten() {
+        by_linter
+            .entry(&lint_message.code)
+            .or_default()
+            .push(lint_message);
+    }
+    let total = by_linter.values().map(Vec::len).sum();
+    let files = lint_messages.keys().filter(|path| path.is_some()).count();
+    writeln!(
+        stdout,
+        "Found {} in {}.\n",
+        plural(total, "issue"),
+        plural(files, "file")
+    )?;
+
+    let severities = [
+        LintSeverity::Error,
+        LintSeverity::Warning,
+        LintSeverity::Advice,
+        LintSeverity::Disabled,
+    ];
+    writeln!(
+        stdout,
+        "| Linter | {} | Fixable |",
+        severities
+            .iter()
+            .map(|severity| severity.label())
+            .join(" | ")
+    )?;
+    writeln!(stdout, "| --- |{}", " ---: |".repeat(severities.len() + 1))?;
+    for (code, lint_messages) in &by_linter {
+        let counts = severities.iter().map(|severity| {
+            lint_messages
+                .iter()
+                .filter(|lint_message| lint_message.severity == *severity)
+                .count()
+        });
+        let fixable = lint_messages
+            .iter()
+            .filter(|lint_message| lint_message.replacement.is_some())
+            .count();
+        writeln!(
+            stdout,
+            "| {} | {} | {} |",
+            escape_markdown(code),
+            counts.map(|count| count.to_string()).join(" | "),
+            fixable
+        )?;
+    }
+
+    let current_dir = std::env::current_dir()?;
+    let mut written = 0;
+    let mut omitted = 0;
+    for (code, mut lint_messages) in by_linter {
+        lint_messages
+            .sort_by_key(|lint_message| (&lint_message.path, lint_message.line, lint_message.char));
+        let mut section = format!(
+            "\n<details>\n<summary><b>{}</b>: {}</summary>\n\n",
+            escape_markdown(code),
+            plural(lint_messages.len(), "issue")
+        );
+        for lint_message in lint_messages {
+            let item = markdown_finding(lint_message, &current_dir);
+            if written + section.len() + item.len() > MAX_MARKDOWN_BYTES {
+                omitted += 1;
+                continue;
+            }
+            section.push_str(&item);
+        }
+        section.push_str("\n</details>\n");
+        written += section.len();
+        stdout.write_all(section.as_bytes())?;
+    }
+    if omitted > 0 {
+        writeln!(
+            stdout,
+            "\n{} not shown, to keep this comment short.",
+            plural(omitted, "more issue")
+        )?;
+    }
+
+    Ok(PrintedLintErrors::Yes)
+}
+
+/// Explain that no linter ran on any file. `any_files_selected` says whether
+/// any files were selected for linting before matching against linter patterns.
+pub fn render_nothing_linted_notice(
+    stdout: &mut impl Write,
+    any_files_selected: bool,
+) -> Result<()> {
+    let reason = if any_files_selected {
+        "None of the selected files matched any linter's include patterns."
+    } else {
+        "No files were selected for linting."
+    };
+    writeln!(
+        stdout,
+        "{} {}\n    {}",
+        style("warning").yellow().theme_bold(),
+        reason,
+        style(
+            "Nothing was linted. Check that you are running lintrunner from the right \
+             directory, and that the paths or revision you passed select the files you expect."
+        )
+        .theme_dim()
+    )?;
+    Ok(())
+}
+
+pub fn render_time_budget_notice(
+    stdout: &mut impl Write,
+    not_run: &[String],
+    time_budget: Duration,
+) -> Result<()> {
+    writeln!(
+        stdout,
+        "{} Not run due to the {}s time budget: {}\n    {}",
+        style("warning").yellow().theme_bold(),
+        time_budget.as_secs(),
+        not_run.join(", "),
+        style(
+            "These linters were cancelled before they finished, so the results above are \
+             partial. Run without --time-budget to get complete results."
+        )
+        .theme_dim()
+    )?;
+    Ok(())
+}
+
+pub fn render_budget_overruns(stdout: &mut impl Write, overruns: &[BudgetOverrun]) -> Result<()> {
+    for overrun in overruns {
+        writeln!(
+            stdout,
+            "{} {} reported {} messages, over its budget of {}",
+            style("error").red().theme_bold(),
+            overrun.code,
+            overrun.count,
+            overrun.max,
+        )?;
+    }
+    writeln!(
+        stdout,
+        "    {}",
+        style(
+            "Fix the new findings, or raise the budget in the config if they are expected. \
+             After a cleanup, `lintrunner budget tighten` lowers budgets to the current counts."
+        )
+        .theme_dim()
+    )?;
+    Ok(())
+}
+
+pub fn render_timing(
+    stdout: &mut impl Write,
+    timings: &BTreeMap<String, LinterTiming>,
+) -> Result<()> {
+    let width = timings
+        .keys()
+        .map(|code| code.len())
+        .chain(std::iter::once("linter".len()))
+        .max()
+        .unwrap_or_default();
+    writeln!(
+        stdout,
+        "{}",
+        style(format!(
+            "{:width$}  {:>11}  {:>9}  {:>9}  {:>9}",
+            "linter", "invocations", "queued", "running", "done at"
+        ))
+        .theme_bold()
+    )?;
+    for (code, timing) in timings {
+        let done_at = match timing.done_at {
+            Some(done_at) => format!("{:.2}s", done_at.as_secs_f64()),
+            None => "-".to_string(),
+        };
+        writeln!(
+            stdout,
+            "{:width$}  {:>11}  {:>8.2}s  {:>8.2}s  {:>9}",
+            code,
+            timing.invocations,
+            timing.queued.as_secs_f64(),
+            timing.running.as_secs_f64(),
+            done_at
+        )?;
+    }
+    Ok(())
+}
+
 // Write formatted context lines, with an styled indicator for which line the lint is about
 fn write_context(stdout: &mut impl Write, path: &str, highlight_line: &usize) -> Result<()> {
     stdout.write_all(b"\n")?;
@@ -183,7 +989,7 @@ fn write_context(stdout: &mut impl Write, path: &str, highlight_line: &usize) ->
                     write!(
                         stdout,
                         "    >>> {:>width$}  |{}",
-                        style(line_number).dim(),
+                        style(line_number).theme_dim(),
                         style(line).yellow(),
                         width = max_pad
                     )?;
@@ -191,7 +997,7 @@ fn write_context(stdout: &mut impl Write, path: &str, highlight_line: &usize) ->
                     write!(
                         stdout,
                         "        {:>width$}  |{}",
-                        style(line_number).dim(),
+                        style(line_number).theme_dim(),
                         line,
                         width = max_pad
                     )?;
@@ -215,12 +1021,18 @@ fn write_context(stdout: &mut impl Write, path: &str, highlight_line: &usize) ->
 }
 
 // Write the context, computing and styling a diff from the original to the suggested replacement.
-fn write_context_diff(stdout: &mut impl Write, original: &str, replacement: &str) -> Result<()> {
-    writeln!(
-        stdout,
-        "\n    {}",
-        style("You can run `lintrunner -a` to apply this patch.").cyan()
-    )?;
+fn write_context_diff(
+    stdout: &mut impl Write,
+    original: &str,
+    replacement: &str,
+    suggested: bool,
+) -> Result<()> {
+    let msg = if suggested {
+        "This is a suggested fix. You can run `lintrunner -a --apply-suggested` to apply it."
+    } else {
+        "You can run `lintrunner -a` to apply this patch."
+    };
+    writeln!(stdout, "\n    {}", style(msg).cyan())?;
     stdout.write_all(b"\n")?;
     let diff = TextDiff::from_lines(original, replacement);
 
@@ -246,7 +1058,7 @@ fn write_context_diff(stdout: &mut impl Write, original: &str, replacement: &str
                 let (sign, s) = match change.tag() {
                     ChangeTag::Delete => ("-", Style::new().red()),
                     ChangeTag::Insert => ("+", Style::new().green()),
-                    ChangeTag::Equal => (" ", Style::new().dim()),
+                    ChangeTag::Equal => (" ", Style::new().theme_dim()),
                 };
                 let changeset = Changeset {
                     max_pad,
@@ -256,12 +1068,16 @@ fn write_context_diff(stdout: &mut impl Write, original: &str, replacement: &str
                 write!(
                     stdout,
                     "    {} |{}",
-                    style(changeset).dim(),
-                    s.apply_to(sign).bold()
+                    style(changeset).theme_dim(),
+                    s.apply_to(sign).theme_bold()
                 )?;
                 for (emphasized, value) in change.iter_strings_lossy() {
                     if emphasized {
-                        write!(stdout, "{}", s.apply_to(value).underlined().on_black())?;
+                        write!(
+                            stdout,
+                            "{}",
+                            s.apply_to(value).theme_underlined().on_black()
+                        )?;
                     } else {
                         write!(stdout, "{}", s.apply_to(value))?;
                     }
@@ -276,21 +1092,77 @@ fn write_context_diff(stdout: &mut impl Write, original: &str, replacement: &str
     Ok(())
 }
 
+// Write a message's description, indented under its summary line.
+fn write_description(
+    stdout: &mut impl Write,
+    description: &str,
+    wrap_width: Option<usize>,
+) -> Result<()> {
+    match wrap_width {
+        Some(width) => {
+            let options = wrap_options(width, spaces(4), spaces(4));
+            for line in textwrap::wrap(description, &options) {
+                writeln!(stdout, "{}", line)?;
+            }
+        }
+        None => {
+            for line in description.lines() {
+                writeln!(stdout, "{}{}", spaces(4), line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
 // Write: `   Error  (LINTER) prefer-using-this-over-that\n`
-fn write_summary_line(stdout: &mut impl Write, lint_message: &LintMessage) -> Result<()> {
-    let error_style = match lint_message.severity {
-        LintSeverity::Error => Style::new().on_red().bold(),
-        LintSeverity::Warning | LintSeverity::Advice | LintSeverity::Disabled => {
-            Style::new().on_yellow().bold()
+//
+// Long names are wrapped, with continuation lines indented to line up with
+// the description below.
+fn write_summary_line(
+    stdout: &mut impl Write,
+    lint_message: &LintMessage,
+    wrap_width: Option<usize>,
+) -> Result<()> {
+    let error_style = theme().severity_style(lint_message.severity);
+    let label = lint_message.severity.label();
+    let prefix = format!("  {} ({}) ", label, lint_message.code);
+    let name_lines = match wrap_width {
+        Some(width) => {
+            // Wrap as if the plain prefix were there, then swap in the styled
+            // one. The prefix is ASCII, apart from possibly the code.
+            let prefix_width = textwrap::core::display_width(&prefix);
+            let initial_indent = " ".repeat(prefix_width);
+            textwrap::wrap(
+                &lint_message.name,
+                wrap_options(width, &initial_indent, spaces(4)),
+            )
+            .into_iter()
+            .enumerate()
+            .map(|(idx, line)| {
+                if idx == 0 {
+                    line.get(prefix_width..).unwrap_or_default().to_string()
+                } else {
+                    line.into_owned()
+                }
+            })
+            .collect::<Vec<_>>()
         }
+        None => vec![lint_message.name.clone()],
     };
-    writeln!(
-        stdout,
-        "  {} ({}) {}",
-        error_style.apply_to(lint_message.severity.label()),
-        lint_message.code,
-        style(&lint_message.name).underlined(),
-    )?;
+    for (idx, line) in name_lines.iter().enumerate() {
+        if idx == 0 {
+            writeln!(
+                stdout,
+                "  {} ({}) {}",
+                error_style.apply_to(label),
+                lint_message.code,
+                style(line).theme_underlined(),
+            )?;
+        } else {
+            let text = line.strip_prefix(spaces(4)).unwrap_or(line);
+            writeln!(stdout, "{}{}", spaces(4), style(text).theme_underlined())?;
+        }
+    }
     Ok(())
 }
 
@@ -345,16 +1217,16 @@ impl fmt::Display for Changeset {
 }
 
 pub fn print_error(err: &anyhow::Error) -> std::io::Result<()> {
-    let mut stderr = Term::stderr();
+    let mut stderr = crate::theme::stderr();
     let mut chain = err.chain();
 
     if let Some(error) = chain.next() {
-        write!(stderr, "{} ", style("error:").red().bold())?;
+        write!(stderr, "{} ", style("error:").red().theme_bold())?;
         let indented = indent(&format!("{}", error), spaces(7));
         writeln!(stderr, "{}", indented)?;
 
         for cause in chain {
-            write!(stderr, "{} ", style("caused_by:").red().bold())?;
+            write!(stderr, "{} ", style("caused_by:").red().theme_bold())?;
             write!(stderr, " ")?;
             let indented = indent(&format!("{}", cause), spaces(11));
             writeln!(stderr, "{}", indented)?;
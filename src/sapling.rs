@@ -20,6 +20,10 @@ impl VersionControl for Repo {
         })
     }
 
+    fn get_root(&self) -> &AbsPath {
+        &self.root
+    }
+
     fn get_head(&self) -> anyhow::Result<String> {
         let mut cmd = std::process::Command::new("sl");
         cmd.arg("whereami");
@@ -0,0 +1,244 @@
+//! Sharing worker slots fairly between linters.
+//!
+//! Every linter invocation needs a slot to run: one per crate for linters
+//! using `{{CRATE_DIR}}`, one otherwise. When a slot frees up, it goes to the
+//! waiting linter using the fewest slots right now, and then to the one that
+//! has used the least slot time so far. A linter with many batches therefore
+//! can't keep the others waiting, and each linter's first result comes in as
+//! early as possible. Between linters that are even on both, the one with the
+//! highest `priority` goes first, so at the start of a run the slots go to
+//! the linters configured to start first.
+
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+/// How often waiters check whether they were cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Where a linter's time went, as shown by `--timing`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinterTiming {
+    /// How many times the linter was invoked.
+    pub invocations: usize,
+    /// Total time its invocations spent waiting for a slot.
+    pub queued: Duration,
+    /// Total time its invocations spent running.
+    pub running: Duration,
+    /// When, since the start of the run, its last invocation finished.
+    pub done_at: Option<Duration>,
+}
+
+#[derive(Default)]
+struct LinterState {
+    running: usize,
+    waiting: usize,
+    priority: i64,
+    timing: LinterTiming,
+}
+
+struct State {
+    free_slots: usize,
+    linters: BTreeMap<String, LinterState>,
+}
+
+impl State {
+    /// The waiting linter that should get the next free slot.
+    fn next_linter(&self) -> Option<&str> {
+        self.linters
+            .iter()
+            .filter(|(_, linter)| linter.waiting > 0)
+            .min_by_key(|(_, linter)| {
+                (
+                    linter.running,
+                    linter.timing.running,
+                    Reverse(linter.priority),
+                )
+            })
+            .map(|(code, _)| code.as_str())
+    }
+}
+
+pub struct Scheduler {
+    state: Mutex<State>,
+    slot_freed: Condvar,
+    start: Instant,
+}
+
+/// A worker slot, given back to the scheduler when dropped.
+pub struct Slot<'a> {
+    scheduler: &'a Scheduler,
+    code: String,
+    started: Instant,
+}
+
+impl Scheduler {
+    pub fn new(slots: usize) -> Scheduler {
+        Scheduler {
+            state: Mutex::new(State {
+                free_slots: slots.max(1),
+                linters: BTreeMap::new(),
+            }),
+            slot_freed: Condvar::new(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Break ties between linters by their priority, highest first.
+    pub fn with_priorities<'a>(self, priorities: impl IntoIterator<Item = (&'a str, i64)>) -> Self {
+        {
+            let mut state = self.state.lock().unwrap();
+            for (code, priority) in priorities {
+                state.linters.entry(code.to_string()).or_default().priority = priority;
+            }
+        }
+        self
+    }
+
+    /// The default number of slots for running `num_linters` linters: one
+    /// per core, but at least one per linter, so every linter can always make
+    /// progress.
+    pub fn default_slots(num_linters: usize) -> usize {
+        std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1)
+            .max(num_linters)
+    }
+
+    /// Wait for a slot to run an invocation of linter `code`. Returns `None`
+    /// if `cancelled` was set while waiting.
+    pub fn acquire(&self, code: &str, cancelled: &AtomicBool) -> Option<Slot<'_>> {
+        let queued_at = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        state.linters.entry(code.to_string()).or_default().waiting += 1;
+        loop {
+            if cancelled.load(Ordering::SeqCst) {
+                state.linters.get_mut(code).unwrap().waiting -= 1;
+                // Someone else may be next now.
+                self.slot_freed.notify_all();
+                return None;
+            }
+            if state.free_slots > 0 && state.next_linter() == Some(code) {
+                break;
+            }
+            state = self
+                .slot_freed
+                .wait_timeout(state, CANCEL_POLL_INTERVAL)
+                .unwrap()
+                .0;
+        }
+        state.free_slots -= 1;
+        let linter = state.linters.get_mut(code).unwrap();
+        linter.waiting -= 1;
+        linter.running += 1;
+        linter.timing.invocations += 1;
+        linter.timing.queued += queued_at.elapsed();
+        debug!(
+            "Linter {} got a slot after waiting {:?}",
+            code,
+            queued_at.elapsed()
+        );
+        // Other waiters may be able to take the remaining free slots.
+        if state.free_slots > 0 {
+            self.slot_freed.notify_all();
+        }
+        Some(Slot {
+            scheduler: self,
+            code: code.to_string(),
+            started: Instant::now(),
+        })
+    }
+
+    /// The timing of every linter that asked for a slot.
+    pub fn timings(&self) -> BTreeMap<String, LinterTiming> {
+        self.state
+            .lock()
+            .unwrap()
+            .linters
+            .iter()
+            .map(|(code, linter)| (code.clone(), linter.timing.clone()))
+            .collect()
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        let mut state = self.scheduler.state.lock().unwrap();
+        state.free_slots += 1;
+        let linter = state.linters.get_mut(&self.code).unwrap();
+        linter.running -= 1;
+        linter.timing.running += self.started.elapsed();
+        linter.timing.done_at = Some(self.scheduler.start.elapsed());
+        self.scheduler.slot_freed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linter(running: usize, waiting: usize, running_secs: u64) -> LinterState {
+        LinterState {
+            running,
+            waiting,
+            priority: 0,
+            timing: LinterTiming {
+                running: Duration::from_secs(running_secs),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn next_slot_goes_to_least_served_linter() {
+        let mut state = State {
+            free_slots: 1,
+            linters: BTreeMap::new(),
+        };
+        assert_eq!(state.next_linter(), None);
+
+        // A has many batches queued, but B isn't running anything yet.
+        state.linters.insert("A".to_string(), linter(1, 10, 0));
+        state.linters.insert("B".to_string(), linter(0, 1, 5));
+        assert_eq!(state.next_linter(), Some("B"));
+
+        // With both running as much, the one that has run less goes first.
+        state.linters.insert("B".to_string(), linter(1, 1, 5));
+        assert_eq!(state.next_linter(), Some("A"));
+
+        // Linters that aren't waiting don't get slots.
+        state.linters.insert("A".to_string(), linter(0, 0, 0));
+        assert_eq!(state.next_linter(), Some("B"));
+
+        // Between even linters, the one with the highest priority goes first.
+        state.linters.insert("A".to_string(), linter(0, 1, 0));
+        state.linters.insert("B".to_string(), linter(0, 1, 0));
+        assert_eq!(state.next_linter(), Some("A"));
+        state.linters.get_mut("B").unwrap().priority = 10;
+        assert_eq!(state.next_linter(), Some("B"));
+    }
+
+    #[test]
+    fn acquire_gives_up_when_cancelled() {
+        let scheduler = Scheduler::new(1);
+        let cancelled = AtomicBool::new(false);
+        let slot = scheduler.acquire("A", &cancelled);
+        assert!(slot.is_some());
+        cancelled.store(true, Ordering::SeqCst);
+        assert!(scheduler.acquire("B", &cancelled).is_none());
+        drop(slot);
+
+        let timings = scheduler.timings();
+        assert_eq!(timings["A"].invocations, 1);
+        assert!(timings["A"].done_at.is_some());
+        assert_eq!(timings["B"].invocations, 0);
+    }
+}
@@ -0,0 +1,85 @@
+//! Matching files by the interpreter in their shebang, for scripts that have
+//! no extension for `include_patterns` to go by.
+//!
+//! A linter's `match_shebang` lists interpreter names. A file whose first line
+//! is a shebang running one of them is linted, on top of the files its
+//! patterns include. Versions are ignored, so `python` matches
+//! `#!/usr/bin/python3.11`, and `env` is looked through, so `bash` matches
+//! `#!/usr/bin/env bash`.
+
+use std::{fs::File, io::Read, path::Path};
+
+/// How much of a file is read to find its shebang.
+const MAX_SHEBANG_LEN: u64 = 256;
+
+fn file_name(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
+
+/// The name of the interpreter in `line`, if it is a shebang: the file name of
+/// its program, or of the program `env` runs.
+fn interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let program = file_name(words.next()?);
+    if program != "env" {
+        return Some(program);
+    }
+    // Skip env's options, like `-S`, and variable assignments.
+    words
+        .find(|word| !word.starts_with('-') && !word.contains('='))
+        .map(file_name)
+}
+
+/// Whether `interpreter` is `name`, maybe followed by a version.
+fn is_interpreter(interpreter: &str, name: &str) -> bool {
+    interpreter
+        .strip_prefix(name)
+        .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+}
+
+/// Whether `file` starts with a shebang running one of `interpreters`. Files
+/// that can't be read don't.
+pub fn matches(file: &Path, interpreters: &[String]) -> bool {
+    if interpreters.is_empty() {
+        return false;
+    }
+    let mut head = Vec::new();
+    let read = File::open(file).and_then(|file| file.take(MAX_SHEBANG_LEN).read_to_end(&mut head));
+    if read.is_err() || !head.starts_with(b"#!") {
+        return false;
+    }
+    let line = String::from_utf8_lossy(&head);
+    let line = line.lines().next().unwrap_or_default();
+    interpreter(line).is_some_and(|interpreter| {
+        interpreters
+            .iter()
+            .any(|name| is_interpreter(interpreter, name))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_interpreter() {
+        assert_eq!(interpreter("#!/bin/bash"), Some("bash"));
+        assert_eq!(interpreter("#! /bin/sh -e"), Some("sh"));
+        assert_eq!(interpreter("#!/usr/bin/env python3"), Some("python3"));
+        assert_eq!(
+            interpreter("#!/usr/bin/env -S PYTHONUTF8=1 python3 -u"),
+            Some("python3")
+        );
+        assert_eq!(interpreter("#!/usr/bin/env"), None);
+        assert_eq!(interpreter("# just a comment"), None);
+    }
+
+    #[test]
+    fn versions_are_ignored() {
+        assert!(is_interpreter("python3.11", "python"));
+        assert!(is_interpreter("python3", "python3"));
+        assert!(!is_interpreter("python3", "python3.11"));
+        assert!(!is_interpreter("pythonw", "python"));
+        assert!(!is_interpreter("bash", "sh"));
+    }
+}
@@ -0,0 +1,159 @@
+//! Detecting files that change while they're being linted.
+//!
+//! An editor auto-saving in the middle of a run leaves lint messages pointing
+//! at lines that have since moved. Before linters are dispatched, the size
+//! and modification time of every file to lint is recorded; when a linter's
+//! results come back, the files it linted are checked against that snapshot.
+//! Messages on a file that changed get a warning saying they may be stale,
+//! or, with `--rerun-modified`, the linter is run again on those files.
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use crate::{
+    io_limit,
+    lint_message::{LintMessage, LintSeverity},
+    path::AbsPath,
+};
+
+/// What a file looked like: its size and modification time, or `None` if it
+/// couldn't be read.
+type FileState = Option<(u64, SystemTime)>;
+
+fn file_state(path: &AbsPath) -> FileState {
+    let metadata = io_limit::limited(|| std::fs::metadata(path)).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// The state of a set of files at one point in time.
+pub struct FileSnapshot {
+    states: Mutex<HashMap<AbsPath, FileState>>,
+}
+
+impl FileSnapshot {
+    pub fn take(files: &[AbsPath]) -> FileSnapshot {
+        FileSnapshot {
+            states: Mutex::new(
+                files
+                    .iter()
+                    .map(|file| (file.clone(), file_state(file)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Record the current state of `files`, after lintrunner itself changed
+    /// them, e.g. by applying fixes.
+    pub fn update(&self, files: &[AbsPath]) {
+        let mut states = self.states.lock().unwrap();
+        for file in files {
+            if let Some(state) = states.get_mut(file) {
+                *state = file_state(file);
+            }
+        }
+    }
+
+    /// The files among `files` that changed since the snapshot was taken.
+    /// Files that weren't in the snapshot are ignored.
+    pub fn modified(&self, files: &[AbsPath]) -> HashSet<AbsPath> {
+        let states = self.states.lock().unwrap();
+        files
+            .iter()
+            .filter(|file| {
+                states
+                    .get(*file)
+                    .is_some_and(|state| *state != file_state(file))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Whether `message` is about one of `files`.
+pub fn is_about(message: &LintMessage, files: &HashSet<AbsPath>) -> bool {
+    message
+        .path
+        .as_ref()
+        .is_some_and(|path| AbsPath::try_from(path).is_ok_and(|path| files.contains(&path)))
+}
+
+/// Add a warning for every file in `modified` that linter `code` reported
+/// messages on.
+pub fn flag_modified(
+    mut messages: Vec<LintMessage>,
+    code: &str,
+    modified: &HashSet<AbsPath>,
+) -> Vec<LintMessage> {
+    let mut flagged = HashSet::new();
+    let mut warnings = Vec::new();
+    for message in &messages {
+        if !is_about(message, modified) {
+            continue;
+        }
+        let path = message.path.clone().unwrap_or_default();
+        if flagged.insert(path.clone()) {
+            warnings.push(modified_during_run_message(code, &path));
+        }
+    }
+    messages.extend(warnings);
+    messages
+}
+
+fn modified_during_run_message(code: &str, path: &str) -> LintMessage {
+    LintMessage {
+        path: Some(path.to_string()),
+        description: Some(
+            "The file changed while the linter was running, so its other messages \
+             may be stale or point at the wrong lines. Re-run lintrunner, or pass \
+             --rerun-modified to do it automatically."
+                .to_string(),
+        ),
+        ..LintMessage::new(code, LintSeverity::Warning, "modified during run")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn message(path: &AbsPath) -> LintMessage {
+        LintMessage {
+            path: Some(path.display().to_string()),
+            line: Some(1),
+            ..modified_during_run_message("X", "")
+        }
+    }
+
+    #[test]
+    fn flags_messages_on_modified_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let edited = dir.path().join("edited.py");
+        let untouched = dir.path().join("untouched.py");
+        std::fs::write(&edited, "x = 1\n")?;
+        std::fs::write(&untouched, "y = 1\n")?;
+        let files = vec![
+            AbsPath::try_from(edited.as_path())?,
+            AbsPath::try_from(untouched.as_path())?,
+        ];
+
+        let snapshot = FileSnapshot::take(&files);
+        assert!(snapshot.modified(&files).is_empty());
+        std::fs::write(&edited, "x = 12\n")?;
+        let modified = snapshot.modified(&files);
+        assert_eq!(modified, HashSet::from([files[0].clone()]));
+        snapshot.update(&files[..1]);
+        assert!(snapshot.modified(&files).is_empty());
+
+        let messages = vec![message(&files[0]), message(&files[0]), message(&files[1])];
+        let flagged = flag_modified(messages, "X", &modified);
+        assert_eq!(flagged.len(), 4);
+        assert_eq!(flagged[3].name, "modified during run");
+        assert_eq!(flagged[3].path, Some(files[0].display().to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,136 @@
+//! Collecting a linter's stdout without holding all of it in memory.
+//!
+//! Output is kept in memory up to [`SPILL_THRESHOLD`] and spilled to a
+//! temporary file past that, then parsed as it is read back. A linter can also
+//! set `max_output_bytes`, past which it is stopped and reported as failed,
+//! instead of filling up the disk.
+
+use std::{
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use tempfile::SpooledTempFile;
+
+/// How much output is kept in memory before spilling to disk.
+pub const SPILL_THRESHOLD: usize = 16 * 1024 * 1024;
+
+/// Everything a linter wrote to a stream, up to its limit.
+pub struct SpooledOutput {
+    file: SpooledTempFile,
+    len: u64,
+}
+
+impl SpooledOutput {
+    /// Read all of `pipe`. If more than `limit` bytes come in, stop reading
+    /// and set `exceeded`.
+    pub fn collect(
+        pipe: Option<impl Read>,
+        limit: Option<u64>,
+        exceeded: &AtomicBool,
+    ) -> io::Result<SpooledOutput> {
+        let mut output = SpooledOutput {
+            file: SpooledTempFile::new(SPILL_THRESHOLD),
+            len: 0,
+        };
+        let mut pipe = match pipe {
+            Some(pipe) => pipe,
+            None => return Ok(output),
+        };
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = match pipe.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                // E.g. EIO from a pty whose child exited. Whatever we got so
+                // far is the best we can do.
+                Err(_) => break,
+            };
+            if limit.is_some_and(|limit| output.len + n as u64 > limit) {
+                exceeded.store(true, Ordering::SeqCst);
+                break;
+            }
+            output.file.write_all(&buf[..n])?;
+            output.len += n as u64;
+        }
+        Ok(output)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the output was too big to keep in memory.
+    pub fn is_spilled(&self) -> bool {
+        self.file.is_rolled()
+    }
+
+    /// Up to the first `max` bytes of the output, lossily decoded, with a
+    /// note about anything left out.
+    pub fn head(&mut self, max: usize) -> io::Result<String> {
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut bytes = Vec::new();
+        (&mut self.file).take(max as u64).read_to_end(&mut bytes)?;
+        let mut head = String::from_utf8_lossy(&bytes).into_owned();
+        if self.len > max as u64 {
+            head.push_str(&format!(
+                "\n[{} more bytes not shown]\n",
+                self.len - max as u64
+            ));
+        }
+        Ok(head)
+    }
+
+    /// Read the output from the start.
+    pub fn reader(&mut self) -> io::Result<impl Read + '_> {
+        self.file.seek(SeekFrom::Start(0))?;
+        Ok(BufReader::new(&mut self.file))
+    }
+
+    /// Iterate over the lines of the output, without their line endings.
+    pub fn lines(&mut self) -> io::Result<impl Iterator<Item = io::Result<Vec<u8>>> + '_> {
+        self.file.seek(SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(&mut self.file);
+        Ok(std::iter::from_fn(move || {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => None,
+                Ok(_) => {
+                    while matches!(line.last(), Some(b'\n' | b'\r')) {
+                        line.pop();
+                    }
+                    Some(Ok(line))
+                }
+                Err(err) => Some(Err(err)),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_lines_up_to_limit() -> io::Result<()> {
+        let exceeded = AtomicBool::new(false);
+        let mut output = SpooledOutput::collect(Some(&b"one\r\ntwo\nthree"[..]), None, &exceeded)?;
+        assert!(!exceeded.load(Ordering::SeqCst));
+        assert_eq!(output.len(), 14);
+        assert_eq!(
+            output.lines()?.collect::<io::Result<Vec<_>>>()?,
+            vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()]
+        );
+        assert_eq!(output.head(3)?, "one\n[11 more bytes not shown]\n");
+
+        let output = SpooledOutput::collect(Some(&b"0123456789"[..]), Some(5), &exceeded)?;
+        assert!(exceeded.load(Ordering::SeqCst));
+        assert!(output.len() <= 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+//! Streaming lint messages to a socket as each linter finishes, for
+//! `--stream-to`. Messages are written as JSON lines, in the same format as
+//! `--output json`, so dashboards and editor plugins can show results while
+//! the run is still going.
+
+use std::{
+    io::Write,
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use anyhow::{bail, Context, Result};
+use log::warn;
+
+use crate::lint_message::LintMessage;
+
+/// Where to stream to, parsed from `unix:/path.sock` or `tcp:host:port`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum StreamTarget {
+    Unix(String),
+    Tcp(String),
+}
+
+impl StreamTarget {
+    pub fn parse(target: &str) -> Result<StreamTarget> {
+        if let Some(path) = target.strip_prefix("unix:") {
+            Ok(StreamTarget::Unix(path.to_string()))
+        } else if let Some(address) = target.strip_prefix("tcp:") {
+            Ok(StreamTarget::Tcp(address.to_string()))
+        } else {
+            bail!(
+                "Invalid --stream-to '{}': expected unix:/path/to.sock or tcp:host:port",
+                target
+            )
+        }
+    }
+}
+
+pub struct LintStream {
+    writer: Mutex<Box<dyn Write + Send>>,
+    // Set after a write fails, so we only warn once.
+    broken: AtomicBool,
+}
+
+impl LintStream {
+    pub fn connect(target: &StreamTarget) -> Result<LintStream> {
+        let writer: Box<dyn Write + Send> = match target {
+            #[cfg(unix)]
+            StreamTarget::Unix(path) => Box::new(
+                std::os::unix::net::UnixStream::connect(path)
+                    .with_context(|| format!("Failed to connect to socket '{}'", path))?,
+            ),
+            #[cfg(not(unix))]
+            StreamTarget::Unix(_) => bail!("Unix sockets aren't supported on this platform"),
+            StreamTarget::Tcp(address) => Box::new(
+                TcpStream::connect(address)
+                    .with_context(|| format!("Failed to connect to '{}'", address))?,
+            ),
+        };
+        Ok(LintStream {
+            writer: Mutex::new(writer),
+            broken: AtomicBool::new(false),
+        })
+    }
+
+    /// Write out `lints`. A consumer going away doesn't fail the run; the
+    /// remaining messages are just not streamed.
+    pub fn send(&self, lints: &[LintMessage]) {
+        if self.broken.load(Ordering::SeqCst) {
+            return;
+        }
+        let mut writer = self.writer.lock().unwrap();
+        let res = lints
+            .iter()
+            .try_for_each(|lint| -> Result<()> {
+                writeln!(writer, "{}", serde_json::to_string(lint)?)?;
+                Ok(())
+            })
+            .and_then(|_| Ok(writer.flush()?));
+        if let Err(err) = res {
+            warn!("Stopped streaming lint messages: {:#}", err);
+            self.broken.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_targets() {
+        assert_eq!(
+            StreamTarget::parse("unix:/tmp/lint.sock").unwrap(),
+            StreamTarget::Unix("/tmp/lint.sock".to_string())
+        );
+        assert_eq!(
+            StreamTarget::parse("tcp:localhost:9000").unwrap(),
+            StreamTarget::Tcp("localhost:9000".to_string())
+        );
+        assert!(StreamTarget::parse("/tmp/lint.sock").is_err());
+    }
+}
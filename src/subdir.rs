@@ -0,0 +1,165 @@
+//! Running lintrunner from a subdirectory of the config's.
+//!
+//! Without `--config`, lintrunner uses the `.lintrunner.toml` in the current
+//! directory or, failing that, the closest one above it in the repo, so it can
+//! be run from anywhere in the repo. With `nested_configs = true`, the configs
+//! of subprojects have the same name as the root one, so a config above the
+//! closest one that turns nested configs on is used instead. Linters' patterns are always relative to the config's
+//! directory, while paths given on the command line are relative to the
+//! current directory, like for any other tool. `--relative-to=config` resolves
+//! them against the config's directory instead, e.g. to paste paths from
+//! lintrunner's output while somewhere else in the tree.
+
+use std::path::{Path, PathBuf};
+
+use clap::ArgEnum;
+
+use crate::lint_config::ConfigFormat;
+
+/// What version control keeps at the root of a repo.
+const VCS_MARKERS: &[&str] = &[".git", ".sl", ".hg"];
+
+/// What relative paths on the command line are resolved against.
+#[derive(ArgEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RelativeTo {
+    /// The current directory.
+    #[default]
+    Cwd,
+    /// The directory of the config.
+    Config,
+}
+
+/// The root of the repo `dir` is in, if it is in one.
+fn vcs_root(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|ancestor| {
+        VCS_MARKERS
+            .iter()
+            .any(|marker| ancestor.join(marker).exists())
+    })
+}
+
+/// Whether the config at `path` sets `nested_configs = true`.
+fn has_nested_configs(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| ConfigFormat::of(path).parse(&contents).ok())
+        .and_then(|config| config.get("nested_configs")?.as_bool())
+        .unwrap_or(false)
+}
+
+/// The config named `name` to use from `dir`: the closest one in `dir` or a
+/// directory above it, up to the root of the repo. If a config above that one
+/// has `nested_configs = true`, the closest one is one of its nested configs,
+/// so the outermost config that has is used instead.
+pub fn find_config(dir: &Path, name: &str) -> Option<PathBuf> {
+    let ancestors = match vcs_root(dir) {
+        Some(root) => dir.ancestors().count() - root.ancestors().count() + 1,
+        None => usize::MAX,
+    };
+    let configs = dir
+        .ancestors()
+        .take(ancestors)
+        .map(|ancestor| ancestor.join(name))
+        .filter(|config| config.is_file())
+        .collect::<Vec<_>>();
+    let (closest, above) = configs.split_first()?;
+    let outermost_nesting = above.iter().rev().find(|config| has_nested_configs(config));
+    Some(outermost_nesting.unwrap_or(closest).clone())
+}
+
+/// `path`, from the command line, resolved as `relative_to` says. Absolute
+/// paths are left alone.
+pub fn resolve(path: &str, relative_to: RelativeTo, config_dir: &Path) -> String {
+    match relative_to {
+        RelativeTo::Config if Path::new(path).is_relative() => {
+            config_dir.join(path).to_string_lossy().into_owned()
+        }
+        _ => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_closest_config() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let deep = root.join("a/b/c");
+        std::fs::create_dir_all(&deep)?;
+        std::fs::create_dir(root.join(".git"))?;
+        std::fs::write(root.join(".lintrunner.toml"), "")?;
+        std::fs::write(root.join("a/.lintrunner.toml"), "")?;
+
+        assert_eq!(
+            find_config(&deep, ".lintrunner.toml"),
+            Some(root.join("a/.lintrunner.toml"))
+        );
+        assert_eq!(
+            find_config(&root.join("a/b"), ".lintrunner.toml"),
+            Some(root.join("a/.lintrunner.toml"))
+        );
+        assert_eq!(find_config(&deep, ".lintrunner.yaml"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn stops_at_the_repo_root() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let outside = dir.path().canonicalize()?;
+        let repo = outside.join("repo");
+        std::fs::create_dir_all(repo.join(".git"))?;
+        std::fs::create_dir_all(repo.join("src"))?;
+        std::fs::write(outside.join(".lintrunner.toml"), "")?;
+
+        assert_eq!(find_config(&repo.join("src"), ".lintrunner.toml"), None);
+        std::fs::write(repo.join(".lintrunner.toml"), "")?;
+        assert_eq!(
+            find_config(&repo.join("src"), ".lintrunner.toml"),
+            Some(repo.join(".lintrunner.toml"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn nested_configs_are_not_primary() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let deep = root.join("sub/deep");
+        std::fs::create_dir_all(&deep)?;
+        std::fs::create_dir(root.join(".git"))?;
+        std::fs::write(root.join(".lintrunner.toml"), "")?;
+        std::fs::write(root.join("sub/.lintrunner.toml"), "")?;
+        assert_eq!(
+            find_config(&deep, ".lintrunner.toml"),
+            Some(root.join("sub/.lintrunner.toml"))
+        );
+
+        std::fs::write(root.join(".lintrunner.toml"), "nested_configs = true\n")?;
+        assert_eq!(
+            find_config(&deep, ".lintrunner.toml"),
+            Some(root.join(".lintrunner.toml"))
+        );
+        assert_eq!(
+            find_config(&root.join("sub"), ".lintrunner.toml"),
+            Some(root.join(".lintrunner.toml"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn resolves_relative_paths() {
+        let config_dir = Path::new("/repo");
+        assert_eq!(resolve("src/a.py", RelativeTo::Cwd, config_dir), "src/a.py");
+        assert_eq!(
+            resolve("src/a.py", RelativeTo::Config, config_dir),
+            "/repo/src/a.py"
+        );
+        assert_eq!(
+            resolve("/elsewhere/a.py", RelativeTo::Config, config_dir),
+            "/elsewhere/a.py"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Inline suppressions: a `lintrunner: ignore[CODE]` comment on a line drops
+//! the messages of linter `CODE` on that line.
+//!
+//! ```python
+//! eval(user_input)  # lintrunner: ignore[BANDIT]
+//! ```
+//!
+//! Several codes can be listed, separated by commas, and a bare
+//! `lintrunner: ignore` drops the messages of every linter. Suppressions are
+//! written by `lintrunner triage`, but can also be added by hand.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{bail, Context, Result};
+
+use crate::lint_message::LintMessage;
+
+const MARKER: &str = "lintrunner: ignore";
+
+/// The codes suppressed on `line`: `Some(vec![])` for every code, or `None`
+/// if the line has no suppression.
+fn suppressed_codes(line: &str) -> Option<Vec<&str>> {
+    let rest = &line[line.find(MARKER)? + MARKER.len()..];
+    match rest.strip_prefix('[') {
+        Some(codes) => {
+            let codes = &codes[..codes.find(']')?];
+            Some(codes.split(',').map(str::trim).collect())
+        }
+        None => Some(Vec::new()),
+    }
+}
+
+fn is_suppressed(line: &str, code: &str) -> bool {
+    suppressed_codes(line).is_some_and(|codes| codes.is_empty() || codes.contains(&code))
+}
+
+/// Drop the messages suppressed by a comment on their line.
+pub fn remove_suppressed(messages: Vec<LintMessage>) -> Vec<LintMessage> {
+    // Only files that mention the marker at all, split into lines.
+    let mut files: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    messages
+        .into_iter()
+        .filter(|message| {
+            let (path, line) = match (&message.path, message.line) {
+                (Some(path), Some(line)) if line > 0 => (path, line),
+                _ => return true,
+            };
+            let lines = files.entry(path.clone()).or_insert_with(|| {
+                let contents = std::fs::read(path).ok()?;
+                let contents = String::from_utf8_lossy(&contents);
+                contents
+                    .contains(MARKER)
+                    .then(|| contents.lines().map(str::to_string).collect())
+            });
+            !lines
+                .as_ref()
+                .and_then(|lines| lines.get(line - 1))
+                .is_some_and(|text| is_suppressed(text, &message.code))
+        })
+        .collect()
+}
+
+/// How line comments start in the language of `path`, going by its name or
+/// extension, or `None` if we don't know, or it has none (like JSON).
+fn comment_prefix(path: &Path) -> Option<&'static str> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    if matches!(
+        name,
+        "Makefile" | "Dockerfile" | "BUILD" | "WORKSPACE" | "CMakeLists.txt"
+    ) {
+        return Some("#");
+    }
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    match extension {
+        "c" | "cc" | "cpp" | "cxx" | "h" | "hh" | "hpp" | "cu" | "cuh" | "m" | "mm" | "rs"
+        | "go" | "java" | "kt" | "scala" | "swift" | "js" | "jsx" | "ts" | "tsx" | "cs"
+        | "proto" | "php" => Some("//"),
+        "py" | "pyi" | "sh" | "bash" | "zsh" | "rb" | "pl" | "pm" | "r" | "R" | "yaml" | "yml"
+        | "toml" | "cmake" | "mk" | "bzl" | "bazel" | "nix" | "ps1" | "tcl" | "jl" | "ex"
+        | "exs" => Some("#"),
+        "sql" | "lua" | "hs" => Some("--"),
+        "tex" | "erl" => Some("%"),
+        "bat" | "cmd" => Some("REM"),
+        _ => None,
+    }
+}
+
+/// Suppress linter `code` on line `line` (1-based) of `path`, by adding a
+/// comment at the end of the line, or adding `code` to the suppression
+/// already there. Returns false, leaving the file alone, if we don't know how
+/// to write a comment in it.
+pub fn add_suppression(path: &Path, line: usize, code: &str) -> Result<bool> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let mut lines = contents.split_inclusive('\n').collect::<Vec<_>>();
+    let index = match line.checked_sub(1) {
+        Some(index) if index < lines.len() => index,
+        _ => bail!("'{}' has no line {}", path.display(), line),
+    };
+    let original = lines[index];
+    let (text, ending) = match original.strip_suffix("\r\n") {
+        Some(text) => (text, "\r\n"),
+        None => match original.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (original, ""),
+        },
+    };
+    let updated = match suppressed_codes(text) {
+        Some(codes) if codes.is_empty() || codes.contains(&code) => return Ok(true),
+        Some(codes) => {
+            let start = text.find(MARKER).unwrap_or_default() + MARKER.len();
+            let end = start + text[start..].find(']').unwrap_or_default() + 1;
+            format!(
+                "{}[{}, {}]{}{}",
+                &text[..start],
+                codes.join(", "),
+                code,
+                &text[end..],
+                ending
+            )
+        }
+        None => match comment_prefix(path) {
+            Some(prefix) => format!(
+                "{}  {} {}[{}]{}",
+                text.trim_end(),
+                prefix,
+                MARKER,
+                code,
+                ending
+            ),
+            None => return Ok(false),
+        },
+    };
+    lines[index] = &updated;
+    std::fs::write(path, lines.concat())
+        .with_context(|| format!("Failed to write '{}'", path.display()))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_adds_suppressions() -> Result<()> {
+        assert!(is_suppressed("x = 1  # lintrunner: ignore[A, B]", "B"));
+        assert!(!is_suppressed("x = 1  # lintrunner: ignore[A, B]", "C"));
+        assert!(is_suppressed("x = 1  # lintrunner: ignore", "C"));
+        assert!(!is_suppressed("x = 1", "A"));
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("a.rs");
+        std::fs::write(&path, "let x = 1;\r\nlet y = 2;\n")?;
+        add_suppression(&path, 2, "A")?;
+        add_suppression(&path, 2, "B")?;
+        add_suppression(&path, 2, "B")?;
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "let x = 1;\r\nlet y = 2;  // lintrunner: ignore[A, B]\n"
+        );
+        assert!(add_suppression(&path, 3, "A").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn refuses_files_without_known_comments() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for name in ["a.json", "a.md", "a.ini", "LICENSE"] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "x\n")?;
+            assert!(!add_suppression(&path, 1, "A")?, "{}", name);
+            assert_eq!(std::fs::read_to_string(&path)?, "x\n", "{}", name);
+        }
+
+        let path = dir.path().join("Makefile");
+        std::fs::write(&path, "all:\n")?;
+        assert!(add_suppression(&path, 1, "A")?);
+        assert_eq!(
+            std::fs::read_to_string(&path)?,
+            "all:  # lintrunner: ignore[A]\n"
+        );
+        Ok(())
+    }
+}
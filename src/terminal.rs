@@ -0,0 +1,95 @@
+//! Putting the terminal back the way we found it when lintrunner dies.
+//!
+//! The progress display hides the cursor, and prompts put the terminal in raw
+//! mode. Both undo that when they finish, but not when lintrunner panics or
+//! is killed by a signal halfway through, which leaves the user's shell
+//! without a cursor or echo. [`install`] records the terminal's modes at
+//! startup, and sets up a panic hook and handlers for SIGINT, SIGTERM, SIGHUP
+//! and SIGQUIT that restore them and show the cursor. The handlers also kill
+//! running linters, which are in process groups of their own that the signal
+//! doesn't reach, and then remove the run's temporary directory. The process
+//! then exits
+//! as it would have otherwise, except that SIGINT exits with
+//! [`crate::exit_code::INTERRUPTED`] for the sake of callers that don't look
+//! at signals.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::OnceLock;
+
+/// Shows the cursor again.
+const SHOW_CURSOR: &[u8] = b"\x1b[?25h";
+
+/// Whether stderr is a terminal, and so may have had its cursor hidden.
+static STDERR_IS_TERM: AtomicBool = AtomicBool::new(false);
+
+/// The modes of the terminal on stdin at startup.
+#[cfg(unix)]
+static SAVED_MODES: OnceLock<libc::termios> = OnceLock::new();
+
+/// Show the cursor and restore the terminal modes saved by [`install`].
+/// Only does what is safe in a signal handler.
+pub fn restore() {
+    #[cfg(unix)]
+    unsafe {
+        if STDERR_IS_TERM.load(Ordering::SeqCst) {
+            libc::write(
+                libc::STDERR_FILENO,
+                SHOW_CURSOR.as_ptr().cast(),
+                SHOW_CURSOR.len(),
+            );
+        }
+        if let Some(modes) = SAVED_MODES.get() {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, modes);
+        }
+    }
+    #[cfg(not(unix))]
+    if STDERR_IS_TERM.load(Ordering::SeqCst) {
+        use std::io::Write;
+        drop(std::io::stderr().write_all(SHOW_CURSOR));
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn restore_and_reraise(signal: libc::c_int) {
+    // Linters are in process groups of their own, so a Ctrl-C in the terminal
+    // doesn't reach them. They have to be gone before the temporary directory
+    // is, as they may be reading their paths files from it.
+    crate::process::kill_registered_groups();
+    restore();
+    crate::tmpdir::remove_from_signal_handler();
+    unsafe {
+        if signal == libc::SIGINT {
+            libc::_exit(crate::exit_code::INTERRUPTED);
+        }
+        // The handler was reset to the default when it ran, so once this
+        // returns, the signal does what it would have done without us.
+        libc::raise(signal);
+    }
+}
+
+/// Restore the terminal when lintrunner panics or is killed. Call once, at
+/// startup.
+pub fn install() {
+    STDERR_IS_TERM.store(console::Term::stderr().is_term(), Ordering::SeqCst);
+    #[cfg(unix)]
+    unsafe {
+        let mut modes = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut modes) == 0 {
+            let _ = SAVED_MODES.set(modes);
+        }
+        for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT] {
+            let mut action = std::mem::zeroed::<libc::sigaction>();
+            action.sa_sigaction = restore_and_reraise as extern "C" fn(libc::c_int) as usize;
+            action.sa_flags = libc::SA_RESETHAND;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        default_hook(info);
+    }));
+}
@@ -1,6 +1,6 @@
-use std::{fs::OpenOptions, io::Write, process::Command};
+use std::{collections::HashMap, fs::OpenOptions, io::Write, process::Command};
 
-use crate::get_version_control;
+use crate::{get_version_control, pathsfile::ChangeInfo};
 
 use anyhow::Result;
 use tempfile::TempDir;
@@ -91,6 +91,25 @@ impl GitCheckout {
         Ok(files)
     }
 
+    // Gets change metadata, keyed by file name.
+    pub fn change_metadata(
+        &self,
+        relative_to: Option<&str>,
+    ) -> Result<HashMap<String, ChangeInfo>> {
+        std::env::set_current_dir(self.root())?;
+        let repo = get_version_control()?;
+        let metadata = repo.get_change_metadata(relative_to)?;
+        Ok(metadata
+            .into_iter()
+            .map(|(abs_path, info)| {
+                (
+                    abs_path.file_name().unwrap().to_string_lossy().to_string(),
+                    info,
+                )
+            })
+            .collect())
+    }
+
     pub fn merge_base_with(&self, merge_base_with: &str) -> Result<String> {
         std::env::set_current_dir(self.root())?;
         let repo = get_version_control()?;
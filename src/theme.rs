@@ -0,0 +1,391 @@
+//! Customizable colors and styling for terminal output.
+//!
+//! The theme comes from the `[colors]` section of the config, overridden by
+//! the `LINTRUNNER_COLORS` environment variable, e.g.
+//! `LINTRUNNER_COLORS='error=magenta:warning=blue:dim=false'`.
+//!
+//! Whether colors are used at all is controlled separately, by `--color` and
+//! the usual environment variables (see [`ColorChoice::apply`]). Everything
+//! lintrunner prints is styled with `console`, so that one decision covers
+//! rendered results, logs, progress and prompts.
+
+use std::{
+    io::{self, Write},
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ArgEnum;
+use console::{Color, Style, StyledObject, Term};
+use serde::{Deserialize, Serialize};
+
+use crate::lint_message::LintSeverity;
+
+pub const COLORS_ENV: &str = "LINTRUNNER_COLORS";
+
+/// The `[colors]` section of the config.
+///
+/// Colors are one of `black`, `red`, `green`, `yellow`, `blue`, `magenta`,
+/// `cyan`, `white`, or an ANSI 256-color number.
+///
+/// # Examples
+/// ```toml
+/// [colors]
+/// error = 'magenta'
+/// advice = '39'
+/// dim = false
+/// ```
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct ColorsConfig {
+    /// Background color of the `Error` badge. Defaults to red.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Background color of the `Warning` badge. Defaults to yellow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    /// Background color of the `Advice` badge. Defaults to yellow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advice: Option<String>,
+    /// Background color of the `Disabled` badge. Defaults to yellow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<String>,
+    /// Set to false to never render dim text, which is unreadable on some
+    /// terminals.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dim: Option<bool>,
+    /// Set to false to never render bold text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bold: Option<bool>,
+    /// Set to false to never underline text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub underline: Option<bool>,
+}
+
+/// When to use colors, for `--color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum ColorChoice {
+    /// Use colors if the output is a terminal, unless the environment says
+    /// otherwise.
+    Auto,
+    Always,
+    Never,
+}
+
+/// What the environment says about colors, following the `NO_COLOR` and
+/// `CLICOLOR` conventions: `Some(true)` if `CLICOLOR_FORCE` is set to
+/// anything but `0`, `Some(false)` if `NO_COLOR` is set to anything or
+/// `CLICOLOR` is `0`, and `None` if it doesn't say.
+fn env_colors(var: impl Fn(&str) -> Option<String>) -> Option<bool> {
+    if var("CLICOLOR_FORCE").is_some_and(|value| !value.is_empty() && value != "0") {
+        return Some(true);
+    }
+    if var("NO_COLOR").is_some_and(|value| !value.is_empty())
+        || var("CLICOLOR").as_deref() == Some("0")
+    {
+        return Some(false);
+    }
+    None
+}
+
+impl ColorChoice {
+    /// Whether to use colors on `term`. `always` and `never` are final;
+    /// with `auto`, the environment decides if it says anything, and
+    /// otherwise colors are used if `term` is a terminal that supports them.
+    fn enabled_for(self, term: &Term) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => env_colors(|name| std::env::var(name).ok())
+                .unwrap_or_else(|| term.is_term() && term.features().colors_supported()),
+        }
+    }
+
+    /// Apply this choice to all `console` styling, for stdout and stderr
+    /// separately.
+    pub fn apply(self) {
+        console::set_colors_enabled(self.enabled_for(&Term::stdout()));
+        console::set_colors_enabled_stderr(self.enabled_for(&Term::stderr()));
+    }
+}
+
+/// Stderr, for output styled with plain `style()`, which follows whether
+/// colors are enabled for stdout. If they are disabled for stderr, escape
+/// codes are stripped, so that they never end up in a pipe. Output is
+/// buffered until flushed or dropped.
+pub fn stderr() -> StyledStderr {
+    StyledStderr { buffer: Vec::new() }
+}
+
+pub struct StyledStderr {
+    buffer: Vec<u8>,
+}
+
+impl Write for StyledStderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let text = String::from_utf8_lossy(&self.buffer);
+        let text = if console::colors_enabled_stderr() {
+            text
+        } else {
+            console::strip_ansi_codes(&text).into_owned().into()
+        };
+        let mut stderr = io::stderr().lock();
+        stderr.write_all(text.as_bytes())?;
+        self.buffer.clear();
+        stderr.flush()
+    }
+}
+
+impl Drop for StyledStderr {
+    fn drop(&mut self) {
+        drop(self.flush());
+    }
+}
+
+pub struct Theme {
+    error: Color,
+    warning: Color,
+    advice: Color,
+    disabled: Color,
+    dim: bool,
+    bold: bool,
+    underline: bool,
+}
+
+const DEFAULT_THEME: Theme = Theme {
+    error: Color::Red,
+    warning: Color::Yellow,
+    advice: Color::Yellow,
+    disabled: Color::Yellow,
+    dim: true,
+    bold: true,
+    underline: true,
+};
+
+fn parse_color(color: &str) -> Result<Color> {
+    Ok(match color.trim().to_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        other => match other.parse::<u8>() {
+            Ok(color) => Color::Color256(color),
+            Err(_) => bail!("Unknown color '{}'", color),
+        },
+    })
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        other => bail!("Expected true or false, got '{}'", other),
+    }
+}
+
+/// Parse `LINTRUNNER_COLORS`: `key=value` pairs separated by `:`, with the
+/// same keys as [`ColorsConfig`].
+pub fn parse_colors_env(value: &str) -> Result<ColorsConfig> {
+    let mut config = ColorsConfig::default();
+    for entry in value.split(':').filter(|entry| !entry.trim().is_empty()) {
+        let (key, value) = entry.split_once('=').with_context(|| {
+            format!(
+                "Invalid {} entry '{}': expected key=value",
+                COLORS_ENV, entry
+            )
+        })?;
+        let value = value.to_string();
+        match key.trim() {
+            "error" => config.error = Some(value),
+            "warning" => config.warning = Some(value),
+            "advice" => config.advice = Some(value),
+            "disabled" => config.disabled = Some(value),
+            "dim" => config.dim = Some(parse_bool(&value)?),
+            "bold" => config.bold = Some(parse_bool(&value)?),
+            "underline" => config.underline = Some(parse_bool(&value)?),
+            other => bail!("Unknown {} key '{}'", COLORS_ENV, other),
+        }
+    }
+    Ok(config)
+}
+
+impl Theme {
+    /// Build a theme from `configs`, where later configs take precedence.
+    pub fn from_configs(configs: &[&ColorsConfig]) -> Result<Theme> {
+        let mut theme = DEFAULT_THEME;
+        for config in configs {
+            let colors = [
+                (&config.error, &mut theme.error),
+                (&config.warning, &mut theme.warning),
+                (&config.advice, &mut theme.advice),
+                (&config.disabled, &mut theme.disabled),
+            ];
+            for (value, color) in colors {
+                if let Some(value) = value {
+                    *color = parse_color(value)?;
+                }
+            }
+            theme.dim = config.dim.unwrap_or(theme.dim);
+            theme.bold = config.bold.unwrap_or(theme.bold);
+            theme.underline = config.underline.unwrap_or(theme.underline);
+        }
+        Ok(theme)
+    }
+
+    /// The style of the badge for `severity`.
+    pub fn severity_style(&self, severity: LintSeverity) -> Style {
+        let color = match severity {
+            LintSeverity::Error => self.error,
+            LintSeverity::Warning => self.warning,
+            LintSeverity::Advice => self.advice,
+            LintSeverity::Disabled => self.disabled,
+        };
+        Style::new().bg(color).theme_bold()
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Set up the theme from the config's `[colors]` section and the
+/// environment. Only the first call has any effect.
+pub fn init_theme(config: Option<&ColorsConfig>) -> Result<()> {
+    let env_config = match std::env::var(COLORS_ENV) {
+        Ok(value) => Some(parse_colors_env(&value)?),
+        Err(_) => None,
+    };
+    let configs = config
+        .into_iter()
+        .chain(env_config.as_ref())
+        .collect::<Vec<_>>();
+    let theme = Theme::from_configs(&configs).context("Invalid color configuration")?;
+    // Ignore the error if the theme was already set.
+    drop(THEME.set(theme));
+    Ok(())
+}
+
+/// The current theme, or the default one if it hasn't been set up (yet).
+pub fn theme() -> &'static Theme {
+    THEME.get().unwrap_or(&DEFAULT_THEME)
+}
+
+/// Styling that respects the theme, e.g. `style(x).theme_dim()` is dim unless
+/// the user turned dim text off.
+pub trait ThemeExt: Sized {
+    fn theme_dim(self) -> Self;
+    fn theme_bold(self) -> Self;
+    fn theme_underlined(self) -> Self;
+}
+
+impl ThemeExt for Style {
+    fn theme_dim(self) -> Self {
+        if theme().dim {
+            self.dim()
+        } else {
+            self
+        }
+    }
+
+    fn theme_bold(self) -> Self {
+        if theme().bold {
+            self.bold()
+        } else {
+            self
+        }
+    }
+
+    fn theme_underlined(self) -> Self {
+        if theme().underline {
+            self.underlined()
+        } else {
+            self
+        }
+    }
+}
+
+impl<D> ThemeExt for StyledObject<D> {
+    fn theme_dim(self) -> Self {
+        if theme().dim {
+            self.dim()
+        } else {
+            self
+        }
+    }
+
+    fn theme_bold(self) -> Self {
+        if theme().bold {
+            self.bold()
+        } else {
+            self
+        }
+    }
+
+    fn theme_underlined(self) -> Self {
+        if theme().underline {
+            self.underlined()
+        } else {
+            self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_overrides_config() -> Result<()> {
+        let config = ColorsConfig {
+            error: Some("blue".to_string()),
+            warning: Some("green".to_string()),
+            dim: Some(true),
+            ..Default::default()
+        };
+        let env_config = parse_colors_env("error=magenta:advice=39:dim=false")?;
+        let theme = Theme::from_configs(&[&config, &env_config])?;
+        assert_eq!(theme.error, Color::Magenta);
+        assert_eq!(theme.warning, Color::Green);
+        assert_eq!(theme.advice, Color::Color256(39));
+        assert_eq!(theme.disabled, Color::Yellow);
+        assert!(!theme.dim);
+        assert!(theme.bold);
+        Ok(())
+    }
+
+    #[test]
+    fn color_env_conventions() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(env_colors(env(&[])), None);
+        assert_eq!(env_colors(env(&[("NO_COLOR", "1")])), Some(false));
+        assert_eq!(env_colors(env(&[("NO_COLOR", "")])), None);
+        assert_eq!(env_colors(env(&[("CLICOLOR", "0")])), Some(false));
+        assert_eq!(env_colors(env(&[("CLICOLOR", "1")])), None);
+        assert_eq!(
+            env_colors(env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")])),
+            Some(true)
+        );
+        assert_eq!(env_colors(env(&[("CLICOLOR_FORCE", "0")])), None);
+    }
+
+    #[test]
+    fn invalid_colors() {
+        assert!(parse_colors_env("error").is_err());
+        assert!(parse_colors_env("errors=red").is_err());
+        assert!(parse_colors_env("dim=maybe").is_err());
+        let config = parse_colors_env("error=chartreuse").unwrap();
+        assert!(Theme::from_configs(&[&config]).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! The temporary directory of a run.
+//!
+//! Everything lintrunner writes to a temporary location (paths files,
+//! materialized contents, spilled linter output, scratch worktrees) goes in
+//! one directory per run, under `--tmpdir`, `LINTRUNNER_TMPDIR`, or the
+//! system's temporary directory, in that order. That keeps lintrunner off a
+//! small or `noexec` `/tmp`, and means a run that dies leaves at most one
+//! directory behind: it is removed when lintrunner exits, panics, or is killed
+//! by SIGINT, SIGTERM, SIGHUP or SIGQUIT.
+
+#[cfg(unix)]
+use std::sync::OnceLock;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use tempfile::TempDir;
+
+/// The environment variable to set the base directory with, if `--tmpdir`
+/// isn't passed.
+pub const TMPDIR_ENV: &str = "LINTRUNNER_TMPDIR";
+
+/// The run's directory, taken out when it is removed.
+static RUN_DIR: Mutex<Option<TempDir>> = Mutex::new(None);
+
+/// The run's directory as a C string, for removing it from a signal handler,
+/// where nothing can be allocated.
+#[cfg(unix)]
+static RUN_DIR_C: OnceLock<std::ffi::CString> = OnceLock::new();
+
+/// Removes the run's directory when dropped, including when unwinding from a
+/// panic.
+pub struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        remove();
+    }
+}
+
+/// Where to put the run's directory: `base` if given, then
+/// `LINTRUNNER_TMPDIR`, then the system's temporary directory.
+fn base_dir(base: Option<&Path>) -> PathBuf {
+    base.map(Path::to_path_buf)
+        .or_else(|| {
+            std::env::var_os(TMPDIR_ENV)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+        })
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Create the run's directory under `base` and make it where temporary files
+/// go. Call once, at startup, before any are created.
+pub fn init(base: Option<&Path>) -> Result<PathBuf> {
+    let base = base_dir(base);
+    std::fs::create_dir_all(&base)
+        .with_context(|| format!("Couldn't create temporary directory '{}'", base.display()))?;
+    let dir = tempfile::Builder::new()
+        .prefix("lintrunner-")
+        .tempdir_in(&base)
+        .with_context(|| {
+            format!(
+                "Couldn't create a temporary directory in '{}'",
+                base.display()
+            )
+        })?;
+    let path = dir.path().to_path_buf();
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        if let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) {
+            let _ = RUN_DIR_C.set(path);
+        }
+    }
+    // Fails only if temporary files were already created elsewhere, which
+    // would be a bug.
+    let _ = tempfile::env::override_temp_dir(&path);
+    *RUN_DIR.lock().unwrap() = Some(dir);
+    Ok(path)
+}
+
+/// Remove the run's directory and everything in it, if it hasn't been yet.
+pub fn remove() {
+    let dir = match RUN_DIR.lock() {
+        Ok(mut dir) => dir.take(),
+        Err(poisoned) => poisoned.into_inner().take(),
+    };
+    drop(dir);
+}
+
+/// Remove the run's directory from a signal handler. Only does what is safe
+/// there: forks and runs `rm -rf` on it.
+#[cfg(unix)]
+pub fn remove_from_signal_handler() {
+    let Some(dir) = RUN_DIR_C.get() else {
+        return;
+    };
+    unsafe {
+        match libc::fork() {
+            0 => {
+                let argv = [
+                    c"rm".as_ptr(),
+                    c"-rf".as_ptr(),
+                    c"--".as_ptr(),
+                    dir.as_ptr(),
+                    std::ptr::null(),
+                ];
+                libc::execv(c"/bin/rm".as_ptr(), argv.as_ptr());
+                libc::_exit(127);
+            }
+            -1 => {}
+            child => {
+                let mut status = 0;
+                libc::waitpid(child, &mut status, 0);
+            }
+        }
+    }
+}
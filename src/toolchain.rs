@@ -0,0 +1,242 @@
+//! Linter toolchains in the lintrunner data directory, and archives of them.
+//!
+//! `lintrunner init` installs toolchains in the data directory: binaries
+//! fetched for a linter's `download`, and whatever its init command puts in
+//! `{{TOOLCHAIN_DIR}}`, like a venv. `lintrunner init --pack` then snapshots
+//! them into an archive, and `lintrunner init --from-archive` restores one, so
+//! that CI machines and new laptops can bootstrap the lint environment from an
+//! artifact instead of package indexes.
+//!
+//! Archives are tarballs, compressed according to their extension: `.tar`,
+//! `.tar.gz` or `.tgz`, and `.tar.zst` or `.tzst`, which needs `zstd` to be
+//! installed. Toolchains are restored at the same place in the data
+//! directory, so ones with absolute paths in them (like venvs) only work on
+//! machines where that is the same path.
+
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+    process::{Child, Command, Stdio},
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use directories::ProjectDirs;
+use flate2::{read::GzDecoder, write::GzEncoder};
+use log::debug;
+
+use crate::{download, linter::Linter};
+
+/// Replaced by the linter's toolchain directory in its `command`,
+/// `init_command`, `health_check` and `env` values.
+pub const TOOLCHAIN_DIR_PLACEHOLDER: &str = "{{TOOLCHAIN_DIR}}";
+
+const TOOLCHAINS_DIR_NAME: &str = "toolchains";
+
+/// The lintrunner data directory, shared between checkouts.
+pub(crate) fn data_dir() -> Result<PathBuf> {
+    let project_dirs = ProjectDirs::from("", "", "lintrunner")
+        .ok_or_else(|| anyhow!("Could not find project directories"))?;
+    Ok(project_dirs.data_dir().to_path_buf())
+}
+
+/// Where the init command of linter `code` can install its toolchain. This
+/// is what `{{TOOLCHAIN_DIR}}` expands to. It isn't created by lintrunner.
+pub fn toolchain_dir(code: &str) -> Result<PathBuf> {
+    Ok(data_dir()?.join(TOOLCHAINS_DIR_NAME).join(code))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+fn compression_for(archive: &Path) -> Result<Compression> {
+    let name = archive.to_string_lossy();
+    if name.ends_with(".tar") {
+        Ok(Compression::None)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Ok(Compression::Gzip)
+    } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+        Ok(Compression::Zstd)
+    } else {
+        bail!(
+            "Unknown archive type '{}': expected .tar, .tar.gz or .tar.zst",
+            archive.display()
+        )
+    }
+}
+
+/// Check that `archive` has an extension that [`pack`] and [`restore`]
+/// support.
+pub fn check_archive_type(archive: &Path) -> Result<()> {
+    compression_for(archive).map(drop)
+}
+
+fn spawn_zstd(args: &[&str], archive: &Path) -> Result<Child> {
+    Command::new("zstd")
+        .args(args)
+        .arg(archive)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run zstd; is it installed?")
+}
+
+fn wait_for_zstd(mut child: Child) -> Result<()> {
+    let status = child.wait()?;
+    ensure!(status.success(), "zstd failed with {}", status);
+    Ok(())
+}
+
+/// The toolchains of `linters` that are installed, relative to the data
+/// directory.
+fn installed_toolchains(linters: &[Linter], data_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for linter in linters
+        .iter()
+        .filter(|linter| linter.config_error.is_none())
+    {
+        if let Some(download) = &linter.download {
+            dirs.push(download.cache_dir()?);
+        }
+        dirs.push(toolchain_dir(&linter.code)?);
+    }
+    dirs.sort();
+    dirs.dedup();
+    Ok(dirs
+        .into_iter()
+        .filter(|dir| dir.is_dir())
+        // Unwrap ok because all of them are under the data directory.
+        .map(|dir| dir.strip_prefix(data_dir).unwrap().to_path_buf())
+        .collect())
+}
+
+fn append_toolchains<W: Write>(writer: W, data_dir: &Path, toolchains: &[PathBuf]) -> Result<W> {
+    let mut builder = tar::Builder::new(writer);
+    // Venvs link to their interpreter; keep the links.
+    builder.follow_symlinks(false);
+    for toolchain in toolchains {
+        debug!("Packing {}", toolchain.display());
+        builder
+            .append_dir_all(toolchain, data_dir.join(toolchain))
+            .with_context(|| format!("Failed to pack {}", toolchain.display()))?;
+    }
+    Ok(builder.into_inner()?)
+}
+
+/// Snapshot the installed toolchains of `linters` into `archive`, returning
+/// how many there were.
+pub fn pack(linters: &[Linter], archive: &Path) -> Result<usize> {
+    let compression = compression_for(archive)?;
+    let data_dir = data_dir()?;
+    let toolchains = installed_toolchains(linters, &data_dir)?;
+    match compression {
+        Compression::None => {
+            let file = File::create(archive)?;
+            append_toolchains(file, &data_dir, &toolchains)?.flush()?;
+        }
+        Compression::Gzip => {
+            let file = File::create(archive)?;
+            let encoder = GzEncoder::new(file, flate2::Compression::default());
+            append_toolchains(encoder, &data_dir, &toolchains)?.finish()?;
+        }
+        Compression::Zstd => {
+            let mut child = spawn_zstd(&["-q", "-f", "-o"], archive)?;
+            // Unwrap ok because stdin is piped.
+            let stdin = child.stdin.take().unwrap();
+            drop(append_toolchains(stdin, &data_dir, &toolchains)?);
+            wait_for_zstd(child)?;
+        }
+    }
+    Ok(toolchains.len())
+}
+
+/// Whether `path`, from an archive, is somewhere toolchains are kept.
+fn is_toolchain_path(path: &Path) -> bool {
+    let mut components = path.components();
+    let top = match components.next() {
+        Some(Component::Normal(top)) => top,
+        _ => return false,
+    };
+    let is_toolchain_dir = top == TOOLCHAINS_DIR_NAME || top == download::DOWNLOADS_DIR_NAME;
+    is_toolchain_dir && components.all(|component| matches!(component, Component::Normal(_)))
+}
+
+fn unpack_toolchains(reader: impl Read, data_dir: &Path) -> Result<usize> {
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    let mut count = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        ensure!(
+            is_toolchain_path(&path),
+            "Refusing to restore '{}': not a toolchain",
+            path.display()
+        );
+        entry.unpack_in(data_dir)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Restore the toolchains in `archive` to the data directory.
+pub fn restore(archive: &Path) -> Result<()> {
+    let compression = compression_for(archive)?;
+    let data_dir = data_dir()?;
+    std::fs::create_dir_all(&data_dir)?;
+    let open =
+        || File::open(archive).with_context(|| format!("Failed to open {}", archive.display()));
+    let count = match compression {
+        Compression::None => unpack_toolchains(open()?, &data_dir)?,
+        Compression::Gzip => unpack_toolchains(GzDecoder::new(open()?), &data_dir)?,
+        Compression::Zstd => {
+            let mut child = spawn_zstd(&["-q", "-d", "-c"], archive)?;
+            // Unwrap ok because stdout is piped.
+            let stdout = child.stdout.take().unwrap();
+            let count = unpack_toolchains(stdout, &data_dir);
+            wait_for_zstd(child)?;
+            count?
+        }
+    };
+    debug!("Restored {} files to {}", count, data_dir.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_toolchains_are_restored() {
+        assert!(is_toolchain_path(Path::new(
+            "toolchains/MYPY/venv/bin/python"
+        )));
+        assert!(is_toolchain_path(Path::new("downloads/mvdan/sh/v3.8.0")));
+        assert!(!is_toolchain_path(Path::new("trusted_configs.json")));
+        assert!(!is_toolchain_path(Path::new(
+            "toolchains/../trusted_configs.json"
+        )));
+        assert!(!is_toolchain_path(Path::new("/toolchains/MYPY")));
+    }
+
+    #[test]
+    fn compression_follows_the_extension() {
+        assert_eq!(
+            compression_for(Path::new("env.tar")).unwrap(),
+            Compression::None
+        );
+        assert_eq!(
+            compression_for(Path::new("env.tgz")).unwrap(),
+            Compression::Gzip
+        );
+        assert_eq!(
+            compression_for(Path::new("env.tar.zst")).unwrap(),
+            Compression::Zstd
+        );
+        assert!(compression_for(Path::new("env.zip")).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+//! Tracking lint counts across runs, for `lintrunner trend`.
+//!
+//! Every lint run appends one point to a JSON lines file in the data dir,
+//! holding how many messages of each severity every linter reported. Unlike
+//! the run history, which only keeps the last few runs, points are kept for
+//! a long time, so a burn-down can be followed over weeks.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::Write,
+};
+
+use anyhow::Result;
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    persistent_data::PersistentDataStore,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+pub enum TrendFormat {
+    Text,
+    Csv,
+}
+
+/// How many messages of each severity a linter reported in one run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeverityCounts {
+    #[serde(default)]
+    pub errors: usize,
+    #[serde(default)]
+    pub warnings: usize,
+    #[serde(default)]
+    pub advice: usize,
+    #[serde(default)]
+    pub disabled: usize,
+}
+
+impl SeverityCounts {
+    /// Everything that would be shown to the user.
+    pub fn total(&self) -> usize {
+        self.errors + self.warnings + self.advice
+    }
+
+    fn add(&mut self, severity: LintSeverity) {
+        match severity {
+            LintSeverity::Error => self.errors += 1,
+            LintSeverity::Warning => self.warnings += 1,
+            LintSeverity::Advice => self.advice += 1,
+            LintSeverity::Disabled => self.disabled += 1,
+        }
+    }
+}
+
+/// The lint counts of one run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrendPoint {
+    pub timestamp: String,
+    pub counts: BTreeMap<String, SeverityCounts>,
+}
+
+/// Count `lint_messages` by code and severity. Every linter in `linters`
+/// gets an entry, so that a linter that ran clean is recorded as zero rather
+/// than missing.
+pub fn count_lints<'a>(
+    linters: impl IntoIterator<Item = &'a String>,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> BTreeMap<String, SeverityCounts> {
+    let mut counts: BTreeMap<String, SeverityCounts> = linters
+        .into_iter()
+        .map(|code| (code.clone(), SeverityCounts::default()))
+        .collect();
+    for message in lint_messages.values().flatten() {
+        counts
+            .entry(message.code.clone())
+            .or_default()
+            .add(message.severity);
+    }
+    counts
+}
+
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// One character per value, scaled between the smallest and largest value.
+/// Runs in which the linter didn't run are shown as spaces.
+pub fn sparkline(values: &[Option<usize>]) -> String {
+    let present = values.iter().flatten();
+    let (min, max) = match (present.clone().min(), present.max()) {
+        (Some(min), Some(max)) => (*min, *max),
+        _ => return " ".repeat(values.len()),
+    };
+    values
+        .iter()
+        .map(|value| match value {
+            None => ' ',
+            Some(_) if max == min => SPARK_CHARS[0],
+            Some(value) => {
+                let level = (value - min) * (SPARK_CHARS.len() - 1) / (max - min);
+                SPARK_CHARS[level]
+            }
+        })
+        .collect()
+}
+
+fn linter_codes(points: &[TrendPoint]) -> BTreeSet<&String> {
+    points
+        .iter()
+        .flat_map(|point| point.counts.keys())
+        .collect()
+}
+
+pub fn render_trend_text(stdout: &mut impl Write, points: &[TrendPoint]) -> Result<()> {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            writeln!(stdout, "No lint runs recorded yet.")?;
+            return Ok(());
+        }
+    };
+    writeln!(
+        stdout,
+        "{} runs from {} to {}",
+        points.len(),
+        first.timestamp,
+        last.timestamp
+    )?;
+    let codes = linter_codes(points);
+    let width = codes.iter().map(|code| code.len()).max().unwrap_or(0);
+    for code in codes {
+        let totals = points
+            .iter()
+            .map(|point| point.counts.get(code).map(SeverityCounts::total))
+            .collect::<Vec<_>>();
+        let mut present = totals.iter().flatten();
+        let start = present.next().copied().unwrap_or(0);
+        let end = present.last().copied().unwrap_or(start);
+        let change = end as i64 - start as i64;
+        writeln!(
+            stdout,
+            "{:width$}  {}  {} -> {} ({:+})",
+            code,
+            sparkline(&totals),
+            start,
+            end,
+            change,
+            width = width
+        )?;
+    }
+    Ok(())
+}
+
+/// Write one row per linter per run.
+pub fn render_trend_csv(stdout: &mut impl Write, points: &[TrendPoint]) -> Result<()> {
+    writeln!(stdout, "timestamp,code,errors,warnings,advice,disabled")?;
+    for point in points {
+        for (code, counts) in &point.counts {
+            writeln!(
+                stdout,
+                "{},{},{},{},{},{}",
+                point.timestamp,
+                code,
+                counts.errors,
+                counts.warnings,
+                counts.advice,
+                counts.disabled
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn do_trend(
+    persistent_data_store: &PersistentDataStore,
+    codes: &[String],
+    last: Option<usize>,
+    format: TrendFormat,
+) -> Result<i32> {
+    let mut points = persistent_data_store.trend_points()?;
+    if let Some(last) = last {
+        points.drain(..points.len().saturating_sub(last));
+    }
+    if !codes.is_empty() {
+        for point in &mut points {
+            point.counts.retain(|code, _| codes.contains(code));
+        }
+        points.retain(|point| !point.counts.is_empty());
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    match format {
+        TrendFormat::Text => render_trend_text(&mut stdout, &points)?,
+        TrendFormat::Csv => render_trend_csv(&mut stdout, &points)?,
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(timestamp: &str, counts: &[(&str, usize)]) -> TrendPoint {
+        TrendPoint {
+            timestamp: timestamp.to_string(),
+            counts: counts
+                .iter()
+                .map(|(code, errors)| {
+                    (
+                        code.to_string(),
+                        SeverityCounts {
+                            errors: *errors,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn renders_sparklines() -> Result<()> {
+        assert_eq!(sparkline(&[Some(0), Some(7), None, Some(14)]), "▁▄ █");
+        assert_eq!(sparkline(&[Some(3), Some(3)]), "▁▁");
+        assert_eq!(sparkline(&[None]), " ");
+
+        let points = vec![
+            point("t1", &[("MYPY", 120), ("FLAKE8", 4)]),
+            point("t2", &[("MYPY", 90)]),
+            point("t3", &[("MYPY", 50), ("FLAKE8", 0)]),
+        ];
+        let mut out = Vec::new();
+        render_trend_text(&mut out, &points)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "3 runs from t1 to t3\n\
+             FLAKE8  █ ▁  4 -> 0 (-4)\n\
+             MYPY    █▅▁  120 -> 50 (-70)\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+//! Reviewing the findings of a past run one at a time, for `lintrunner
+//! triage`.
+//!
+//! Findings are shown one by one, and commands read from stdin act on the
+//! current one: open it in `$EDITOR`, apply its fix, or suppress it with an
+//! inline comment (see [`crate::suppress`]).
+
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io::{BufRead, Write},
+    path::Path,
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use console::{style, Term};
+
+use crate::{
+    apply::{self, FileLocks, Patch, PatchOutcome},
+    lint_message::LintMessage,
+    path::AbsPath,
+    persistent_data::PersistentDataStore,
+    render::render_lint_messages,
+    suppress,
+    theme::ThemeExt,
+};
+
+const HELP: &str = "\
+Commands:
+  n, <enter>  next finding
+  p           previous finding
+  <number>    go to that finding
+  l           list all findings
+  o           open the finding in $VISUAL or $EDITOR
+  f           apply the finding's fix
+  s           suppress the finding with an inline comment
+  q           quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Open,
+    Fixed,
+    Suppressed,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Open => write!(f, "open"),
+            Status::Fixed => write!(f, "fixed"),
+            Status::Suppressed => write!(f, "suppressed"),
+        }
+    }
+}
+
+/// Read the findings in `report`, a file of JSON lint messages, one per
+/// line, as written by `--output=json` or `--tee-json`.
+pub(crate) fn read_report(report: &str) -> Result<Vec<LintMessage>> {
+    let contents = std::fs::read_to_string(report)
+        .with_context(|| format!("Failed to read report '{}'", report))?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Failed to parse lint message in '{}'", report))
+        })
+        .collect()
+}
+
+fn location(message: &LintMessage) -> String {
+    match (&message.path, message.line) {
+        (Some(path), Some(line)) => format!("{}:{}", path, line),
+        (Some(path), None) => path.clone(),
+        (None, _) => "<no file>".to_string(),
+    }
+}
+
+/// Open `path` at `line` in the user's editor, and wait for it to exit.
+fn open_in_editor(path: &str, line: Option<usize>) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut editor = shell_words::split(&editor).context("Failed to split $EDITOR")?;
+    if editor.is_empty() {
+        bail!("$EDITOR is empty");
+    }
+    let program = editor.remove(0);
+    let mut command = Command::new(&program);
+    command.args(editor);
+    // Understood by vi, emacs, nano and most other editors.
+    if let Some(line) = line {
+        command.arg(format!("+{}", line));
+    }
+    let status = command
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to run editor '{}'", program))?;
+    if !status.success() {
+        bail!("Editor '{}' exited with {}", program, status);
+    }
+    Ok(())
+}
+
+/// Apply the fix of `message`. `None` if it has none.
+fn apply_fix(message: &LintMessage) -> Result<Option<PatchOutcome>> {
+    let (path, replacement) = match (&message.path, &message.replacement) {
+        (Some(path), Some(replacement)) => (path, replacement),
+        _ => return Ok(None),
+    };
+    let patch = Patch {
+        path: AbsPath::try_from(path)?,
+        original: message.original.as_deref(),
+        replacement,
+    };
+    let outcomes = apply::apply_patches(&[patch], &FileLocks::default(), 1)?;
+    Ok(outcomes.first().copied())
+}
+
+fn show(
+    stdout: &mut Term,
+    messages: &[LintMessage],
+    statuses: &[Status],
+    index: usize,
+    wrap_width: Option<usize>,
+) -> Result<()> {
+    let message = &messages[index];
+    writeln!(
+        stdout,
+        "\n{} {}",
+        style(format!("[{}/{}]", index + 1, messages.len())).theme_bold(),
+        style(statuses[index]).theme_dim()
+    )?;
+    let lints = HashMap::from([(message.path.clone(), vec![message.clone()])]);
+    render_lint_messages(stdout, &lints, wrap_width)?;
+    Ok(())
+}
+
+pub fn do_triage(
+    persistent_data_store: &PersistentDataStore,
+    report: Option<String>,
+    wrap_width: Option<usize>,
+) -> Result<i32> {
+    let mut messages = match &report {
+        Some(report) => read_report(report)?,
+        None => persistent_data_store.past_lint_messages(None)?,
+    };
+    let mut stdout = Term::stdout();
+    if messages.is_empty() {
+        stdout.write_line("No findings to triage.")?;
+        return Ok(0);
+    }
+    messages.sort_by_key(|message| (message.path.clone(), message.line, message.char));
+    let mut statuses = vec![Status::Open; messages.len()];
+
+    writeln!(
+        stdout,
+        "{} findings to triage. Type 'h' for help.",
+        messages.len()
+    )?;
+    let mut index = 0;
+    show(&mut stdout, &messages, &statuses, index, wrap_width)?;
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock().lines();
+    loop {
+        write!(stdout, "\n[n]ext [p]rev [o]pen [f]ix [s]uppress [q]uit > ")?;
+        stdout.flush()?;
+        let command = match input.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        let message = &messages[index];
+        let mut next = None;
+        match command.trim() {
+            "" | "n" => next = Some((index + 1).min(messages.len() - 1)),
+            "p" => next = Some(index.saturating_sub(1)),
+            "q" => break,
+            "h" | "?" => writeln!(stdout, "{}", HELP)?,
+            "l" => {
+                for (i, (message, status)) in messages.iter().zip(&statuses).enumerate() {
+                    writeln!(
+                        stdout,
+                        "{:>4}  {:<10}  {}  {} ({})",
+                        i + 1,
+                        status,
+                        location(message),
+                        message.name,
+                        message.code
+                    )?;
+                }
+            }
+            "o" => match &message.path {
+                Some(path) => open_in_editor(path, message.line)?,
+                None => writeln!(stdout, "This finding isn't about a file.")?,
+            },
+            "f" => match apply_fix(message)? {
+                Some(PatchOutcome::Applied) => {
+                    statuses[index] = Status::Fixed;
+                    writeln!(stdout, "Applied the fix.")?;
+                }
+                Some(PatchOutcome::Stale) => writeln!(
+                    stdout,
+                    "The file changed since the run, so the fix wasn't applied. \
+                     Re-run lintrunner to get a fresh one."
+                )?,
+                None => writeln!(stdout, "This finding has no fix.")?,
+            },
+            "s" => match (&message.path, message.line) {
+                (Some(path), Some(line)) => {
+                    if suppress::add_suppression(Path::new(path), line, &message.code)? {
+                        statuses[index] = Status::Suppressed;
+                        writeln!(stdout, "Suppressed {} at {}:{}.", message.code, path, line)?;
+                    } else {
+                        writeln!(
+                            stdout,
+                            "Can't suppress findings in {}: lintrunner doesn't know how to \
+                             write a comment in it.",
+                            path
+                        )?;
+                    }
+                }
+                _ => writeln!(stdout, "Only findings on a line can be suppressed.")?,
+            },
+            other => match other.parse::<usize>() {
+                Ok(number) if (1..=messages.len()).contains(&number) => next = Some(number - 1),
+                _ => writeln!(stdout, "Unknown command '{}'. Type 'h' for help.", other)?,
+            },
+        }
+        if let Some(next) = next {
+            index = next;
+            show(&mut stdout, &messages, &statuses, index, wrap_width)?;
+        }
+    }
+
+    let count = |status| statuses.iter().filter(|s| **s == status).count();
+    writeln!(
+        stdout,
+        "\nFixed {}, suppressed {}, {} left open.",
+        count(Status::Fixed),
+        count(Status::Suppressed),
+        count(Status::Open)
+    )?;
+    Ok(0)
+}
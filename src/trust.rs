@@ -0,0 +1,222 @@
+//! Asking before running the commands of configs the user hasn't trusted,
+//! for people who run lintrunner in repos they cloned from someone else.
+//!
+//! A config file is trusted if it is committed, unmodified, in a repo the
+//! user trusts, or if the user trusted its exact contents. `lintrunner trust`
+//! does both for the configs in use: it trusts their repo, and the current
+//! contents of those that aren't committed (like `.lintrunner.private.toml`).
+//! Trust is recorded in the lintrunner-wide data dir.
+//!
+//! Before running linters or their init commands with an untrusted config,
+//! lintrunner asks whether to go ahead. Runs that can't ask, like editors, git
+//! hooks or CI, fail instead, unless `LINTRUNNER_TRUST_ALL=1` is set.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use console::style;
+use dialoguer::{theme::ColorfulTheme, Select};
+use directories::ProjectDirs;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    interactive,
+    theme::{self, ThemeExt},
+};
+
+const TRUST_FILE_NAME: &str = "trusted.json";
+
+/// If set (to anything but `0`), every config file is trusted.
+pub const TRUST_ALL_ENV: &str = "LINTRUNNER_TRUST_ALL";
+
+#[derive(Serialize, Deserialize, Default)]
+struct TrustFile {
+    /// Roots of trusted repos, whose committed configs are trusted.
+    repos: BTreeSet<PathBuf>,
+    /// Map of config file path to the blake3 hash of its trusted contents.
+    configs: BTreeMap<PathBuf, String>,
+}
+
+fn trust_file_path() -> Result<PathBuf> {
+    let project_dirs = ProjectDirs::from("", "", "lintrunner")
+        .ok_or_else(|| anyhow!("Could not find project directories"))?;
+    Ok(project_dirs.data_dir().join(TRUST_FILE_NAME))
+}
+
+impl TrustFile {
+    fn load() -> Result<TrustFile> {
+        let path = trust_file_path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse '{}'", path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(TrustFile::default()),
+            Err(err) => Err(err).with_context(|| format!("Failed to read '{}'", path.display())),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = trust_file_path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write '{}'", path.display()))
+    }
+}
+
+/// A config file, as far as trust is concerned.
+struct ConfigFile {
+    path: PathBuf,
+    hash: String,
+    /// The root of the git repo the file is committed in, unmodified.
+    committed_in: Option<PathBuf>,
+}
+
+fn git(dir: &Path, args: &[&str]) -> Option<std::process::Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+}
+
+/// The root of the repo `path` is committed in, if it is tracked and has no
+/// changes since the last commit.
+fn committed_in(path: &Path) -> Option<PathBuf> {
+    let dir = path.parent()?;
+    let path = path.to_str()?;
+    git(dir, &["ls-files", "--error-unmatch", "--", path])?;
+    git(dir, &["diff", "--quiet", "HEAD", "--", path])?;
+    let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let root = String::from_utf8(root.stdout).ok()?;
+    PathBuf::from(root.trim()).canonicalize().ok()
+}
+
+fn config_file(path: &Path) -> Result<ConfigFile> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Could not find config file at {}", path.display()))?;
+    let contents = std::fs::read(&path)?;
+    Ok(ConfigFile {
+        hash: blake3::hash(&contents).to_string(),
+        committed_in: committed_in(&path),
+        path,
+    })
+}
+
+impl TrustFile {
+    fn trusts(&self, file: &ConfigFile) -> bool {
+        file.committed_in
+            .as_ref()
+            .is_some_and(|repo| self.repos.contains(repo))
+            || self.configs.get(&file.path) == Some(&file.hash)
+    }
+
+    fn trust(&mut self, file: ConfigFile) {
+        match file.committed_in {
+            Some(repo) => {
+                self.repos.insert(repo);
+            }
+            None => {
+                self.configs.insert(file.path, file.hash);
+            }
+        }
+    }
+}
+
+/// Trust the config files in `paths`: their repo if they're committed,
+/// otherwise their current contents. With `revoke`, stop trusting them and
+/// their repo instead.
+pub fn do_trust(paths: &[PathBuf], revoke: bool) -> Result<i32> {
+    let mut trust_file = TrustFile::load()?;
+    for path in paths {
+        let file = config_file(path)?;
+        if revoke {
+            trust_file.configs.remove(&file.path);
+            if let Some(repo) = &file.committed_in {
+                trust_file.repos.remove(repo);
+            }
+            println!("No longer trusting {}", file.path.display());
+            continue;
+        }
+        match &file.committed_in {
+            Some(repo) => println!("Trusting configs committed in {}", repo.display()),
+            None => println!("Trusting the current contents of {}", file.path.display()),
+        }
+        trust_file.trust(file);
+    }
+    trust_file.save()?;
+    Ok(0)
+}
+
+fn trust_all() -> bool {
+    std::env::var(TRUST_ALL_ENV).is_ok_and(|value| !matches!(value.as_str(), "" | "0"))
+}
+
+/// Make sure the user is fine with running the commands of the config files
+/// in `paths`, asking them if it isn't trusted yet.
+pub fn check_trusted(paths: &[PathBuf]) -> Result<()> {
+    let mut trust_file = TrustFile::load()?;
+    let mut untrusted = Vec::new();
+    for path in paths {
+        let file = config_file(path)?;
+        if !trust_file.trusts(&file) {
+            untrusted.push(file);
+        }
+    }
+    if untrusted.is_empty() {
+        return Ok(());
+    }
+    if trust_all() {
+        debug!("Trusting all config files, since LINTRUNNER_TRUST_ALL is set");
+        return Ok(());
+    }
+    let listed = untrusted
+        .iter()
+        .map(|file| format!("  {}", file.path.display()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if !interactive::can_prompt() {
+        bail!(
+            "These config files aren't trusted:\n{}\n\
+             Review them, then run `lintrunner trust` to run their commands. \
+             Set LINTRUNNER_TRUST_ALL=1 to run them without trusting them, \
+             like in CI.",
+            listed
+        );
+    }
+
+    writeln!(
+        theme::stderr(),
+        "{}\n{}\nTheir linters can run any command on this machine. \
+         Review them before going ahead.",
+        style("These config files aren't trusted yet:")
+            .theme_bold()
+            .yellow(),
+        listed
+    )?;
+    let choice = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Run their commands?")
+        .items(&["Yes, and trust them from now on", "Yes, this once", "No"])
+        .default(2)
+        .interact_opt()?;
+    match choice {
+        Some(0) => {
+            for file in untrusted {
+                trust_file.trust(file);
+            }
+            trust_file.save()
+        }
+        Some(1) => Ok(()),
+        _ => bail!("Not running commands from untrusted config files."),
+    }
+}
@@ -0,0 +1,103 @@
+//! Recognizing vendored, third-party code, for the `skip_vendored` option.
+//!
+//! A file is considered vendored if, below the config file's directory, it is
+//! under:
+//! - a directory named `third_party` or `vendor`,
+//! - a directory containing a `LICENSE.vendor` file, or
+//! - a git submodule (a directory containing its own `.git`).
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use crate::path::AbsPath;
+
+const VENDORED_DIR_NAMES: &[&str] = &["third_party", "vendor"];
+const VENDOR_LICENSE_FILE: &str = "LICENSE.vendor";
+
+/// Whether `dir` is the root of a vendored tree.
+fn is_vendored_dir(dir: &Path) -> bool {
+    let is_vendored_name = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| VENDORED_DIR_NAMES.contains(&name));
+    is_vendored_name || dir.join(VENDOR_LICENSE_FILE).is_file() || dir.join(".git").exists()
+}
+
+/// Remove vendored files from `files`. Only directories strictly below `root`
+/// are considered, so a repo that is itself vendored code can still be
+/// linted.
+pub fn remove_vendored(files: &mut Vec<AbsPath>, root: &Path) {
+    let mut is_vendored_cache: HashMap<PathBuf, bool> = HashMap::new();
+    files.retain(|file| {
+        let vendored_dir = file
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(root) && *dir != root)
+            .find(|dir| {
+                *is_vendored_cache
+                    .entry(dir.to_path_buf())
+                    .or_insert_with(|| is_vendored_dir(dir))
+            });
+        match vendored_dir {
+            Some(dir) => {
+                debug!(
+                    "Skipping '{}', which is vendored under '{}'",
+                    file.display(),
+                    dir.display()
+                );
+                false
+            }
+            None => true,
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use anyhow::Result;
+
+    use super::*;
+
+    #[test]
+    fn removes_vendored_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        let write = |path: &str| -> Result<AbsPath> {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(&path, "")?;
+            AbsPath::try_from(path.as_path())
+        };
+        let ours = write("src/main.rs")?;
+        let ours_nested = write("src/vendoring/lib.rs")?;
+        write("third_party/foo/foo.c")?;
+        write("src/vendor/bar.rs")?;
+        write("libs/baz/LICENSE.vendor")?;
+        write("libs/baz/src/baz.c")?;
+        write("submodule/.git")?;
+        write("submodule/qux.py")?;
+        // The root having a .git doesn't make everything vendored.
+        write(".git/HEAD")?;
+
+        let mut files = [
+            "src/main.rs",
+            "src/vendoring/lib.rs",
+            "third_party/foo/foo.c",
+            "src/vendor/bar.rs",
+            "libs/baz/src/baz.c",
+            "submodule/qux.py",
+        ]
+        .iter()
+        .map(|path| AbsPath::try_from(root.join(path).as_path()))
+        .collect::<Result<Vec<_>>>()?;
+        remove_vendored(&mut files, &root);
+        assert!(files == vec![ours, ours_nested]);
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use anyhow;
 
-use crate::path::AbsPath;
+use crate::{path::AbsPath, pathsfile::ChangeMetadata};
 
 // Trait describing the operations we need in lintrunner for a version
 // control system.
@@ -23,4 +25,36 @@ pub trait VersionControl {
 
     // Get all files in the repo.
     fn get_all_files(&self, under: Option<&AbsPath>) -> anyhow::Result<Vec<AbsPath>>;
+
+    // Gets how each file changed relative to the given commit, for linters
+    // that take a JSON paths file. Implementations that can't tell return
+    // nothing, and the metadata is left out.
+    fn get_change_metadata(&self, _relative_to: Option<&str>) -> anyhow::Result<ChangeMetadata> {
+        Ok(ChangeMetadata::new())
+    }
+
+    // Gets the root of the repository.
+    fn get_root(&self) -> &AbsPath;
+
+    // Gets the roots of other repos checked out inside this one, which it
+    // doesn't track, like a scripts repo cloned into the worktree.
+    fn get_nested_roots(&self) -> anyhow::Result<Vec<AbsPath>> {
+        Ok(Vec::new())
+    }
+
+    // Gets the email of the current user, as recorded in commits, for --mine.
+    fn get_user(&self) -> anyhow::Result<String> {
+        anyhow::bail!("--mine is not supported for this version control system")
+    }
+
+    // Counts how many lines of the file each author last changed, keyed by
+    // email, for --mine. Lines that aren't committed yet are counted for
+    // `user`.
+    fn get_line_authors(
+        &self,
+        _path: &AbsPath,
+        _user: &str,
+    ) -> anyhow::Result<HashMap<String, usize>> {
+        anyhow::bail!("--mine is not supported for this version control system")
+    }
 }
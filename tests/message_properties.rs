@@ -0,0 +1,180 @@
+//! Property tests for parsing linter output and applying the fixes in it.
+//!
+//! Linters are third-party code, so their output can be anything: truncated
+//! JSON, messages of the wrong shape, or edits pointing past the end of the
+//! file. Whatever it is, lintrunner should report an error rather than panic.
+//! The fuzz targets in `fuzz/` exercise the same functions for longer.
+
+use lintrunner::{
+    lint_message::{apply_edits, LintMessage, TextEdit},
+    linter::parse_messages,
+};
+use proptest::prelude::*;
+use serde_json::{json, Value};
+
+/// Text with multi-byte characters and empty lines, which positions in it
+/// must account for.
+fn text() -> impl Strategy<Value = String> {
+    "[ab é\n]{0,30}"
+}
+
+/// Edits that may or may not fit `text()`, including the invalid 0 line or
+/// column.
+fn edit() -> impl Strategy<Value = TextEdit> {
+    (0..6usize, 0..12usize, 0..6usize, 0..12usize, "[xy\n]{0,3}").prop_map(
+        |(line, char, end_line, end_char, replacement)| TextEdit {
+            line,
+            char,
+            end_line,
+            end_char,
+            replacement,
+        },
+    )
+}
+
+fn severity() -> impl Strategy<Value = &'static str> {
+    prop_oneof![
+        Just("error"),
+        Just("warning"),
+        Just("advice"),
+        Just("disabled")
+    ]
+}
+
+/// A message as a linter would print it. Fixes come with their `original`,
+/// so that nothing is read from disk.
+fn message() -> impl Strategy<Value = Value> {
+    (
+        proptest::option::of("[a-z/]{1,12}\\.py"),
+        proptest::option::of(1..1000usize),
+        proptest::option::of(1..200usize),
+        "[A-Z]{1,8}",
+        severity(),
+        ".{0,20}",
+        proptest::option::of(".{0,40}"),
+        proptest::option::of((text(), text())),
+    )
+        .prop_map(
+            |(path, line, char, code, severity, name, description, fix)| {
+                let (original, replacement) = fix.unzip();
+                json!({
+                    "path": path,
+                    "line": line,
+                    "char": char,
+                    "code": code,
+                    "severity": severity,
+                    "name": name,
+                    "description": description,
+                    "original": original,
+                    "replacement": replacement,
+                })
+            },
+        )
+}
+
+fn to_values(messages: Vec<LintMessage>) -> Vec<Value> {
+    messages
+        .into_iter()
+        .map(|message| serde_json::to_value(message).unwrap())
+        .collect()
+}
+
+/// `messages` as they come back from serializing a parsed message.
+fn normalized(messages: &[Value]) -> Vec<Value> {
+    let messages = messages
+        .iter()
+        .map(|message| serde_json::from_value::<LintMessage>(message.clone()).unwrap())
+        .collect();
+    to_values(messages)
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_output_does_not_panic(output in any::<String>()) {
+        let _ = parse_messages(&output);
+    }
+
+    #[test]
+    fn json_like_output_does_not_panic(
+        output in "[\\[\\]{}\",:\n 0-9a-z]{0,60}",
+    ) {
+        let _ = parse_messages(&output);
+    }
+
+    #[test]
+    fn truncated_messages_do_not_panic(message in message(), cut in any::<prop::sample::Index>()) {
+        let line = message.to_string();
+        let mut cut = cut.index(line.len() + 1);
+        while !line.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let _ = parse_messages(&line[..cut]);
+    }
+
+    #[test]
+    fn output_shapes_parse_the_same(messages in prop::collection::vec(message(), 0..5)) {
+        let expected = normalized(&messages);
+        let lines = messages
+            .iter()
+            .map(|message| message.to_string() + "\n")
+            .collect::<String>();
+        prop_assert_eq!(to_values(parse_messages(&lines).unwrap()), expected.clone());
+        if !messages.is_empty() {
+            let array = serde_json::to_string_pretty(&messages).unwrap();
+            prop_assert_eq!(to_values(parse_messages(&array).unwrap()), expected.clone());
+        }
+        let wrapper = json!({ "version": 1, "messages": messages }).to_string();
+        prop_assert_eq!(to_values(parse_messages(&wrapper).unwrap()), expected);
+    }
+
+    #[test]
+    fn applying_edits_does_not_panic(text in text(), edits in prop::collection::vec(edit(), 0..4)) {
+        let _ = apply_edits(&text, &edits);
+    }
+
+    #[test]
+    fn applying_one_edit_replaces_its_range(
+        text in text(),
+        start in any::<prop::sample::Index>(),
+        len in any::<prop::sample::Index>(),
+        replacement in "[xy\n]{0,3}",
+    ) {
+        let chars = text.chars().collect::<Vec<_>>();
+        let start = start.index(chars.len() + 1);
+        let end = start + len.index(chars.len() - start + 1);
+        // The 1-based line and column of the character at `index`.
+        let position = |index: usize| {
+            let before = &chars[..index];
+            let line = before.iter().filter(|c| **c == '\n').count() + 1;
+            let line_start = before.iter().rposition(|c| *c == '\n').map_or(0, |i| i + 1);
+            (line, index - line_start + 1)
+        };
+        let ((line, char), (end_line, end_char)) = (position(start), position(end));
+        let edit = TextEdit { line, char, end_line, end_char, replacement: replacement.clone() };
+        let expected = chars[..start].iter().collect::<String>()
+            + &replacement
+            + &chars[end..].iter().collect::<String>();
+        prop_assert_eq!(apply_edits(&text, &[edit]).unwrap(), expected);
+    }
+
+    #[test]
+    fn suggestions_resolve_like_their_edits(
+        original in text(),
+        edits in prop::collection::vec(edit(), 1..3),
+    ) {
+        let output = json!({
+            "path": "file.py", "line": 1, "char": 1, "code": "X",
+            "severity": "warning", "name": "x", "description": null,
+            "original": original, "version": 2,
+            "suggestions": [{ "edits": edits }],
+        })
+        .to_string();
+        match apply_edits(&original, &edits) {
+            Ok(replacement) => {
+                let messages = parse_messages(&output).unwrap();
+                prop_assert_eq!(messages[0].replacement.as_ref(), Some(&replacement));
+            }
+            Err(_) => prop_assert!(parse_messages(&output).is_err()),
+        }
+    }
+}